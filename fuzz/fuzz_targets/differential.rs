@@ -0,0 +1,16 @@
+#![no_main]
+use hessian_rs::{to_vec, Value};
+use libfuzzer_sys::fuzz_target;
+
+// The serde deserializer reads the bytes of a value, `from_value` reads the
+// `Value` itself, both must give the same `serde_json::Value`
+fuzz_target!(|value: Value| {
+    let buf = to_vec(&value).unwrap();
+    let from_bytes = serde_hessian::de::from_slice::<_, serde_json::Value>(&buf);
+    let from_value = serde_hessian::from_value::<serde_json::Value>(value);
+    match (from_bytes, from_value) {
+        (Ok(l), Ok(r)) => assert_eq!(l, r),
+        (Err(_), Err(_)) => {}
+        (l, r) => panic!("serde path {:?}, value path {:?}", l, r),
+    }
+});
@@ -0,0 +1,21 @@
+#![no_main]
+use hessian_rs::{from_slice, to_vec, Value};
+use libfuzzer_sys::fuzz_target;
+
+// NaN isn't equal to itself, values holding one are only decoded
+fn has_nan(value: &Value) -> bool {
+    match value {
+        Value::Double(d) => d.is_nan(),
+        Value::List(l) => l.iter().any(has_nan),
+        Value::Map(m) => m.iter().any(|(k, v)| has_nan(k) || has_nan(v)),
+        _ => false,
+    }
+}
+
+fuzz_target!(|value: Value| {
+    let buf = to_vec(&value).unwrap();
+    let decoded = from_slice(&buf).unwrap();
+    if !has_nan(&value) {
+        assert_eq!(decoded, value);
+    }
+});
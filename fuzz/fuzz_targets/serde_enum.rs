@@ -0,0 +1,37 @@
+#![no_main]
+// the fields are only decoded
+#![allow(dead_code)]
+use std::collections::HashMap;
+
+use libfuzzer_sys::fuzz_target;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+enum Shape {
+    Empty,
+    Circle(u32),
+    Rect(i8, String),
+    Polygon { sides: u64, name: Option<String> },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum Tagged {
+    Circle { radius: i32 },
+    Label { text: String },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Untagged {
+    Int(i32),
+    List(Vec<String>),
+    Map(HashMap<String, i64>),
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_hessian::de::from_slice::<_, Shape>(data);
+    let _ = serde_hessian::de::from_slice::<_, Vec<Shape>>(data);
+    let _ = serde_hessian::de::from_slice::<_, Tagged>(data);
+    let _ = serde_hessian::de::from_slice::<_, Untagged>(data);
+});
@@ -0,0 +1,15 @@
+#![no_main]
+use std::collections::HashMap;
+
+use libfuzzer_sys::fuzz_target;
+
+// `serde_json::Value` goes through `deserialize_any`, like any
+// self-describing value would
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_hessian::de::from_slice::<_, HashMap<String, serde_json::Value>>(data);
+    let _ = serde_hessian::de::from_slice::<_, serde_json::Value>(data);
+    // and the deserializer of `hessian_rs::Value`
+    if let Ok(value) = hessian_rs::from_slice(data) {
+        let _ = serde_hessian::from_value::<HashMap<String, serde_json::Value>>(value);
+    }
+});
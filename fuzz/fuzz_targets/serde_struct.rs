@@ -0,0 +1,37 @@
+#![no_main]
+// the fields are only decoded
+#![allow(dead_code)]
+use std::collections::HashMap;
+
+use libfuzzer_sys::fuzz_target;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct Part {
+    name: String,
+    weight: u16,
+    serial: (u8, String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "example.Car")]
+struct Car {
+    color: String,
+    mileage: i64,
+    parts: Vec<Part>,
+    owner: Option<String>,
+    options: HashMap<String, i32>,
+    image: Vec<u8>,
+    price: f64,
+    sold: bool,
+    grade: char,
+}
+
+#[derive(Debug, Deserialize)]
+struct Wrapper(Car);
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_hessian::de::from_slice::<_, Car>(data);
+    let _ = serde_hessian::de::from_slice::<_, Vec<Wrapper>>(data);
+    let _ = serde_hessian::de::from_slice::<_, serde_hessian::Typed<Part>>(data);
+});
@@ -0,0 +1,349 @@
+//! The `codegen` subcommand, prints Rust structs which decode the sample
+//! payloads it's given with serde-hessian.
+//!
+//! ```text
+//! #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+//! #[serde(rename = "example.Car")]
+//! pub struct Car {
+//!     pub color: String,
+//!     #[serde(rename = "mileageKm")]
+//!     pub mileage_km: Option<i64>,
+//! }
+//! ```
+//!
+//! The schemas of the values of every payload are merged, see
+//! `hessian_rs::value::Schema`. A class becomes a struct renamed to the
+//! class name, wherever its objects are, and a map with string keys a
+//! struct named after the field holding it. A field which is null or
+//! missing in some of the payloads is an `Option`. What can't be told from
+//! the payloads, e.g. the elements of lists which are always empty or
+//! values of several types, is a `serde_json::Value`.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+use hessian_rs::value::Schema;
+
+// The type of values whose type isn't known
+const ANY: &str = "serde_json::Value";
+
+// Words which can't be raw identifiers
+const RESERVED: &[&str] = &["crate", "self", "Self", "super"];
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+/// The Rust definitions of the types of the values of `schema`.
+pub fn codegen(schema: &Schema) -> String {
+    let mut gen = Codegen::default();
+    gen.gather(schema);
+    let root = gen.rust_type(schema, "Root", None);
+    let mut out = String::from("use serde::{Deserialize, Serialize};\n");
+    if !matches!(schema, Schema::Struct { .. }) {
+        write!(out, "\npub type Root = {};\n", root).unwrap();
+    }
+    // the classes in the order they were met, the structs of the maps in
+    // their fields before them
+    let classes: Vec<(String, String)> = gen
+        .classes
+        .iter()
+        .map(|(class, name)| (class.clone(), name.clone()))
+        .collect();
+    for (class, name) in classes {
+        let fields = gen.fields.remove(&class).unwrap_or_default();
+        let definition = gen.definition(&name, Some(&class), &fields);
+        gen.definitions.push(definition);
+    }
+    for definition in gen.definitions {
+        out.push('\n');
+        out.push_str(&definition);
+    }
+    out
+}
+
+#[derive(Default)]
+struct Codegen {
+    // The struct name of each class, in the order they were met
+    classes: Vec<(String, String)>,
+    // The fields of each class, merged from all of its objects
+    fields: BTreeMap<String, BTreeMap<String, Schema>>,
+    // The struct names taken
+    names: HashSet<String>,
+    // The definitions of the structs generated so far
+    definitions: Vec<String>,
+}
+
+impl Codegen {
+    // Merge the fields of the objects of each class and name the classes
+    fn gather(&mut self, schema: &Schema) {
+        match schema {
+            Schema::Struct {
+                r#type: Some(class),
+                fields,
+            } => {
+                if !self.classes.iter().any(|(c, _)| c == class) {
+                    let name = self.name(&class_name(class));
+                    self.classes.push((class.clone(), name));
+                }
+                let merged = match self.fields.remove(class) {
+                    Some(known) => merge_fields(known, fields.clone()),
+                    None => fields.clone(),
+                };
+                self.fields.insert(class.clone(), merged);
+                fields.values().for_each(|s| self.gather(s));
+            }
+            Schema::Struct { fields, .. } => fields.values().for_each(|s| self.gather(s)),
+            Schema::List { element, .. } => self.gather(element),
+            Schema::Map { key, value, .. } => {
+                self.gather(key);
+                self.gather(value);
+            }
+            Schema::Nullable(s) => self.gather(s),
+            _ => {}
+        }
+    }
+
+    // A struct name based on `hint` which isn't taken yet
+    fn name(&mut self, hint: &str) -> String {
+        let mut name = hint.to_string();
+        let mut n = 1;
+        while !self.names.insert(name.clone()) {
+            n += 1;
+            name = format!("{}{}", hint, n);
+        }
+        name
+    }
+
+    // The Rust type of `schema`, generating the structs of the maps with
+    // string keys in it, named after `hint`. A class refers to itself
+    // through a `Box`
+    fn rust_type(&mut self, schema: &Schema, hint: &str, class: Option<&str>) -> String {
+        match schema {
+            Schema::Bool => "bool".to_string(),
+            Schema::Int => "i32".to_string(),
+            // dates are decoded as milliseconds since the epoch
+            Schema::Long | Schema::Date => "i64".to_string(),
+            Schema::Double => "f64".to_string(),
+            Schema::Bytes => "Vec<u8>".to_string(),
+            Schema::String => "String".to_string(),
+            Schema::Null => format!("Option<{}>", ANY),
+            Schema::Empty | Schema::Ref | Schema::Union(_) => ANY.to_string(),
+            Schema::Nullable(s) => format!("Option<{}>", self.rust_type(s, hint, class)),
+            Schema::List { element, .. } => {
+                format!("Vec<{}>", self.rust_type(element, hint, None))
+            }
+            Schema::Map { key, value, .. } => {
+                let key = match **key {
+                    Schema::Bool | Schema::Int | Schema::Long | Schema::String => {
+                        self.rust_type(key, hint, None)
+                    }
+                    _ => "String".to_string(),
+                };
+                format!(
+                    "std::collections::HashMap<{}, {}>",
+                    key,
+                    self.rust_type(value, hint, None)
+                )
+            }
+            Schema::Struct {
+                r#type: Some(tp), ..
+            } => {
+                let name = self
+                    .classes
+                    .iter()
+                    .find(|(c, _)| c == tp)
+                    .map(|(_, name)| name.clone())
+                    .unwrap_or_else(|| ANY.to_string());
+                match class == Some(tp.as_str()) {
+                    true => format!("Box<{}>", name),
+                    false => name,
+                }
+            }
+            Schema::Struct {
+                r#type: None,
+                fields,
+            } => {
+                let name = self.name(&camel_case(hint));
+                let definition = self.definition(&name, None, fields);
+                self.definitions.push(definition);
+                name
+            }
+        }
+    }
+
+    fn definition(
+        &mut self,
+        name: &str,
+        class: Option<&str>,
+        fields: &BTreeMap<String, Schema>,
+    ) -> String {
+        let mut out = String::from("#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]\n");
+        if let Some(class) = class {
+            writeln!(out, "#[serde(rename = {:?})]", class).unwrap();
+        }
+        writeln!(out, "pub struct {} {{", name).unwrap();
+        for (field, schema) in fields {
+            let ident = snake_case(field);
+            if ident.trim_start_matches("r#") != field {
+                writeln!(out, "    #[serde(rename = {:?})]", field).unwrap();
+            }
+            let tp = self.rust_type(schema, field, class);
+            writeln!(out, "    pub {}: {},", ident, tp).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+// The fields of two objects of a class, a field only one of them has is
+// optional
+fn merge_fields(
+    mut a: BTreeMap<String, Schema>,
+    mut b: BTreeMap<String, Schema>,
+) -> BTreeMap<String, Schema> {
+    let names: HashSet<String> = a.keys().chain(b.keys()).cloned().collect();
+    names
+        .into_iter()
+        .map(|name| {
+            let schema = match (a.remove(&name), b.remove(&name)) {
+                (Some(x), Some(y)) => x.merge(y),
+                (Some(x), None) | (None, Some(x)) => x.merge(Schema::Null),
+                (None, None) => unreachable!(),
+            };
+            (name, schema)
+        })
+        .collect()
+}
+
+// The simple name of a java class, `Car` for `example.Car` and
+// `OuterInner` for `example.Outer$Inner`
+fn class_name(class: &str) -> String {
+    camel_case(class.rsplit('.').next().unwrap_or(class))
+}
+
+fn camel_case(s: &str) -> String {
+    let mut out = String::new();
+    for word in s.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            out.push(first.to_ascii_uppercase());
+            out.extend(chars);
+        }
+    }
+    match out.chars().next() {
+        None => "Struct".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("T{}", out),
+        Some(_) => out,
+    }
+}
+
+// A field identifier, `mileage_km` for `mileageKm`, `url` for `URL`
+fn snake_case(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            continue;
+        }
+        // a word starts at an uppercase letter after a lowercase one, or
+        // before one, as `Path` in `URLPath`
+        let starts_word = c.is_ascii_uppercase()
+            && i > 0
+            && match chars[i - 1] {
+                prev if prev.is_ascii_lowercase() || prev.is_ascii_digit() => true,
+                prev => {
+                    prev.is_ascii_uppercase()
+                        && matches!(chars.get(i + 1), Some(n) if n.is_ascii_lowercase())
+                }
+            };
+        if starts_word && !out.is_empty() && !out.ends_with('_') {
+            out.push('_');
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    let out = out.trim_end_matches('_').to_string();
+    match out.chars().next() {
+        None => "field".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("f{}", out),
+        Some(_) if RESERVED.contains(&out.as_str()) => format!("{}_", out),
+        Some(_) if KEYWORDS.contains(&out.as_str()) => format!("r#{}", out),
+        Some(_) => out,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{class_name, codegen, snake_case};
+    use hessian_rs::{hessian, Value};
+
+    #[test]
+    fn test_codegen() {
+        let first = hessian!("example.Car" => {
+            "color": "red",
+            "mileageKm": 65536,
+            "owner": { "name": "a", "type": 1 },
+            "next": (hessian!("example.Car" => { "color": "blue" })),
+            "parts": [(hessian!("example.Part" => { "id": 1 }))]
+        });
+        let second = hessian!("example.Car" => {
+            "color": "green",
+            "mileageKm": (Value::Long(1)),
+            "made": (Value::Date(0)),
+            "parts": []
+        });
+        let schema = first.schema().merge(second.schema());
+        assert_eq!(
+            codegen(&schema),
+            r#"use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Owner {
+    pub name: String,
+    pub r#type: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "example.Car")]
+pub struct Car {
+    pub color: String,
+    pub made: Option<i64>,
+    #[serde(rename = "mileageKm")]
+    pub mileage_km: Option<i64>,
+    pub next: Option<Box<Car>>,
+    pub owner: Option<Owner>,
+    pub parts: Option<Vec<Part>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "example.Part")]
+pub struct Part {
+    pub id: i32,
+}
+"#
+        );
+        assert_eq!(
+            codegen(&hessian!([1, "a"]).schema()),
+            "use serde::{Deserialize, Serialize};\n\npub type Root = Vec<serde_json::Value>;\n"
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(class_name("example.Outer$Inner"), "OuterInner");
+        assert_eq!(class_name("car"), "Car");
+        assert_eq!(snake_case("mileageKm"), "mileage_km");
+        assert_eq!(snake_case("URLPath"), "url_path");
+        assert_eq!(snake_case("x-y"), "x_y");
+        assert_eq!(snake_case("self"), "self_");
+        assert_eq!(snake_case("match"), "r#match");
+        assert_eq!(snake_case("1st"), "f1st");
+    }
+}
@@ -0,0 +1,250 @@
+//! The `inspect` subcommand, prints the byte span of every token of a
+//! payload next to what it decodes to.
+//!
+//! ```text
+//! 00000000  43 0b 65 78 61 6d 70 6c ..  class-def example.Car ["color"]
+//! 00000014  60                          object example.Car
+//! 00000015  03 72 65 64                   color: string "red"
+//! ```
+//!
+//! Tokens are printed as they're read, so a payload which fails to decode
+//! is printed up to the failing token, whose offset is in the error.
+
+use std::fmt;
+use std::io::Write;
+
+use hessian_rs::constant::Object;
+use hessian_rs::de::Deserializer;
+use hessian_rs::value::Definition;
+use hessian_rs::{ByteCodecType, Value};
+
+use crate::Result;
+
+// Bytes of a span which are printed
+const SPAN_BYTES: usize = 8;
+// Chars of a string which are printed
+const STRING_CHARS: usize = 64;
+
+/// Print the tokens of every value of `input` to `out`.
+pub fn inspect<W: Write>(input: &[u8], out: &mut W) -> Result<()> {
+    let mut inspector = Inspector {
+        de: Deserializer::new(input),
+        input,
+        out,
+        definitions: Vec::new(),
+        offset: 0,
+    };
+    while inspector.de.peek_byte().is_ok() {
+        if let Err(err) = inspector.value(0, "") {
+            return Err(format!("at offset {:#x}: {}", inspector.offset, err).into());
+        }
+    }
+    Ok(())
+}
+
+struct Inspector<'a, W> {
+    de: Deserializer<&'a [u8]>,
+    input: &'a [u8],
+    out: &'a mut W,
+    // The class definitions are kept here to print objects field by field
+    definitions: Vec<Definition>,
+    // Offset of the last token started
+    offset: usize,
+}
+
+impl<'a, W: Write> Inspector<'a, W> {
+    // Print the token from `start` to the current position
+    fn print(&mut self, start: usize, depth: usize, token: fmt::Arguments) -> Result<()> {
+        let span = &self.input[start..self.de.position() as usize];
+        let mut bytes: Vec<String> = span
+            .iter()
+            .take(SPAN_BYTES)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        if span.len() > SPAN_BYTES {
+            bytes.push("..".to_string());
+        }
+        writeln!(
+            self.out,
+            "{:08x}  {:<26}  {:indent$}{}",
+            start,
+            bytes.join(" "),
+            "",
+            token,
+            indent = depth * 2
+        )?;
+        Ok(())
+    }
+
+    fn value(&mut self, depth: usize, label: &str) -> Result<()> {
+        let start = self.de.position() as usize;
+        self.offset = start;
+        match self.de.peek_byte_code_type()? {
+            ByteCodecType::Definition => {
+                self.de.read_byte()?;
+                let name = self.de.read_string_value()?;
+                let length = self.de.read_i32()?;
+                let fields = (0..length)
+                    .map(|_| self.de.read_string_value())
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                self.print(
+                    start,
+                    depth,
+                    format_args!("class-def {} {:?}", name, fields),
+                )?;
+                self.definitions.push(Definition { name, fields });
+                // the definition precedes the object using it
+                self.value(depth, label)
+            }
+            ByteCodecType::Object(tag) => {
+                self.de.read_byte()?;
+                let id = match tag {
+                    Object::Compact(b) => (b - 0x60) as usize,
+                    Object::Normal => self.de.read_i32()? as usize,
+                };
+                let def = match self.definitions.get(id) {
+                    Some(def) => def.clone(),
+                    None => return Err(format!("unknown class definition {}", id).into()),
+                };
+                self.print(start, depth, format_args!("{}object {}", label, def.name))?;
+                for field in def.fields.iter() {
+                    self.value(depth + 1, &format!("{}: ", field))?;
+                }
+                Ok(())
+            }
+            ByteCodecType::List(_) => {
+                let (tp, length) = self.de.read_list_header()?;
+                let tp = tp.map(|tp| format!(" {}", tp)).unwrap_or_default();
+                match length {
+                    Some(length) => {
+                        self.print(
+                            start,
+                            depth,
+                            format_args!("{}list{} ({} elements)", label, tp, length),
+                        )?;
+                        for _ in 0..length {
+                            self.value(depth + 1, "")?;
+                        }
+                        Ok(())
+                    }
+                    None => {
+                        self.print(start, depth, format_args!("{}list{}", label, tp))?;
+                        while self.de.peek_byte()? != b'Z' {
+                            self.value(depth + 1, "")?;
+                        }
+                        self.end(depth)
+                    }
+                }
+            }
+            ByteCodecType::Map(typed) => {
+                self.de.read_byte()?;
+                let tp = if typed {
+                    format!(" {}", self.de.read_type()?)
+                } else {
+                    String::new()
+                };
+                self.print(start, depth, format_args!("{}map{}", label, tp))?;
+                while self.de.peek_byte()? != b'Z' {
+                    self.value(depth + 1, "key ")?;
+                    self.value(depth + 1, "value ")?;
+                }
+                self.end(depth)
+            }
+            _ => {
+                let value = self.de.read_value()?;
+                self.print(start, depth, format_args!("{}{}", label, Scalar(&value)))
+            }
+        }
+    }
+
+    // Read the 'Z' ending a map or a variable-length list
+    fn end(&mut self, depth: usize) -> Result<()> {
+        let start = self.de.position() as usize;
+        self.offset = start;
+        self.de.read_byte()?;
+        self.print(start, depth, format_args!("end"))
+    }
+}
+
+// Describes a value which isn't a container
+struct Scalar<'a>(&'a Value);
+
+impl fmt::Display for Scalar<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(i) => write!(f, "int {}", i),
+            Value::Long(l) => write!(f, "long {}", l),
+            Value::Double(d) => write!(f, "double {}", d),
+            Value::Date(d) => write!(f, "date {}", d),
+            Value::Bytes(b) => write!(f, "binary ({} bytes)", b.len()),
+            Value::String(s) if s.chars().count() > STRING_CHARS => {
+                let prefix: String = s.chars().take(STRING_CHARS).collect();
+                write!(f, "string {:?}.. ({} chars)", prefix, s.chars().count())
+            }
+            Value::String(s) => write!(f, "string {:?}", s),
+            Value::Ref(r) => write!(f, "ref #{}", r),
+            v => write!(f, "{}", v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inspect;
+    use hessian_rs::value::Definition;
+    use hessian_rs::{hessian, ser::Serializer, Value};
+
+    fn inspect_to_string(input: &[u8]) -> (String, Option<String>) {
+        let mut out = Vec::new();
+        let err = inspect(input, &mut out).err().map(|e| e.to_string());
+        (String::from_utf8(out).unwrap(), err)
+    }
+
+    #[test]
+    fn test_inspect() {
+        let def = Definition {
+            name: "example.Car".to_string(),
+            fields: vec!["color".to_string(), "parts".to_string()],
+        };
+        let mut ser = Serializer::new(Vec::new());
+        ser.serialize_fields_with_definition(
+            &def,
+            &[Value::from("red"), hessian!([1, "[string" => ["x"]])],
+        )
+        .unwrap();
+        ser.serialize_value(&hessian!({ 1: null })).unwrap();
+        let (out, err) = inspect_to_string(ser.get_ref());
+        assert_eq!(err, None);
+        assert_eq!(
+            out,
+            concat!(
+                "00000000  43 0b 65 78 61 6d 70 6c ..  class-def example.Car [\"color\", \"parts\"]\n",
+                "0000001a  60                          object example.Car\n",
+                "0000001b  03 72 65 64                   color: string \"red\"\n",
+                "0000001f  7a                            parts: list (2 elements)\n",
+                "00000020  91                              int 1\n",
+                "00000021  71 07 5b 73 74 72 69 6e ..      list [string (1 elements)\n",
+                "0000002a  01 78                             string \"x\"\n",
+                "0000002c  48                          map\n",
+                "0000002d  91                            key int 1\n",
+                "0000002e  4e                            value null\n",
+                "0000002f  5a                          end\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_inspect_error() {
+        let (out, err) = inspect_to_string(&[0x57, 0x91, 0x06, b'a']);
+        assert_eq!(
+            out,
+            concat!(
+                "00000000  57                          list\n",
+                "00000001  91                            int 1\n",
+            )
+        );
+        assert!(err.unwrap().starts_with("at offset 0x2: "));
+    }
+}
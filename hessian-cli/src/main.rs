@@ -0,0 +1,282 @@
+//! Convert hessian payloads to JSON and back.
+//!
+//! ```text
+//! hessian-cli to-json [--hex] [--types] [--pretty] [FILE]
+//! hessian-cli from-json [--hex] [FILE]
+//! hessian-cli inspect [--hex] [FILE]
+//! hessian-cli codegen [--hex] [FILE]...
+//! ```
+//!
+//! The input is read from `FILE`, or from stdin when it's missing or `-`.
+//! See `hessian_rs::value` for how values are converted to JSON.
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+
+mod codegen;
+mod inspect;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use hessian_rs::de::Deserializer;
+use hessian_rs::ser::Serializer;
+use hessian_rs::value::Schema;
+use hessian_rs::Value;
+use serde_json::Value as Json;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn cli() -> Command<'static> {
+    let file = Arg::new("file")
+        .value_name("FILE")
+        .help("Input file, stdin when missing or -");
+    let hex = Arg::new("hex").long("hex").action(ArgAction::SetTrue);
+    Command::new("hessian-cli")
+        .about("Convert hessian2 payloads to JSON and back")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("to-json")
+                .about("Print every hessian value of the input as a line of JSON")
+                .arg(file.clone())
+                .arg(
+                    hex.clone()
+                        .help("Read the input as hex text, whitespace is ignored"),
+                )
+                .arg(
+                    Arg::new("types")
+                        .long("types")
+                        .action(ArgAction::SetTrue)
+                        .help("Keep the type of typed maps and objects in an \"@type\" entry"),
+                )
+                .arg(
+                    Arg::new("pretty")
+                        .long("pretty")
+                        .action(ArgAction::SetTrue)
+                        .help("Pretty print the JSON"),
+                ),
+        )
+        .subcommand(
+            Command::new("from-json")
+                .about("Encode every JSON value of the input as hessian")
+                .arg(file.clone())
+                .arg(hex.clone().help("Write hex text instead of binary")),
+        )
+        .subcommand(
+            Command::new("inspect")
+                .about("Print the byte span of every token of the input and what it decodes to")
+                .arg(file.clone())
+                .arg(
+                    hex.clone()
+                        .help("Read the input as hex text, whitespace is ignored"),
+                ),
+        )
+        .subcommand(
+            Command::new("codegen")
+                .about(
+                    "Print Rust structs which decode the values of the inputs with serde-hessian",
+                )
+                .arg(
+                    file.multiple_values(true)
+                        .help("Input files, stdin when missing or -"),
+                )
+                .arg(hex.help("Read the inputs as hex text, whitespace is ignored")),
+        )
+}
+
+fn main() {
+    let matches = cli().get_matches();
+    let res = match matches.subcommand() {
+        Some(("to-json", m)) => to_json(m),
+        Some(("from-json", m)) => from_json(m),
+        Some(("inspect", m)) => {
+            read_hessian(m).and_then(|input| inspect::inspect(&input, &mut io::stdout().lock()))
+        }
+        Some(("codegen", m)) => generate(m),
+        _ => unreachable!("a subcommand is required"),
+    };
+    if let Err(err) = res {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn flag(m: &ArgMatches, name: &str) -> bool {
+    m.get_one::<bool>(name).copied().unwrap_or(false)
+}
+
+fn read_input(m: &ArgMatches) -> Result<Vec<u8>> {
+    read_file(m.get_one::<String>("file").map(String::as_str))
+}
+
+fn read_file(path: Option<&str>) -> Result<Vec<u8>> {
+    match path {
+        None | Some("-") => {
+            let mut buf = Vec::new();
+            io::stdin().read_to_end(&mut buf)?;
+            Ok(buf)
+        }
+        Some(path) => Ok(fs::read(path)?),
+    }
+}
+
+// Read the hessian input, decoding hex text with --hex
+fn read_hessian(m: &ArgMatches) -> Result<Vec<u8>> {
+    let input = read_input(m)?;
+    if flag(m, "hex") {
+        parse_hex(&input)
+    } else {
+        Ok(input)
+    }
+}
+
+fn to_json(m: &ArgMatches) -> Result<()> {
+    let input = read_hessian(m)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for value in decode_all(&input)? {
+        let mut json = Json::from(value);
+        if !flag(m, "types") {
+            strip_types(&mut json);
+        }
+        if flag(m, "pretty") {
+            serde_json::to_writer_pretty(&mut out, &json)?;
+        } else {
+            serde_json::to_writer(&mut out, &json)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn from_json(m: &ArgMatches) -> Result<()> {
+    let input = read_input(m)?;
+    let mut ser = Serializer::new(Vec::new());
+    for json in serde_json::Deserializer::from_slice(&input).into_iter::<Json>() {
+        ser.serialize_value(&Value::from(json?))?;
+    }
+    let output = ser.into_inner();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    if flag(m, "hex") {
+        writeln!(out, "{}", to_hex(&output))?;
+    } else {
+        out.write_all(&output)?;
+    }
+    Ok(out.flush()?)
+}
+
+fn generate(m: &ArgMatches) -> Result<()> {
+    let paths: Vec<Option<&str>> = match m.get_many::<String>("file") {
+        Some(paths) => paths.map(|p| Some(p.as_str())).collect(),
+        None => vec![None],
+    };
+    let mut schema = Schema::Empty;
+    for path in paths {
+        let mut input = read_file(path)?;
+        if flag(m, "hex") {
+            input = parse_hex(&input)?;
+        }
+        for value in decode_all(&input)? {
+            schema = schema.merge(value.schema());
+        }
+    }
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    out.write_all(codegen::codegen(&schema).as_bytes())?;
+    Ok(out.flush()?)
+}
+
+// Decode the values of the input until its end
+fn decode_all(input: &[u8]) -> Result<Vec<Value>> {
+    let mut de = Deserializer::new(input);
+    let mut values = Vec::new();
+    while de.peek_byte().is_ok() {
+        values.push(de.read_value()?);
+    }
+    Ok(values)
+}
+
+// Remove the "@type" entries of typed maps, recursively
+fn strip_types(json: &mut Json) {
+    match json {
+        Json::Array(array) => array.iter_mut().for_each(strip_types),
+        Json::Object(object) => {
+            object.remove("@type");
+            object.values_mut().for_each(strip_types);
+        }
+        _ => {}
+    }
+}
+
+fn parse_hex(text: &[u8]) -> Result<Vec<u8>> {
+    let digits: Vec<u8> = text
+        .iter()
+        .copied()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err("odd number of hex digits".into());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).map_err(|_| "invalid hex digit")?;
+            u8::from_str_radix(pair, 16).map_err(|_| format!("invalid hex byte {:?}", pair).into())
+        })
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cli, decode_all, parse_hex, strip_types, to_hex};
+    use hessian_rs::{hessian, Value};
+    use serde_json::{json, Value as Json};
+
+    #[test]
+    fn test_cli() {
+        cli().debug_assert();
+        let m = cli().get_matches_from(["hessian-cli", "to-json", "--hex", "--types", "a.bin"]);
+        let (name, m) = m.subcommand().unwrap();
+        assert_eq!(name, "to-json");
+        assert!(super::flag(m, "hex") && super::flag(m, "types") && !super::flag(m, "pretty"));
+        assert_eq!(m.get_one::<String>("file").unwrap(), "a.bin");
+        let m = cli().get_matches_from(["hessian-cli", "codegen", "a.bin", "b.bin"]);
+        let files: Vec<&String> = m
+            .subcommand()
+            .unwrap()
+            .1
+            .get_many("file")
+            .unwrap()
+            .collect();
+        assert_eq!(files, ["a.bin", "b.bin"]);
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(parse_hex(b"91 0a\n ff").unwrap(), [0x91, 0x0a, 0xff]);
+        assert!(parse_hex(b"910").is_err());
+        assert!(parse_hex(b"zz").is_err());
+        assert_eq!(to_hex(&[0x91, 0x0a, 0xff]), "910aff");
+    }
+
+    #[test]
+    fn test_decode_all() {
+        let values = decode_all(&[0x91, b'N', 0x79, 0x91]).unwrap();
+        assert_eq!(values, [Value::Int(1), Value::Null, hessian!([1])]);
+        assert!(decode_all(&[0x91, 0x79]).is_err());
+        assert!(decode_all(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_strip_types() {
+        let mut json = Json::from(hessian!("example.Car" => { "parts": [{ "@type": "x" }] }));
+        strip_types(&mut json);
+        assert_eq!(json, json!({ "parts": [{}] }));
+    }
+}
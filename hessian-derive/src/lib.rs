@@ -0,0 +1,122 @@
+//! `#[derive(HessianObject)]`, implements `hessian_rs::HessianObject` for a
+//! struct with named fields.
+//!
+//! ```ignore
+//! #[derive(Clone, HessianObject)]
+//! #[hessian(class = "com.example.Car")]
+//! struct Car {
+//!     color: String,
+//!     #[hessian(field = "modelName")]
+//!     model_name: String,
+//! }
+//! ```
+//!
+//! The class is named by `#[hessian(class = "..")]`, the name of the struct
+//! when it's missing, and a field by `#[hessian(field = "..")]`, its rust name
+//! when it's missing. Fields are converted with `hessian_rs::Value::from` and
+//! `TryFrom<Value>`, so their types must implement `Clone` and both, which the
+//! derived structs do too.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+#[proc_macro_derive(HessianObject, attributes(hessian))]
+pub fn derive_hessian_object(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut class = input.ident.to_string();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("hessian")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("class") {
+                class = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("expected `class`"))
+            }
+        })?;
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "HessianObject requires named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "HessianObject can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut idents = Vec::new();
+    let mut names = Vec::new();
+    for field in fields {
+        let ident = field.ident.clone().expect("named field");
+        let mut name = ident.to_string();
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("hessian")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("field") {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `field`"))
+                }
+            })?;
+        }
+        idents.push(ident);
+        names.push(name);
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::hessian_rs::HessianObject for #ident #ty_generics #where_clause {
+            fn definition() -> ::hessian_rs::value::Definition {
+                ::hessian_rs::value::Definition {
+                    name: ::std::string::String::from(#class),
+                    fields: ::std::vec![#(::std::string::String::from(#names)),*],
+                }
+            }
+
+            fn to_fields(&self) -> ::std::vec::Vec<::hessian_rs::Value> {
+                ::std::vec![#(::hessian_rs::Value::from(::std::clone::Clone::clone(&self.#idents))),*]
+            }
+
+            fn from_value(
+                value: ::hessian_rs::Value,
+            ) -> ::std::result::Result<Self, ::hessian_rs::Error> {
+                let mut fields = ::hessian_rs::value::ObjectFields::new::<Self>(value)?;
+                ::std::result::Result::Ok(#ident {
+                    #(#idents: fields.take(#names)?,)*
+                })
+            }
+        }
+
+        impl #impl_generics ::hessian_rs::value::ToHessian for #ident #ty_generics #where_clause {
+            fn to_hessian(self) -> ::hessian_rs::Value {
+                ::hessian_rs::HessianObject::to_value(&self)
+            }
+        }
+
+        impl #impl_generics ::std::convert::TryFrom<::hessian_rs::Value> for #ident #ty_generics #where_clause {
+            type Error = ::hessian_rs::Error;
+
+            fn try_from(value: ::hessian_rs::Value) -> ::std::result::Result<Self, ::hessian_rs::Error> {
+                <Self as ::hessian_rs::HessianObject>::from_value(value)
+            }
+        }
+    })
+}
@@ -0,0 +1,79 @@
+use std::convert::TryFrom;
+
+use hessian_rs::de::Deserializer;
+use hessian_rs::ser::Serializer;
+use hessian_rs::value::Definition;
+use hessian_rs::{hessian, HessianObject, Value};
+
+#[derive(Clone, Debug, PartialEq, HessianObject)]
+#[hessian(class = "com.example.Car")]
+struct Car {
+    color: String,
+    #[hessian(field = "modelName")]
+    model_name: String,
+    mileage: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, HessianObject)]
+struct Garage {
+    cars: Vec<Car>,
+}
+
+fn car() -> Car {
+    Car {
+        color: "red".to_string(),
+        model_name: "corvette".to_string(),
+        mileage: 65536,
+    }
+}
+
+#[test]
+fn test_definition() {
+    assert_eq!(
+        Car::definition(),
+        Definition {
+            name: "com.example.Car".to_string(),
+            fields: vec![
+                "color".to_string(),
+                "modelName".to_string(),
+                "mileage".to_string()
+            ],
+        }
+    );
+    assert_eq!(Garage::definition().name, "Garage");
+}
+
+#[test]
+fn test_object_roundtrip() {
+    let mut ser = Serializer::new(Vec::new());
+    ser.serialize_object(&car()).unwrap();
+    let buf = ser.into_inner();
+    assert!(buf.starts_with(b"C\x0fcom.example.Car\x93\x05color\x09modelName\x07mileage\x60"));
+    let value = Deserializer::new(&buf).read_value().unwrap();
+    assert_eq!(value, car().to_value());
+    assert_eq!(Car::from_value(value).unwrap(), car());
+}
+
+#[test]
+fn test_value() {
+    let garage = Garage {
+        cars: vec![car(), car()],
+    };
+    let value = Value::from(garage.clone());
+    assert_eq!(
+        value,
+        hessian!("Garage" => {
+            "cars": [
+                "com.example.Car" => { "color": "red", "modelName": "corvette", "mileage": 65536i64 },
+                "com.example.Car" => { "color": "red", "modelName": "corvette", "mileage": 65536i64 },
+            ]
+        })
+    );
+    assert_eq!(Garage::try_from(value).unwrap(), garage);
+
+    let err = Car::from_value(hessian!({ "color": "red", "mileage": 1 })).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("missing field modelName of com.example.Car"));
+    assert!(Car::from_value(Value::Int(1)).is_err());
+}
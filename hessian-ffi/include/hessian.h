@@ -0,0 +1,99 @@
+/* C ABI of the hessian codec, see hessian-ffi/src/lib.rs. */
+
+#ifndef HESSIAN_H
+#define HESSIAN_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum HessianStatus {
+    HESSIAN_OK = 0,
+    HESSIAN_NULL_POINTER = 1,
+    HESSIAN_DECODE_ERROR = 2,
+    HESSIAN_ENCODE_ERROR = 3,
+    HESSIAN_INVALID_VALUE = 4,
+} HessianStatus;
+
+typedef enum HessianTag {
+    HESSIAN_NULL = 0,
+    HESSIAN_BOOL = 1,
+    HESSIAN_INT = 2,
+    HESSIAN_LONG = 3,
+    HESSIAN_DOUBLE = 4,
+    /* milliseconds since the epoch in long_value */
+    HESSIAN_DATE = 5,
+    HESSIAN_BYTES = 6,
+    /* UTF-8 in bytes, not NUL terminated */
+    HESSIAN_STRING = 7,
+    HESSIAN_REF = 8,
+    HESSIAN_LIST = 9,
+    HESSIAN_MAP = 10,
+} HessianTag;
+
+/* A byte buffer, data is NULL for an empty one. */
+typedef struct HessianBytes {
+    uint8_t *data;
+    size_t len;
+} HessianBytes;
+
+typedef struct HessianValue HessianValue;
+typedef struct HessianEntry HessianEntry;
+
+/* A list, type_name.data is NULL when the list is untyped. */
+typedef struct HessianList {
+    HessianBytes type_name;
+    HessianValue *items;
+    size_t len;
+} HessianList;
+
+/* A map, type_name.data is NULL when the map is untyped. */
+typedef struct HessianMap {
+    HessianBytes type_name;
+    HessianEntry *entries;
+    size_t len;
+} HessianMap;
+
+typedef union HessianData {
+    bool boolean;
+    int32_t int_value;
+    int64_t long_value;
+    double double_value;
+    uint32_t ref_value;
+    HessianBytes bytes;
+    HessianList list;
+    HessianMap map;
+} HessianData;
+
+struct HessianValue {
+    HessianTag tag;
+    HessianData data;
+};
+
+struct HessianEntry {
+    HessianValue key;
+    HessianValue value;
+};
+
+/* The message of the last error of this thread, or NULL. */
+const char *hessian_last_error(void);
+
+/* Decode the first value of buf, free it with hessian_value_free. */
+HessianStatus hessian_decode(const uint8_t *buf, size_t len, HessianValue *out);
+
+/* Encode value, free the output with hessian_buffer_free. */
+HessianStatus hessian_encode(const HessianValue *value, HessianBytes *out);
+
+void hessian_value_free(HessianValue *value);
+
+void hessian_buffer_free(HessianBytes *bytes);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* HESSIAN_H */
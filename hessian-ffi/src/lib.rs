@@ -0,0 +1,471 @@
+//! C ABI of the hessian codec, declared in `include/hessian.h`.
+//!
+//! Values are trees of `HessianValue`, a tag and a union of payloads.
+//! Decoded trees are owned by the library and freed with
+//! `hessian_value_free`, encoded buffers with `hessian_buffer_free`.
+//! Trees passed to `hessian_encode` are owned by the caller.
+//!
+//! Every function returns a `HessianStatus`, the message of the last error
+//! of the calling thread is returned by `hessian_last_error`.
+
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use hessian_rs::value::{List, Map};
+use hessian_rs::Value;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HessianStatus {
+    Ok = 0,
+    NullPointer = 1,
+    DecodeError = 2,
+    EncodeError = 3,
+    InvalidValue = 4,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HessianTag {
+    Null = 0,
+    Bool = 1,
+    Int = 2,
+    Long = 3,
+    Double = 4,
+    /// milliseconds since the epoch in `long_value`
+    Date = 5,
+    Bytes = 6,
+    /// UTF-8 in `bytes`, not NUL terminated
+    String = 7,
+    Ref = 8,
+    List = 9,
+    Map = 10,
+}
+
+/// A byte buffer, `data` is null for an empty one.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct HessianBytes {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// A list, `type_name.data` is null when the list is untyped.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct HessianList {
+    pub type_name: HessianBytes,
+    pub items: *mut HessianValue,
+    pub len: usize,
+}
+
+/// A map, `type_name.data` is null when the map is untyped.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct HessianMap {
+    pub type_name: HessianBytes,
+    pub entries: *mut HessianEntry,
+    pub len: usize,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union HessianData {
+    pub boolean: bool,
+    pub int_value: i32,
+    pub long_value: i64,
+    pub double_value: f64,
+    pub ref_value: u32,
+    pub bytes: HessianBytes,
+    pub list: HessianList,
+    pub map: HessianMap,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct HessianValue {
+    pub tag: HessianTag,
+    pub data: HessianData,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct HessianEntry {
+    pub key: HessianValue,
+    pub value: HessianValue,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(status: HessianStatus, msg: String) -> HessianStatus {
+    let msg = CString::new(msg.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+    status
+}
+
+/// Returns the message of the last error of this thread, or null. The
+/// message is valid until the next call failing on this thread.
+#[no_mangle]
+pub extern "C" fn hessian_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |msg| msg.as_ptr()))
+}
+
+/// Decode the first value of `buf` into `out`.
+///
+/// # Safety
+///
+/// `buf` must point to `len` readable bytes and `out` to a writable
+/// `HessianValue`, which must be freed with `hessian_value_free`.
+#[no_mangle]
+pub unsafe extern "C" fn hessian_decode(
+    buf: *const u8,
+    len: usize,
+    out: *mut HessianValue,
+) -> HessianStatus {
+    if (buf.is_null() && len > 0) || out.is_null() {
+        return fail(HessianStatus::NullPointer, "null pointer".to_string());
+    }
+    let input = if len == 0 {
+        &[][..]
+    } else {
+        slice::from_raw_parts(buf, len)
+    };
+    match hessian_rs::from_slice(input) {
+        Ok(value) => {
+            out.write(to_ffi(value));
+            HessianStatus::Ok
+        }
+        Err(err) => fail(HessianStatus::DecodeError, err.to_string()),
+    }
+}
+
+/// Encode `value`, the encoded bytes are written to `out`.
+///
+/// # Safety
+///
+/// `value` must point to a valid tree and `out` to a writable
+/// `HessianBytes`, which must be freed with `hessian_buffer_free`.
+#[no_mangle]
+pub unsafe extern "C" fn hessian_encode(
+    value: *const HessianValue,
+    out: *mut HessianBytes,
+) -> HessianStatus {
+    if value.is_null() || out.is_null() {
+        return fail(HessianStatus::NullPointer, "null pointer".to_string());
+    }
+    let value = match from_ffi(&*value) {
+        Ok(value) => value,
+        Err(msg) => return fail(HessianStatus::InvalidValue, msg),
+    };
+    match hessian_rs::to_vec(&value) {
+        Ok(bytes) => {
+            out.write(bytes_to_ffi(bytes));
+            HessianStatus::Ok
+        }
+        Err(err) => fail(HessianStatus::EncodeError, err.to_string()),
+    }
+}
+
+/// Free a value decoded by `hessian_decode`, it's reset to null.
+///
+/// # Safety
+///
+/// `value` must be null or a value returned by `hessian_decode` not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn hessian_value_free(value: *mut HessianValue) {
+    if let Some(value) = value.as_mut() {
+        free_value(value);
+        value.tag = HessianTag::Null;
+    }
+}
+
+/// Free a buffer returned by `hessian_encode`, it's reset to empty.
+///
+/// # Safety
+///
+/// `bytes` must be null or a buffer returned by `hessian_encode` not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn hessian_buffer_free(bytes: *mut HessianBytes) {
+    if let Some(bytes) = bytes.as_mut() {
+        drop(into_boxed_slice(bytes.data, bytes.len));
+        *bytes = bytes_to_ffi(Vec::new());
+    }
+}
+
+fn into_raw<T>(items: Vec<T>) -> (*mut T, usize) {
+    if items.is_empty() {
+        return (ptr::null_mut(), 0);
+    }
+    let len = items.len();
+    (Box::into_raw(items.into_boxed_slice()) as *mut T, len)
+}
+
+unsafe fn into_boxed_slice<T>(data: *mut T, len: usize) -> Option<Box<[T]>> {
+    if data.is_null() {
+        return None;
+    }
+    Some(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)))
+}
+
+fn bytes_to_ffi(bytes: Vec<u8>) -> HessianBytes {
+    let (data, len) = into_raw(bytes);
+    HessianBytes { data, len }
+}
+
+fn type_to_ffi(tp: Option<String>) -> HessianBytes {
+    match tp {
+        // a typed value has a non null type, even when it's empty
+        Some(tp) if tp.is_empty() => HessianBytes {
+            data: ptr::NonNull::dangling().as_ptr(),
+            len: 0,
+        },
+        Some(tp) => bytes_to_ffi(tp.into_bytes()),
+        None => bytes_to_ffi(Vec::new()),
+    }
+}
+
+fn to_ffi(value: Value) -> HessianValue {
+    let (tag, data) = match value {
+        Value::Null => (HessianTag::Null, HessianData { long_value: 0 }),
+        Value::Bool(b) => (HessianTag::Bool, HessianData { boolean: b }),
+        Value::Int(i) => (HessianTag::Int, HessianData { int_value: i }),
+        Value::Long(l) => (HessianTag::Long, HessianData { long_value: l }),
+        Value::Double(d) => (HessianTag::Double, HessianData { double_value: d }),
+        Value::Date(d) => (HessianTag::Date, HessianData { long_value: d }),
+        Value::Bytes(b) => (
+            HessianTag::Bytes,
+            HessianData {
+                bytes: bytes_to_ffi(b.into_vec()),
+            },
+        ),
+        // C has no tag for them, the bytes are passed as read
+        Value::Unknown(b) => (
+            HessianTag::Bytes,
+            HessianData {
+                bytes: bytes_to_ffi(b),
+            },
+        ),
+        Value::String(s) => (
+            HessianTag::String,
+            HessianData {
+                bytes: bytes_to_ffi(s.into_string().into_bytes()),
+            },
+        ),
+        Value::Ref(r) => (HessianTag::Ref, HessianData { ref_value: r }),
+        v @ Value::Shared(_) => return to_ffi(v.unshared()),
+        Value::List(list) => {
+            let (tp, items) = match list {
+                List::Typed(tp, items) => (Some(tp), items),
+                List::Untyped(items) => (None, items),
+            };
+            let (items, len) = into_raw(items.into_iter().map(to_ffi).collect());
+            let list = HessianList {
+                type_name: type_to_ffi(tp),
+                items,
+                len,
+            };
+            (HessianTag::List, HessianData { list })
+        }
+        Value::Map(map) => {
+            let (tp, entries) = match map {
+                Map::Typed(tp, entries) => (Some(tp), entries),
+                Map::Untyped(entries) => (None, entries),
+            };
+            let entries = entries
+                .into_iter()
+                .map(|(key, value)| HessianEntry {
+                    key: to_ffi(key),
+                    value: to_ffi(value),
+                })
+                .collect();
+            let (entries, len) = into_raw(entries);
+            let map = HessianMap {
+                type_name: type_to_ffi(tp),
+                entries,
+                len,
+            };
+            (HessianTag::Map, HessianData { map })
+        }
+    };
+    HessianValue { tag, data }
+}
+
+unsafe fn free_type(tp: HessianBytes) {
+    if tp.len > 0 {
+        drop(into_boxed_slice(tp.data, tp.len));
+    }
+}
+
+unsafe fn free_value(value: &mut HessianValue) {
+    match value.tag {
+        HessianTag::Bytes | HessianTag::String => drop(into_boxed_slice(
+            value.data.bytes.data,
+            value.data.bytes.len,
+        )),
+        HessianTag::List => {
+            let list = value.data.list;
+            free_type(list.type_name);
+            if let Some(mut items) = into_boxed_slice(list.items, list.len) {
+                items.iter_mut().for_each(|v| free_value(v));
+            }
+        }
+        HessianTag::Map => {
+            let map = value.data.map;
+            free_type(map.type_name);
+            if let Some(mut entries) = into_boxed_slice(map.entries, map.len) {
+                for entry in entries.iter_mut() {
+                    free_value(&mut entry.key);
+                    free_value(&mut entry.value);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+unsafe fn slice_from_ffi<'a, T>(data: *const T, len: usize) -> Result<&'a [T], String> {
+    match (data.is_null(), len) {
+        (_, 0) => Ok(&[]),
+        (true, _) => Err(format!("null pointer with length {}", len)),
+        (false, _) => Ok(slice::from_raw_parts(data, len)),
+    }
+}
+
+unsafe fn string_from_ffi(bytes: HessianBytes) -> Result<String, String> {
+    let bytes = slice_from_ffi(bytes.data, bytes.len)?;
+    String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string())
+}
+
+unsafe fn type_from_ffi(tp: HessianBytes) -> Result<Option<String>, String> {
+    if tp.data.is_null() {
+        Ok(None)
+    } else {
+        string_from_ffi(tp).map(Some)
+    }
+}
+
+unsafe fn from_ffi(value: &HessianValue) -> Result<Value, String> {
+    let data = &value.data;
+    Ok(match value.tag {
+        HessianTag::Null => Value::Null,
+        HessianTag::Bool => Value::Bool(data.boolean),
+        HessianTag::Int => Value::Int(data.int_value),
+        HessianTag::Long => Value::Long(data.long_value),
+        HessianTag::Double => Value::Double(data.double_value),
+        HessianTag::Date => Value::Date(data.long_value),
+        HessianTag::Bytes => Value::Bytes(slice_from_ffi(data.bytes.data, data.bytes.len)?.into()),
+        HessianTag::String => Value::String(string_from_ffi(data.bytes)?.into()),
+        HessianTag::Ref => Value::Ref(data.ref_value),
+        HessianTag::List => {
+            let list = data.list;
+            let items = slice_from_ffi(list.items, list.len)?
+                .iter()
+                .map(|v| from_ffi(v))
+                .collect::<Result<Vec<_>, _>>()?;
+            match type_from_ffi(list.type_name)? {
+                Some(tp) => Value::List(List::from((tp, items))),
+                None => Value::List(List::from(items)),
+            }
+        }
+        HessianTag::Map => {
+            let map = data.map;
+            let entries = slice_from_ffi(map.entries, map.len)?
+                .iter()
+                .map(|e| Ok((from_ffi(&e.key)?, from_ffi(&e.value)?)))
+                .collect::<Result<_, String>>()?;
+            match type_from_ffi(map.type_name)? {
+                Some(tp) => Value::Map(Map::from((tp, entries))),
+                None => Value::Map(Map::from(entries)),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hessian_rs::hessian;
+    use std::ffi::CStr;
+    use std::mem::MaybeUninit;
+
+    unsafe fn decode(input: &[u8]) -> HessianValue {
+        let mut out = MaybeUninit::uninit();
+        let status = hessian_decode(input.as_ptr(), input.len(), out.as_mut_ptr());
+        assert_eq!(status, HessianStatus::Ok);
+        out.assume_init()
+    }
+
+    unsafe fn encode(value: &HessianValue) -> Vec<u8> {
+        let mut out = MaybeUninit::uninit();
+        assert_eq!(hessian_encode(value, out.as_mut_ptr()), HessianStatus::Ok);
+        let mut out = out.assume_init();
+        let bytes = slice_from_ffi(out.data, out.len).unwrap().to_vec();
+        hessian_buffer_free(&mut out);
+        assert!(out.data.is_null());
+        bytes
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let value = hessian!("example.Car" => {
+            "color": "red",
+            "mileage": 65536_i64,
+            "price": 1.5,
+            "parts": "[string" => ["wheel", "door"],
+            "prev": null,
+            "tags": ["fast", true, b"\x01\x02".to_vec(), { "": "" }],
+        });
+        let input = hessian_rs::to_vec(&value).unwrap();
+        unsafe {
+            let mut decoded = decode(&input);
+            assert_eq!(decoded.tag, HessianTag::Map);
+            assert_eq!(decoded.data.map.len, 6);
+            assert_eq!(
+                string_from_ffi(decoded.data.map.type_name).unwrap(),
+                "example.Car"
+            );
+            assert_eq!(from_ffi(&decoded).unwrap(), value);
+            assert_eq!(hessian_rs::from_slice(&encode(&decoded)).unwrap(), value);
+            hessian_value_free(&mut decoded);
+            assert_eq!(decoded.tag, HessianTag::Null);
+
+            let untyped = decode(&[0x79, 0x91]);
+            assert!(untyped.data.list.type_name.data.is_null());
+            assert_eq!(from_ffi(&untyped).unwrap(), hessian!([1]));
+            let typed = decode(&[0x71, 0x00, 0x91]);
+            assert_eq!(from_ffi(&typed).unwrap(), hessian!("" => [1]));
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            let mut out = MaybeUninit::uninit();
+            let status = hessian_decode([0x79].as_ptr(), 1, out.as_mut_ptr());
+            assert_eq!(status, HessianStatus::DecodeError);
+            assert!(!CStr::from_ptr(hessian_last_error()).to_bytes().is_empty());
+            let status = hessian_decode(ptr::null(), 1, out.as_mut_ptr());
+            assert_eq!(status, HessianStatus::NullPointer);
+
+            let value = HessianValue {
+                tag: HessianTag::String,
+                data: HessianData {
+                    bytes: HessianBytes {
+                        data: [0xff_u8].as_ptr() as *mut u8,
+                        len: 1,
+                    },
+                },
+            };
+            let mut out = MaybeUninit::uninit();
+            assert_eq!(
+                hessian_encode(&value, out.as_mut_ptr()),
+                HessianStatus::InvalidValue
+            );
+        }
+    }
+}
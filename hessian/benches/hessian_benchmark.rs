@@ -1,5 +1,5 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
-use hessian_rs::{from_slice, to_vec};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use hessian_rs::{de::Deserializer, from_slice, ser::Serializer, to_vec, value::Definition, Value};
 
 static INPUT: &[u8] = include_bytes!("../tests/fixtures/map/custom_map_type.bin");
 
@@ -18,5 +18,118 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, criterion_benchmark);
+// 1000 objects of one class, the first carries the definition
+fn objects() -> Vec<u8> {
+    let def = Definition {
+        name: "example.Car".to_string(),
+        fields: vec![
+            "color".to_string(),
+            "model".to_string(),
+            "mileage".to_string(),
+        ],
+    };
+    let mut buf = Vec::new();
+    let mut ser = Serializer::new(&mut buf);
+    ser.write_list_begin(1000, None).unwrap();
+    for i in 0..1000 {
+        let fields = [Value::from("red"), Value::from("corvette"), Value::Int(i)];
+        ser.serialize_fields_with_definition(&def, &fields).unwrap();
+    }
+    buf
+}
+
+// A list holding a map holding a list.. `depth` containers deep
+fn nested(depth: usize) -> Value {
+    (0..depth).fold(Value::Int(1), |v, i| {
+        if i % 2 == 0 {
+            hessian_rs::hessian!([v])
+        } else {
+            hessian_rs::hessian!({ "next": v })
+        }
+    })
+}
+
+// 10000 maps of short string keys and values, like records of codes
+fn short_strings() -> Value {
+    let records: Vec<_> = (0..10_000)
+        .map(|i| {
+            hessian_rs::hessian!({
+                "id": (format!("id-{}", i)),
+                "status": "active",
+                "region": (format!("region-{}", i % 16))
+            })
+        })
+        .collect();
+    Value::List(records.into())
+}
+
+// 10000 binaries of 16 bytes, like UUIDs
+fn short_binaries() -> Value {
+    let ids: Vec<_> = (0..10_000_u32)
+        .map(|i| Value::Bytes(i.to_be_bytes().repeat(4).into()))
+        .collect();
+    Value::List(ids.into())
+}
+
+// Values decoded and encoded by the `payload` benchmarks, by name
+fn payloads() -> Vec<(&'static str, Value)> {
+    let ints: Vec<_> = (0..100_000).map(Value::Int).collect();
+    vec![
+        ("typed_list", Value::List(("[int", ints).into())),
+        // several chunks, with multibyte chars
+        ("string", Value::from("abc中文😀".repeat(20_000))),
+        ("ascii_string", Value::from("abcdefgh".repeat(40_000))),
+        ("binary", Value::Bytes(vec![0x5a; 1 << 20].into())),
+        ("nested", nested(200)),
+        // short enough to be kept inline with the smol_str and smallvec
+        // features
+        ("short_strings", short_strings()),
+        ("short_binaries", short_binaries()),
+    ]
+}
+
+fn payload_benchmark(c: &mut Criterion) {
+    for (name, value) in payloads() {
+        let buf = to_vec(&value).unwrap();
+        let mut group = c.benchmark_group("payload");
+        group.throughput(Throughput::Bytes(buf.len() as u64));
+        group.bench_with_input(BenchmarkId::new("decode", name), &buf, |b, buf| {
+            b.iter(|| from_slice(black_box(buf)).unwrap())
+        });
+        #[cfg(feature = "arena")]
+        group.bench_with_input(BenchmarkId::new("decode_arena", name), &buf, |b, buf| {
+            let mut bump = hessian_rs::value::Bump::new();
+            b.iter(|| {
+                hessian_rs::from_slice_in(black_box(buf), &bump).unwrap();
+                bump.reset();
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("encode", name), &value, |b, value| {
+            b.iter(|| to_vec(black_box(value)).unwrap())
+        });
+        group.finish();
+    }
+
+    let buf = objects();
+    let mut group = c.benchmark_group("payload");
+    group.throughput(Throughput::Bytes(buf.len() as u64));
+    group.bench_with_input(BenchmarkId::new("decode", "objects"), &buf, |b, buf| {
+        b.iter(|| from_slice(black_box(buf)).unwrap())
+    });
+    group.bench_with_input(
+        BenchmarkId::new("decode_interned", "objects"),
+        &buf,
+        |b, buf| {
+            b.iter(|| {
+                Deserializer::new(black_box(buf))
+                    .intern_keys(true)
+                    .read_value()
+                    .unwrap()
+            })
+        },
+    );
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark, payload_benchmark);
 criterion_main!(benches);
@@ -0,0 +1,306 @@
+//! Split a byte stream into values, for framed transports.
+//!
+//! `HessianCodec` decodes from a buffer which may end in the middle of a
+//! value and reports how many bytes it consumed. With the `tokio-codec`
+//! feature it's a `tokio_util::codec::Decoder` and `Encoder` of values,
+//! e.g. for a `Framed` socket.
+//!
+//! Every value is a message of its own, types and class definitions aren't
+//! shared between them, see `SessionCodec` for that.
+
+use std::convert::TryFrom;
+use std::io;
+
+use crate::de::Deserializer;
+use crate::error::{Error, Result};
+use crate::parser::ValueParser;
+use crate::ser::SerializerBuilder;
+use crate::value::Value;
+#[cfg(feature = "tokio-codec")]
+use bytes::{Buf, BufMut, BytesMut};
+
+// Bytes of the length prefix of a frame
+const PREFIX_LEN: usize = 4;
+
+pub struct HessianCodec {
+    length_prefixed: bool,
+    max_frame_length: usize,
+    builder: SerializerBuilder,
+    // The values of an unprefixed stream, parsed as it comes
+    parser: ValueParser,
+    // The position in the stream of the start of `src`
+    start: u64,
+    // The bytes at the start of `src` fed to the parser already
+    fed: usize,
+}
+
+impl Default for HessianCodec {
+    fn default() -> Self {
+        HessianCodec {
+            length_prefixed: false,
+            max_frame_length: 8 * 1024 * 1024,
+            builder: SerializerBuilder::default(),
+            parser: ValueParser::new().separate_tables(),
+            start: 0,
+            fed: 0,
+        }
+    }
+}
+
+impl HessianCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prefix every value with its length, a big-endian u32, instead of
+    /// writing the values back to back. Disabled by default.
+    pub fn length_prefixed(mut self, length_prefixed: bool) -> Self {
+        self.length_prefixed = length_prefixed;
+        self
+    }
+
+    /// The length of the largest value decoded, 8 MiB by default. A larger
+    /// one fails to decode rather than being buffered until it's complete.
+    pub fn max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    /// Encode values with the options of `builder`.
+    pub fn serializer_options(mut self, builder: SerializerBuilder) -> Self {
+        self.builder = builder;
+        self
+    }
+
+    /// Decode the value at the start of `src`, returns it with the number
+    /// of bytes it takes, or `None` when `src` doesn't hold all of it yet.
+    ///
+    /// `src` is the input of the previous call without the bytes of the
+    /// value returned, followed by the bytes received since, as a transport
+    /// buffers them. The bytes of an unprefixed value fed by a previous call
+    /// aren't parsed again.
+    pub fn decode(&mut self, src: &[u8]) -> Result<Option<(Value, usize)>> {
+        if !self.length_prefixed {
+            return self.decode_unprefixed(src);
+        }
+        if src.len() < PREFIX_LEN {
+            return Ok(None);
+        }
+        let mut prefix = [0; PREFIX_LEN];
+        prefix.copy_from_slice(&src[..PREFIX_LEN]);
+        let len = u32::from_be_bytes(prefix) as usize;
+        if len > self.max_frame_length {
+            return Err(self.too_long(len));
+        }
+        let frame = match src.get(PREFIX_LEN..PREFIX_LEN + len) {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        let mut de = Deserializer::new(frame);
+        let value = de.read_value()?;
+        if de.position() as usize != len {
+            return Err(invalid_data(format!(
+                "{} trailing bytes in a frame",
+                len - de.position() as usize
+            )));
+        }
+        Ok(Some((value, PREFIX_LEN + len)))
+    }
+
+    fn decode_unprefixed(&mut self, src: &[u8]) -> Result<Option<(Value, usize)>> {
+        if src.len() < self.fed {
+            // not the input of the previous call
+            self.restart();
+        }
+        let fed = self.parser.feed(&src[self.fed..]);
+        self.fed = src.len();
+        let lost = fed.is_err();
+        let result = match self.parser.next_value_end() {
+            Some((value, end)) => {
+                let len = (end - self.start) as usize;
+                self.start = end;
+                self.fed -= len;
+                match len > self.max_frame_length {
+                    true => Err(self.too_long(len)),
+                    false => Ok(Some((value, len))),
+                }
+            }
+            None => match fed {
+                Err(e) => Err(e),
+                Ok(()) if self.fed > self.max_frame_length => Err(self.too_long(self.fed)),
+                Ok(()) => Ok(None),
+            },
+        };
+        // the parser is lost after an error, what follows the value returned
+        // is parsed from its start by the next call and fails there
+        if lost || result.is_err() {
+            self.restart();
+        }
+        result
+    }
+
+    fn restart(&mut self) {
+        self.parser = ValueParser::new().separate_tables();
+        self.start = 0;
+        self.fed = 0;
+    }
+
+    /// Write `value` to `dst`, prefixed with its length when enabled.
+    pub fn encode<W: io::Write>(&mut self, value: &Value, mut dst: W) -> Result<()> {
+        if !self.length_prefixed {
+            return self.builder.build(dst).serialize_value(value);
+        }
+        let mut buf = Vec::new();
+        self.builder.build(&mut buf).serialize_value(value)?;
+        let len = u32::try_from(buf.len())
+            .map_err(|_| invalid_data(format!("frame of {} bytes is too long", buf.len())))?;
+        dst.write_all(&len.to_be_bytes())?;
+        dst.write_all(&buf)?;
+        Ok(())
+    }
+
+    fn too_long(&self, len: usize) -> Error {
+        invalid_data(format!(
+            "frame of {} bytes exceeds the maximum of {}",
+            len, self.max_frame_length
+        ))
+    }
+}
+
+fn invalid_data(msg: String) -> Error {
+    Error::IoError(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+#[cfg(feature = "tokio-codec")]
+impl tokio_util::codec::Decoder for HessianCodec {
+    type Item = Value;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Value>> {
+        Ok(HessianCodec::decode(self, src)?.map(|(value, len)| {
+            src.advance(len);
+            value
+        }))
+    }
+}
+
+#[cfg(feature = "tokio-codec")]
+impl tokio_util::codec::Encoder<Value> for HessianCodec {
+    type Error = Error;
+
+    fn encode(&mut self, value: Value, dst: &mut BytesMut) -> Result<()> {
+        HessianCodec::encode(self, &value, dst.writer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HessianCodec;
+    use crate::Value;
+
+    // Feed `input` to `codec` a byte at a time, like a transport would
+    fn decode_all(codec: &mut HessianCodec, input: &[u8]) -> Vec<Value> {
+        let mut buf = Vec::new();
+        let mut values = Vec::new();
+        for b in input {
+            buf.push(*b);
+            while let Some((value, len)) = codec.decode(&buf).unwrap() {
+                buf.drain(..len);
+                values.push(value);
+            }
+        }
+        assert!(buf.is_empty());
+        values
+    }
+
+    #[test]
+    fn test_codec() {
+        let values = vec![
+            Value::Int(1),
+            hessian!("example.Car" => { "color": "red" }),
+            Value::from("x".repeat(100)),
+        ];
+        for length_prefixed in [false, true] {
+            let mut codec = HessianCodec::new().length_prefixed(length_prefixed);
+            let mut buf = Vec::new();
+            for v in values.iter() {
+                codec.encode(v, &mut buf).unwrap();
+            }
+            assert_eq!(decode_all(&mut codec, &buf), values);
+        }
+
+        let mut codec = HessianCodec::new().length_prefixed(true);
+        let mut buf = Vec::new();
+        codec.encode(&Value::Int(1), &mut buf).unwrap();
+        assert_eq!(buf, [0, 0, 0, 1, 0x91]);
+        assert!(codec.decode(&[0, 0, 0, 2, 0x91, 0x91]).is_err());
+
+        let mut codec = HessianCodec::new().max_frame_length(4);
+        assert!(codec.decode(&[0x91]).unwrap().is_some());
+        assert!(codec.decode(&[0x57, 0x91, 0x91, 0x91, 0x91]).is_err());
+        // a value received at once
+        assert!(codec.decode(&[0x04, b'a', b'b', b'c', b'd']).is_err());
+        assert!(codec.decode(&[0x03, b'a', b'b', b'c']).unwrap().is_some());
+        let mut codec = codec.length_prefixed(true);
+        assert!(codec.decode(&[0, 0, 0, 5]).is_err());
+    }
+
+    #[test]
+    fn test_codec_messages() {
+        // every value defines its classes from the first ref
+        let values = vec![
+            hessian!("example.Car" => { "color": "red" }),
+            hessian!("example.Plane" => { "wings": 2 }),
+            hessian!("example.Car" => { "color": "green" }),
+        ];
+        let mut codec = HessianCodec::new();
+        let mut buf = Vec::new();
+        for v in values.iter() {
+            codec.encode(v, &mut buf).unwrap();
+        }
+        assert_eq!(decode_all(&mut codec, &buf), values);
+        let mut decoded = Vec::new();
+        let mut src = &buf[..];
+        while let Some((value, len)) = codec.decode(src).unwrap() {
+            decoded.push(value);
+            src = &src[len..];
+        }
+        assert_eq!(decoded, values);
+
+        // the value before an error is decoded
+        let mut codec = HessianCodec::new();
+        assert_eq!(
+            codec.decode(&[0x91, b'Z']).unwrap(),
+            Some((Value::Int(1), 1))
+        );
+        assert!(codec.decode(b"Z").is_err());
+        assert_eq!(codec.decode(&[0x92]).unwrap(), Some((Value::Int(2), 1)));
+    }
+
+    #[cfg(feature = "tokio-codec")]
+    #[test]
+    fn test_tokio_codec() {
+        use bytes::BytesMut;
+        use tokio_util::codec::{Decoder, Encoder};
+
+        for length_prefixed in [false, true] {
+            let mut codec = HessianCodec::new().length_prefixed(length_prefixed);
+            let mut buf = BytesMut::new();
+            Encoder::encode(&mut codec, Value::Int(1), &mut buf).unwrap();
+            Encoder::encode(&mut codec, Value::from("abc"), &mut buf).unwrap();
+            let second = buf.split_off(buf.len() - 2);
+            assert_eq!(
+                Decoder::decode(&mut codec, &mut buf).unwrap(),
+                Some(Value::Int(1))
+            );
+            // the start of the string
+            assert_eq!(Decoder::decode(&mut codec, &mut buf).unwrap(), None);
+            buf.unsplit(second);
+            assert_eq!(
+                Decoder::decode(&mut codec, &mut buf).unwrap(),
+                Some(Value::from("abc"))
+            );
+            assert!(buf.is_empty());
+        }
+    }
+}
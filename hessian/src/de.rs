@@ -1,5 +1,8 @@
-use std::collections::HashMap;
-use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::collections::hash_map::{Entry, HashMap};
+use std::convert::TryFrom;
+use std::io::{self, Cursor, Read};
+use std::ops::Range;
+use std::sync::Arc;
 
 use byteorder::{BigEndian, ReadBytesExt};
 
@@ -7,13 +10,80 @@ use super::constant::{
     Binary, ByteCodecType, Date, Double, Integer, List, Long, Object, String as StringType,
 };
 use super::error::Error::SyntaxError;
-use super::error::{ErrorKind, Result};
-use super::value::{self, Definition, Value};
+use super::error::{Error, ErrorKind, Result};
+use super::value::{self, Definition, Str, Value};
+
+#[cfg(feature = "arena")]
+mod arena;
+mod reader;
+
+#[cfg(feature = "arena")]
+pub use arena::from_slice_in;
+pub use reader::{from_reader, ReaderDeserializer};
+
+/// The class definitions and types a deserializer has read, in ref order.
+///
+/// Some servers send a class definition once per session rather than once
+/// per message, `Deserializer::into_tables` and `with_tables` carry them from
+/// the deserializer of a message to the one of the next.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tables {
+    pub definitions: Vec<Definition>,
+    pub types: Vec<String>,
+}
+
+/// What to do with a key repeated in a map, which buggy producers write,
+/// see `Deserializer::duplicate_keys`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// Keep the value of the first entry with the key
+    First,
+    /// Keep the value of the last entry with the key, as `HashMap::insert`
+    #[default]
+    Last,
+    /// Fail with `ErrorKind::DuplicateKey`
+    Error,
+}
+
+/// Replaces or drops values as `Deserializer::read_value` decodes them,
+/// e.g. to mask the passwords of captured traffic without walking the
+/// values once decoded, see `Deserializer::hook`.
+pub trait DecodeHook {
+    /// A string which isn't a map key. Returns it by default.
+    fn on_string(&mut self, s: Str) -> Value {
+        Value::String(s)
+    }
+
+    /// The value of a field of an object of the class `class`, or of an
+    /// entry of a map whose key is a string, `class` being the type of the
+    /// map. Returns the value to keep, `None` drops the field. The value
+    /// went through the hook already. Returns it by default.
+    fn on_field(&mut self, class: Option<&str>, field: &str, value: Value) -> Option<Value> {
+        let _ = (class, field);
+        Some(value)
+    }
+}
 
 pub struct Deserializer<R: AsRef<[u8]>> {
     buffer: Cursor<R>,
     type_references: Vec<String>,
-    class_references: Vec<Definition>,
+    // Shared, so objects can be read without copying the field names
+    class_references: Vec<Arc<Definition>>,
+    cesu8: bool,
+    allow_nan: bool,
+    duplicate_keys: DuplicateKeys,
+    permissive: bool,
+    // The length the input must reach before a read which ran out of input
+    // can succeed, so `attempt` doesn't parse the same bytes again for
+    // every piece fed
+    wanted: usize,
+    // The string map keys and field names read so far by their UTF-8
+    // bytes, when keys are interned
+    keys: Option<HashMap<Box<[u8]>, Str>>,
+    // The interned field names of the definitions by index, so objects
+    // don't look them up
+    field_keys: Vec<Vec<Str>>,
+    hook: Option<Box<dyn DecodeHook + Send>>,
 }
 
 impl<R: AsRef<[u8]>> Deserializer<R> {
@@ -22,9 +92,116 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
             buffer: Cursor::new(rd),
             type_references: Vec::new(),
             class_references: Vec::new(),
+            cesu8: false,
+            allow_nan: true,
+            duplicate_keys: DuplicateKeys::default(),
+            permissive: false,
+            wanted: 0,
+            keys: None,
+            field_keys: Vec::new(),
+            hook: None,
+        }
+    }
+
+    /// Decode surrogate pairs written as two 3-byte sequences (CESU-8), as
+    /// java writes chars outside the BMP. Disabled by default, such strings
+    /// fail to decode as invalid UTF-8.
+    pub fn cesu8(mut self, cesu8: bool) -> Self {
+        self.cesu8 = cesu8;
+        self
+    }
+
+    /// Accept NaN and the infinities, enabled by default. When disabled
+    /// reading them fails like reading a value of an unexpected type.
+    pub fn allow_nan(mut self, allow_nan: bool) -> Self {
+        self.allow_nan = allow_nan;
+        self
+    }
+
+    /// What to do with a key repeated in a map, the last value is kept by
+    /// default. `read_value_in` keeps every entry whatever the policy.
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// Read a value starting with a tag hessian doesn't define as a
+    /// `Value::Unknown` of the tag and the undefined tags right after it,
+    /// rather than failing, so tooling can inspect a partially corrupt
+    /// payload. Skipping them is a guess, the values after may be read
+    /// wrong. Disabled by default, `read_value_in` fails whatever the option.
+    pub fn permissive(mut self, permissive: bool) -> Self {
+        self.permissive = permissive;
+        self
+    }
+
+    /// Pass the strings and the fields `read_value` reads to `hook`, which
+    /// may replace or drop them. The values read otherwise, e.g. by
+    /// `read_value_in` or `skip_value`, aren't passed.
+    pub fn hook<H: DecodeHook + Send + 'static>(mut self, hook: H) -> Self {
+        self.hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Share the storage of repeated string map keys and object field names,
+    /// e.g. of a list of objects or of maps of the same shape, rather than
+    /// allocating each of them. Disabled by default, as each key costs a
+    /// lookup.
+    pub fn intern_keys(mut self, intern_keys: bool) -> Self {
+        self.keys = match intern_keys {
+            true => Some(HashMap::new()),
+            false => None,
+        };
+        self
+    }
+
+    /// Use class definitions read beforehand, e.g. agreed on for a session
+    /// like `Serializer::preload_definitions`. They take the refs
+    /// `0..defs.len()` in order, definitions read afterwards follow them.
+    pub fn with_definitions<I: IntoIterator<Item = Definition>>(mut self, defs: I) -> Self {
+        self.class_references = defs.into_iter().map(Arc::new).collect();
+        self
+    }
+
+    /// Use the definitions and types of a previous deserializer, see `Tables`.
+    pub fn with_tables(mut self, tables: Tables) -> Self {
+        self.class_references = tables.definitions.into_iter().map(Arc::new).collect();
+        self.type_references = tables.types;
+        self
+    }
+
+    /// The class definitions known to this deserializer in ref order,
+    /// including the ones it started with, e.g. to report the java classes
+    /// a payload refers to. `skip_value` records them as well, so they can
+    /// be listed without building the values.
+    pub fn definitions(&self) -> impl ExactSizeIterator<Item = &Definition> + '_ {
+        self.class_references.iter().map(|def| &**def)
+    }
+
+    /// Returns the definitions and types known to this deserializer,
+    /// including the ones it started with.
+    pub fn into_tables(self) -> Tables {
+        Tables {
+            definitions: self
+                .class_references
+                .into_iter()
+                .map(|def| Arc::try_unwrap(def).unwrap_or_else(|def| (*def).clone()))
+                .collect(),
+            types: self.type_references,
         }
     }
 
+    /// Returns the input.
+    pub fn get_ref(&self) -> &R {
+        self.buffer.get_ref()
+    }
+
+    /// Returns the offset of the next byte to read.
+    #[inline]
+    pub fn position(&self) -> u64 {
+        self.buffer.position()
+    }
+
     fn error<T>(&self, err: ErrorKind) -> Result<T> {
         Err(SyntaxError(err))
     }
@@ -34,67 +211,137 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
         Ok(self.buffer.read_u8()?)
     }
 
+    /// Read the next `n` bytes as is, e.g. a framing header or a fragment
+    /// to pass through without decoding it.
+    #[inline]
+    pub fn read_raw(&mut self, n: usize) -> Result<Vec<u8>> {
+        self.read_bytes(n)
+    }
+
     #[inline]
     fn read_bytes(&mut self, n: usize) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-        match self.buffer.by_ref().take(n as u64).read_to_end(&mut buf)? {
-            m if m == n => Ok(buf),
-            _ => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Unexpected EOF").into()),
-        }
+        Ok(self.read_slice(n)?.to_vec())
     }
 
+    // Copy the next `n` bytes of a binary chunk to `buf`
     #[inline]
     fn read_bytes_into(&mut self, buf: &mut Vec<u8>, n: usize) -> Result<()> {
-        match self.buffer.by_ref().take(n as u64).read_to_end(buf)? {
-            m if m == n => Ok(()),
-            _ => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Unexpected EOF").into()),
+        buf.extend_from_slice(self.read_chunk(n)?);
+        Ok(())
+    }
+
+    // The next `n` bytes of a binary chunk
+    #[inline]
+    fn read_chunk(&mut self, n: usize) -> Result<&[u8]> {
+        if n > self.remaining() {
+            let end = self.buffer.position() as usize + n;
+            return Err(self.chunk_eof_before(end));
         }
+        self.read_slice(n)
     }
 
+    // The next `n` bytes of the underlying slice
     #[inline]
-    pub fn peek_byte(&mut self) -> Result<u8> {
-        let tag = self.buffer.read_u8()?;
-        self.buffer.seek(SeekFrom::Current(-1))?;
-        Ok(tag)
+    fn read_slice(&mut self, n: usize) -> Result<&[u8]> {
+        let pos = self.buffer.position() as usize;
+        if n > self.remaining() {
+            return Err(self.eof_before(pos + n));
+        }
+        self.buffer.set_position((pos + n) as u64);
+        Ok(&self.buffer.get_ref().as_ref()[pos..pos + n])
+    }
+
+    // The error of the input ending before `end`, the offset the value being
+    // read can't end before
+    #[cold]
+    fn eof_before(&mut self, end: usize) -> Error {
+        self.wanted = self.wanted.max(end);
+        unexpected_eof()
     }
 
+    // `eof_before`, in the data of a string or binary chunk
+    #[cold]
+    fn chunk_eof_before(&mut self, end: usize) -> Error {
+        self.wanted = self.wanted.max(end);
+        SyntaxError(ErrorKind::UnexpectedEofInChunk)
+    }
+
+    // The number of bytes left to read
     #[inline]
-    pub fn peek_byte_code_type(&mut self) -> Result<ByteCodecType> {
+    fn remaining(&self) -> usize {
+        let len = self.buffer.get_ref().as_ref().len() as u64;
+        len.saturating_sub(self.buffer.position()) as usize
+    }
+
+    // The capacity to allocate for `n` items of at least a byte each, no
+    // more than the bytes left so a bogus length doesn't allocate
+    #[inline]
+    fn capacity_hint(&self, n: usize) -> usize {
+        n.min(self.remaining())
+    }
+
+    /// Returns the next byte without reading it. The input is its own
+    /// lookahead, nothing is read back or seeked.
+    #[inline]
+    pub fn peek_byte(&self) -> Result<u8> {
+        let pos = self.buffer.position() as usize;
+        match self.buffer.get_ref().as_ref().get(pos) {
+            Some(b) => Ok(*b),
+            None => Err(unexpected_eof()),
+        }
+    }
+
+    /// Returns the type of the next value without reading it.
+    #[inline]
+    pub fn peek_byte_code_type(&self) -> Result<ByteCodecType> {
         let tag = self.peek_byte()?;
         Ok(ByteCodecType::from(tag))
     }
 
     pub fn read_definition(&mut self) -> Result<()> {
         // TODO(lynskylate@gmail.com): optimize error
-        let name = match self.read_value() {
-            Ok(Value::String(n)) => Ok(n),
-            _ => self.error(ErrorKind::UnknownType),
-        }?;
-        let length = match self.read_value() {
-            Ok(Value::Int(l)) => Ok(l),
-            _ => self.error(ErrorKind::UnknownType),
-        }?;
+        let name = match self.read_plain_value()? {
+            Value::String(n) => n.into_string(),
+            v => return self.error(ErrorKind::UnexpectedType(v.to_string())),
+        };
+        let length = match self.read_value()? {
+            Value::Int(l) => l,
+            v => return self.error(ErrorKind::UnexpectedType(v.to_string())),
+        };
 
-        let mut fields = Vec::new();
+        let mut fields = Vec::with_capacity(self.capacity_hint(length.max(0) as usize));
 
         for _ in 0..length {
-            match self.read_value() {
-                Ok(Value::String(s)) => fields.push(s),
-                Ok(v) => {
+            match self.read_plain_value()? {
+                Value::String(s) => fields.push(s.into_string()),
+                v => {
                     return self.error(ErrorKind::UnexpectedType(v.to_string()));
                 }
-                _ => {
-                    return self.error(ErrorKind::UnknownType);
-                }
             }
         }
 
-        self.class_references.push(Definition { name, fields });
+        self.class_references
+            .push(Arc::new(Definition { name, fields }));
         Ok(())
     }
 
     #[inline]
     pub fn read_definition_id(&mut self, tag: Object) -> Result<&Definition> {
+        let index = self.read_definition_index(tag)?;
+        Ok(&self.class_references[index])
+    }
+
+    /// Like `read_definition_id`, but the definition can be kept while the
+    /// fields of the object are read, rather than cloned.
+    #[inline]
+    pub fn read_shared_definition(&mut self, tag: Object) -> Result<Arc<Definition>> {
+        let index = self.read_definition_index(tag)?;
+        Ok(Arc::clone(&self.class_references[index]))
+    }
+
+    // The index in `class_references` of the definition an object refers to
+    #[inline]
+    fn read_definition_index(&mut self, tag: Object) -> Result<usize> {
         let ref_id = match tag {
             Object::Compact(b) => b as usize - 0x60,
             Object::Normal => {
@@ -105,9 +352,11 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
                 }
             }
         };
-        self.class_references
-            .get(ref_id)
-            .ok_or(SyntaxError(ErrorKind::OutOfDefinitionRange(ref_id)))
+        if ref_id < self.class_references.len() {
+            Ok(ref_id)
+        } else {
+            Err(SyntaxError(ErrorKind::OutOfDefinitionRange(ref_id)))
+        }
     }
 
     /// Read an object from buffer
@@ -140,24 +389,33 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
     /// The integer value refers to the object definition.
     ///
     fn read_object(&mut self, tag: Object) -> Result<Value> {
-        let definition = self.read_definition_id(tag)?;
-
-        let Definition { name, fields } = definition.clone();
-        let mut map = HashMap::new();
-        for k in fields {
-            let v = self.read_value()?;
-            map.insert(Value::String(k), v);
+        let index = self.read_definition_index(tag)?;
+        let def = Arc::clone(&self.class_references[index]);
+        let mut map = HashMap::with_capacity(def.fields.len());
+        for (i, field) in def.fields.iter().enumerate() {
+            let key = match self.keys {
+                Some(_) => self.field_key(index, i),
+                None => field.as_str().into(),
+            };
+            let value = self.read_value()?;
+            let value = match &mut self.hook {
+                Some(hook) => match hook.on_field(Some(&def.name), field, value) {
+                    Some(value) => value,
+                    None => continue,
+                },
+                None => value,
+            };
+            map.insert(Value::String(key), value);
         }
-        Ok(Value::Map((name, map).into()))
+        Ok(Value::Map((def.name.clone(), map).into()))
     }
 
-    fn read_long_binary(&mut self, tag: u8) -> Result<Value> {
-        let mut buf = Vec::new();
+    fn read_long_binary_into(&mut self, buf: &mut Vec<u8>, tag: u8) -> Result<()> {
         let mut tag = tag;
         // Get non-final chunk starts with 'A'
         while tag == 0x41 {
-            let length = self.buffer.read_i16::<BigEndian>()? as usize;
-            self.read_bytes_into(&mut buf, length)?;
+            let length = self.buffer.read_u16::<BigEndian>()? as usize;
+            self.read_bytes_into(buf, length)?;
             tag = self.read_byte()?;
         }
 
@@ -165,18 +423,18 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
         match tag {
             b'B' => {
                 // Get the last chunk starts with 'B'
-                let length = self.buffer.read_i16::<BigEndian>()? as usize;
-                self.read_bytes_into(&mut buf, length)?;
+                let length = self.buffer.read_u16::<BigEndian>()? as usize;
+                self.read_bytes_into(buf, length)?;
             }
-            0x20..=0x2f => self.read_bytes_into(&mut buf, (tag - 0x20) as usize)?,
+            0x20..=0x2f => self.read_bytes_into(buf, (tag - 0x20) as usize)?,
             0x34..=0x37 => {
                 let second_byte = self.read_byte()?;
                 let length = i16::from_be_bytes([tag - 0x34, second_byte]) as usize;
-                self.read_bytes_into(&mut buf, length)?;
+                self.read_bytes_into(buf, length)?;
             }
-            _ => { /* TODO: error */ }
+            _ => return self.error(ErrorKind::InvalidChunkContinuation),
         }
-        Ok(Value::Bytes(buf))
+        Ok(())
     }
 
     /// read bytes from buffer
@@ -202,14 +460,20 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
     /// len = code - 0x20
     ///
     fn read_binary(&mut self, bin: Binary) -> Result<Value> {
+        let mut buf = Vec::new();
+        self.read_binary_into(&mut buf, bin)?;
+        Ok(Value::Bytes(buf.into()))
+    }
+
+    fn read_binary_into(&mut self, buf: &mut Vec<u8>, bin: Binary) -> Result<()> {
         match bin {
-            Binary::Short(b) => Ok(Value::Bytes(self.read_bytes((b - 0x20) as usize)?)),
+            Binary::Short(b) => self.read_bytes_into(buf, (b - 0x20) as usize),
             Binary::TwoOctet(b) => {
                 let second_byte = self.read_byte()?;
-                let v = self.read_bytes(i16::from_be_bytes([b - 0x34, second_byte]) as usize)?;
-                Ok(Value::Bytes(v))
+                let length = i16::from_be_bytes([b - 0x34, second_byte]) as usize;
+                self.read_bytes_into(buf, length)
             }
-            Binary::Long(b) => self.read_long_binary(b),
+            Binary::Long(b) => self.read_long_binary_into(buf, b),
         }
     }
 
@@ -251,24 +515,23 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
     /// ```
     ///
     fn read_int(&mut self, i: Integer) -> Result<Value> {
+        Ok(Value::Int(self.read_int_internal(i)?))
+    }
+
+    #[inline]
+    fn read_int_internal(&mut self, i: Integer) -> Result<i32> {
         match i {
-            Integer::Direct(b) => Ok(Value::Int(b as i32 - 0x90)),
+            Integer::Direct(b) => Ok(b as i32 - 0x90),
             Integer::Byte(b) => {
                 let b2 = self.read_byte()?;
-                Ok(Value::Int(
-                    i16::from_be_bytes([b.overflowing_sub(0xc8).0, b2]) as i32,
-                ))
+                Ok(i16::from_be_bytes([b.overflowing_sub(0xc8).0, b2]) as i32)
             }
             Integer::Short(b) => {
-                let bs = self.read_bytes(2)?;
-                Ok(Value::Int(
-                    i32::from_be_bytes([b.overflowing_sub(0xd4).0, bs[0], bs[1], 0x00]) >> 8,
-                ))
-            }
-            Integer::Normal => {
-                let val = self.buffer.read_i32::<BigEndian>()?;
-                Ok(Value::Int(val))
+                let mut bs = [0; 2];
+                self.buffer.read_exact(&mut bs)?;
+                Ok(i32::from_be_bytes([b.overflowing_sub(0xd4).0, bs[0], bs[1], 0x00]) >> 8)
             }
+            Integer::Normal => Ok(self.buffer.read_i32::<BigEndian>()?),
         }
     }
 
@@ -313,23 +576,27 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
     /// ```
     ///
     fn read_long(&mut self, l: Long) -> Result<Value> {
+        Ok(Value::Long(self.read_long_internal(l)?))
+    }
+
+    #[inline]
+    fn read_long_internal(&mut self, l: Long) -> Result<i64> {
         match l {
-            Long::Direct(b) => Ok(Value::Long(b as i64 - 0xe0)),
+            Long::Direct(b) => Ok(b as i64 - 0xe0),
             Long::Byte(b) => {
                 let b2 = self.read_byte()?;
-                Ok(Value::Long(
-                    i16::from_be_bytes([b.overflowing_sub(0xf8).0, b2]) as i64,
-                ))
+                Ok(i16::from_be_bytes([b.overflowing_sub(0xf8).0, b2]) as i64)
             }
             Long::Short(b) => {
-                let bs = self.read_bytes(2)?;
-                Ok(Value::Long(
+                let mut bs = [0; 2];
+                self.buffer.read_exact(&mut bs)?;
+                Ok(
                     (i32::from_be_bytes([b.overflowing_sub(0x3c).0, bs[0], bs[1], 0x00]) >> 8)
                         as i64,
-                ))
+                )
             }
-            Long::Int32 => Ok(Value::Long(self.buffer.read_i32::<BigEndian>()? as i64)),
-            Long::Normal => Ok(Value::Long(self.buffer.read_i64::<BigEndian>()?)),
+            Long::Int32 => Ok(self.buffer.read_i32::<BigEndian>()? as i64),
+            Long::Normal => Ok(self.buffer.read_i64::<BigEndian>()?),
         }
     }
 
@@ -369,6 +636,11 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
     /// can be represented as the 4-octet float and then cast to double.
     ///
     fn read_double(&mut self, tag: Double) -> Result<Value> {
+        Ok(Value::Double(self.read_double_internal(tag)?))
+    }
+
+    #[inline]
+    fn read_double_internal(&mut self, tag: Double) -> Result<f64> {
         let val = match tag {
             Double::Normal => self.buffer.read_f64::<BigEndian>()?,
             Double::Zero => 0.0,
@@ -377,7 +649,13 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
             Double::Short => self.buffer.read_i16::<BigEndian>()? as f64,
             Double::Float => (self.buffer.read_i32::<BigEndian>()? as f64) * 0.001,
         };
-        Ok(Value::Double(val))
+        if !self.allow_nan && !val.is_finite() {
+            return self.error(ErrorKind::UnexpectedType(format!(
+                "non-finite double {}",
+                val
+            )));
+        }
+        Ok(val)
     }
 
     /// read a date from buffer,
@@ -391,43 +669,83 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
     /// See http://hessian.caucho.com/doc/hessian-serialization.html##date
     ///
     fn read_date(&mut self, d: Date) -> Result<Value> {
+        Ok(Value::Date(self.read_date_internal(d)?))
+    }
+
+    #[inline]
+    fn read_date_internal(&mut self, d: Date) -> Result<i64> {
         let val = match d {
             Date::Millisecond => self.buffer.read_i64::<BigEndian>()?,
             Date::Minute => self.buffer.read_i32::<BigEndian>()? as i64 * 60000,
         };
-        Ok(Value::Date(val))
+        Ok(val)
     }
 
-    fn read_utf8_string(&mut self, s: &mut Vec<u8>, len: usize) -> Result<()> {
+    // Copy the bytes of the next `len` chars to `s`, or skip them if `None`.
+    // The span of the chars is found on the underlying slice and copied at
+    // once, but for surrogate pairs which are combined into 4-byte sequences.
+    // The bytes are validated as UTF-8 once the whole string is read.
+    fn read_utf8_string(&mut self, mut s: Option<&mut Vec<u8>>, len: usize) -> Result<()> {
+        let cesu8 = self.cesu8;
+        let data = self.buffer.get_ref().as_ref();
+        let mut pos = (self.buffer.position() as usize).min(data.len());
+        let mut copied = pos;
         let mut len = len;
         while len > 0 {
-            let byte = self.read_byte()?;
-            match byte {
-                0x00..=0x7f => s.push(byte),
-                0xc2..=0xdf => {
-                    s.push(byte);
-                    s.push(self.read_byte()?);
-                }
-                0xe0..=0xef => {
-                    s.push(byte);
-                    let mut buf = [0; 2];
-                    self.buffer.read_exact(&mut buf)?;
-                    s.extend_from_slice(&buf);
-                }
-                0xf0..=0xf4 => {
-                    s.push(byte);
-                    let mut buf = [0; 3];
-                    self.buffer.read_exact(&mut buf)?;
-                    s.extend_from_slice(&buf);
-                }
-                _ => {}
+            let rest = &data[pos..];
+            let ascii = rest.iter().take(len).take_while(|b| b.is_ascii()).count();
+            if ascii > 0 {
+                pos += ascii;
+                len -= ascii;
+                continue;
             }
-            len -= 1
+            let (width, chars) = match rest.first() {
+                // each char left is at least a byte
+                None => return Err(self.chunk_eof_before(pos + len)),
+                Some(0xc2..=0xdf) => (2, 1),
+                // high surrogate
+                Some(0xed) if cesu8 && s.is_some() => match surrogate_pair(rest) {
+                    Some(c) => {
+                        let s = s.as_deref_mut().unwrap();
+                        s.extend_from_slice(&data[copied..pos]);
+                        s.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        pos += 6;
+                        copied = pos;
+                        len = len.saturating_sub(2);
+                        continue;
+                    }
+                    None => (3, 1),
+                },
+                Some(0xe0..=0xef) => (3, 1),
+                // a surrogate pair in java, which is two chars of the length
+                Some(0xf0..=0xf4) => (4, 2),
+                Some(b) => return self.error(ErrorKind::InvalidUtf8Lead(*b)),
+            };
+            if rest.len() < width {
+                return Err(self.chunk_eof_before(pos + width + len.saturating_sub(chars)));
+            }
+            pos += width;
+            len = len.saturating_sub(chars);
         }
+        if let Some(s) = s {
+            s.extend_from_slice(&data[copied..pos]);
+        }
+        self.buffer.set_position(pos as u64);
         Ok(())
     }
 
-    fn read_string_internal(&mut self, buf: &mut Vec<u8>, tag: StringType) -> Result<()> {
+    // The span in the input of the next `len` chars, see `read_utf8_string`
+    fn read_utf8_span(&mut self, len: usize) -> Result<Range<usize>> {
+        let start = self.buffer.position() as usize;
+        self.read_utf8_string(None, len)?;
+        Ok(start..self.buffer.position() as usize)
+    }
+
+    fn read_string_internal(
+        &mut self,
+        mut buf: Option<&mut Vec<u8>>,
+        tag: StringType,
+    ) -> Result<()> {
         match tag {
             StringType::Compact(b) => {
                 let len = b as usize;
@@ -439,15 +757,13 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
             }
             StringType::Chunk => {
                 let len = self.buffer.read_u16::<BigEndian>()? as usize;
-                self.read_utf8_string(buf, len)?;
+                self.read_utf8_string(buf.as_deref_mut(), len)?;
                 let next_tag = ByteCodecType::from(self.read_byte()?);
                 match next_tag {
                     ByteCodecType::String(s) => {
                         self.read_string_internal(buf, s)?;
                     }
-                    _ => {
-                        return self.error(ErrorKind::UnexpectedType(next_tag.to_string()));
-                    }
+                    _ => return self.error(ErrorKind::InvalidChunkContinuation),
                 }
             }
             StringType::FinalChunk => {
@@ -460,7 +776,7 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
 
     /// read a string from buffer
     ///
-    /// The length is the number of characters, which may be different than the number of bytes.
+    /// The length is the number of 16-bit characters, which may be different than the number of bytes.
     ///
     /// v2.0
     /// ```ignore
@@ -486,10 +802,19 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
     /// ```
     ///
     fn read_string(&mut self, tag: StringType) -> Result<Value> {
+        if let StringType::Compact(len) = tag {
+            // A short string is validated in the input then copied once, or
+            // not at all when `Str` keeps it inline. Strings with surrogate
+            // pairs to combine are read again below.
+            let span = self.read_utf8_span(len as usize)?;
+            if let Ok(s) = std::str::from_utf8(&self.buffer.get_ref().as_ref()[span.clone()]) {
+                return Ok(Value::String(s.into()));
+            }
+            self.buffer.set_position(span.start as u64);
+        }
         let mut buf = Vec::new();
-        self.read_string_internal(&mut buf, tag)?;
-        let s = String::from_utf8(buf)?;
-        Ok(Value::String(s))
+        self.read_string_internal(Some(&mut buf), tag)?;
+        Ok(Value::String(utf8_string(buf)?.into()))
     }
 
     /// v2.0
@@ -500,8 +825,9 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
     /// See http://hessian.caucho.com/doc/hessian-serialization.html##ref
     ///
     pub fn read_type(&mut self) -> Result<String> {
-        match self.read_value() {
+        match self.read_plain_value() {
             Ok(Value::String(s)) => {
+                let s = s.into_string();
                 self.type_references.push(s.clone());
                 Ok(s)
             }
@@ -517,19 +843,110 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
         }
     }
 
-    fn read_varlength_map_internal(&mut self) -> Result<HashMap<Value, Value>> {
+    fn read_varlength_map_internal(&mut self, typ: Option<&str>) -> Result<HashMap<Value, Value>> {
         let mut map = HashMap::new();
-        let mut tag = self.peek_byte()?;
-        while tag != b'Z' {
-            let key = self.read_value()?;
+        while self.peek_byte()? != b'Z' {
+            let key = self.read_key()?;
             let val = self.read_value()?;
-            map.insert(key, val);
-            tag = self.peek_byte()?;
+            let val = match (&mut self.hook, &key) {
+                (Some(hook), Value::String(field)) => match hook.on_field(typ, field, val) {
+                    Some(val) => val,
+                    None => continue,
+                },
+                _ => val,
+            };
+            match (map.entry(key), self.duplicate_keys) {
+                (Entry::Vacant(entry), _) => {
+                    entry.insert(val);
+                }
+                (Entry::Occupied(mut entry), DuplicateKeys::Last) => {
+                    entry.insert(val);
+                }
+                (Entry::Occupied(_), DuplicateKeys::First) => {}
+                (Entry::Occupied(entry), DuplicateKeys::Error) => {
+                    return self.error(ErrorKind::DuplicateKey(entry.key().to_string()))
+                }
+            }
         }
         self.read_byte()?;
         Ok(map)
     }
 
+    // A map key, interned when it's a string and keys are interned
+    fn read_key(&mut self) -> Result<Value> {
+        if self.keys.is_none() {
+            return self.read_plain_value();
+        }
+        if let ByteCodecType::String(StringType::Compact(len)) = self.peek_byte_code_type()? {
+            // a short key is looked up by its bytes in the input, neither
+            // copied nor validated again once known
+            let start = self.buffer.position();
+            self.read_byte()?;
+            let span = self.read_utf8_span(len as usize)?;
+            if let Some(key) = self.intern_span(span) {
+                return Ok(Value::String(key));
+            }
+            self.buffer.set_position(start);
+        }
+        match self.read_plain_value()? {
+            Value::String(s) => Ok(Value::String(self.intern(&s))),
+            key => Ok(key),
+        }
+    }
+
+    // A value whose string isn't passed to the hook, for a key or a name
+    fn read_plain_value(&mut self) -> Result<Value> {
+        match self.peek_byte_code_type()? {
+            ByteCodecType::String(tag) => {
+                self.read_byte()?;
+                self.read_string(tag)
+            }
+            _ => self.read_value(),
+        }
+    }
+
+    // The interned key of the bytes `span` of the input, or `None` if they
+    // aren't valid UTF-8, e.g. surrogate pairs to combine
+    fn intern_span(&mut self, span: Range<usize>) -> Option<Str> {
+        let bytes = &self.buffer.get_ref().as_ref()[span];
+        let keys = self.keys.as_mut()?;
+        if let Some(key) = keys.get(bytes) {
+            return Some(key.clone());
+        }
+        let key = Str::shared(std::str::from_utf8(bytes).ok()?);
+        keys.insert(bytes.into(), key.clone());
+        Some(key)
+    }
+
+    // The interned name of the field `i` of the definition `index`
+    fn field_key(&mut self, index: usize, i: usize) -> Str {
+        if index >= self.field_keys.len() {
+            self.field_keys.resize(index + 1, Vec::new());
+        }
+        if self.field_keys[index].is_empty() {
+            let def = Arc::clone(&self.class_references[index]);
+            let keys = def.fields.iter().map(|field| self.intern(field)).collect();
+            self.field_keys[index] = keys;
+        }
+        self.field_keys[index][i].clone()
+    }
+
+    // `s` interned when keys are interned, else a copy of it
+    fn intern(&mut self, s: &str) -> Str {
+        let keys = match self.keys.as_mut() {
+            Some(keys) => keys,
+            None => return Str::from(s),
+        };
+        match keys.get(s.as_bytes()) {
+            Some(key) => key.clone(),
+            None => {
+                let key = Str::shared(s);
+                keys.insert(s.as_bytes().into(), key.clone());
+                key
+            }
+        }
+    }
+
     fn read_varlength_list_internal(&mut self) -> Result<Vec<Value>> {
         let mut tag = self.peek_byte()?;
         let mut list = Vec::new();
@@ -542,7 +959,7 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
     }
 
     fn read_exact_length_list_internal(&mut self, length: usize) -> Result<Vec<Value>> {
-        let mut list = Vec::new();
+        let mut list = Vec::with_capacity(self.capacity_hint(length));
         for _ in 0..length {
             list.push(self.read_value()?)
         }
@@ -645,9 +1062,10 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
     fn read_map(&mut self, typed: bool) -> Result<Value> {
         let map = if typed {
             let typ = self.read_type()?;
-            value::Map::from((typ, self.read_varlength_map_internal()?))
+            let map = self.read_varlength_map_internal(Some(&typ))?;
+            value::Map::from((typ, map))
         } else {
-            value::Map::from(self.read_varlength_map_internal()?)
+            value::Map::from(self.read_varlength_map_internal(None)?)
         };
         Ok(Value::Map(map))
     }
@@ -669,6 +1087,22 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
         }
     }
 
+    // The undefined tag just read and those following it, but an end of
+    // list or map which may close the container the tag is in
+    fn read_unknown(&mut self) -> Result<Value> {
+        let start = self.buffer.position() as usize - 1;
+        while let Ok(tag) = self.peek_byte() {
+            match ByteCodecType::from(tag) {
+                ByteCodecType::Unknown if tag != b'Z' => self.read_byte()?,
+                _ => break,
+            };
+        }
+        let end = self.buffer.position() as usize;
+        Ok(Value::Unknown(
+            self.buffer.get_ref().as_ref()[start..end].to_vec(),
+        ))
+    }
+
     /// Read a hessian 2.0 value
     pub fn read_value(&mut self) -> Result<Value> {
         let v = self.read_byte()?;
@@ -678,7 +1112,10 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
             ByteCodecType::Double(d) => self.read_double(d),
             ByteCodecType::Date(d) => self.read_date(d),
             ByteCodecType::Binary(bin) => self.read_binary(bin),
-            ByteCodecType::String(s) => self.read_string(s),
+            ByteCodecType::String(s) => match (self.read_string(s)?, &mut self.hook) {
+                (Value::String(s), Some(hook)) => Ok(hook.on_string(s)),
+                (value, _) => Ok(value),
+            },
             ByteCodecType::List(l) => self.read_list(l),
             ByteCodecType::Map(typed) => self.read_map(typed),
             ByteCodecType::True => Ok(Value::Bool(true)),
@@ -690,11 +1127,427 @@ impl<R: AsRef<[u8]>> Deserializer<R> {
             }
             ByteCodecType::Ref => self.read_ref(),
             ByteCodecType::Object(o) => self.read_object(o),
-            _ => self.error(ErrorKind::UnknownType),
+            ByteCodecType::Unknown if self.permissive => self.read_unknown(),
+            ByteCodecType::Unknown => self.error(ErrorKind::InvalidTag(v)),
+        }
+    }
+
+    /// Read the next value, or `None` when the input ends before the value
+    /// does. The position and the definitions read are then restored, so the
+    /// read can be retried once more input is available, see `feed`.
+    ///
+    /// A retry reads the value again from its start, a stream of large lists
+    /// or maps fed in small pieces is better read by `parser::ValueParser`.
+    pub fn try_read_value(&mut self) -> Result<Option<Value>> {
+        self.attempt(Self::read_value)
+    }
+
+    // Run `read`, or restore the position and the definitions and return
+    // `None` when the input ends before `read` does
+    pub(crate) fn attempt<T, F>(&mut self, read: F) -> Result<Option<T>>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        let len = self.buffer.get_ref().as_ref().len();
+        if len < self.wanted {
+            return Ok(None);
+        }
+        self.wanted = 0;
+        let position = self.buffer.position();
+        let types = self.type_references.len();
+        let classes = self.class_references.len();
+        match read(self) {
+            Ok(v) => {
+                self.wanted = 0;
+                Ok(Some(v))
+            }
+            Err(e) if e.is_eof() => {
+                self.buffer.set_position(position);
+                self.type_references.truncate(types);
+                self.class_references.truncate(classes);
+                self.wanted = self.wanted.max(len + 1);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // Forget the definitions and types read, e.g. at the end of a message
+    pub(crate) fn clear_tables(&mut self) {
+        self.type_references.clear();
+        self.class_references.clear();
+        self.field_keys.clear();
+    }
+
+    /// Run `read`, then restore the position and the definitions and types,
+    /// so what `read` read is read again next, e.g. to look at a value
+    /// before deciding how to read it.
+    pub fn look_ahead<T, F>(&mut self, read: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        let position = self.buffer.position();
+        let types = self.type_references.len();
+        let classes = self.class_references.len();
+        let result = read(self);
+        self.buffer.set_position(position);
+        self.type_references.truncate(types);
+        self.class_references.truncate(classes);
+        self.field_keys.truncate(classes);
+        result
+    }
+
+    /// Read an int without going through `Value`
+    pub fn read_i32(&mut self) -> Result<i32> {
+        match ByteCodecType::from(self.read_byte()?) {
+            ByteCodecType::Int(i) => self.read_int_internal(i),
+            tag => self.error(ErrorKind::UnexpectedType(tag.to_string())),
+        }
+    }
+
+    /// Read a long without going through `Value`, an int is accepted as well
+    pub fn read_i64(&mut self) -> Result<i64> {
+        match ByteCodecType::from(self.read_byte()?) {
+            ByteCodecType::Long(l) => self.read_long_internal(l),
+            ByteCodecType::Int(i) => Ok(self.read_int_internal(i)? as i64),
+            tag => self.error(ErrorKind::UnexpectedType(tag.to_string())),
+        }
+    }
+
+    /// Read a string without going through `Value`
+    pub fn read_string_value(&mut self) -> Result<String> {
+        match ByteCodecType::from(self.read_byte()?) {
+            ByteCodecType::String(s) => {
+                let mut buf = Vec::new();
+                self.read_string_internal(Some(&mut buf), s)?;
+                utf8_string(buf)
+            }
+            tag => self.error(ErrorKind::UnexpectedType(tag.to_string())),
+        }
+    }
+
+    /// Read a string whose UTF-8 data is contiguous in the input, which is
+    /// a single chunk without surrogate pairs to combine, and return the span
+    /// of the data, e.g. to borrow the string from the input. The span is
+    /// valid UTF-8.
+    ///
+    /// For any other value the position is left unchanged and `None` is
+    /// returned, the value can then be read with `read_value`.
+    pub fn read_str_span(&mut self) -> Result<Option<Range<usize>>> {
+        let start = self.buffer.position();
+        let len = match ByteCodecType::from(self.peek_byte()?) {
+            ByteCodecType::String(StringType::Compact(b)) => {
+                self.read_byte()?;
+                b as usize
+            }
+            ByteCodecType::String(StringType::Small(b)) => {
+                self.read_byte()?;
+                (b as usize - 0x30) * 256 + self.read_byte()? as usize
+            }
+            ByteCodecType::String(StringType::FinalChunk) => {
+                self.read_byte()?;
+                self.buffer.read_u16::<BigEndian>()? as usize
+            }
+            _ => return Ok(None),
+        };
+        let span = self.read_utf8_span(len)?;
+        if std::str::from_utf8(&self.buffer.get_ref().as_ref()[span.clone()]).is_err() {
+            self.buffer.set_position(start);
+            return Ok(None);
+        }
+        Ok(Some(span))
+    }
+
+    /// Read binary data which is a single chunk and return its span in the
+    /// input, see `read_str_span`.
+    pub fn read_bytes_span(&mut self) -> Result<Option<Range<usize>>> {
+        let len = match ByteCodecType::from(self.peek_byte()?) {
+            ByteCodecType::Binary(Binary::Short(b)) => {
+                self.read_byte()?;
+                (b - 0x20) as usize
+            }
+            ByteCodecType::Binary(Binary::TwoOctet(b)) => {
+                self.read_byte()?;
+                let second_byte = self.read_byte()?;
+                i16::from_be_bytes([b - 0x34, second_byte]) as usize
+            }
+            ByteCodecType::Binary(Binary::Long(b'B')) => {
+                self.read_byte()?;
+                self.buffer.read_u16::<BigEndian>()? as usize
+            }
+            _ => return Ok(None),
+        };
+        let start = self.buffer.position() as usize;
+        self.read_chunk(len)?;
+        Ok(Some(start..start + len))
+    }
+
+    /// Read the header of a list, returns the list type and its length.
+    ///
+    /// A `None` length means a variable-length list, which ends with 'Z'.
+    pub fn read_list_header(&mut self) -> Result<(Option<String>, Option<usize>)> {
+        let list = match ByteCodecType::from(self.read_byte()?) {
+            ByteCodecType::List(list) => list,
+            tag => return self.error(ErrorKind::UnexpectedType(tag.to_string())),
+        };
+        self.read_list_header_internal(list)
+    }
+
+    // The rest of the header of a list whose tag has been read
+    fn read_list_header_internal(&mut self, list: List) -> Result<(Option<String>, Option<usize>)> {
+        let typed = match list {
+            List::ShortFixedLength(typed, _)
+            | List::VarLength(typed)
+            | List::FixedLength(typed) => typed,
+        };
+        let typ = if typed { Some(self.read_type()?) } else { None };
+        let length = match list {
+            List::ShortFixedLength(_, length) => Some(length),
+            List::VarLength(_) => None,
+            List::FixedLength(_) => match self.read_value()? {
+                Value::Int(l) => Some(l as usize),
+                v => return self.error(ErrorKind::UnexpectedType(v.to_string())),
+            },
+        };
+        Ok((typ, length))
+    }
+
+    fn read_list_with<T, F>(&mut self, read: F) -> Result<Vec<T>>
+    where
+        F: FnMut(&mut Self) -> Result<T>,
+    {
+        let (_, length) = self.read_list_header()?;
+        self.read_items_with(length, read)
+    }
+
+    // The items of a list whose header has been read
+    fn read_items_with<T, F>(&mut self, length: Option<usize>, mut read: F) -> Result<Vec<T>>
+    where
+        F: FnMut(&mut Self) -> Result<T>,
+    {
+        match length {
+            Some(length) => {
+                let mut list = Vec::with_capacity(self.capacity_hint(length));
+                for _ in 0..length {
+                    list.push(read(self)?);
+                }
+                Ok(list)
+            }
+            None => {
+                let mut list = Vec::new();
+                while self.peek_byte()? != b'Z' {
+                    list.push(read(self)?);
+                }
+                self.read_byte()?;
+                Ok(list)
+            }
+        }
+    }
+
+    /// Read a list of ints such as `[int` straight into a `Vec<i32>`
+    pub fn read_int_list(&mut self) -> Result<Vec<i32>> {
+        self.read_list_with(Self::read_i32)
+    }
+
+    /// Read a list of longs such as `[long` straight into a `Vec<i64>`
+    pub fn read_long_list(&mut self) -> Result<Vec<i64>> {
+        self.read_list_with(Self::read_i64)
+    }
+
+    /// Read a list of strings such as `[string` straight into a `Vec<String>`
+    pub fn read_string_list(&mut self) -> Result<Vec<String>> {
+        self.read_list_with(Self::read_string_value)
+    }
+
+    /// Read binary data or a list of bytes such as `[byte` into a `Vec<u8>`
+    pub fn read_byte_list(&mut self) -> Result<Vec<u8>> {
+        match self.peek_byte_code_type()? {
+            ByteCodecType::Binary(bin) => {
+                self.read_byte()?;
+                match self.read_binary(bin)? {
+                    Value::Bytes(bytes) => Ok(bytes.into_vec()),
+                    _ => unreachable!("read_binary always returns bytes"),
+                }
+            }
+            _ => self.read_list_with(Self::read_list_byte),
+        }
+    }
+
+    /// Read the items of a list of bytes whose header has been read by
+    /// `read_list_header`, with the length it returned.
+    pub fn read_byte_items(&mut self, length: Option<usize>) -> Result<Vec<u8>> {
+        self.read_items_with(length, Self::read_list_byte)
+    }
+
+    // A byte of a list, signed as a java byte or unsigned as a rust one
+    fn read_list_byte(&mut self) -> Result<u8> {
+        let i = self.read_i32()?;
+        match u8::try_from(i) {
+            Ok(b) => Ok(b),
+            Err(_) => match i8::try_from(i) {
+                Ok(b) => Ok(b as u8),
+                Err(_) => self.error(ErrorKind::UnexpectedType(format!("int {} as a byte", i))),
+            },
+        }
+    }
+
+    #[inline]
+    fn skip_bytes(&mut self, n: usize) -> Result<()> {
+        if self.remaining() < n {
+            let end = self.buffer.position() as usize + n;
+            return Err(self.chunk_eof_before(end));
+        }
+        self.buffer.set_position(self.buffer.position() + n as u64);
+        Ok(())
+    }
+
+    fn skip_binary(&mut self, bin: Binary) -> Result<()> {
+        match bin {
+            Binary::Short(b) => self.skip_bytes((b - 0x20) as usize),
+            Binary::TwoOctet(b) => {
+                let second_byte = self.read_byte()?;
+                self.skip_bytes(i16::from_be_bytes([b - 0x34, second_byte]) as usize)
+            }
+            Binary::Long(b) => self.read_long_binary_into(&mut Vec::new(), b),
+        }
+    }
+
+    fn skip_list(&mut self, list: List) -> Result<()> {
+        let length = match list {
+            List::ShortFixedLength(typed, length) => {
+                if typed {
+                    self.read_type()?;
+                }
+                Some(length)
+            }
+            List::VarLength(typed) => {
+                if typed {
+                    self.read_type()?;
+                }
+                None
+            }
+            List::FixedLength(typed) => {
+                if typed {
+                    self.read_type()?;
+                }
+                match self.read_value()? {
+                    Value::Int(l) => Some(l as usize),
+                    v => return self.error(ErrorKind::UnexpectedType(v.to_string())),
+                }
+            }
+        };
+        match length {
+            Some(length) => {
+                for _ in 0..length {
+                    self.skip_value()?;
+                }
+            }
+            None => {
+                while self.peek_byte()? != b'Z' {
+                    self.skip_value()?;
+                }
+                self.read_byte()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_map(&mut self, typed: bool) -> Result<()> {
+        if typed {
+            self.read_type()?;
+        }
+        while self.peek_byte()? != b'Z' {
+            self.skip_value()?;
+            self.skip_value()?;
+        }
+        self.read_byte()?;
+        Ok(())
+    }
+
+    /// Skip a hessian 2.0 value without building it.
+    ///
+    /// Class definitions and types met on the way are still recorded,
+    /// so later values which refer to them can be read correctly.
+    pub fn skip_value(&mut self) -> Result<()> {
+        let v = self.read_byte()?;
+        match ByteCodecType::from(v) {
+            ByteCodecType::Int(i) => self.read_int(i).map(|_| ()),
+            ByteCodecType::Long(l) => self.read_long(l).map(|_| ()),
+            ByteCodecType::Double(d) => self.read_double(d).map(|_| ()),
+            ByteCodecType::Date(d) => self.read_date(d).map(|_| ()),
+            ByteCodecType::Binary(bin) => self.skip_binary(bin),
+            ByteCodecType::String(s) => self.read_string_internal(None, s),
+            ByteCodecType::List(l) => self.skip_list(l),
+            ByteCodecType::Map(typed) => self.skip_map(typed),
+            ByteCodecType::True | ByteCodecType::False | ByteCodecType::Null => Ok(()),
+            ByteCodecType::Definition => {
+                self.read_definition()?;
+                self.skip_value()
+            }
+            ByteCodecType::Ref => self.read_ref().map(|_| ()),
+            ByteCodecType::Object(o) => {
+                let length = self.read_definition_id(o)?.fields.len();
+                for _ in 0..length {
+                    self.skip_value()?;
+                }
+                Ok(())
+            }
+            ByteCodecType::Unknown if self.permissive => self.read_unknown().map(|_| ()),
+            ByteCodecType::Unknown => self.error(ErrorKind::InvalidTag(v)),
         }
     }
 }
 
+impl Deserializer<Vec<u8>> {
+    /// Append `bytes` to the input of a deserializer reading a stream
+    /// piece by piece. The bytes already read are dropped, so `position`
+    /// restarts from 0, while definitions are kept for the next values.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let position = self.buffer.position() as usize;
+        self.wanted = self.wanted.saturating_sub(position);
+        let buf = self.buffer.get_mut();
+        buf.drain(..position);
+        buf.extend_from_slice(bytes);
+        self.buffer.set_position(0);
+    }
+}
+
+// The bytes of every decoded string are validated here, once the whole
+// string is read
+#[cfg(not(feature = "simdutf8"))]
+#[inline]
+fn utf8_string(buf: Vec<u8>) -> Result<String> {
+    Ok(String::from_utf8(buf)?)
+}
+
+#[cfg(feature = "simdutf8")]
+#[inline]
+fn utf8_string(buf: Vec<u8>) -> Result<String> {
+    match simdutf8::basic::from_utf8(&buf) {
+        // SAFETY: `buf` was just validated as UTF-8
+        Ok(_) => Ok(unsafe { String::from_utf8_unchecked(buf) }),
+        // the error of std, with the position of the invalid sequence
+        Err(_) => Ok(String::from_utf8(buf)?),
+    }
+}
+
+pub(crate) fn unexpected_eof() -> Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "Unexpected EOF").into()
+}
+
+// The char of a high surrogate at the start of `buf` and the low surrogate
+// following it, each a 3-byte sequence, if `buf` starts with such a pair
+fn surrogate_pair(buf: &[u8]) -> Option<char> {
+    match buf {
+        [0xed, h1 @ 0xa0..=0xaf, h2, 0xed, l1 @ 0xb0..=0xbf, l2, ..] => {
+            let high = 0xd000 | ((*h1 as u32 & 0x3f) << 6) | (*h2 as u32 & 0x3f);
+            let low = 0xd000 | ((*l1 as u32 & 0x3f) << 6) | (*l2 as u32 & 0x3f);
+            std::char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+        }
+        _ => None,
+    }
+}
+
 /// Read a hessain 2.0 value from a slice
 pub fn from_slice(v: &[u8]) -> Result<Value> {
     let mut de = Deserializer::new(v);
@@ -704,8 +1557,11 @@ pub fn from_slice(v: &[u8]) -> Result<Value> {
 
 #[cfg(test)]
 mod tests {
-    use super::Deserializer;
-    use crate::value::Value;
+    use super::{DecodeHook, Deserializer, DuplicateKeys};
+    use crate::constant::{ByteCodecType, Object};
+    use crate::error::{Error::SyntaxError, ErrorKind};
+    use crate::ser::Serializer;
+    use crate::value::{Definition, Str, Value};
     use std::collections::HashMap;
 
     fn test_decode_ok(rdr: &[u8], target: Value) {
@@ -759,6 +1615,183 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_read_raw() {
+        let mut de = Deserializer::new(&[0xca, 0xfe, 0x91, 0x01]);
+        assert_eq!(de.read_raw(2).unwrap(), [0xca, 0xfe]);
+        assert_eq!(de.read_value().unwrap(), Value::Int(1));
+        assert!(de.read_raw(0).unwrap().is_empty());
+        assert_eq!(de.position(), 3);
+        assert!(de.read_raw(2).is_err());
+    }
+
+    #[test]
+    fn test_tables() {
+        let first = [
+            b'C', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r', 0x91,
+            0x05, b'c', b'o', b'l', b'o', b'r', 0x60, 0x03, b'r', b'e', b'd', 0x72, 0x04, b'[',
+            b'i', b'n', b't', 0x90, 0x91,
+        ];
+        // the next message refers to the definition and the type of the first
+        let next = [0x60, 0x04, b'b', b'l', b'u', b'e', 0x71, 0x90, 0x92];
+
+        let mut de = Deserializer::new(&first[..]);
+        de.read_value().unwrap();
+        de.read_value().unwrap();
+        let tables = de.into_tables();
+        assert_eq!(tables.types, ["[int"]);
+        assert_eq!(tables.definitions[0].name, "example.Car");
+
+        assert!(Deserializer::new(&next[..]).read_value().is_err());
+        let mut de = Deserializer::new(&next[..]).with_tables(tables.clone());
+        let mut blue = HashMap::new();
+        blue.insert(Value::from("color"), Value::from("blue"));
+        assert_eq!(
+            de.read_value().unwrap(),
+            Value::Map(("example.Car", blue).into())
+        );
+        assert_eq!(
+            de.read_value().unwrap(),
+            Value::List(("[int", vec![Value::Int(2)]).into())
+        );
+
+        let mut de = Deserializer::new(&next[..6]).with_definitions(tables.definitions);
+        assert!(de.read_value().unwrap().is_map());
+    }
+
+    #[test]
+    fn test_definitions() {
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf);
+        let car = Definition {
+            name: "example.Car".to_string(),
+            fields: vec!["color".to_string(), "owner".to_string()],
+        };
+        let person = Definition {
+            name: "example.Person".to_string(),
+            fields: vec!["name".to_string()],
+        };
+        ser.serialize_fields_with_definition(&car, &[Value::from("red"), Value::Null])
+            .unwrap();
+        ser.serialize_fields_with_definition(&person, &[Value::from("a")])
+            .unwrap();
+
+        let mut de = Deserializer::new(&buf[..]);
+        assert_eq!(de.definitions().len(), 0);
+        while de.peek_byte().is_ok() {
+            de.skip_value().unwrap();
+        }
+        let names: Vec<&str> = de.definitions().map(|def| def.name.as_str()).collect();
+        assert_eq!(names, ["example.Car", "example.Person"]);
+        assert_eq!(de.definitions().next(), Some(&car));
+    }
+
+    #[test]
+    fn test_hook() {
+        struct Mask;
+
+        impl DecodeHook for Mask {
+            fn on_string(&mut self, s: Str) -> Value {
+                Value::from(s.to_uppercase())
+            }
+
+            fn on_field(
+                &mut self,
+                class: Option<&str>,
+                field: &str,
+                value: Value,
+            ) -> Option<Value> {
+                match field {
+                    "password" => Some(Value::from("***")),
+                    "token" if class == Some("example.Session") => None,
+                    _ => Some(value),
+                }
+            }
+        }
+
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf);
+        let user = Definition {
+            name: "example.User".to_string(),
+            fields: vec!["name".to_string(), "password".to_string()],
+        };
+        ser.serialize_fields_with_definition(&user, &[Value::from("a"), Value::from("secret")])
+            .unwrap();
+        ser.serialize_value(&hessian!({ "password": "secret", "token": "t" }))
+            .unwrap();
+        ser.serialize_value(&hessian!("example.Session" => { "token": "t", "user": "a" }))
+            .unwrap();
+
+        let mut de = Deserializer::new(&buf[..]).hook(Mask);
+        assert_eq!(
+            de.read_value().unwrap(),
+            hessian!("example.User" => { "name": "A", "password": "***" })
+        );
+        assert_eq!(
+            de.read_value().unwrap(),
+            hessian!({ "password": "***", "token": "T" })
+        );
+        assert_eq!(
+            de.read_value().unwrap(),
+            hessian!("example.Session" => { "user": "A" })
+        );
+    }
+
+    #[test]
+    fn test_read_shared_definition() {
+        let def = Definition {
+            name: "example.Car".to_string(),
+            fields: vec!["color".to_string()],
+        };
+        let mut de = Deserializer::new(&[][..]).with_definitions(vec![def]);
+        let shared = de.read_shared_definition(Object::Compact(0x60)).unwrap();
+        assert_eq!(shared.fields, ["color"]);
+        // the definition stays shared with the deserializer
+        assert_eq!(
+            *de.read_definition_id(Object::Compact(0x60)).unwrap(),
+            *shared
+        );
+        assert_eq!(de.into_tables().definitions, [(*shared).clone()]);
+
+        let mut de = Deserializer::new(&[][..]);
+        assert!(de.read_shared_definition(Object::Compact(0x61)).is_err());
+    }
+
+    #[test]
+    fn test_feed() {
+        let car = [
+            b'C', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r', 0x91,
+            0x05, b'c', b'o', b'l', b'o', b'r', 0x60, 0x03, b'r', b'e', b'd', 0x60, 0x04, b'b',
+            b'l', b'u', b'e',
+        ];
+        let mut de = Deserializer::new(Vec::new());
+        assert!(de.try_read_value().unwrap().is_none());
+        // the definition is read again with the rest of the first object
+        de.feed(&car[..8]);
+        assert!(de.try_read_value().unwrap().is_none());
+        de.feed(&car[8..22]);
+        assert!(de.try_read_value().unwrap().is_none());
+        de.feed(&car[22..27]);
+        let mut red = HashMap::new();
+        red.insert(Value::from("color"), Value::from("red"));
+        assert_eq!(
+            de.try_read_value().unwrap(),
+            Some(Value::Map(("example.Car", red).into()))
+        );
+        assert!(de.try_read_value().unwrap().is_none());
+        de.feed(&car[27..]);
+        assert_eq!(de.position(), 0);
+        let mut blue = HashMap::new();
+        blue.insert(Value::from("color"), Value::from("blue"));
+        assert_eq!(
+            de.try_read_value().unwrap(),
+            Some(Value::Map(("example.Car", blue).into()))
+        );
+        // data which can't be read is an error, even with more input
+        de.feed(&[0x45]);
+        assert!(de.try_read_value().is_err());
+    }
+
     #[test]
     fn test_decode_double() {
         test_decode_ok(&[0x5b], Value::Double(0.0));
@@ -770,6 +1803,11 @@ mod tests {
             &[b'D', 0x40, 0x28, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00],
             Value::Double(12.25),
         );
+
+        let inf = [b'D', 0x7f, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        test_decode_ok(&inf, Value::Double(f64::INFINITY));
+        let mut de = Deserializer::new(&inf).allow_nan(false);
+        assert!(de.read_value().is_err());
     }
 
     #[test]
@@ -783,19 +1821,123 @@ mod tests {
 
     #[test]
     fn test_short_binary() {
-        test_decode_ok(&[0x20], Value::Bytes(Vec::new()));
-        test_decode_ok(&[0x23, 0x01, 0x02, 0x03], Value::Bytes(vec![1, 2, 3]));
+        test_decode_ok(&[0x20], Value::Bytes(Vec::new().into()));
+        test_decode_ok(
+            &[0x23, 0x01, 0x02, 0x03],
+            Value::Bytes(vec![1, 2, 3].into()),
+        );
+    }
+
+    #[test]
+    fn test_read_string() {
+        test_decode_ok(&[0x02, b'f', 0xc3, 0xa9], Value::from("fé"));
+        // the chunks of a string split by chars
+        test_decode_ok(
+            &[b'R', 0x00, 0x02, 0xf0, 0x9f, 0x98, 0x80, 0x01, b'a'],
+            Value::from("😀a"),
+        );
+        let mut de =
+            Deserializer::new(&[0x02, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80, 0x90]).cesu8(true);
+        assert_eq!(de.read_value().unwrap(), Value::from("😀"));
+        assert_eq!(de.read_value().unwrap(), Value::Int(0));
+
+        // missing bytes
+        for buf in [&[0x03, b'a', b'b'][..], &[0x01, 0xe4, 0xb8]] {
+            match Deserializer::new(buf).read_value() {
+                Err(SyntaxError(ErrorKind::UnexpectedEofInChunk)) => {}
+                v => panic!("expect an eof in the chunk, got {:?}", v),
+            }
+        }
+        // invalid utf-8
+        match Deserializer::new(&[0x01, 0xff]).read_value() {
+            Err(SyntaxError(ErrorKind::InvalidUtf8Lead(0xff))) => {}
+            v => panic!("expect an invalid leading byte, got {:?}", v),
+        }
+        assert!(Deserializer::new(&[0x01, 0xc3, b'a']).read_value().is_err());
+        // a chunk followed by a binary chunk
+        match Deserializer::new(&[b'R', 0x00, 0x01, b'a', 0x21, 0x00]).read_value() {
+            Err(SyntaxError(ErrorKind::InvalidChunkContinuation)) => {}
+            v => panic!("expect an invalid chunk continuation, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_read_span() {
+        let buf = [
+            0x02, b'f', 0xc3, 0xa9, 0x22, 0x01, 0x02, b'R', 0x00, 0x01, b'a', 0x00,
+        ];
+        let mut de = Deserializer::new(&buf);
+        assert_eq!(de.read_bytes_span().unwrap(), None);
+        assert_eq!(de.read_str_span().unwrap(), Some(1..4));
+        assert_eq!(de.read_str_span().unwrap(), None);
+        assert_eq!(de.read_bytes_span().unwrap(), Some(5..7));
+        // a string of chunks is read as a value
+        assert_eq!(de.read_str_span().unwrap(), None);
+        assert_eq!(de.read_value().unwrap(), Value::from("a"));
+
+        // surrogate pairs to combine
+        let buf = [0x02, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80];
+        let mut de = Deserializer::new(&buf).cesu8(true);
+        assert_eq!(de.read_str_span().unwrap(), None);
+        assert_eq!(de.read_value().unwrap(), Value::from("😀"));
+    }
+
+    #[test]
+    fn test_invalid_binary() {
+        match Deserializer::new(&[0x23, 0x01]).read_value() {
+            Err(SyntaxError(ErrorKind::UnexpectedEofInChunk)) => {}
+            v => panic!("expect an eof in the chunk, got {:?}", v),
+        }
+        match Deserializer::new(&[0x41, 0x00, 0x01, 0x01, 0x01]).read_value() {
+            Err(SyntaxError(ErrorKind::InvalidChunkContinuation)) => {}
+            v => panic!("expect an invalid chunk continuation, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_invalid_tag() {
+        for tag in [0x40, 0x45, 0x50] {
+            match Deserializer::new(&[tag]).read_value() {
+                Err(SyntaxError(ErrorKind::InvalidTag(t))) => assert_eq!(t, tag),
+                v => panic!("expect an invalid tag, got {:?}", v),
+            }
+            match Deserializer::new(&[0x57, tag]).skip_value() {
+                Err(SyntaxError(ErrorKind::InvalidTag(t))) => assert_eq!(t, tag),
+                v => panic!("expect an invalid tag, got {:?}", v),
+            }
+        }
+    }
+
+    #[test]
+    fn test_permissive() {
+        let buf = [b'Z', 0x91, 0x40, 0x45, 0x92, 0x57, 0x50, 0x40, b'Z', 0x93];
+        let mut de = Deserializer::new(&buf[..]).permissive(true);
+        assert_eq!(de.read_value().unwrap(), Value::Unknown(vec![b'Z']));
+        assert_eq!(de.read_value().unwrap(), Value::Int(1));
+        assert_eq!(de.read_value().unwrap(), Value::Unknown(vec![0x40, 0x45]));
+        assert_eq!(de.read_value().unwrap(), Value::Int(2));
+        assert_eq!(
+            de.read_value().unwrap(),
+            Value::List(vec![Value::Unknown(vec![0x50, 0x40])].into())
+        );
+        assert_eq!(de.read_value().unwrap(), Value::Int(3));
+
+        let mut de = Deserializer::new(&buf[..]).permissive(true);
+        for _ in 0..6 {
+            de.skip_value().unwrap();
+        }
+        assert_eq!(de.position(), buf.len() as u64);
     }
 
     #[test]
     fn test_boolean() {
-        test_decode_ok(&[b'T'], Value::Bool(true));
-        test_decode_ok(&[b'F'], Value::Bool(false));
+        test_decode_ok(b"T", Value::Bool(true));
+        test_decode_ok(b"F", Value::Bool(false));
     }
 
     #[test]
     fn test_null() {
-        test_decode_ok(&[b'N'], Value::Null);
+        test_decode_ok(b"N", Value::Null);
     }
 
     #[test]
@@ -816,9 +1958,9 @@ mod tests {
     #[test]
     fn test_read_map() {
         let mut map = HashMap::new();
-        map.insert(Value::Int(1), Value::String("fee".to_string()));
-        map.insert(Value::Int(16), Value::String("fie".to_string()));
-        map.insert(Value::Int(256), Value::String("foe".to_string()));
+        map.insert(Value::Int(1), Value::String("fee".into()));
+        map.insert(Value::Int(16), Value::String("fie".into()));
+        map.insert(Value::Int(256), Value::String("foe".into()));
         test_decode_ok(
             &[
                 b'M', 0x13, b'c', b'o', b'm', b'.', b'c', b'a', b'u', b'c', b'h', b'o', b'.', b't',
@@ -837,16 +1979,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_duplicate_keys() {
+        // { 1: "fee", 1: "fie" }
+        let rdr = &[
+            b'H', 0x91, 0x03, b'f', b'e', b'e', 0x91, 0x03, b'f', b'i', b'e', b'Z',
+        ];
+        for (policy, value) in [(DuplicateKeys::First, "fee"), (DuplicateKeys::Last, "fie")] {
+            let map = Deserializer::new(&rdr[..])
+                .duplicate_keys(policy)
+                .read_value()
+                .unwrap();
+            assert_eq!(map.as_map().unwrap()[&Value::Int(1)], Value::from(value));
+        }
+        let mut de = Deserializer::new(&rdr[..]).duplicate_keys(DuplicateKeys::Error);
+        match de.read_value() {
+            Err(SyntaxError(ErrorKind::DuplicateKey(key))) => assert_eq!(key, "1"),
+            v => panic!("{:?}", v),
+        }
+    }
+
     #[test]
     fn test_read_object() {
         let mut map = HashMap::new();
+        map.insert(Value::String("Color".into()), Value::String("red".into()));
         map.insert(
-            Value::String("Color".to_string()),
-            Value::String("red".to_string()),
-        );
-        map.insert(
-            Value::String("Model".to_string()),
-            Value::String("corvette".to_string()),
+            Value::String("Model".into()),
+            Value::String("corvette".into()),
         );
         test_decode_ok(
             &[
@@ -858,11 +2017,159 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_intern_keys() {
+        // longer than the strings `Str` may keep inline, one of them too
+        // long for the compact form
+        let keys = [
+            "key_longer_than_23_bytes",
+            "a key too long for the compact form",
+        ];
+        let map: HashMap<_, _> = keys
+            .iter()
+            .map(|k| (Value::String((*k).into()), Value::Int(1)))
+            .chain(Some((Value::Int(2), Value::Int(3))))
+            .collect();
+        let def = Definition {
+            name: "example.Car".to_string(),
+            fields: vec!["field_longer_than_23_bytes".to_string()],
+        };
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf);
+        ser.write_list_begin(4, None).unwrap();
+        for _ in 0..2 {
+            ser.serialize_value(&Value::Map(map.clone().into()))
+                .unwrap();
+            ser.serialize_fields_with_definition(&def, &[Value::Null])
+                .unwrap();
+        }
+
+        let value = Deserializer::new(&buf).read_value().unwrap();
+        let interned = Deserializer::new(&buf)
+            .intern_keys(true)
+            .read_value()
+            .unwrap();
+        assert_eq!(interned, value);
+        let key = |v: &Value, key: &str| match v.as_map().unwrap().get_key_value(&key.into()) {
+            Some((Value::String(s), _)) => s.as_ptr(),
+            kv => panic!("{:?}", kv),
+        };
+        for k in keys {
+            assert_eq!(key(&interned[0], k), key(&interned[2], k));
+            assert_ne!(key(&value[0], k), key(&value[2], k));
+        }
+        let field = "field_longer_than_23_bytes";
+        assert_eq!(key(&interned[1], field), key(&interned[3], field));
+    }
+
+    #[test]
+    fn test_peek_byte() {
+        let de = Deserializer::new(vec![0x91]);
+        assert_eq!(de.peek_byte().unwrap(), 0x91);
+        assert!(matches!(
+            de.peek_byte_code_type().unwrap(),
+            ByteCodecType::Int(_)
+        ));
+        let mut de = Deserializer::new(&[b'N'][..]);
+        assert_eq!(de.peek_byte().unwrap(), b'N');
+        assert_eq!(de.position(), 0);
+        assert_eq!(de.read_value().unwrap(), Value::Null);
+        assert!(de.peek_byte().is_err());
+        assert!(de.peek_byte_code_type().is_err());
+    }
+
+    #[test]
+    fn test_read_primitive_list() {
+        let mut de = Deserializer::new(&[b'V', 0x04, b'[', b'i', b'n', b't', 0x92, 0x90, 0x91][..]);
+        assert_eq!(de.read_int_list().unwrap(), vec![0, 1]);
+
+        let mut de = Deserializer::new(&[0x72, 0x05, b'[', b'l', b'o', b'n', b'g', 0xe1, 0x91][..]);
+        assert_eq!(de.read_long_list().unwrap(), vec![1, 1]);
+
+        let mut de = Deserializer::new(&[0x57, 0x01, b'a', 0x01, b'b', b'Z'][..]);
+        assert_eq!(de.read_string_list().unwrap(), vec!["a", "b"]);
+
+        let mut de = Deserializer::new(&[0x72, 0x05, b'[', b'b', b'y', b't', b'e', 0x91, 0x8f][..]);
+        assert_eq!(de.read_byte_list().unwrap(), vec![1, 0xff]);
+        let mut de = Deserializer::new(&[0x22, 0x01, 0x02][..]);
+        assert_eq!(de.read_byte_list().unwrap(), vec![1, 2]);
+        // 256 doesn't fit in a byte
+        let mut de = Deserializer::new(&[0x7a, 0x91, 0xc9, 0x00][..]);
+        assert!(de.read_byte_list().is_err());
+
+        let mut de = Deserializer::new(&[0x79, 0x01, b'a'][..]);
+        assert!(de.read_int_list().is_err());
+    }
+
+    #[test]
+    fn test_skip_value() {
+        // skip an object, its definition is still usable for the next value
+        let rdr = &[
+            b'C', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r', 0x92,
+            0x05, b'C', b'o', b'l', b'o', b'r', 0x05, b'M', b'o', b'd', b'e', b'l', b'O', 0x90,
+            0x03, b'r', b'e', b'd', 0x08, b'c', b'o', b'r', b'v', b'e', b't', b't', b'e', 0x60,
+            0x03, b'r', b'e', b'd', 0x04, b'f', b'i', b'a', b't',
+        ];
+        let mut de = Deserializer::new(&rdr[..]);
+        de.skip_value().unwrap();
+        let value = de.read_value().unwrap();
+        assert_eq!(value.as_map().unwrap().r#type(), Some("example.Car"));
+
+        let mut de = Deserializer::new(&[0x57, 0x90, 0x23, 0x01, 0x02, 0x03, b'Z', b'T'][..]);
+        de.skip_value().unwrap();
+        assert_eq!(de.read_value().unwrap(), Value::Bool(true));
+
+        let mut de = Deserializer::new(&[0x23, 0x01][..]);
+        assert!(de.skip_value().is_err());
+
+        // strings are skipped without being copied
+        let rdr = [
+            b'R', 0x00, 0x02, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80, 0x01, b'a', b'T',
+        ];
+        for cesu8 in [false, true] {
+            let mut de = Deserializer::new(&rdr[..]).cesu8(cesu8);
+            de.skip_value().unwrap();
+            assert_eq!(de.read_value().unwrap(), Value::Bool(true));
+        }
+    }
+
+    #[test]
+    fn test_look_ahead() {
+        // an object with its definition, read again after looking at it
+        let rdr = &[
+            b'C', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r', 0x91,
+            0x05, b'C', b'o', b'l', b'o', b'r', 0x60, 0x03, b'r', b'e', b'd',
+        ];
+        let mut de = Deserializer::new(&rdr[..]);
+        let end = de
+            .look_ahead(|de| {
+                de.skip_value()?;
+                Ok(de.position())
+            })
+            .unwrap();
+        assert_eq!(end, rdr.len() as u64);
+        assert_eq!(de.position(), 0);
+        let value = de.read_value().unwrap();
+        assert_eq!(value["Color"], Value::from("red"));
+        assert_eq!(de.into_tables().definitions.len(), 1);
+    }
+
+    #[test]
+    fn test_bogus_length() {
+        // lengths past the end fail without allocating them
+        for rdr in [
+            &[b'B', 0xff, 0xff][..],
+            &[0x58, 0x49, 0x7f, 0xff, 0xff, 0xff],
+        ] {
+            assert!(Deserializer::new(rdr).read_value().is_err());
+        }
+    }
+
     #[test]
     fn test_read_ref() {
         let mut map = HashMap::new();
-        map.insert(Value::String("head".to_string()), Value::Int(1));
-        map.insert(Value::String("tail".to_string()), Value::Ref(0));
+        map.insert(Value::String("head".into()), Value::Int(1));
+        map.insert(Value::String("tail".into()), Value::Ref(0));
         test_decode_ok(
             &[
                 b'C', 0x0a, b'L', b'i', b'n', b'k', b'e', b'd', b'L', b'i', b's', b't', 0x92, 0x04,
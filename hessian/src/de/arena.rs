@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+use std::mem;
+
+use bumpalo::collections::Vec as BumpVec;
+
+use super::Deserializer;
+use crate::constant::{Binary, ByteCodecType, Object};
+use crate::error::{ErrorKind, Result};
+use crate::value::{ArenaValue, Bump, Value};
+
+impl<R: AsRef<[u8]>> Deserializer<R> {
+    /// Read a hessian 2.0 value whose strings, bytes and containers are
+    /// allocated in `bump`, see `ArenaValue`.
+    pub fn read_value_in<'b>(&mut self, bump: &'b Bump) -> Result<ArenaValue<'b>> {
+        ArenaReader {
+            de: self,
+            bump,
+            scratch: Vec::new(),
+            classes: Vec::new(),
+            keys: HashSet::new(),
+        }
+        .read_value()
+    }
+}
+
+/// Read a hessian 2.0 value from a slice into `bump`
+pub fn from_slice_in<'b>(v: &[u8], bump: &'b Bump) -> Result<ArenaValue<'b>> {
+    let mut de = Deserializer::new(v);
+    de.read_value_in(bump)
+}
+
+struct ArenaReader<'d, 'b, R: AsRef<[u8]>> {
+    de: &'d mut Deserializer<R>,
+    bump: &'b Bump,
+    // The bytes of the string or binary being read, before they are copied
+    // to the arena
+    scratch: Vec<u8>,
+    // The name and the field names of the definitions copied to the arena,
+    // by index, so objects of a class share them
+    classes: Vec<Option<(&'b str, &'b [&'b str])>>,
+    // The string map keys copied to the arena, when keys are interned
+    keys: HashSet<&'b str>,
+}
+
+impl<'d, 'b, R: AsRef<[u8]>> ArenaReader<'d, 'b, R> {
+    fn read_value(&mut self) -> Result<ArenaValue<'b>> {
+        let tag = self.de.read_byte()?;
+        let value = match ByteCodecType::from(tag) {
+            ByteCodecType::Int(i) => ArenaValue::Int(self.de.read_int_internal(i)?),
+            ByteCodecType::Long(l) => ArenaValue::Long(self.de.read_long_internal(l)?),
+            ByteCodecType::Double(d) => ArenaValue::Double(self.de.read_double_internal(d)?),
+            ByteCodecType::Date(d) => ArenaValue::Date(self.de.read_date_internal(d)?),
+            ByteCodecType::Binary(bin) => {
+                let bytes = match bin {
+                    // a single chunk is copied from the input as is
+                    Binary::Short(b) => self.de.read_chunk((b - 0x20) as usize)?,
+                    Binary::TwoOctet(b) => {
+                        let second_byte = self.de.read_byte()?;
+                        let length = i16::from_be_bytes([b - 0x34, second_byte]) as usize;
+                        self.de.read_chunk(length)?
+                    }
+                    Binary::Long(_) => {
+                        self.scratch.clear();
+                        self.de.read_binary_into(&mut self.scratch, bin)?;
+                        &self.scratch
+                    }
+                };
+                ArenaValue::Bytes(self.bump.alloc_slice_copy(bytes))
+            }
+            ByteCodecType::String(s) => {
+                self.scratch.clear();
+                self.de.read_string_internal(Some(&mut self.scratch), s)?;
+                ArenaValue::String(self.alloc_str()?)
+            }
+            ByteCodecType::List(list) => {
+                let (tp, length) = self.de.read_list_header_internal(list)?;
+                let tp = tp.map(|tp| &*self.bump.alloc_str(&tp));
+                let values = match length {
+                    Some(length) => {
+                        let mut values =
+                            BumpVec::with_capacity_in(self.de.capacity_hint(length), self.bump);
+                        for _ in 0..length {
+                            values.push(self.read_value()?);
+                        }
+                        values
+                    }
+                    None => {
+                        let mut values = BumpVec::new_in(self.bump);
+                        while self.de.peek_byte()? != b'Z' {
+                            values.push(self.read_value()?);
+                        }
+                        self.de.read_byte()?;
+                        values
+                    }
+                };
+                ArenaValue::List(tp, values.into_bump_slice())
+            }
+            ByteCodecType::Map(typed) => {
+                let tp = match typed {
+                    true => Some(&*self.bump.alloc_str(&self.de.read_type()?)),
+                    false => None,
+                };
+                let mut entries = BumpVec::new_in(self.bump);
+                while self.de.peek_byte()? != b'Z' {
+                    let key = self.read_key()?;
+                    entries.push((key, self.read_value()?));
+                }
+                self.de.read_byte()?;
+                ArenaValue::Map(tp, entries.into_bump_slice())
+            }
+            ByteCodecType::True => ArenaValue::Bool(true),
+            ByteCodecType::False => ArenaValue::Bool(false),
+            ByteCodecType::Null => ArenaValue::Null,
+            ByteCodecType::Definition => {
+                self.de.read_definition()?;
+                self.read_value()?
+            }
+            ByteCodecType::Ref => match self.read_value()? {
+                ArenaValue::Int(i) => ArenaValue::Ref(i as u32),
+                v => {
+                    return self
+                        .de
+                        .error(ErrorKind::UnexpectedType(Value::from(v).to_string()))
+                }
+            },
+            ByteCodecType::Object(o) => self.read_object(o)?,
+            ByteCodecType::Unknown => return self.de.error(ErrorKind::InvalidTag(tag)),
+        };
+        Ok(value)
+    }
+
+    // A map key, copied to the arena once when keys are interned, see
+    // `Deserializer::intern_keys`
+    fn read_key(&mut self) -> Result<ArenaValue<'b>> {
+        let tag = match ByteCodecType::from(self.de.peek_byte()?) {
+            ByteCodecType::String(s) if self.de.keys.is_some() => s,
+            _ => return self.read_value(),
+        };
+        self.de.read_byte()?;
+        self.scratch.clear();
+        self.de.read_string_internal(Some(&mut self.scratch), tag)?;
+        let known = std::str::from_utf8(&self.scratch)
+            .ok()
+            .and_then(|s| self.keys.get(s).copied());
+        let key = match known {
+            Some(key) => key,
+            None => {
+                let key = self.alloc_str()?;
+                self.keys.insert(key);
+                key
+            }
+        };
+        Ok(ArenaValue::String(key))
+    }
+
+    fn read_object(&mut self, tag: Object) -> Result<ArenaValue<'b>> {
+        let (name, fields) = self.class(tag)?;
+        let mut entries = BumpVec::with_capacity_in(fields.len(), self.bump);
+        for field in fields {
+            entries.push((ArenaValue::String(field), self.read_value()?));
+        }
+        Ok(ArenaValue::Map(Some(name), entries.into_bump_slice()))
+    }
+
+    // The name and the field names of the definition an object refers to,
+    // copied to the arena the first time
+    fn class(&mut self, tag: Object) -> Result<(&'b str, &'b [&'b str])> {
+        let index = self.de.read_definition_index(tag)?;
+        if index >= self.classes.len() {
+            self.classes.resize(index + 1, None);
+        }
+        if let Some(class) = self.classes[index] {
+            return Ok(class);
+        }
+        let bump = self.bump;
+        let def = &self.de.class_references[index];
+        let name = &*bump.alloc_str(&def.name);
+        let fields = &*bump.alloc_slice_fill_iter(def.fields.iter().map(|f| &*bump.alloc_str(f)));
+        self.classes[index] = Some((name, fields));
+        Ok((name, fields))
+    }
+
+    // Copy the string in `scratch` to the arena once validated
+    fn alloc_str(&mut self) -> Result<&'b str> {
+        match std::str::from_utf8(&self.scratch) {
+            Ok(s) => Ok(self.bump.alloc_str(s)),
+            // the same error as `read_value`
+            Err(_) => Err(String::from_utf8(mem::take(&mut self.scratch))
+                .unwrap_err()
+                .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::from_slice_in;
+    use crate::de::Deserializer;
+    use crate::value::{ArenaValue, Bump, Value};
+    use crate::{from_slice, to_vec};
+
+    #[test]
+    fn test_read_value_in() {
+        let value = hessian!({
+            "cars": [
+                (hessian!("example.Car" => { "color": "red", "mileage": 65536 })),
+                (hessian!("example.Car" => { "color": "green", "mileage": 65537 }))
+            ],
+            "date": (Value::Date(894621091000)),
+            "bytes": (Value::Bytes(vec![1, 2, 3].into())),
+            "double": 12.25,
+            "none": (Value::Null)
+        });
+        let buf = to_vec(&value).unwrap();
+        let bump = Bump::new();
+        let arena = from_slice_in(&buf, &bump).unwrap();
+        assert_eq!(Value::from(arena), value);
+        assert_eq!(arena.get("bytes"), Some(&ArenaValue::Bytes(&[1, 2, 3])));
+        assert_eq!(arena.get("missing"), None);
+    }
+
+    #[test]
+    fn test_read_object_in() {
+        // two objects of example.Car, then a ref
+        let buf = [
+            0x7b, b'C', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r',
+            0x91, 0x05, b'c', b'o', b'l', b'o', b'r', 0x60, 0x03, b'r', b'e', b'd', 0x60, 0x04,
+            b'b', b'l', b'u', b'e', 0x51, 0x91,
+        ];
+        let bump = Bump::new();
+        let arena = from_slice_in(&buf, &bump).unwrap();
+        assert_eq!(Value::from(arena), from_slice(&buf).unwrap());
+        match arena {
+            ArenaValue::List(None, [first, second, ArenaValue::Ref(1)]) => {
+                assert_eq!(first.get("color"), Some(&ArenaValue::String("red")));
+                // the field names are copied to the arena once
+                match (first, second) {
+                    (
+                        ArenaValue::Map(_, [(ArenaValue::String(a), _)]),
+                        ArenaValue::Map(_, [(ArenaValue::String(b), _)]),
+                    ) => assert_eq!(a.as_ptr(), b.as_ptr()),
+                    v => panic!("{:?}", v),
+                }
+            }
+            v => panic!("{:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_intern_keys_in() {
+        let value = hessian!([{ "color": "red" }, { "color": "green" }]);
+        let buf = to_vec(&value).unwrap();
+        let bump = Bump::new();
+        let arena = Deserializer::new(&buf)
+            .intern_keys(true)
+            .read_value_in(&bump)
+            .unwrap();
+        assert_eq!(Value::from(arena), value);
+        match arena {
+            ArenaValue::List(
+                None,
+                [ArenaValue::Map(None, [(ArenaValue::String(a), _)]), ArenaValue::Map(None, [(ArenaValue::String(b), _)])],
+            ) => assert_eq!(a.as_ptr(), b.as_ptr()),
+            v => panic!("{:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_read_value_in_error() {
+        let bump = Bump::new();
+        assert!(from_slice_in(&[0x02, 0xff, 0xfe], &bump).is_err());
+        assert!(from_slice_in(&[0x60], &bump).is_err());
+        assert!(from_slice_in(&[0x57, 0x91], &bump).is_err());
+    }
+}
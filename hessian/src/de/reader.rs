@@ -0,0 +1,182 @@
+use std::io::{self, Read};
+
+use super::{unexpected_eof, Deserializer};
+use crate::constant::ByteCodecType;
+use crate::error::Result;
+use crate::value::Value;
+
+// The bytes asked of the reader at once, at least
+const PIECE: usize = 8 * 1024;
+
+/// A deserializer of the values of an `io::Read`, e.g. a socket or a pipe,
+/// which needn't implement `Seek`.
+///
+/// The reader is read in pieces into a buffer, which is also the lookahead
+/// of `peek_byte`. Each read asks for at least as many bytes as are buffered,
+/// so a large value is read again from its start a few times only when a
+/// piece ends in its middle.
+pub struct ReaderDeserializer<R: Read> {
+    reader: R,
+    de: Deserializer<Vec<u8>>,
+    piece: Vec<u8>,
+}
+
+impl<R: Read> ReaderDeserializer<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_deserializer(reader, Deserializer::new(Vec::new()))
+    }
+
+    /// Read the values with the options and tables of `de`, e.g.
+    /// `Deserializer::new(Vec::new()).cesu8(true)`. The input of `de` is
+    /// read before the one of `reader`.
+    pub fn with_deserializer(reader: R, de: Deserializer<Vec<u8>>) -> Self {
+        ReaderDeserializer {
+            reader,
+            de,
+            piece: Vec::new(),
+        }
+    }
+
+    /// Returns the next byte without reading it, reading the reader until
+    /// it gives one.
+    pub fn peek_byte(&mut self) -> Result<u8> {
+        loop {
+            if let Ok(b) = self.de.peek_byte() {
+                return Ok(b);
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Returns the type of the next value without reading it.
+    pub fn peek_byte_code_type(&mut self) -> Result<ByteCodecType> {
+        Ok(ByteCodecType::from(self.peek_byte()?))
+    }
+
+    /// Returns whether the reader ends before another value, e.g. to read
+    /// the values of a file until its end.
+    pub fn is_end(&mut self) -> Result<bool> {
+        match self.peek_byte() {
+            Ok(_) => Ok(false),
+            Err(e) if e.is_eof() => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read the next value, fails if the reader ends before it does.
+    pub fn read_value(&mut self) -> Result<Value> {
+        loop {
+            if let Some(value) = self.de.try_read_value()? {
+                return Ok(value);
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Returns the deserializer of the buffered input, e.g. to read its
+    /// tables.
+    pub fn deserializer(&self) -> &Deserializer<Vec<u8>> {
+        &self.de
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns a mutable reference to the reader. Bytes read from it
+    /// directly are not seen by the deserializer.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Unwrap the reader, the bytes buffered but not read yet are lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // Read the next piece of the reader into the input, fails at its end
+    fn fill(&mut self) -> Result<()> {
+        let len = PIECE.max(self.de.remaining());
+        self.piece.resize(len, 0);
+        let n = loop {
+            match self.reader.read(&mut self.piece) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        if n == 0 {
+            return Err(unexpected_eof());
+        }
+        self.de.feed(&self.piece[..n]);
+        Ok(())
+    }
+}
+
+/// Read a hessian 2.0 value from a reader
+pub fn from_reader<R: Read>(reader: R) -> Result<Value> {
+    ReaderDeserializer::new(reader).read_value()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_reader, ReaderDeserializer};
+    use crate::constant::ByteCodecType;
+    use crate::{to_vec, Value};
+    use std::io::{self, Read};
+
+    // Gives at most `n` bytes per read, and is interrupted before each one
+    struct Trickle<'a> {
+        input: &'a [u8],
+        n: usize,
+        interrupted: bool,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupted = !self.interrupted;
+            if self.interrupted {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let n = self.n.min(buf.len()).min(self.input.len());
+            buf[..n].copy_from_slice(&self.input[..n]);
+            self.input = &self.input[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_reader_deserializer() {
+        let values = [
+            hessian!({ "a": [1, 2, (hessian!("example.Car" => { "color": "red" }))] }),
+            Value::from("abc中文😀".repeat(30_000)),
+            hessian!("example.Car" => { "color": "green" }),
+            Value::Null,
+        ];
+        let mut buf = Vec::new();
+        for value in &values {
+            buf.extend(to_vec(value).unwrap());
+        }
+        for n in &[1, 7, buf.len()] {
+            let mut de = ReaderDeserializer::new(Trickle {
+                input: &buf,
+                n: *n,
+                interrupted: false,
+            });
+            assert!(matches!(
+                de.peek_byte_code_type(),
+                Ok(ByteCodecType::Map(false))
+            ));
+            for value in &values {
+                assert!(!de.is_end().unwrap());
+                assert_eq!(&de.read_value().unwrap(), value);
+            }
+            assert!(de.is_end().unwrap());
+            assert!(de.read_value().unwrap_err().is_eof());
+        }
+
+        assert_eq!(from_reader(&buf[..]).unwrap(), values[0]);
+        // a value cut in the middle
+        assert!(from_reader(&buf[..3]).unwrap_err().is_eof());
+    }
+}
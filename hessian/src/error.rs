@@ -8,6 +8,26 @@ pub enum ErrorKind {
     UnexpectedType(String),
     OutOfTypeRefRange(usize),
     OutOfDefinitionRange(usize),
+    CircularReference(String),
+    /// the input ends in the middle of the data of a string or binary chunk
+    UnexpectedEofInChunk,
+    /// a byte which isn't the tag of any value
+    InvalidTag(u8),
+    /// a byte which can't start a UTF-8 sequence in a string
+    InvalidUtf8Lead(u8),
+    /// a non-final chunk followed by something else than a chunk of the
+    /// same type
+    InvalidChunkContinuation,
+    /// a key repeated in a map, when duplicate keys are denied
+    DuplicateKey(String),
+    /// an object written with another number of values than its class has
+    /// fields: the class, the fields and the values written so far
+    FieldCount(String, usize, usize),
+    /// a list written with another number of elements than it was declared
+    /// with: the length and the elements written so far
+    ListLength(usize, usize),
+    /// a key written in a map without a value, or a value without a key
+    UnpairedMapEntry,
 }
 
 impl fmt::Display for ErrorKind {
@@ -19,6 +39,19 @@ impl fmt::Display for ErrorKind {
             UnexpectedType(typ) => write!(f, "unexpected type {}", typ),
             OutOfTypeRefRange(index) => write!(f, "out of type ref range: {}", index),
             OutOfDefinitionRange(index) => write!(f, "out of type definition range: {}", index),
+            CircularReference(typ) => write!(f, "circular reference to a value of type {}", typ),
+            UnexpectedEofInChunk => write!(f, "unexpected end of input in a chunk"),
+            InvalidTag(b) => write!(f, "invalid tag 0x{:02x}", b),
+            InvalidUtf8Lead(b) => write!(f, "invalid utf-8 leading byte 0x{:02x}", b),
+            InvalidChunkContinuation => write!(f, "chunk not followed by a chunk of its type"),
+            DuplicateKey(key) => write!(f, "duplicate map key {}", key),
+            FieldCount(class, fields, values) => {
+                write!(f, "{} has {} fields but {} values", class, fields, values)
+            }
+            ListLength(len, elements) => {
+                write!(f, "list of {} elements has {} elements", len, elements)
+            }
+            UnpairedMapEntry => write!(f, "map key without a value or value without a key"),
         }
     }
 }
@@ -30,6 +63,37 @@ pub enum Error {
     FromUtf8Error(FromUtf8Error),
 }
 
+impl Error {
+    /// Whether the input ends before the value does, the read can then be
+    /// retried once more input is available. Truncated input is always
+    /// reported this way.
+    pub fn is_eof(&self) -> bool {
+        match self {
+            Error::SyntaxError(kind) => *kind == ErrorKind::UnexpectedEofInChunk,
+            Error::IoError(err) => err.kind() == io::ErrorKind::UnexpectedEof,
+            Error::FromUtf8Error(_) => false,
+        }
+    }
+
+    /// Whether the input isn't valid hessian, reading more of it won't help
+    pub fn is_syntax(&self) -> bool {
+        match self {
+            Error::SyntaxError(_) => !self.is_eof(),
+            Error::IoError(_) => false,
+            Error::FromUtf8Error(_) => true,
+        }
+    }
+
+    /// Whether reading or writing the underlying stream failed, other than
+    /// by ending early
+    pub fn is_io(&self) -> bool {
+        match self {
+            Error::IoError(_) => !self.is_eof(),
+            _ => false,
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -40,6 +104,16 @@ impl fmt::Display for Error {
     }
 }
 
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::SyntaxError(_) => None,
+            Error::IoError(err) => Some(err),
+            Error::FromUtf8Error(err) => Some(err),
+        }
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(error: io::Error) -> Error {
         Error::IoError(error)
@@ -1,11 +1,24 @@
+#[macro_use]
+mod macros;
+
+pub mod codec;
 pub mod constant;
 pub mod de;
 mod error;
+pub mod parser;
 pub mod ser;
+pub mod session;
 pub mod value;
 
 pub use constant::ByteCodecType;
-pub use de::from_slice;
+#[cfg(feature = "arena")]
+pub use de::from_slice_in;
+pub use de::{from_reader, from_slice};
 pub use error::{Error, ErrorKind};
-pub use ser::to_vec;
-pub use value::Value;
+pub use parser::{Parser, ValueParser};
+pub use ser::{to_vec, to_vec_in, to_writer};
+pub use session::SessionCodec;
+pub use value::{HessianObject, Value};
+
+#[cfg(feature = "derive")]
+pub use hessian_derive::HessianObject;
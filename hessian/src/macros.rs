@@ -0,0 +1,133 @@
+/// Construct a `hessian_rs::Value` from a literal like syntax.
+///
+/// ```
+/// use hessian_rs::{hessian, Value};
+///
+/// let car = hessian!("example.Car" => {
+///     "color": "red",
+///     "mileage": 65536,
+///     "parts": "[string" => ["wheel", "door"],
+///     "prev": null,
+///     "tags": ["fast", 1_i64, { "nested": true }],
+/// });
+/// assert_eq!(car.as_map().unwrap().r#type(), Some("example.Car"));
+/// assert_eq!(hessian!(null), Value::Null);
+/// ```
+///
+/// Lists are written as `[...]` and maps as `{ key: value, ... }`,
+/// a `"type" =>` prefix makes a typed list or map. Map keys must be a
+/// single token tree, wrap complex expressions in parentheses.
+/// Any other expression is converted with `Value::from`.
+#[macro_export]
+macro_rules! hessian {
+    // Munch the elements of a list
+    (@list [$($elems:expr,)*]) => {
+        vec![$($elems,)*]
+    };
+    (@list [$($elems:expr,)*] null $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@list [$($elems,)* $crate::Value::Null,] $($($rest)*)?)
+    };
+    (@list [$($elems:expr,)*] [$($list:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@list [$($elems,)* $crate::hessian!([$($list)*]),] $($($rest)*)?)
+    };
+    (@list [$($elems:expr,)*] {$($map:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@list [$($elems,)* $crate::hessian!({$($map)*}),] $($($rest)*)?)
+    };
+    (@list [$($elems:expr,)*] $typ:literal => [$($list:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@list [$($elems,)* $crate::hessian!($typ => [$($list)*]),] $($($rest)*)?)
+    };
+    (@list [$($elems:expr,)*] $typ:literal => {$($map:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@list [$($elems,)* $crate::hessian!($typ => {$($map)*}),] $($($rest)*)?)
+    };
+    (@list [$($elems:expr,)*] $next:expr $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@list [$($elems,)* $crate::hessian!($next),] $($($rest)*)?)
+    };
+
+    // Munch the entries of a map
+    (@map [$($entries:expr,)*]) => {
+        vec![$($entries,)*]
+            .into_iter()
+            .collect::<::std::collections::HashMap<$crate::Value, $crate::Value>>()
+    };
+    (@map [$($entries:expr,)*] $key:tt : null $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@map [$($entries,)* ($crate::hessian!($key), $crate::Value::Null),] $($($rest)*)?)
+    };
+    (@map [$($entries:expr,)*] $key:tt : [$($list:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@map [$($entries,)* ($crate::hessian!($key), $crate::hessian!([$($list)*])),] $($($rest)*)?)
+    };
+    (@map [$($entries:expr,)*] $key:tt : {$($map:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@map [$($entries,)* ($crate::hessian!($key), $crate::hessian!({$($map)*})),] $($($rest)*)?)
+    };
+    (@map [$($entries:expr,)*] $key:tt : $typ:literal => [$($list:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@map [$($entries,)* ($crate::hessian!($key), $crate::hessian!($typ => [$($list)*])),] $($($rest)*)?)
+    };
+    (@map [$($entries:expr,)*] $key:tt : $typ:literal => {$($map:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@map [$($entries,)* ($crate::hessian!($key), $crate::hessian!($typ => {$($map)*})),] $($($rest)*)?)
+    };
+    (@map [$($entries:expr,)*] $key:tt : $value:expr $(, $($rest:tt)*)?) => {
+        $crate::hessian!(@map [$($entries,)* ($crate::hessian!($key), $crate::hessian!($value)),] $($($rest)*)?)
+    };
+
+    (null) => {
+        $crate::Value::Null
+    };
+    ([$($list:tt)*]) => {
+        $crate::Value::List($crate::value::List::from($crate::hessian!(@list [] $($list)*)))
+    };
+    ({$($map:tt)*}) => {
+        $crate::Value::Map($crate::value::Map::from($crate::hessian!(@map [] $($map)*)))
+    };
+    ($typ:literal => [$($list:tt)*]) => {
+        $crate::Value::List($crate::value::List::from(($typ, $crate::hessian!(@list [] $($list)*))))
+    };
+    ($typ:literal => {$($map:tt)*}) => {
+        $crate::Value::Map($crate::value::Map::from(($typ, $crate::hessian!(@map [] $($map)*))))
+    };
+    ($other:expr) => {
+        $crate::Value::from($other)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::value::{List, Map, Value};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_hessian_macro() {
+        assert_eq!(hessian!(null), Value::Null);
+        assert_eq!(hessian!(1), Value::Int(1));
+        assert_eq!(hessian!(-1_i64), Value::Long(-1));
+        assert_eq!(hessian!("abc"), Value::String("abc".into()));
+        assert_eq!(hessian!([]), Value::List(List::from(vec![])));
+        assert_eq!(
+            hessian!([1, null, "a",]),
+            Value::List(List::from(vec![
+                Value::Int(1),
+                Value::Null,
+                Value::String("a".into())
+            ]))
+        );
+        assert_eq!(
+            hessian!("[int" => [1, 2]),
+            Value::List(List::from(("[int", vec![Value::Int(1), Value::Int(2)])))
+        );
+
+        let len = 2;
+        let mut map = HashMap::new();
+        map.insert(Value::from("a"), Value::Int(1));
+        map.insert(Value::Int(2), Value::List(List::from(vec![Value::Int(2)])));
+        map.insert(
+            Value::from("c"),
+            Value::Map(Map::from(("example.Car", HashMap::new()))),
+        );
+        assert_eq!(
+            hessian!({
+                "a": 1,
+                (len): [len],
+                "c": "example.Car" => {},
+            }),
+            Value::Map(Map::from(map))
+        );
+    }
+}
@@ -0,0 +1,582 @@
+//! Parse a hessian stream pushed piece by piece.
+//!
+//! A `Parser` does no IO: the caller reads the bytes however it likes, an
+//! event loop, io_uring or a WASM host, and feeds them to the parser, which
+//! returns the events they complete. Only the bytes of an item which isn't
+//! complete yet, e.g. half a string, are kept until the next feed.
+//!
+//! A `ValueParser` builds whole values from the events instead.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use crate::constant::ByteCodecType;
+use crate::de::{unexpected_eof, Deserializer};
+use crate::error::Error::SyntaxError;
+use crate::error::{ErrorKind, Result};
+use crate::value::{Definition, List, Map, Value};
+
+/// What a `Parser` read, in the order of the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A value which is neither a list nor a map: null, a bool, a number, a
+    /// date, bytes, a string or a ref.
+    Value(Value),
+    /// The start of a list, with its type when typed and its length when
+    /// written.
+    ListStart {
+        tp: Option<String>,
+        len: Option<usize>,
+    },
+    ListEnd,
+    /// The start of a map, with its type when typed. An object is a map
+    /// typed by its class, whose keys are the names of its fields.
+    MapStart {
+        tp: Option<String>,
+    },
+    MapEnd,
+}
+
+// A container being read
+enum Frame {
+    // A list of a known length, with the number of values left
+    List(usize),
+    // A list which ends with 'Z'
+    VarList,
+    Map,
+    // An object, with the index of the field whose value comes next
+    Object(Arc<Definition>, usize),
+}
+
+// An item of the stream, read at once
+enum Token {
+    Value(Value),
+    List(Option<String>, Option<usize>),
+    Map(Option<String>),
+    Object(Arc<Definition>),
+    Definition,
+    End,
+}
+
+fn read_token<R: AsRef<[u8]>>(de: &mut Deserializer<R>) -> Result<Token> {
+    if de.peek_byte()? == b'Z' {
+        de.read_byte()?;
+        return Ok(Token::End);
+    }
+    let token = match de.peek_byte_code_type()? {
+        ByteCodecType::List(_) => {
+            let (tp, len) = de.read_list_header()?;
+            Token::List(tp, len)
+        }
+        ByteCodecType::Map(typed) => {
+            de.read_byte()?;
+            Token::Map(if typed { Some(de.read_type()?) } else { None })
+        }
+        ByteCodecType::Object(o) => {
+            de.read_byte()?;
+            Token::Object(de.read_shared_definition(o)?)
+        }
+        ByteCodecType::Definition => {
+            de.read_byte()?;
+            de.read_definition()?;
+            Token::Definition
+        }
+        _ => Token::Value(de.read_value()?),
+    };
+    Ok(token)
+}
+
+/// A push parser of a hessian stream, see the module documentation.
+///
+/// ```
+/// use hessian_rs::parser::{Event, Parser};
+/// use hessian_rs::Value;
+///
+/// let mut parser = Parser::new();
+/// // an untyped list of 2 ints, cut in the middle of the second one
+/// assert_eq!(
+///     parser.feed(&[0x7a, 0x91, 0xc9]).unwrap(),
+///     [
+///         Event::ListStart { tp: None, len: Some(2) },
+///         Event::Value(Value::Int(1)),
+///     ]
+/// );
+/// assert!(parser.needs_more());
+/// assert_eq!(
+///     parser.feed(&[0x00]).unwrap(),
+///     [Event::Value(Value::Int(256)), Event::ListEnd]
+/// );
+/// assert!(!parser.needs_more());
+/// ```
+pub struct Parser {
+    de: Deserializer<Vec<u8>>,
+    stack: Vec<Frame>,
+    // Whether bytes of an incomplete item are kept
+    pending: bool,
+    // The bytes of the stream dropped from the input of `de`
+    offset: u64,
+    // Whether the definitions and types are forgotten after every value,
+    // as for the messages of a `HessianCodec`
+    separate_tables: bool,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Parser {
+            de: Deserializer::new(Vec::new()),
+            stack: Vec::new(),
+            pending: false,
+            offset: 0,
+            separate_tables: false,
+        }
+    }
+
+    /// Parse the next bytes of the stream, returns the events of the items
+    /// they complete. After an error the parser is lost in the stream and
+    /// shouldn't be fed anymore.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Vec<Event>> {
+        let mut events = Vec::new();
+        self.feed_into(bytes, &mut events, &mut Vec::new())?;
+        Ok(events)
+    }
+
+    // `feed`, which keeps the events read before an error, and the
+    // positions where the values at the top level end
+    fn feed_into(
+        &mut self,
+        bytes: &[u8],
+        events: &mut Vec<Event>,
+        ends: &mut Vec<u64>,
+    ) -> Result<()> {
+        self.offset += self.de.position();
+        self.de.feed(bytes);
+        while let Some(token) = self.de.attempt(read_token)? {
+            let len = events.len();
+            self.push(token, events)?;
+            if events.len() > len && self.stack.is_empty() {
+                ends.push(self.position());
+                if self.separate_tables {
+                    self.de.clear_tables();
+                }
+            }
+        }
+        self.pending = self.de.peek_byte().is_ok();
+        Ok(())
+    }
+
+    /// Returns whether the stream fed so far stops in the middle of a value,
+    /// so more input is needed before a value is complete.
+    pub fn needs_more(&self) -> bool {
+        self.pending || !self.stack.is_empty()
+    }
+
+    /// Returns the number of bytes of the stream parsed so far, or up to
+    /// the error after one.
+    pub fn position(&self) -> u64 {
+        self.offset + self.de.position()
+    }
+
+    /// Returns the type of the next item of the stream, once its first
+    /// byte is fed but the item isn't complete yet.
+    pub fn peek_byte_code_type(&self) -> Option<ByteCodecType> {
+        self.de.peek_byte_code_type().ok()
+    }
+
+    /// Tell the parser the stream ends, fails if it stops in the middle of
+    /// a value.
+    pub fn finish(&self) -> Result<()> {
+        match self.needs_more() {
+            true => Err(unexpected_eof()),
+            false => Ok(()),
+        }
+    }
+
+    fn push(&mut self, token: Token, events: &mut Vec<Event>) -> Result<()> {
+        // whether the token completes a value
+        let complete = match token {
+            Token::Definition => false,
+            Token::Value(v) => {
+                events.push(Event::Value(v));
+                true
+            }
+            Token::List(tp, len) => {
+                events.push(Event::ListStart { tp, len });
+                match len {
+                    Some(0) => events.push(Event::ListEnd),
+                    Some(n) => self.stack.push(Frame::List(n)),
+                    None => self.stack.push(Frame::VarList),
+                }
+                len == Some(0)
+            }
+            Token::Map(tp) => {
+                events.push(Event::MapStart { tp });
+                self.stack.push(Frame::Map);
+                false
+            }
+            Token::Object(def) => {
+                events.push(Event::MapStart {
+                    tp: Some(def.name.clone()),
+                });
+                match def.fields.first() {
+                    Some(field) => {
+                        events.push(Event::Value(Value::String(field.into())));
+                        self.stack.push(Frame::Object(def, 0));
+                        false
+                    }
+                    None => {
+                        events.push(Event::MapEnd);
+                        true
+                    }
+                }
+            }
+            Token::End => {
+                let end = match self.stack.pop() {
+                    Some(Frame::VarList) => Event::ListEnd,
+                    Some(Frame::Map) => Event::MapEnd,
+                    _ => {
+                        return Err(SyntaxError(ErrorKind::UnexpectedType(
+                            "end of a list or a map outside of one".to_string(),
+                        )))
+                    }
+                };
+                events.push(end);
+                true
+            }
+        };
+        if complete {
+            self.complete(events);
+        }
+        Ok(())
+    }
+
+    // A value is complete, which may complete the containers it's in
+    fn complete(&mut self, events: &mut Vec<Event>) {
+        while let Some(frame) = self.stack.last_mut() {
+            match frame {
+                Frame::List(left) => {
+                    *left -= 1;
+                    if *left > 0 {
+                        break;
+                    }
+                    events.push(Event::ListEnd);
+                }
+                Frame::Object(def, field) => {
+                    *field += 1;
+                    if let Some(name) = def.fields.get(*field) {
+                        events.push(Event::Value(Value::String(name.into())));
+                        break;
+                    }
+                    events.push(Event::MapEnd);
+                }
+                Frame::VarList | Frame::Map => break,
+            }
+            self.stack.pop();
+        }
+    }
+}
+
+// A list or a map being built by a `ValueParser`
+enum Partial {
+    List(Option<String>, Vec<Value>),
+    // with the key whose value comes next, once read
+    Map(Option<String>, HashMap<Value, Value>, Option<Value>),
+}
+
+/// A push parser of whole values, built from the events of a `Parser`.
+///
+/// Unlike `Deserializer::try_read_value`, a list or a map fed piece by piece
+/// isn't parsed again from its start for every piece.
+///
+/// ```
+/// use hessian_rs::parser::ValueParser;
+/// use hessian_rs::Value;
+///
+/// let mut parser = ValueParser::new();
+/// // 1, then an untyped list of 2 ints cut in the middle of the second one
+/// parser.feed(&[0x91, 0x7a, 0x91, 0xc9]).unwrap();
+/// assert_eq!(parser.next_value(), Some(Value::Int(1)));
+/// assert_eq!(parser.next_value(), None);
+/// parser.feed(&[0x00]).unwrap();
+/// let list = vec![Value::Int(1), Value::Int(256)];
+/// assert_eq!(parser.next_value(), Some(Value::List(list.into())));
+/// ```
+#[derive(Default)]
+pub struct ValueParser {
+    parser: Parser,
+    partial: Vec<Partial>,
+    // Values complete and not returned yet, with their ends
+    values: VecDeque<Value>,
+    ends: VecDeque<u64>,
+}
+
+impl ValueParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the next bytes of the stream, the values they complete are
+    /// returned by `next_value`, including the ones before an error. After
+    /// an error the parser is lost in the stream and shouldn't be fed
+    /// anymore.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<()> {
+        let mut events = Vec::new();
+        let mut ends = Vec::new();
+        let result = self.parser.feed_into(bytes, &mut events, &mut ends);
+        for event in events {
+            self.push(event);
+        }
+        self.ends.extend(ends);
+        result
+    }
+
+    /// Returns the next complete value, in the order of the stream.
+    pub fn next_value(&mut self) -> Option<Value> {
+        self.next_value_end().map(|(value, _)| value)
+    }
+
+    // The next complete value with the position of the stream where it ends
+    pub(crate) fn next_value_end(&mut self) -> Option<(Value, u64)> {
+        let value = self.values.pop_front()?;
+        Some((value, self.ends.pop_front()?))
+    }
+
+    // Forget the definitions and types after every value, see `Parser`
+    pub(crate) fn separate_tables(mut self) -> Self {
+        self.parser.separate_tables = true;
+        self
+    }
+
+    /// See `Parser::needs_more`.
+    pub fn needs_more(&self) -> bool {
+        self.parser.needs_more()
+    }
+
+    /// See `Parser::position`.
+    pub fn position(&self) -> u64 {
+        self.parser.position()
+    }
+
+    /// See `Parser::peek_byte_code_type`.
+    pub fn peek_byte_code_type(&self) -> Option<ByteCodecType> {
+        self.parser.peek_byte_code_type()
+    }
+
+    /// See `Parser::finish`.
+    pub fn finish(&self) -> Result<()> {
+        self.parser.finish()
+    }
+
+    fn push(&mut self, event: Event) {
+        let value = match event {
+            Event::Value(v) => v,
+            Event::ListStart { tp, .. } => {
+                self.partial.push(Partial::List(tp, Vec::new()));
+                return;
+            }
+            Event::MapStart { tp } => {
+                self.partial.push(Partial::Map(tp, HashMap::new(), None));
+                return;
+            }
+            // the parser checks the ends match the starts
+            Event::ListEnd | Event::MapEnd => match self.partial.pop() {
+                Some(Partial::List(tp, values)) => Value::List(match tp {
+                    Some(tp) => List::Typed(tp, values),
+                    None => List::Untyped(values),
+                }),
+                Some(Partial::Map(tp, entries, _)) => Value::Map(match tp {
+                    Some(tp) => Map::Typed(tp, entries),
+                    None => Map::Untyped(entries),
+                }),
+                None => unreachable!("an end without a start"),
+            },
+        };
+        match self.partial.last_mut() {
+            None => self.values.push_back(value),
+            Some(Partial::List(_, values)) => values.push(value),
+            Some(Partial::Map(_, entries, key)) => match key.take() {
+                Some(k) => {
+                    entries.insert(k, value);
+                }
+                None => *key = Some(value),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, Parser, ValueParser};
+    use crate::constant::{ByteCodecType, String as StringType};
+    use crate::value::{List, Map, Value};
+    use crate::{from_slice, to_vec};
+    use std::collections::HashMap;
+
+    // The values the events describe
+    fn build(events: &mut std::vec::IntoIter<Event>) -> Value {
+        match events.next().unwrap() {
+            Event::Value(v) => v,
+            Event::ListStart { tp, .. } => {
+                let mut values = Vec::new();
+                while events.as_slice()[0] != Event::ListEnd {
+                    values.push(build(events));
+                }
+                events.next();
+                Value::List(match tp {
+                    Some(tp) => List::Typed(tp, values),
+                    None => List::Untyped(values),
+                })
+            }
+            Event::MapStart { tp } => {
+                let mut entries = HashMap::new();
+                while events.as_slice()[0] != Event::MapEnd {
+                    let k = build(events);
+                    entries.insert(k, build(events));
+                }
+                events.next();
+                Value::Map(match tp {
+                    Some(tp) => Map::Typed(tp, entries),
+                    None => Map::Untyped(entries),
+                })
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_feed_piece_by_piece() {
+        let value = hessian!({
+            "cars": [
+                (hessian!("example.Car" => { "color": "red", "model": "corvette" })),
+                (hessian!("example.Car" => { "color": "green", "model": "civic" }))
+            ],
+            "empty": [],
+            "ints": (Value::List(("[int", vec![Value::Int(1), Value::Int(2)]).into())),
+            "text": (Value::from("abc中文😀".repeat(30_000)))
+        });
+        let buf = to_vec(&value).unwrap();
+        for size in &[1, 7, buf.len()] {
+            let mut parser = Parser::new();
+            let mut events = Vec::new();
+            for chunk in buf.chunks(*size) {
+                events.extend(parser.feed(chunk).unwrap());
+            }
+            assert!(!parser.needs_more());
+            let mut events = events.into_iter();
+            assert_eq!(build(&mut events), value);
+            assert_eq!(events.len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_feed_objects() {
+        // the definition of example.Car then two objects, with a field each
+        let buf = [
+            0x7a, b'C', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r',
+            0x91, 0x05, b'c', b'o', b'l', b'o', b'r', 0x60, 0x03, b'r', b'e', b'd', 0x60, 0x04,
+            b'b', b'l', b'u', b'e',
+        ];
+        let mut parser = Parser::new();
+        let events = parser.feed(&buf).unwrap();
+        let car = |color: &str| {
+            vec![
+                Event::MapStart {
+                    tp: Some("example.Car".to_string()),
+                },
+                Event::Value(Value::from("color")),
+                Event::Value(Value::from(color)),
+                Event::MapEnd,
+            ]
+        };
+        let mut expected = vec![Event::ListStart {
+            tp: None,
+            len: Some(2),
+        }];
+        expected.extend(car("red"));
+        expected.extend(car("blue"));
+        expected.push(Event::ListEnd);
+        assert_eq!(events, expected);
+        assert_eq!(build(&mut events.into_iter()), from_slice(&buf).unwrap());
+    }
+
+    #[test]
+    fn test_needs_more() {
+        let mut parser = Parser::new();
+        assert!(!parser.needs_more());
+        // a map with a key but no value yet
+        assert_eq!(
+            parser.feed(&[b'H', 0x91]).unwrap(),
+            [Event::MapStart { tp: None }, Event::Value(Value::Int(1))]
+        );
+        assert!(parser.needs_more());
+        assert!(parser.peek_byte_code_type().is_none());
+        assert_eq!(
+            parser.feed(&[0x03, b'a', b'b']).unwrap(),
+            Vec::<Event>::new()
+        );
+        assert!(parser.needs_more());
+        assert!(matches!(
+            parser.peek_byte_code_type(),
+            Some(ByteCodecType::String(StringType::Compact(0x03)))
+        ));
+        assert_eq!(
+            parser.feed(b"cZN").unwrap(),
+            [
+                Event::Value(Value::from("abc")),
+                Event::MapEnd,
+                Event::Value(Value::Null)
+            ]
+        );
+        assert!(!parser.needs_more());
+    }
+
+    #[test]
+    fn test_value_parser() {
+        let values = [
+            hessian!({ "a": [1, 2, (hessian!("example.Car" => { "color": "red" }))] }),
+            Value::Null,
+            Value::from("abc中文😀".repeat(30_000)),
+            hessian!([]),
+        ];
+        let mut buf = Vec::new();
+        for value in &values {
+            buf.extend(to_vec(value).unwrap());
+        }
+        for size in &[1, 7, buf.len()] {
+            let mut parser = ValueParser::new();
+            let mut read = Vec::new();
+            for chunk in buf.chunks(*size) {
+                parser.feed(chunk).unwrap();
+                read.extend(std::iter::from_fn(|| parser.next_value()));
+            }
+            assert!(parser.finish().is_ok());
+            assert_eq!(read, values);
+            assert_eq!(parser.position(), buf.len() as u64);
+        }
+
+        // the values before an error are kept
+        let mut parser = ValueParser::new();
+        assert!(parser.feed(&[0x91, 0x92, 0x45]).is_err());
+        assert_eq!(parser.next_value(), Some(Value::Int(1)));
+        assert_eq!(parser.next_value(), Some(Value::Int(2)));
+        assert_eq!(parser.position(), 3);
+
+        let mut parser = ValueParser::new();
+        parser.feed(&[0x91, 0x03, b'r']).unwrap();
+        assert_eq!(parser.next_value(), Some(Value::Int(1)));
+        assert!(parser.finish().is_err());
+        assert_eq!(parser.position(), 1);
+    }
+
+    #[test]
+    fn test_feed_error() {
+        assert!(Parser::new().feed(b"Z").is_err());
+        assert!(Parser::new().feed(&[0x79, 0x91, b'Z']).is_err());
+        assert!(Parser::new().feed(&[0x60]).is_err());
+    }
+}
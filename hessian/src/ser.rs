@@ -1,15 +1,264 @@
+use std::collections::{HashMap, HashSet};
 use std::io;
+use std::iter::FromIterator;
+use std::sync::Arc;
 
 use byteorder::{BigEndian, WriteBytesExt};
 use indexmap::{IndexMap, IndexSet};
 
-use super::error::Result;
-use super::value::{self, Definition, Value};
+use super::error::{Error, ErrorKind, Result};
+use super::value::{self, Definition, HessianObject, Value};
+
+mod guard;
+
+pub use guard::{ListWriter, MapWriter, ObjectWriter};
+
+const MAX_BINARY_CHUNK_LEN: usize = 0xffff;
+
+const MAX_STRING_CHUNK_LEN: usize = 0x8000;
+
+// Returns the length in 16-bit chars and the end in bytes of the first
+// chunk of `s`, a char outside the BMP is never split across chunks
+fn string_chunk_end(s: &str) -> (usize, usize) {
+    let mut len = 0;
+    for (i, c) in s.char_indices() {
+        if len + c.len_utf16() > MAX_STRING_CHUNK_LEN {
+            return (len, i);
+        }
+        len += c.len_utf16();
+    }
+    (len, s.len())
+}
+
+// Read until `buf` is full or the reader is exhausted
+fn read_full<R: io::Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+/// Writer which only counts the bytes written to it
+struct SizeCounter(usize);
+
+impl io::Write for SizeCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Options of a `Serializer`, see `Serializer::builder`.
+///
+/// Every compact encoding is enabled by default, disable them for peers
+/// which only accept the long forms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerializerBuilder {
+    compact_int: bool,
+    compact_long: bool,
+    compact_double: bool,
+    compact_date: bool,
+    compact_string: bool,
+    compact_objects: bool,
+    canonical: bool,
+    shared_refs: bool,
+    cesu8: bool,
+    allow_nan: bool,
+}
+
+impl Default for SerializerBuilder {
+    fn default() -> Self {
+        SerializerBuilder {
+            compact_int: true,
+            compact_long: true,
+            compact_double: true,
+            compact_date: true,
+            compact_string: true,
+            compact_objects: true,
+            canonical: false,
+            shared_refs: false,
+            cesu8: false,
+            allow_nan: true,
+        }
+    }
+}
+
+impl SerializerBuilder {
+    /// Write ints in one to three bytes when they fit, otherwise always `'I' b3 b2 b1 b0`.
+    pub fn compact_int(mut self, compact_int: bool) -> Self {
+        self.compact_int = compact_int;
+        self
+    }
+
+    /// Write longs in one to five bytes when they fit, otherwise always `'L'` and 8 bytes.
+    pub fn compact_long(mut self, compact_long: bool) -> Self {
+        self.compact_long = compact_long;
+        self
+    }
+
+    /// Write doubles holding small integers or whole milliseconds in one
+    /// to five bytes, otherwise always `'D'` and 8 bytes. A compact form is
+    /// only used when it decodes to exactly the same double.
+    pub fn compact_double(mut self, compact_double: bool) -> Self {
+        self.compact_double = compact_double;
+        self
+    }
+
+    /// Write dates of whole minutes as `x4b` and a 32-bit int of minutes,
+    /// like java does, otherwise always `x4a` and 64-bit milliseconds.
+    pub fn compact_date(mut self, compact_date: bool) -> Self {
+        self.compact_date = compact_date;
+        self
+    }
+
+    /// Write the length of short strings in one or two bytes, otherwise
+    /// always `'S' b1 b0`.
+    pub fn compact_string(mut self, compact_string: bool) -> Self {
+        self.compact_string = compact_string;
+        self
+    }
+
+    /// See `Serializer::compact_objects`.
+    pub fn compact_objects(mut self, compact_objects: bool) -> Self {
+        self.compact_objects = compact_objects;
+        self
+    }
+
+    /// See `Serializer::canonical`.
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    /// See `Serializer::shared_refs`.
+    pub fn shared_refs(mut self, shared_refs: bool) -> Self {
+        self.shared_refs = shared_refs;
+        self
+    }
+
+    /// Write chars outside the BMP as a surrogate pair of two 3-byte
+    /// sequences (CESU-8) like java does, instead of one 4-byte UTF-8
+    /// sequence. Disabled by default.
+    pub fn cesu8(mut self, cesu8: bool) -> Self {
+        self.cesu8 = cesu8;
+        self
+    }
+
+    /// See `Serializer::allow_nan`.
+    pub fn allow_nan(mut self, allow_nan: bool) -> Self {
+        self.allow_nan = allow_nan;
+        self
+    }
+
+    pub fn build<W: io::Write>(self, writer: W) -> Serializer<W> {
+        Serializer {
+            writer,
+            type_cache: IndexSet::new(),
+            classes_cache: IndexMap::new(),
+            options: self,
+            ref_count: 0,
+            shared: if self.shared_refs {
+                Some(HashMap::new())
+            } else {
+                None
+            },
+            preloaded: DefinitionCache::new(),
+            reserved: HashSet::new(),
+        }
+    }
+}
+
+/// Class definitions known to readers beforehand, shared by serializers.
+///
+/// The cache is immutable and cheap to clone, build it once, e.g. with
+/// `Serializer::definition_cache` after writing a first message, and attach
+/// it to every new serializer with `Serializer::preload_definitions`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DefinitionCache(Arc<IndexMap<String, Definition>>);
+
+impl DefinitionCache {
+    pub fn new() -> Self {
+        DefinitionCache::default()
+    }
+
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&Definition> {
+        self.0.get(name)
+    }
+
+    #[inline]
+    pub fn get_index_of(&self, name: &str) -> Option<usize> {
+        self.0.get_index_of(name)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate the definitions in ref order
+    pub fn iter(&self) -> impl Iterator<Item = &Definition> {
+        self.0.values()
+    }
+}
+
+impl FromIterator<Definition> for DefinitionCache {
+    /// Collect definitions in ref order, a later definition with the same
+    /// name replaces the earlier one.
+    fn from_iter<I: IntoIterator<Item = Definition>>(iter: I) -> Self {
+        DefinitionCache(Arc::new(
+            iter.into_iter()
+                .map(|def| (def.name.clone(), def))
+                .collect(),
+        ))
+    }
+}
+
+impl From<Vec<Definition>> for DefinitionCache {
+    fn from(defs: Vec<Definition>) -> Self {
+        defs.into_iter().collect()
+    }
+}
 
 pub struct Serializer<W> {
     writer: W,
     type_cache: IndexSet<String>,
-    classes_cache: IndexMap<String, Definition>,
+    // Definitions written so far, `None` for a reserved one not written yet
+    classes_cache: IndexMap<String, Option<Definition>>,
+    options: SerializerBuilder,
+    // Number of maps, lists and objects written, which is the id of the next one
+    ref_count: u32,
+    // The ids of the values written by `serialize_shared` so far, by
+    // address, if shared references are enabled. The Arcs keep the
+    // addresses from being reused by other values.
+    shared: Option<HashMap<usize, (u32, Arc<Value>)>>,
+    // Definitions known to the reader beforehand, never written
+    preloaded: DefinitionCache,
+    // Definitions reserved by `begin_buffered_object` on this serializer
+    reserved: HashSet<String>,
+}
+
+// The lengths of the caches of a serializer, see `Serializer::checkpoint`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Checkpoint {
+    types: usize,
+    classes: usize,
+    ref_count: u32,
 }
 
 trait IdentifyLast: Iterator + Sized {
@@ -57,15 +306,161 @@ where
     }
 }
 
+impl Serializer<()> {
+    pub fn builder() -> SerializerBuilder {
+        SerializerBuilder::default()
+    }
+}
+
 impl<W: io::Write> Serializer<W> {
     pub fn new(writer: W) -> Self {
-        Serializer {
-            writer,
-            type_cache: IndexSet::new(),
-            classes_cache: IndexMap::new(),
+        SerializerBuilder::default().build(writer)
+    }
+
+    /// Returns the options of this serializer, to build another one alike.
+    pub fn to_builder(&self) -> SerializerBuilder {
+        self.options
+    }
+
+    /// Write the same bytes for the same logical value.
+    ///
+    /// Map entries are written in key order and every NaN is written as
+    /// the same bit pattern. Disabled by default.
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.options.canonical = canonical;
+        self
+    }
+
+    #[inline]
+    pub fn is_canonical(&self) -> bool {
+        self.options.canonical
+    }
+
+    /// Write instances of the first 16 definitions with the one byte
+    /// tags `0x60` to `0x6f` instead of `'O'` and an int. Enabled by default.
+    pub fn compact_objects(mut self, compact_objects: bool) -> Self {
+        self.options.compact_objects = compact_objects;
+        self
+    }
+
+    /// Write NaN and the infinities, which some peers reject, enabled by
+    /// default. When disabled writing them fails and nothing is written.
+    pub fn allow_nan(mut self, allow_nan: bool) -> Self {
+        self.options.allow_nan = allow_nan;
+        self
+    }
+
+    /// Write a `ref` to the earlier copy when `serialize_shared` meets a
+    /// value it has already written. Disabled by default.
+    ///
+    /// Every map, list and object written through this serializer is
+    /// numbered in order, matching the numbering readers use to resolve refs.
+    pub fn shared_refs(mut self, shared_refs: bool) -> Self {
+        self.options.shared_refs = shared_refs;
+        self.shared = if shared_refs {
+            Some(HashMap::new())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Use definitions the reader already knows, e.g. agreed on for a session.
+    ///
+    /// The preloaded definitions take the refs `0..defs.len()` in order and
+    /// are never written, they are kept across `reset` and `clear_caches`.
+    /// Definitions written before are forgotten.
+    pub fn preload_definitions(mut self, defs: DefinitionCache) -> Self {
+        self.preloaded = defs;
+        self.classes_cache.clear();
+        self
+    }
+
+    #[inline]
+    pub fn preloaded_definitions(&self) -> &DefinitionCache {
+        &self.preloaded
+    }
+
+    /// Returns the preloaded definitions and the ones written so far, to
+    /// preload other serializers with.
+    pub fn definition_cache(&self) -> DefinitionCache {
+        if self.classes_cache.is_empty() {
+            return self.preloaded.clone();
+        }
+        self.preloaded
+            .iter()
+            .chain(self.classes_cache.values().flatten())
+            .cloned()
+            .collect()
+    }
+
+    /// Forget the types and definitions written so far, as if starting a
+    /// new stream. Preloaded definitions are kept.
+    pub fn clear_caches(&mut self) {
+        self.type_cache.clear();
+        self.classes_cache.clear();
+        self.reserved.clear();
+    }
+
+    /// Reset the serializer to write a new, unrelated message.
+    ///
+    /// Clears the caches and the refs numbering, the options and the
+    /// preloaded definitions are kept.
+    pub fn reset(&mut self) {
+        self.clear_caches();
+        self.ref_count = 0;
+        if let Some(shared) = self.shared.as_mut() {
+            shared.clear();
         }
     }
 
+    // The caches only grow while values are written, so they are restored
+    // by truncating them to their lengths at the checkpoint
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            types: self.type_cache.len(),
+            classes: self.classes_cache.len(),
+            ref_count: self.ref_count,
+        }
+    }
+
+    /// Forget the types, definitions and refs written since `checkpoint`,
+    /// when what was written since is discarded.
+    pub(crate) fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.type_cache.truncate(checkpoint.types);
+        self.classes_cache.truncate(checkpoint.classes);
+        let classes = &self.classes_cache;
+        self.reserved.retain(|name| classes.contains_key(name));
+        self.ref_count = checkpoint.ref_count;
+        if let Some(shared) = self.shared.as_mut() {
+            shared.retain(|_, (id, _)| *id < checkpoint.ref_count);
+        }
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    ///
+    /// Bytes written directly to it are not seen by the serializer, so
+    /// refs and definitions may no longer match what a reader sees.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Unwrap the underlying writer, without flushing it.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
     pub fn extend_from_slice(&mut self, slice: &[u8]) -> Result<()> {
         self.writer.write_all(slice)?;
         Ok(())
@@ -84,12 +479,65 @@ impl<W: io::Write> Serializer<W> {
             Value::Ref(i) => self.serialize_ref(i),
             Value::List(ref l) => self.serialize_list(l),
             Value::Map(ref m) => self.serialize_map(m),
+            // written back as read
+            Value::Unknown(ref bytes) => self.extend_from_slice(bytes),
+            Value::Shared(ref v) => self.serialize_shared(v),
         }
     }
 
+    /// Write `value`, or a ref to it if this serializer has written it
+    /// before and shared references are enabled, see `shared_refs`.
+    ///
+    /// Values are told apart by their Arc like java objects by their
+    /// identity: the clones of an Arc are the same value, equal values in
+    /// different Arcs are different ones. Only maps and lists take a ref.
+    /// `serialize_value` writes the `Value::Shared` nested in a value this
+    /// way, e.g. a map held twice by a list.
+    pub fn serialize_shared(&mut self, value: &Arc<Value>) -> Result<()> {
+        if let (Value::List(_) | Value::Map(_), Some(shared)) = (&**value, self.shared.as_mut()) {
+            let address = Arc::as_ptr(value) as usize;
+            if let Some((id, _)) = shared.get(&address) {
+                let id = *id;
+                return self.serialize_ref(id);
+            }
+            shared.insert(address, (self.ref_count, value.clone()));
+        }
+        self.serialize_value(value)
+    }
+
+    /// Returns the number of bytes `serialize_value` would write for `value`
+    /// next, taking the types and definitions already written into account.
+    pub fn size_of(&self, value: &Value) -> Result<usize> {
+        let mut counter = Serializer {
+            writer: SizeCounter(0),
+            type_cache: self.type_cache.clone(),
+            classes_cache: self.classes_cache.clone(),
+            options: self.options,
+            ref_count: self.ref_count,
+            shared: self.shared.clone(),
+            preloaded: self.preloaded.clone(),
+            reserved: self.reserved.clone(),
+        };
+        counter.serialize_value(value)?;
+        Ok(counter.writer.0)
+    }
+
     #[inline]
     pub fn get_definition(&self, name: &str) -> Option<&Definition> {
-        self.classes_cache.get(name)
+        self.preloaded
+            .get(name)
+            .or_else(|| self.classes_cache.get(name).and_then(Option::as_ref))
+    }
+
+    /// Returns the ref of the definition named `name`, preloaded, written or
+    /// reserved by `begin_buffered_object`.
+    #[inline]
+    pub fn get_definition_index(&self, name: &str) -> Option<usize> {
+        self.preloaded.get_index_of(name).or_else(|| {
+            self.classes_cache
+                .get_index_of(name)
+                .map(|inx| self.preloaded.len() + inx)
+        })
     }
 
     #[inline]
@@ -105,30 +553,119 @@ impl<W: io::Write> Serializer<W> {
         Ok(())
     }
 
+    /// Write `object` as an instance of its class, see `HessianObject`.
+    pub fn serialize_object<T: HessianObject>(&mut self, object: &T) -> Result<()> {
+        self.serialize_fields_with_definition(&T::definition(), &object.to_fields())
+    }
+
     #[inline]
     pub fn write_object_start(&mut self, def: &Definition) -> Result<()> {
         let ref_num = self.write_definition(def)?;
-        self.writer.write_u8(b'O')?;
-        self.serialize_int(ref_num as i32)?;
+        self.write_object_ref(ref_num)
+    }
+
+    /// Write the start of an object whose definition has ref `ref_num`,
+    /// the definition must have been written or reserved before.
+    pub fn write_object_ref(&mut self, ref_num: usize) -> Result<()> {
+        self.ref_count += 1;
+        self.write_object_tag(ref_num)
+    }
+
+    fn write_object_tag(&mut self, ref_num: usize) -> Result<()> {
+        // object ::= 'O' int value*
+        //        ::= [x60-x6f] value*
+        if self.options.compact_objects && ref_num < 16 {
+            self.writer.write_u8(0x60 + ref_num as u8)?;
+        } else {
+            self.writer.write_u8(b'O')?;
+            self.serialize_int(ref_num as i32)?;
+        }
+        Ok(())
+    }
+
+    /// Begin an object whose fields are not known up front, e.g. the first
+    /// instance of a class whose fields are given one at a time.
+    ///
+    /// The ref of the definition named `name` is reserved if it isn't
+    /// known, so definitions written by the fields take the next refs. The
+    /// returned serializer buffers the fields and continues the types,
+    /// definitions and refs of this one, which must not be used until
+    /// `end_buffered_object` takes them back.
+    pub fn begin_buffered_object(&mut self, name: &str) -> Serializer<Vec<u8>> {
+        if self.get_definition_index(name).is_none() {
+            self.classes_cache.insert(name.to_string(), None);
+            self.reserved.insert(name.to_string());
+        }
+        // the object is numbered before its fields
+        self.ref_count += 1;
+        Serializer {
+            writer: Vec::new(),
+            type_cache: std::mem::take(&mut self.type_cache),
+            classes_cache: std::mem::take(&mut self.classes_cache),
+            options: self.options,
+            ref_count: self.ref_count,
+            shared: self.shared.take(),
+            preloaded: self.preloaded.clone(),
+            reserved: HashSet::new(),
+        }
+    }
+
+    /// Write the object begun by `begin_buffered_object` with the fields
+    /// buffered in `fields`, preceded by its definition if it was reserved.
+    pub fn end_buffered_object(
+        &mut self,
+        def: &Definition,
+        fields: Serializer<Vec<u8>>,
+    ) -> Result<()> {
+        self.type_cache = fields.type_cache;
+        self.classes_cache = fields.classes_cache;
+        self.ref_count = fields.ref_count;
+        self.shared = fields.shared;
+        let ref_num = self.write_definition(def)?;
+        self.write_object_tag(ref_num)?;
+        self.writer.write_all(&fields.writer)?;
         Ok(())
     }
 
+    /// Write the definition of a class ahead of its objects and return its ref.
+    ///
+    /// Registering the known classes in the same order at the start of every
+    /// message, e.g. after `reset`, gives them the same refs in every message.
+    /// Fails if another definition of the same name is already known.
+    pub fn register_definition(&mut self, def: &Definition) -> Result<usize> {
+        if let Some(known) = self.get_definition(&def.name) {
+            if known != def {
+                return Err(Error::SyntaxError(ErrorKind::UnexpectedType(format!(
+                    "definition of {} with fields {:?}, but {:?} is registered",
+                    def.name, def.fields, known.fields
+                ))));
+            }
+        }
+        self.write_definition(def)
+    }
+
     // class-def  ::= 'C' string int string*
     // Write deinition if not exists in classes cache, and return ref num finally
     pub fn write_definition(&mut self, def: &Definition) -> Result<usize> {
-        match self.classes_cache.get_index_of(&def.name) {
-            Some(inx) => Ok(inx),
-            None => {
-                self.writer.write_u8(b'C')?;
-                self.serialize_string(def.name.as_str())?;
-                self.serialize_int(def.fields.len() as i32)?;
-                for name in &def.fields {
-                    self.serialize_string(name.as_str())?;
-                }
-                self.classes_cache.insert(def.name.clone(), def.clone());
-                Ok(self.classes_cache.len() - 1)
+        if let Some(inx) = self.preloaded.get_index_of(&def.name) {
+            return Ok(inx);
+        }
+        let inx = match self.classes_cache.get_index_of(&def.name) {
+            Some(inx) if !self.reserved.remove(&def.name) => {
+                return Ok(self.preloaded.len() + inx);
             }
+            Some(inx) => inx,
+            None => self.classes_cache.len(),
+        };
+        self.writer.write_u8(b'C')?;
+        self.serialize_string(def.name.as_str())?;
+        self.serialize_int(def.fields.len() as i32)?;
+        for name in &def.fields {
+            self.serialize_string(name.as_str())?;
         }
+        self.classes_cache
+            .insert(def.name.clone(), Some(def.clone()));
+        Ok(self.preloaded.len() + inx)
     }
 
     fn write_type(&mut self, tp: &str) -> Result<()> {
@@ -142,6 +679,7 @@ impl<W: io::Write> Serializer<W> {
     }
 
     pub fn write_list_begin(&mut self, length: usize, tp: Option<&str>) -> Result<()> {
+        self.ref_count += 1;
         if length <= 7 {
             if let Some(tp) = tp {
                 self.writer.write_u8((0x70 + length) as u8)?;
@@ -162,7 +700,20 @@ impl<W: io::Write> Serializer<W> {
         Ok(())
     }
 
+    /// Start a list of unknown length, end it with `write_object_end`.
+    pub fn write_var_list_begin(&mut self, tp: Option<&str>) -> Result<()> {
+        self.ref_count += 1;
+        if let Some(tp) = tp {
+            self.writer.write_u8(0x55)?;
+            self.write_type(tp)?;
+        } else {
+            self.writer.write_u8(0x57)?;
+        }
+        Ok(())
+    }
+
     pub fn write_map_start(&mut self, tp: Option<&str>) -> Result<()> {
+        self.ref_count += 1;
         match tp {
             Some(tp) => {
                 self.writer.write_u8(b'M')?;
@@ -181,19 +732,31 @@ impl<W: io::Write> Serializer<W> {
         Ok(())
     }
 
+    /// Write pre-encoded bytes as is, e.g. a cached fragment or a framing
+    /// header.
+    ///
+    /// The caches don't see them, a fragment must not rely on types or class
+    /// definitions written by this serializer nor write ones it'll reuse.
+    #[inline]
+    pub fn write_raw(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes)?;
+        Ok(())
+    }
+
     pub fn serialize_map(&mut self, map: &value::Map) -> Result<()> {
-        match map.r#type() {
-            Some(tp) => {
-                self.writer.write_u8(b'M')?;
-                self.write_type(tp)?;
+        self.write_map_start(map.r#type())?;
+        if self.options.canonical {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|l, r| l.0.cmp(r.0));
+            for (k, v) in entries {
+                self.serialize_value(k)?;
+                self.serialize_value(v)?;
             }
-            None => {
-                self.writer.write_u8(b'H')?;
+        } else {
+            for (k, v) in map.iter() {
+                self.serialize_value(k)?;
+                self.serialize_value(v)?;
             }
-        };
-        for (k, v) in map.iter() {
-            self.serialize_value(k)?;
-            self.serialize_value(v)?;
         }
         self.writer.write_u8(b'Z')?;
         Ok(())
@@ -209,14 +772,22 @@ impl<W: io::Write> Serializer<W> {
         Ok(())
     }
 
+    // date ::= x4a b7 b6 b5 b4 b3 b2 b1 b0
+    //      ::= x4b b3 b2 b1 b0       # minutes since epoch
     pub fn serialize_date(&mut self, d: i64) -> Result<()> {
+        let minutes = d / 60000;
+        if self.options.compact_date && d % 60000 == 0 && minutes as i32 as i64 == minutes {
+            self.writer.write_all(&[0x4b])?;
+            self.writer.write_i32::<BigEndian>(minutes as i32)?;
+            return Ok(());
+        }
         self.writer.write_all(&[0x4a])?;
         self.writer.write_i64::<BigEndian>(d)?;
         Ok(())
     }
 
     pub fn serialize_null(&mut self) -> Result<()> {
-        self.writer.write_all(&[b'N'])?;
+        self.writer.write_all(b"N")?;
         Ok(())
     }
 
@@ -234,54 +805,84 @@ impl<W: io::Write> Serializer<W> {
 
     #[allow(clippy::match_overlapping_arm)]
     pub fn serialize_long(&mut self, v: i64) -> Result<()> {
-        let bytes = match v {
-            -8..=15 => vec![(0xe0 + v) as u8],
-            -2048..=2047 => vec![(((v >> 8) + 0xf8) & 0xff) as u8, (v & 0xff) as u8],
-            -262_144..=262_143 => vec![
+        if !self.options.compact_long {
+            self.writer.write_u8(b'L')?;
+            self.writer.write_i64::<BigEndian>(v)?;
+            return Ok(());
+        }
+        // written from arrays on the stack, as many values are small ints
+        match v {
+            -8..=15 => self.writer.write_all(&[(0xe0 + v) as u8])?,
+            -2048..=2047 => self
+                .writer
+                .write_all(&[(((v >> 8) + 0xf8) & 0xff) as u8, (v & 0xff) as u8])?,
+            -262_144..=262_143 => self.writer.write_all(&[
                 ((v >> 16) + 0x3c) as u8,
                 ((v >> 8) & 0xff) as u8,
                 (v & 0xff) as u8,
-            ],
-            _ if v >= i32::min_value() as i64 && v <= i32::max_value() as i64 => vec![
+            ])?,
+            _ if v >= i32::MIN as i64 && v <= i32::MAX as i64 => self.writer.write_all(&[
                 0x59_u8,
                 (v >> 24 & 0xff) as u8,
                 (v >> 16 & 0xff) as u8,
                 (v >> 8 & 0xff) as u8,
                 (v & 0xff) as u8,
-            ],
-            _ => [&[b'L'], v.to_be_bytes().as_ref()].concat(),
-        };
-        self.writer.write_all(&bytes)?;
+            ])?,
+            _ => {
+                self.writer.write_u8(b'L')?;
+                self.writer.write_i64::<BigEndian>(v)?;
+            }
+        }
         Ok(())
     }
 
     #[allow(clippy::match_overlapping_arm)]
     pub fn serialize_int(&mut self, v: i32) -> Result<()> {
-        let bytes = match v {
-            -16..=47 => vec![(0x90 + v) as u8],
-            -2048..=2047 => vec![(((v >> 8) & 0xff) + 0xc8) as u8, (v & 0xff) as u8],
-            -262_144..=262_143 => vec![
+        if !self.options.compact_int {
+            self.writer.write_u8(b'I')?;
+            self.writer.write_i32::<BigEndian>(v)?;
+            return Ok(());
+        }
+        match v {
+            -16..=47 => self.writer.write_all(&[(0x90 + v) as u8])?,
+            -2048..=2047 => self
+                .writer
+                .write_all(&[(((v >> 8) & 0xff) + 0xc8) as u8, (v & 0xff) as u8])?,
+            -262_144..=262_143 => self.writer.write_all(&[
                 (((v >> 16) & 0xff) + 0xd4) as u8,
                 ((v >> 8) & 0xff) as u8,
                 (v & 0xff) as u8,
-            ],
-            _ => vec![
-                b'I',
-                (v >> 24 & 0xff) as u8,
-                (v >> 16 & 0xff) as u8,
-                (v >> 8 & 0xff) as u8,
-                (v & 0xff) as u8,
-            ],
-        };
-
-        self.writer.write_all(&bytes)?;
+            ])?,
+            _ => {
+                self.writer.write_u8(b'I')?;
+                self.writer.write_i32::<BigEndian>(v)?;
+            }
+        }
         Ok(())
     }
 
     #[allow(clippy::match_overlapping_arm)]
     pub fn serialize_double(&mut self, v: f64) -> Result<()> {
-        let int_v = v.trunc() as i32;
-        if (int_v as f64 - v).abs() < f64::EPSILON {
+        if !self.options.allow_nan && !v.is_finite() {
+            return Err(Error::SyntaxError(ErrorKind::UnexpectedType(format!(
+                "non-finite double {}",
+                v
+            ))));
+        }
+        let v = if self.options.canonical && v.is_nan() {
+            f64::NAN
+        } else {
+            v
+        };
+        if !self.options.compact_double {
+            self.writer.write_u8(b'D')?;
+            self.writer.write_f64::<BigEndian>(v)?;
+            return Ok(());
+        }
+        // Compact forms are only used when they decode to the same bits,
+        // -0.0 or 0.1 don't fit them.
+        let int_v = v as i32;
+        if (int_v as f64).to_bits() == v.to_bits() && (-32768..=32767).contains(&int_v) {
             match int_v {
                 0 => self.writer.write_u8(0x5b)?,
                 1 => self.writer.write_u8(0x5c)?,
@@ -289,106 +890,219 @@ impl<W: io::Write> Serializer<W> {
                     self.writer.write_u8(0x5d)?;
                     self.writer.write_u8(int_v as u8)?;
                 }
-                -32768..=32767 => {
+                _ => {
                     self.writer.write_u8(0x5e)?;
                     self.writer.write_i16::<BigEndian>(int_v as i16)?;
                 }
-                _ => {}
             }
+            return Ok(());
+        }
+        let mills = (v * 1000.0).round() as i32;
+        if (mills as f64 * 0.001).to_bits() == v.to_bits() {
+            self.writer.write_u8(0x5f)?;
+            self.writer.write_i32::<BigEndian>(mills)?;
         } else {
-            let mills = v * 1000.0;
-            if (mills * 0.001 - v).abs() < f64::EPSILON {
-                self.writer.write_u8(0x5f)?;
-                self.writer.write_i32::<BigEndian>(mills as i32)?;
-            } else {
-                self.writer.write_u8(0x44)?;
-                self.writer.write_f64::<BigEndian>(v)?;
-            }
+            self.writer.write_u8(0x44)?;
+            self.writer.write_f64::<BigEndian>(v)?;
         }
         Ok(())
     }
 
+    // binary ::= x41 b1 b0 <binary-data> binary
+    //        ::= B b1 b0 <binary-data>
+    //        ::= [x20-x2f] <binary-data>
     pub fn serialize_binary(&mut self, v: &[u8]) -> Result<()> {
         if v.len() < 16 {
             self.writer.write_all(&[(v.len() + 0x20) as u8])?;
             self.writer.write_all(v)?;
         } else {
-            for (last, chunk) in v.chunks(0xffff).identify_last() {
-                let flag = if last { b'B' } else { b'A' };
-                let len_bytes = (v.len() as u16).to_be_bytes();
-                self.writer.write_all(&[flag])?;
-                self.writer.write_all(&len_bytes)?;
-                self.writer.write_all(chunk)?
+            for (last, chunk) in v.chunks(MAX_BINARY_CHUNK_LEN).identify_last() {
+                self.write_binary_chunk_tagged(chunk, last)?;
             }
         }
         Ok(())
     }
 
+    fn write_binary_chunk_tagged(&mut self, chunk: &[u8], last: bool) -> Result<()> {
+        let flag = if last { b'B' } else { b'A' };
+        self.writer.write_u8(flag)?;
+        self.writer.write_u16::<BigEndian>(chunk.len() as u16)?;
+        self.writer.write_all(chunk)?;
+        Ok(())
+    }
+
+    /// Write everything read from `reader` as one binary value, chunk by
+    /// chunk, without buffering more than two chunks. Returns the number of
+    /// bytes read.
+    pub fn write_binary_from_reader<R: io::Read>(&mut self, mut reader: R) -> Result<u64> {
+        let mut current = vec![0; MAX_BINARY_CHUNK_LEN];
+        let mut next = vec![0; MAX_BINARY_CHUNK_LEN];
+        let mut current_len = read_full(&mut reader, &mut current)?;
+        if current_len < MAX_BINARY_CHUNK_LEN {
+            self.serialize_binary(&current[..current_len])?;
+            return Ok(current_len as u64);
+        }
+        let mut total = current_len as u64;
+        loop {
+            // a chunk is only final if nothing follows it
+            let next_len = read_full(&mut reader, &mut next)?;
+            if next_len == 0 {
+                self.write_binary_chunk_tagged(&current[..current_len], true)?;
+                return Ok(total);
+            }
+            self.write_binary_chunk_tagged(&current[..current_len], false)?;
+            total += next_len as u64;
+            std::mem::swap(&mut current, &mut next);
+            current_len = next_len;
+        }
+    }
+
+    /// Start a binary value written in pieces with `write_binary_chunk`,
+    /// which must be finished with `end_binary` before writing anything else.
+    pub fn begin_binary(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Write a piece of the binary value started by `begin_binary`.
+    ///
+    /// Every piece is written as non-final chunks right away, so nothing is
+    /// buffered whatever the size of the whole value.
+    pub fn write_binary_chunk(&mut self, data: &[u8]) -> Result<()> {
+        for chunk in data.chunks(MAX_BINARY_CHUNK_LEN) {
+            self.write_binary_chunk_tagged(chunk, false)?;
+        }
+        Ok(())
+    }
+
+    /// Finish the binary value started by `begin_binary` with an empty final chunk.
+    pub fn end_binary(&mut self) -> Result<()> {
+        self.write_binary_chunk_tagged(&[], true)
+    }
+
+    /// Start a string written in pieces with `write_string_chunk`, which
+    /// must be finished with `end_string` before writing anything else.
+    pub fn begin_string(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Write a piece of the string started by `begin_string` as non-final chunks.
+    pub fn write_string_chunk(&mut self, data: &str) -> Result<()> {
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (len, end) = string_chunk_end(rest);
+            self.writer.write_u8(b'R')?;
+            self.writer.write_u16::<BigEndian>(len as u16)?;
+            self.write_utf8(&rest[..end])?;
+            rest = &rest[end..];
+        }
+        Ok(())
+    }
+
+    /// Finish the string started by `begin_string` with an empty final chunk.
+    pub fn end_string(&mut self) -> Result<()> {
+        self.writer.write_all(&[b'S', 0x00, 0x00])?;
+        Ok(())
+    }
+
+    fn write_utf8(&mut self, s: &str) -> Result<()> {
+        if !self.options.cesu8 {
+            self.writer.write_all(s.as_bytes())?;
+            return Ok(());
+        }
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            if c.len_utf8() < 4 {
+                continue;
+            }
+            self.writer.write_all(&s.as_bytes()[start..i])?;
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                let unit = *unit;
+                self.writer.write_all(&[
+                    0xe0 | (unit >> 12) as u8,
+                    0x80 | ((unit >> 6) & 0x3f) as u8,
+                    0x80 | (unit & 0x3f) as u8,
+                ])?;
+            }
+            start = i + 4;
+        }
+        self.writer.write_all(&s.as_bytes()[start..])?;
+        Ok(())
+    }
+
     // Serialize String to bytes, format as
     //    string ::= x52 b1 b0 <utf8-data> string
     //    ::= S b1 b0 <utf8-data>
     //    ::= [x00-x1f] <utf8-data>
     //    ::= [x30-x33] b0 <utf8-data>
+    //
+    // The length is the number of 16-bit chars like java, so a char outside
+    // the BMP counts twice and is never split across chunks.
     pub fn serialize_string(&mut self, v: &str) -> Result<()> {
-        const MAX_CHUNK_BYTE_SIZE: u32 = 0x8000;
-        let bytes = v.as_bytes();
-        let mut len = 0;
-        let mut offset = 0;
-        let mut last = 0;
-        let mut i = 0;
-        while i < bytes.len() {
-            len += 1;
-            let byte = bytes[i];
-            if byte & 0x80 > 0 {
-                // more than one byte for this char
-                if byte & 0xe0 == 0xc0 {
-                    i += 2;
-                } else if byte & 0xf0 == 0xe0 {
-                    i += 3;
-                } else {
-                    i += 4;
-                }
-            } else {
-                i += 1;
-            }
-            if len >= MAX_CHUNK_BYTE_SIZE {
+        let mut rest = v;
+        loop {
+            let (len, end) = string_chunk_end(rest);
+            if end < rest.len() {
                 self.writer.write_u8(b'R')?;
                 self.writer.write_u16::<BigEndian>(len as u16)?;
-                self.writer.write_all(&bytes[offset..i - last])?;
-                len = 0;
-                offset += i;
-                last = i;
+                self.write_utf8(&rest[..end])?;
+                rest = &rest[end..];
+                continue;
             }
-        }
-        match len {
-            0..=31 => self.writer.write_u8(len as u8)?,
-            32..=1023 => self
-                .writer
-                .write_all(&[(0x30 + ((len >> 8) & 0xff)) as u8, (len & 0xff) as u8])?,
-            _ => {
-                self.writer.write_u8(b'S')?;
-                self.writer.write_u16::<BigEndian>(len as u16)?;
+            match len {
+                _ if !self.options.compact_string => {
+                    self.writer.write_u8(b'S')?;
+                    self.writer.write_u16::<BigEndian>(len as u16)?;
+                }
+                0..=31 => self.writer.write_u8(len as u8)?,
+                32..=1023 => self
+                    .writer
+                    .write_all(&[(0x30 + ((len >> 8) & 0xff)) as u8, (len & 0xff) as u8])?,
+                _ => {
+                    self.writer.write_u8(b'S')?;
+                    self.writer.write_u16::<BigEndian>(len as u16)?;
+                }
             }
+            self.write_utf8(rest)?;
+            return Ok(());
         }
-        self.writer.write_all(&bytes[offset..i - last])?;
-        Ok(())
     }
 }
 
 /// Serialize a `Value` to bytes
 pub fn to_vec(value: &Value) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
-    let mut ser = Serializer::new(&mut buf);
-    ser.serialize_value(value)?;
+    to_vec_in(&mut buf, value)?;
     Ok(buf)
 }
 
+/// Serialize a `Value` to the end of `buf`, reusing its allocation
+pub fn to_vec_in(buf: &mut Vec<u8>, value: &Value) -> Result<()> {
+    to_writer(buf, value)
+}
+
+/// Serialize a `Value` to a writer
+pub fn to_writer<W: io::Write>(writer: W, value: &Value) -> Result<()> {
+    let mut ser = Serializer::new(writer);
+    ser.serialize_value(value)
+}
+
+impl Value {
+    /// Returns the exact length of `to_vec(self)` without allocating the output.
+    pub fn encoded_len(&self) -> Result<usize> {
+        let mut ser = Serializer::new(SizeCounter(0));
+        ser.serialize_value(self)?;
+        Ok(ser.writer.0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{to_vec, Serializer};
+    use super::{to_vec, to_vec_in, to_writer, DefinitionCache, Serializer};
     use crate::de::Deserializer;
     use crate::value::Value::Int;
-    use crate::value::{self, ToHessian, Value};
+    use crate::value::{self, Definition, ToHessian, Value};
+    use std::io::{self, Write};
+    use std::sync::Arc;
 
     fn test_encode_ok(value: Value, target: &[u8]) {
         assert_eq!(to_vec(&value).unwrap(), target, "{:?} encode error", value);
@@ -414,22 +1128,101 @@ mod tests {
     #[test]
     fn test_encde_string() {
         // TODO(lynskylate): Add more test for encode string
-        test_encode_ok(Value::String(String::new()), &[0x00]);
+        test_encode_ok(Value::String("".into()), &[0x00]);
         test_encode_ok(
             Value::String(vec!['a'; 0x1f].into_iter().collect()),
             &[&[0x1f_u8], vec![b'a'; 0x1f].as_slice()].concat(),
         );
     }
 
+    #[test]
+    fn test_encode_chunked_string() {
+        let long = "a".repeat(0x8000 * 2 + 1);
+        let buf = to_vec(&Value::String(long.clone().into())).unwrap();
+        assert_eq!(buf[..3], [b'R', 0x80, 0x00]);
+        assert_eq!(buf[0x8003..0x8006], [b'R', 0x80, 0x00]);
+        assert_eq!(buf[0x10006..0x10008], [0x01, b'a']);
+        assert_eq!(buf.len(), 0x10008);
+
+        // a surrogate pair at the chunk boundary moves to the next chunk
+        let emoji = format!("{}😀{}", "a".repeat(0x7fff), "中".repeat(0x8000));
+        let buf = to_vec(&Value::String(emoji.clone().into())).unwrap();
+        assert_eq!(buf[..3], [b'R', 0x7f, 0xff]);
+        assert_eq!(buf[0x8002..0x8005], [b'R', 0x80, 0x00]);
+        assert_eq!(buf[0x8005..0x8009], *"😀".as_bytes());
+
+        for s in [long, emoji, "😀".repeat(20), "中".repeat(0x8000)] {
+            let buf = to_vec(&Value::String(s.clone().into())).unwrap();
+            assert_eq!(crate::from_slice(&buf).unwrap(), Value::String(s.into()));
+        }
+    }
+
+    #[test]
+    fn test_encode_chunked_binary() {
+        let data: Vec<u8> = (0..0xffff * 2 + 10).map(|i| i as u8).collect();
+        let buf = to_vec(&Value::Bytes(data.clone().into())).unwrap();
+        assert_eq!(buf[..3], [b'A', 0xff, 0xff]);
+        assert_eq!(buf[0x10002..0x10005], [b'A', 0xff, 0xff]);
+        assert_eq!(buf[0x20004..0x20007], [b'B', 0x00, 0x0a]);
+        assert_eq!(
+            crate::from_slice(&buf).unwrap(),
+            Value::Bytes(data.clone().into())
+        );
+
+        for len in [0, 15, 16, 0xffff, 0xffff + 1, data.len()] {
+            let mut streamed = Vec::new();
+            let mut ser = Serializer::new(&mut streamed);
+            let n = ser.write_binary_from_reader(&data[..len]).unwrap();
+            assert_eq!(n, len as u64);
+            assert_eq!(streamed, to_vec(&Value::Bytes(data[..len].into())).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_streaming_chunks() {
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf);
+        ser.begin_binary().unwrap();
+        ser.write_binary_chunk(&[1, 2]).unwrap();
+        ser.write_binary_chunk(&[]).unwrap();
+        ser.write_binary_chunk(&[0; 0x10000]).unwrap();
+        ser.end_binary().unwrap();
+        ser.begin_string().unwrap();
+        ser.write_string_chunk("ab").unwrap();
+        ser.write_string_chunk("😀").unwrap();
+        ser.end_string().unwrap();
+        assert_eq!(buf[..5], [b'A', 0x00, 0x02, 1, 2]);
+        assert_eq!(buf[5..8], [b'A', 0xff, 0xff]);
+
+        let mut de = Deserializer::new(&buf);
+        let mut bytes = vec![1, 2];
+        bytes.extend_from_slice(&[0; 0x10000]);
+        assert_eq!(de.read_value().unwrap(), Value::Bytes(bytes.into()));
+        assert_eq!(de.read_value().unwrap(), Value::String("ab😀".into()));
+    }
+
+    #[test]
+    fn test_encode_cesu8() {
+        let mut buf = Vec::new();
+        let mut ser = Serializer::builder().cesu8(true).build(&mut buf);
+        ser.serialize_string("a😀").unwrap();
+        assert_eq!(buf, [0x03, b'a', 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]);
+        assert_eq!(
+            Deserializer::new(&buf).cesu8(true).read_value().unwrap(),
+            Value::String("a😀".into())
+        );
+        assert!(Deserializer::new(&buf).read_value().is_err());
+    }
+
     #[test]
     fn test_encode_bool() {
-        test_encode_ok(Value::Bool(true), &[b'T']);
-        test_encode_ok(Value::Bool(false), &[b'F']);
+        test_encode_ok(Value::Bool(true), b"T");
+        test_encode_ok(Value::Bool(false), b"F");
     }
 
     #[test]
     fn test_encode_null() {
-        test_encode_ok(Value::Null, &[b'N']);
+        test_encode_ok(Value::Null, b"N");
     }
 
     #[test]
@@ -447,11 +1240,11 @@ mod tests {
         test_encode_ok(Value::Long(2048), &[0x3c, 0x08, 0x00]);
         test_encode_ok(Value::Long(262144), &[0x59, 0x00, 0x04, 0x00, 0x00]);
         test_encode_ok(
-            Value::Long(i32::max_value() as i64),
+            Value::Long(i32::MAX as i64),
             &[0x59, 0x7f, 0xff, 0xff, 0xff],
         );
         test_encode_ok(
-            Value::Long(i32::max_value() as i64 + 1),
+            Value::Long(i32::MAX as i64 + 1),
             &[b'L', 0x00, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00],
         );
     }
@@ -467,6 +1260,56 @@ mod tests {
             Value::Double(32767.99999),
             &[0x44, 0x40, 0xdf, 0xff, 0xff, 0xff, 0xd6, 0x0e, 0x95],
         );
+        test_encode_ok(Value::Double(40000.0), &[0x5f, 0x02, 0x62, 0x5a, 0x00]);
+        test_encode_ok(
+            Value::Double(-0.0),
+            &[0x44, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        );
+        test_encode_ok(
+            Value::Double(1e-17),
+            &[0x44, 0x3c, 0x67, 0x0e, 0xf5, 0x46, 0x46, 0xd4, 0x97],
+        );
+    }
+
+    #[test]
+    fn test_double_roundtrip() {
+        fn roundtrip(v: f64) {
+            let buf = to_vec(&Value::Double(v)).unwrap();
+            let mut de = crate::de::Deserializer::new(&buf);
+            match de.read_value().unwrap() {
+                Value::Double(d) => assert_eq!(d.to_bits(), v.to_bits(), "{} as {:x?}", v, buf),
+                other => panic!("{} decoded as {}", v, other),
+            }
+        }
+
+        // xorshift64, random bit patterns and values near the compact forms
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..10000 {
+            let bits = next();
+            roundtrip(f64::from_bits(bits));
+            let n = bits as i32;
+            roundtrip(n as f64);
+            roundtrip(n as f64 * 0.001);
+            roundtrip(n as f64 / 1000.0);
+            roundtrip((n % 100000) as f64 / 10.0);
+        }
+        for v in [
+            0.1,
+            0.3,
+            -0.0,
+            1e-17,
+            f64::MIN_POSITIVE,
+            f64::INFINITY,
+            2147483.647,
+        ] {
+            roundtrip(v);
+        }
     }
 
     #[test]
@@ -474,7 +1317,17 @@ mod tests {
         test_encode_ok(
             Value::Date(894621091000),
             &[0x4a, 0x00, 0x00, 0x00, 0xd0, 0x4b, 0x92, 0x84, 0xb8],
-        )
+        );
+        test_encode_ok(Value::Date(894621060000), &[0x4b, 0x00, 0xe3, 0x83, 0x8f]);
+        test_encode_ok(
+            Value::Date(i64::MAX / 60000 * 60000),
+            &[0x4a, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0x26, 0x00],
+        );
+
+        let mut buf = Vec::new();
+        let mut ser = Serializer::builder().compact_date(false).build(&mut buf);
+        ser.serialize_date(894621060000).unwrap();
+        assert_eq!(buf, [0x4a, 0x00, 0x00, 0x00, 0xd0, 0x4b, 0x92, 0x0b, 0xa0]);
     }
 
     #[test]
@@ -490,6 +1343,341 @@ mod tests {
         assert_eq!(ser.type_cache.len(), 1);
     }
 
+    #[test]
+    fn test_writer_accessors() {
+        let mut ser = Serializer::new(io::BufWriter::new(Vec::new()));
+        ser.serialize_int(1).unwrap();
+        assert!(ser.get_ref().get_ref().is_empty());
+        ser.flush().unwrap();
+        assert_eq!(ser.get_ref().get_ref(), &[0x91]);
+        ser.get_mut().write_all(b"N").unwrap();
+        assert_eq!(ser.into_inner().into_inner().unwrap(), &[0x91, b'N']);
+    }
+
+    #[test]
+    fn test_reset() {
+        let car = hessian!("example.Car" => { "color": "red" });
+        let list = hessian!("[int" => [1]);
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf).shared_refs(true);
+        ser.serialize_value(&car).unwrap();
+        ser.serialize_value(&list).unwrap();
+        ser.reset();
+        ser.serialize_value(&car).unwrap();
+        ser.serialize_value(&list).unwrap();
+        // the second message is written exactly like the first one
+        let (first, second) = buf.split_at(buf.len() / 2);
+        assert_eq!(first, second);
+
+        let def = Definition {
+            name: "example.Car".to_string(),
+            fields: vec!["color".to_string()],
+        };
+        let mut buf = Vec::new();
+        let fields = [Value::from("red")];
+        let cache = DefinitionCache::from(vec![def.clone()]);
+        let mut ser = Serializer::new(&mut buf).preload_definitions(cache);
+        ser.serialize_fields_with_definition(&def, &fields).unwrap();
+        ser.reset();
+        ser.serialize_fields_with_definition(&def, &fields).unwrap();
+        assert_eq!(
+            buf,
+            [0x60, 0x03, b'r', b'e', b'd', 0x60, 0x03, b'r', b'e', b'd']
+        );
+    }
+
+    #[test]
+    fn test_definition_cache() {
+        let car = Definition {
+            name: "example.Car".to_string(),
+            fields: vec!["color".to_string()],
+        };
+        let bike = Definition {
+            name: "example.Bike".to_string(),
+            fields: vec![],
+        };
+        let fields = [Value::from("red")];
+
+        // warm up the cache with a first message
+        let mut ser = Serializer::new(Vec::new());
+        ser.serialize_fields_with_definition(&car, &fields).unwrap();
+        let cache = ser.definition_cache();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get("example.Car"), Some(&car));
+
+        let mut ser = Serializer::new(Vec::new()).preload_definitions(cache.clone());
+        ser.serialize_fields_with_definition(&bike, &[]).unwrap();
+        ser.serialize_fields_with_definition(&car, &fields).unwrap();
+        assert_eq!(ser.get_definition("example.Car"), Some(&car));
+        let mut expected = vec![b'C', 0x0c];
+        expected.extend_from_slice(b"example.Bike");
+        expected.extend_from_slice(&[0x90, 0x61, 0x60, 0x03, b'r', b'e', b'd']);
+        assert_eq!(ser.get_ref(), &expected);
+
+        let all = ser.definition_cache();
+        assert_eq!(all.iter().collect::<Vec<_>>(), vec![&car, &bike]);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_var_list() {
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf);
+        ser.write_var_list_begin(None).unwrap();
+        ser.serialize_int(1).unwrap();
+        ser.write_object_end().unwrap();
+        ser.write_var_list_begin(Some("[int")).unwrap();
+        ser.write_object_end().unwrap();
+        assert_eq!(
+            buf,
+            [&[0x57, 0x91, b'Z', 0x55, 0x04][..], b"[int", b"Z"].concat()
+        );
+
+        let mut de = crate::de::Deserializer::new(&buf);
+        assert_eq!(de.read_value().unwrap(), hessian!([1]));
+        assert_eq!(
+            de.read_value().unwrap(),
+            Value::List(("[int", vec![]).into())
+        );
+    }
+
+    #[test]
+    fn test_write_raw() {
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf);
+        ser.write_raw(&[0x01, 0x02]).unwrap();
+        ser.write_raw(&to_vec(&Value::from("a")).unwrap()).unwrap();
+        ser.serialize_null().unwrap();
+        assert_eq!(buf, [0x01, 0x02, 0x01, b'a', b'N']);
+    }
+
+    #[test]
+    fn test_register_definition() {
+        let car = Definition {
+            name: "example.Car".to_string(),
+            fields: vec!["color".to_string()],
+        };
+        let bike = Definition {
+            name: "example.Bike".to_string(),
+            fields: vec![],
+        };
+        let mut ser = Serializer::new(Vec::new());
+        for _ in 0..2 {
+            ser.reset();
+            assert_eq!(ser.register_definition(&bike).unwrap(), 0);
+            assert_eq!(ser.register_definition(&car).unwrap(), 1);
+            assert_eq!(ser.register_definition(&car).unwrap(), 1);
+            assert_eq!(ser.get_definition_index("example.Car"), Some(1));
+            ser.serialize_fields_with_definition(&car, &[Value::from("red")])
+                .unwrap();
+        }
+        assert_eq!(ser.get_definition_index("example.Boat"), None);
+        let mut other = car.clone();
+        other.fields.push("wheels".to_string());
+        assert!(ser.register_definition(&other).is_err());
+
+        let mut de = crate::de::Deserializer::new(ser.get_ref());
+        assert_eq!(
+            de.read_value().unwrap(),
+            hessian!("example.Car" => { "color": "red" })
+        );
+    }
+
+    #[test]
+    fn test_to_vec_in() {
+        let mut buf = Vec::with_capacity(16);
+        to_vec_in(&mut buf, &Value::Int(1)).unwrap();
+        to_vec_in(&mut buf, &Value::from("a")).unwrap();
+        assert_eq!(buf, [0x91, 0x01, b'a']);
+        buf.clear();
+        to_writer(&mut buf, &Value::Null).unwrap();
+        assert_eq!(buf, [b'N']);
+    }
+
+    #[test]
+    fn test_buffered_object() {
+        let car = Definition {
+            name: "example.Car".to_string(),
+            fields: vec!["part".to_string()],
+        };
+        let part = Definition {
+            name: "example.Part".to_string(),
+            fields: vec![],
+        };
+        let mut ser = Serializer::new(Vec::new());
+        let mut fields = ser.begin_buffered_object("example.Car");
+        // the part's definition is written after the car's one
+        fields.write_object_start(&part).unwrap();
+        ser.end_buffered_object(&car, fields).unwrap();
+        ser.write_object_start(&car).unwrap();
+        ser.write_object_start(&part).unwrap();
+
+        let mut de = Deserializer::new(ser.into_inner());
+        for _ in 0..2 {
+            let car = de.read_value().unwrap();
+            assert_eq!(car.as_map().unwrap().r#type(), Some("example.Car"));
+            let part = car["part"].as_map().unwrap();
+            assert_eq!(part.r#type(), Some("example.Part"));
+        }
+    }
+
+    #[test]
+    fn test_encoded_len() {
+        let values = vec![
+            Value::Null,
+            Int(262144),
+            Value::Long(i64::MAX),
+            Value::Double(12.25),
+            Value::String("中文".repeat(100).into()),
+            Value::Bytes(vec![0; 20].into()),
+            hessian!("[int" => [1, 2, hessian!("[int" => [])]),
+            hessian!("example.Car" => { "color": "red", "parts": [1.5, null] }),
+        ];
+        for value in values {
+            assert_eq!(
+                value.encoded_len().unwrap(),
+                to_vec(&value).unwrap().len(),
+                "{:?} size error",
+                value
+            );
+        }
+
+        let list = hessian!("[int" => [1]);
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf);
+        assert_eq!(ser.size_of(&list).unwrap(), 7);
+        ser.serialize_value(&list).unwrap();
+        // the type is written as a reference the second time
+        assert_eq!(ser.size_of(&list).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_canonical() {
+        let map = hessian!({ "b": 2, "a": 1, 3: { "d": 4, "c": 3 } });
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf).canonical(true);
+        ser.serialize_value(&map).unwrap();
+        assert_eq!(
+            buf,
+            [
+                &[b'H', 0x93, b'H', 0x01, b'c', 0x93, 0x01, b'd', 0x94, b'Z'][..],
+                &[0x01, b'a', 0x91, 0x01, b'b', 0x92, b'Z'],
+            ]
+            .concat()
+        );
+
+        let nan = f64::from_bits(f64::NAN.to_bits() | 1);
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf).canonical(true);
+        ser.serialize_double(nan).unwrap();
+        assert_eq!(buf, to_vec(&Value::Double(f64::NAN)).unwrap());
+    }
+
+    #[test]
+    fn test_allow_nan() {
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf).allow_nan(false);
+        for v in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(ser.serialize_double(v).is_err());
+        }
+        ser.serialize_double(f64::MAX).unwrap();
+        assert_eq!(buf, to_vec(&Value::Double(f64::MAX)).unwrap());
+    }
+
+    #[test]
+    fn test_shared_refs() {
+        let car = Arc::new(hessian!("example.Car" => { "a": 1 }));
+        let list = Arc::new(hessian!([1]));
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf).shared_refs(true);
+        ser.serialize_shared(&car).unwrap();
+        ser.serialize_shared(&list).unwrap();
+        // an equal car which isn't the same one
+        ser.serialize_shared(&Arc::new((*car).clone())).unwrap();
+        ser.serialize_shared(&car.clone()).unwrap();
+        ser.serialize_shared(&list).unwrap();
+        // other values are always written
+        ser.serialize_shared(&Arc::new(Value::Int(1))).unwrap();
+        assert_eq!(
+            buf,
+            [
+                &[b'M', 0x0b][..],
+                b"example.Car",
+                &[0x01, b'a', 0x91, b'Z', 0x79, 0x91, b'M', 0x90, 0x01, b'a', 0x91, b'Z'],
+                &[0x51, 0x90, 0x51, 0x91, 0x91],
+            ]
+            .concat()
+        );
+
+        // written in full when disabled
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf);
+        ser.serialize_shared(&list).unwrap();
+        ser.serialize_shared(&list).unwrap();
+        assert_eq!(buf, [0x79, 0x91, 0x79, 0x91]);
+    }
+
+    #[test]
+    fn test_nested_shared_refs() {
+        let car = Value::Shared(Arc::new(hessian!({ "a": 1 })));
+        let list = Value::List(vec![car.clone(), car.clone(), hessian!({ "a": 1 })].into());
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf).shared_refs(true);
+        ser.serialize_value(&list).unwrap();
+        // the list is ref 0, the car ref 1
+        let map = [b'H', 0x01, b'a', 0x91, b'Z'];
+        assert_eq!(buf, [&[0x7b][..], &map, &[0x51, 0x91], &map].concat());
+        assert_eq!(
+            crate::from_slice(&buf).unwrap(),
+            hessian!([{ "a": 1 }, (Value::Ref(1)), { "a": 1 }])
+        );
+
+        // the same as the value it holds otherwise
+        assert_eq!(
+            to_vec(&list).unwrap(),
+            [&[0x7b][..], &map, &map, &map].concat()
+        );
+        assert_eq!(car, hessian!({ "a": 1 }));
+        assert_eq!(car.to_string(), hessian!({ "a": 1 }).to_string());
+        assert_eq!(car.unshared(), hessian!({ "a": 1 }));
+    }
+
+    #[test]
+    fn test_builder() {
+        let value = hessian!([1, 2_i64, 3.0, "a"]);
+        let mut buf = Vec::new();
+        let mut ser = Serializer::builder()
+            .compact_int(false)
+            .compact_long(false)
+            .compact_double(false)
+            .compact_string(false)
+            .build(&mut buf);
+        ser.serialize_value(&value).unwrap();
+        assert_eq!(
+            ser.to_builder(),
+            Serializer::builder()
+                .compact_int(false)
+                .compact_long(false)
+                .compact_double(false)
+                .compact_string(false)
+        );
+        assert_eq!(
+            buf,
+            [
+                &[0x7c, b'I', 0, 0, 0, 1][..],
+                &[b'L', 0, 0, 0, 0, 0, 0, 0, 2],
+                &[b'D', 0x40, 0x08, 0, 0, 0, 0, 0, 0],
+                &[b'S', 0, 1, b'a'],
+            ]
+            .concat()
+        );
+        assert_eq!(crate::from_slice(&buf).unwrap(), value);
+        assert_eq!(
+            Serializer::builder().build(Vec::new()).to_builder(),
+            Serializer::builder()
+        );
+    }
+
     #[test]
     fn test_encode_definiton() {
         use crate::value::Definition;
@@ -512,11 +1700,24 @@ mod tests {
             name: "example.Car".to_string(),
             fields: vec!["color".to_string()],
         };
-        let fields = vec![Value::String("red".to_string())];
+        let fields = vec![Value::String("red".into())];
 
         let mut buf = Vec::new();
         let mut ser = Serializer::new(&mut buf);
         ser.serialize_fields_with_definition(&def, &fields).unwrap();
+        assert_eq!(buf[buf.len() - 5], 0x60);
+
+        let mut long_form = Vec::new();
+        let mut ser = Serializer::new(&mut long_form).compact_objects(false);
+        ser.serialize_fields_with_definition(&def, &fields).unwrap();
+        assert_eq!(
+            long_form[long_form.len() - 6..long_form.len() - 4],
+            [b'O', 0x90]
+        );
+        assert_eq!(
+            Deserializer::new(&long_form).read_value().unwrap(),
+            Deserializer::new(&buf).read_value().unwrap()
+        );
 
         let mut de = Deserializer::new(&buf);
         let v = de.read_value().unwrap();
@@ -0,0 +1,284 @@
+//! Writers which check the structure of the values written with the low
+//! level methods of `Serializer`, e.g. that an object gets a value for
+//! each field of its definition or a map a value for each key.
+
+use std::io;
+
+use super::Serializer;
+use crate::error::{Error, ErrorKind, Result};
+use crate::value::{Definition, Value};
+
+impl<W: io::Write> Serializer<W> {
+    /// Write the start of an object of the class `def`, its definition
+    /// first if it wasn't written yet, and return the writer of its fields.
+    ///
+    /// Unlike after `write_object_start`, writing more or fewer values than
+    /// the definition has fields fails rather than writing a corrupt
+    /// object.
+    pub fn begin_object(&mut self, def: &Definition) -> Result<ObjectWriter<'_, W>> {
+        self.write_object_start(def)?;
+        Ok(ObjectWriter {
+            ser: self,
+            class: def.name.clone(),
+            fields: def.fields.len(),
+            written: 0,
+        })
+    }
+
+    /// Write the start of a list of `len` elements, or of a list ended by
+    /// `ListWriter::end` if `None`, of the type `tp` if any, and return the
+    /// writer of its elements.
+    ///
+    /// Writing another number of elements than `len` fails rather than
+    /// writing a corrupt list.
+    pub fn begin_list(
+        &mut self,
+        len: Option<usize>,
+        tp: Option<&str>,
+    ) -> Result<ListWriter<'_, W>> {
+        match len {
+            Some(len) => self.write_list_begin(len, tp)?,
+            None => self.write_var_list_begin(tp)?,
+        }
+        Ok(ListWriter {
+            ser: self,
+            len,
+            written: 0,
+        })
+    }
+
+    /// Write the start of a map of the type `tp` if any and return the
+    /// writer of its entries, which ends the map with `MapWriter::end`.
+    ///
+    /// Writing a key without a value or a value without a key fails rather
+    /// than writing a corrupt map.
+    pub fn begin_map(&mut self, tp: Option<&str>) -> Result<MapWriter<'_, W>> {
+        self.write_map_start(tp)?;
+        Ok(MapWriter {
+            ser: self,
+            key_written: false,
+        })
+    }
+}
+
+/// The fields of an object begun by `Serializer::begin_object`, written in
+/// the order of its definition. `end` checks each field got a value, an
+/// object dropped without calling it isn't checked.
+pub struct ObjectWriter<'a, W> {
+    ser: &'a mut Serializer<W>,
+    class: String,
+    fields: usize,
+    written: usize,
+}
+
+impl<'a, W: io::Write> ObjectWriter<'a, W> {
+    /// Write the value of the next field.
+    pub fn field(&mut self, value: &Value) -> Result<()> {
+        self.next_field()?.serialize_value(value)
+    }
+
+    /// The serializer to write the value of the next field with, by any
+    /// means, e.g. a nested object. Exactly one value must be written.
+    /// Fails if every field got a value already.
+    pub fn next_field(&mut self) -> Result<&mut Serializer<W>> {
+        if self.written == self.fields {
+            return Err(self.mismatch(self.written + 1));
+        }
+        self.written += 1;
+        Ok(self.ser)
+    }
+
+    /// End the object, failing if some of its fields didn't get a value.
+    pub fn end(self) -> Result<()> {
+        match self.written == self.fields {
+            true => Ok(()),
+            false => Err(self.mismatch(self.written)),
+        }
+    }
+
+    fn mismatch(&self, values: usize) -> Error {
+        Error::SyntaxError(ErrorKind::FieldCount(
+            self.class.clone(),
+            self.fields,
+            values,
+        ))
+    }
+}
+
+/// The elements of a list begun by `Serializer::begin_list`. `end` checks
+/// the list got as many elements as it was declared with, or ends it if
+/// its length wasn't declared. A list dropped without calling it isn't
+/// checked nor ended.
+pub struct ListWriter<'a, W> {
+    ser: &'a mut Serializer<W>,
+    len: Option<usize>,
+    written: usize,
+}
+
+impl<'a, W: io::Write> ListWriter<'a, W> {
+    /// Write the next element.
+    pub fn element(&mut self, value: &Value) -> Result<()> {
+        self.next_element()?.serialize_value(value)
+    }
+
+    /// The serializer to write the next element with, by any means, e.g. a
+    /// nested list. Exactly one value must be written. Fails if the list
+    /// got as many elements as it was declared with already.
+    pub fn next_element(&mut self) -> Result<&mut Serializer<W>> {
+        if let Some(len) = self.len.filter(|len| self.written == *len) {
+            return Err(Error::SyntaxError(ErrorKind::ListLength(len, len + 1)));
+        }
+        self.written += 1;
+        Ok(self.ser)
+    }
+
+    /// End the list, failing if it got fewer elements than it was declared
+    /// with.
+    pub fn end(self) -> Result<()> {
+        match self.len {
+            Some(len) if len != self.written => {
+                Err(Error::SyntaxError(ErrorKind::ListLength(len, self.written)))
+            }
+            Some(_) => Ok(()),
+            None => self.ser.write_object_end(),
+        }
+    }
+}
+
+/// The entries of a map begun by `Serializer::begin_map`, a key then its
+/// value. `end` checks the last key got a value and ends the map, a map
+/// dropped without calling it isn't checked nor ended.
+pub struct MapWriter<'a, W> {
+    ser: &'a mut Serializer<W>,
+    // Whether the value of the last key written is missing
+    key_written: bool,
+}
+
+impl<'a, W: io::Write> MapWriter<'a, W> {
+    /// Write an entry.
+    pub fn entry(&mut self, key: &Value, value: &Value) -> Result<()> {
+        self.next_key()?.serialize_value(key)?;
+        self.next_value()?.serialize_value(value)
+    }
+
+    /// The serializer to write the next key with, by any means. Exactly one
+    /// value must be written. Fails if the last key didn't get a value.
+    pub fn next_key(&mut self) -> Result<&mut Serializer<W>> {
+        if self.key_written {
+            return Err(Error::SyntaxError(ErrorKind::UnpairedMapEntry));
+        }
+        self.key_written = true;
+        Ok(self.ser)
+    }
+
+    /// The serializer to write the value of the last key with, by any
+    /// means. Exactly one value must be written. Fails if no key is
+    /// missing a value.
+    pub fn next_value(&mut self) -> Result<&mut Serializer<W>> {
+        if !self.key_written {
+            return Err(Error::SyntaxError(ErrorKind::UnpairedMapEntry));
+        }
+        self.key_written = false;
+        Ok(self.ser)
+    }
+
+    /// End the map, failing if the last key didn't get a value.
+    pub fn end(self) -> Result<()> {
+        if self.key_written {
+            return Err(Error::SyntaxError(ErrorKind::UnpairedMapEntry));
+        }
+        self.ser.write_object_end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::de::Deserializer;
+    use crate::error::{Error, ErrorKind};
+    use crate::ser::Serializer;
+    use crate::value::{Definition, Value};
+
+    #[test]
+    fn test_object_writer() {
+        let car = Definition {
+            name: "example.Car".to_string(),
+            fields: vec!["color".to_string(), "owner".to_string()],
+        };
+        let person = Definition {
+            name: "example.Person".to_string(),
+            fields: vec!["name".to_string()],
+        };
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf);
+        let mut object = ser.begin_object(&car).unwrap();
+        object.field(&Value::from("red")).unwrap();
+        let mut owner = object.next_field().unwrap().begin_object(&person).unwrap();
+        owner.field(&Value::from("a")).unwrap();
+        owner.end().unwrap();
+        object.end().unwrap();
+        assert_eq!(
+            Deserializer::new(&buf[..]).read_value().unwrap(),
+            hessian!("example.Car" => {
+                "color": "red",
+                "owner": (hessian!("example.Person" => { "name": "a" }))
+            })
+        );
+
+        let mut ser = Serializer::new(Vec::new());
+        let mut object = ser.begin_object(&person).unwrap();
+        object.field(&Value::from("a")).unwrap();
+        match object.field(&Value::from("b")) {
+            Err(Error::SyntaxError(ErrorKind::FieldCount(class, 1, 2))) => {
+                assert_eq!(class, "example.Person")
+            }
+            v => panic!("expect too many values, got {:?}", v),
+        }
+        let object = ser.begin_object(&car).unwrap();
+        match object.end() {
+            Err(Error::SyntaxError(ErrorKind::FieldCount(_, 2, 0))) => {}
+            v => panic!("expect too few values, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_list_and_map_writers() {
+        let mut buf = Vec::new();
+        let mut ser = Serializer::new(&mut buf);
+        let mut list = ser.begin_list(Some(2), Some("[object")).unwrap();
+        list.element(&Value::Int(1)).unwrap();
+        let mut map = list.next_element().unwrap().begin_map(None).unwrap();
+        map.entry(&Value::from("a"), &Value::Int(1)).unwrap();
+        map.next_key().unwrap().serialize_string("b").unwrap();
+        let mut inner = map.next_value().unwrap().begin_list(None, None).unwrap();
+        inner.element(&Value::Null).unwrap();
+        inner.end().unwrap();
+        map.end().unwrap();
+        list.end().unwrap();
+        assert_eq!(
+            Deserializer::new(&buf[..]).read_value().unwrap(),
+            hessian!("[object" => [1, { "a": 1, "b": [null] }])
+        );
+
+        let mut ser = Serializer::new(Vec::new());
+        let mut list = ser.begin_list(Some(1), None).unwrap();
+        list.element(&Value::Int(1)).unwrap();
+        match list.element(&Value::Int(2)) {
+            Err(Error::SyntaxError(ErrorKind::ListLength(1, 2))) => {}
+            v => panic!("expect too many elements, got {:?}", v),
+        }
+        let list = ser.begin_list(Some(1), None).unwrap();
+        match list.end() {
+            Err(Error::SyntaxError(ErrorKind::ListLength(1, 0))) => {}
+            v => panic!("expect too few elements, got {:?}", v),
+        }
+
+        let mut map = ser.begin_map(None).unwrap();
+        assert!(map.next_value().is_err());
+        map.next_key().unwrap().serialize_int(1).unwrap();
+        assert!(map.next_key().is_err());
+        match map.end() {
+            Err(Error::SyntaxError(ErrorKind::UnpairedMapEntry)) => {}
+            v => panic!("expect a key without a value, got {:?}", v),
+        }
+    }
+}
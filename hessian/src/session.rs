@@ -0,0 +1,162 @@
+//! Encode and decode the frames of a long-lived connection.
+//!
+//! Peers of such connections, e.g. Dubbo-style RPC, send a class definition
+//! or a type once and refer to it in the following frames. A `SessionCodec`
+//! keeps what it wrote and read from a frame to the next, the frames of a
+//! session are the pieces of a single hessian stream.
+
+use std::mem;
+
+use crate::de::{Deserializer, Tables};
+use crate::error::Result;
+use crate::ser::{Serializer, SerializerBuilder};
+use crate::value::Value;
+
+pub struct SessionCodec {
+    ser: Serializer<Vec<u8>>,
+    tables: Tables,
+}
+
+impl Default for SessionCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionCodec {
+    pub fn new() -> Self {
+        Self::with_builder(SerializerBuilder::default())
+    }
+
+    /// Encode frames with the options of `builder`.
+    pub fn with_builder(builder: SerializerBuilder) -> Self {
+        SessionCodec {
+            ser: builder.build(Vec::new()),
+            tables: Tables::default(),
+        }
+    }
+
+    /// Encode `values` as the next frame, referring to the definitions and
+    /// types of the previous frames.
+    pub fn encode_frame(&mut self, values: &[Value]) -> Result<Vec<u8>> {
+        self.encode_frame_with(|ser| values.iter().try_for_each(|v| ser.serialize_value(v)))
+    }
+
+    /// Encode what `f` writes as the next frame, e.g. objects with
+    /// `Serializer::serialize_object`.
+    ///
+    /// When `f` fails the frame is discarded, and so are the definitions and
+    /// types it wrote: the next frames write them again.
+    pub fn encode_frame_with<F>(&mut self, f: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce(&mut Serializer<Vec<u8>>) -> Result<()>,
+    {
+        let checkpoint = self.ser.checkpoint();
+        let res = f(&mut self.ser);
+        let frame = mem::take(self.ser.get_mut());
+        if res.is_err() {
+            self.ser.rollback(checkpoint);
+        }
+        res.map(|_| frame)
+    }
+
+    /// Decode every value of the next frame, which may refer to the
+    /// definitions and types of the previous frames.
+    pub fn decode_frame(&mut self, frame: &[u8]) -> Result<Vec<Value>> {
+        let mut de = Deserializer::new(frame).with_tables(mem::take(&mut self.tables));
+        let mut values = Vec::new();
+        let res = loop {
+            if de.peek_byte().is_err() {
+                break Ok(());
+            }
+            match de.read_value() {
+                Ok(v) => values.push(v),
+                Err(e) => break Err(e),
+            }
+        };
+        self.tables = de.into_tables();
+        res.map(|_| values)
+    }
+
+    /// Forget the state of both directions, e.g. for a new connection.
+    pub fn reset(&mut self) {
+        self.ser.reset();
+        self.ser.get_mut().clear();
+        self.tables = Tables::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionCodec;
+    use crate::ser::SerializerBuilder;
+    use crate::value::Definition;
+    use crate::{de::Deserializer, Value};
+
+    #[test]
+    fn test_session() {
+        let values = vec![Value::Int(1), hessian!("example.Car" => { "color": "red" })];
+
+        let mut client = SessionCodec::new();
+        let mut server = SessionCodec::new();
+        let first = client.encode_frame(&values).unwrap();
+        let second = client.encode_frame(&values).unwrap();
+        // the type isn't written again
+        assert!(second.len() < first.len());
+        assert!(Deserializer::new(&second[1..]).read_value().is_err());
+
+        assert_eq!(server.decode_frame(&first).unwrap(), values);
+        assert_eq!(server.decode_frame(&second).unwrap(), values);
+        assert!(server.decode_frame(&[]).unwrap().is_empty());
+
+        let car = Definition {
+            name: "example.Car".to_string(),
+            fields: vec!["color".to_string()],
+        };
+        let write_car = |ser: &mut crate::ser::Serializer<Vec<u8>>| {
+            ser.serialize_fields_with_definition(&car, &[Value::from("blue")])
+        };
+        let first_car = client.encode_frame_with(write_car).unwrap();
+        let second_car = client.encode_frame_with(write_car).unwrap();
+        assert_eq!(first_car[0], b'C');
+        assert_eq!(second_car, [0x60, 0x04, b'b', b'l', b'u', b'e']);
+        assert_eq!(
+            server.decode_frame(&first_car).unwrap(),
+            server.decode_frame(&second_car).unwrap()
+        );
+
+        server.reset();
+        assert!(server.decode_frame(&second).is_err());
+        client.reset();
+        assert_eq!(client.encode_frame(&values).unwrap(), first);
+    }
+
+    #[test]
+    fn test_failed_frame() {
+        let map = hessian!("example.Car" => { "color": "red" });
+        let mut client = SessionCodec::with_builder(SerializerBuilder::default().allow_nan(false));
+        assert!(client
+            .encode_frame(&[map.clone(), Value::Double(f64::NAN)])
+            .is_err());
+        // the type of the discarded frame is written again
+        let frame = client.encode_frame(std::slice::from_ref(&map)).unwrap();
+        assert_eq!(SessionCodec::new().decode_frame(&frame).unwrap(), [map]);
+
+        let car = Definition {
+            name: "example.Car".to_string(),
+            fields: vec!["color".to_string()],
+        };
+        assert!(client
+            .encode_frame_with(|ser| {
+                ser.serialize_fields_with_definition(&car, &[Value::from("blue")])?;
+                ser.serialize_value(&Value::Double(f64::NAN))
+            })
+            .is_err());
+        let frame = client
+            .encode_frame_with(|ser| {
+                ser.serialize_fields_with_definition(&car, &[Value::from("blue")])
+            })
+            .unwrap();
+        assert_eq!(frame[0], b'C');
+    }
+}
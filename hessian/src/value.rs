@@ -1,11 +1,40 @@
 extern crate ordered_float;
 
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "arena")]
+mod arena;
+#[cfg(feature = "json")]
+mod json;
+mod merge;
+mod object;
+mod schema;
+mod small;
+mod text;
+mod walk;
+
+#[cfg(feature = "arena")]
+pub use arena::{ArenaValue, Bump};
+pub use merge::{Delta, MergeStrategy};
+pub use object::HessianObject;
+#[doc(hidden)]
+pub use object::ObjectFields;
+pub use schema::{Schema, SchemaDelta};
+pub use small::{Bytes, Str};
+pub use walk::{Path, Segment};
+
+use crate::error::{Error, ErrorKind};
 use ordered_float::OrderedFloat;
 use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt;
 use std::hash::{Hash, Hasher};
-use std::ops::{Deref, DerefMut};
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut, Index, IndexMut};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// class definition
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -22,6 +51,31 @@ pub enum List {
 }
 
 impl List {
+    /// A list of the java type `typ`, e.g. `java.util.ArrayList` or
+    /// `[int`.
+    pub fn typed<T: Into<String>>(typ: T, values: Vec<Value>) -> Self {
+        List::Typed(typ.into(), values)
+    }
+
+    pub fn untyped(values: Vec<Value>) -> Self {
+        List::Untyped(values)
+    }
+
+    /// An empty untyped list with room for `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> Self {
+        List::Untyped(Vec::with_capacity(capacity))
+    }
+
+    /// Makes the list typed `typ`, or untyped if `None`, keeping its
+    /// elements.
+    pub fn set_type(&mut self, typ: Option<String>) {
+        let values = std::mem::take(self.vec_mut());
+        *self = match typ {
+            Some(typ) => List::Typed(typ, values),
+            None => List::Untyped(values),
+        };
+    }
+
     pub fn r#type(&self) -> Option<&str> {
         match self {
             List::Typed(ref typ, _) => Some(typ),
@@ -42,6 +96,33 @@ impl List {
             List::Untyped(val) => val,
         }
     }
+
+    pub(crate) fn vec_mut(&mut self) -> &mut Vec<Value> {
+        match self {
+            List::Typed(_, val) => val,
+            List::Untyped(val) => val,
+        }
+    }
+
+    /// Appends an element to the back of the list.
+    pub fn push<V: Into<Value>>(&mut self, value: V) {
+        self.vec_mut().push(value.into())
+    }
+
+    /// Removes the last element and returns it, or `None` if the list is empty.
+    pub fn pop(&mut self) -> Option<Value> {
+        self.vec_mut().pop()
+    }
+
+    /// Inserts an element at `index`, panics if `index > len`.
+    pub fn insert<V: Into<Value>>(&mut self, index: usize, value: V) {
+        self.vec_mut().insert(index, value.into())
+    }
+
+    /// Removes and returns the element at `index`, panics if out of bounds.
+    pub fn remove(&mut self, index: usize) -> Value {
+        self.vec_mut().remove(index)
+    }
 }
 
 impl From<Vec<Value>> for List {
@@ -62,6 +143,13 @@ impl From<(&str, Vec<Value>)> for List {
     }
 }
 
+/// An untyped list of the values
+impl<T: Into<Value>> FromIterator<T> for List {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        List::Untyped(iter.into_iter().map(Into::into).collect())
+    }
+}
+
 impl Deref for List {
     type Target = [Value];
 
@@ -84,6 +172,30 @@ pub enum Map {
 }
 
 impl Map {
+    /// A map of the java type `typ`, e.g. `java.util.HashMap`.
+    pub fn typed<T: Into<String>>(typ: T, entries: HashMap<Value, Value>) -> Self {
+        Map::Typed(typ.into(), entries)
+    }
+
+    pub fn untyped(entries: HashMap<Value, Value>) -> Self {
+        Map::Untyped(entries)
+    }
+
+    /// An empty untyped map with room for `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Map::Untyped(HashMap::with_capacity(capacity))
+    }
+
+    /// Makes the map typed `typ`, or untyped if `None`, keeping its
+    /// entries.
+    pub fn set_type(&mut self, typ: Option<String>) {
+        let entries = std::mem::take(self.value_mut());
+        *self = match typ {
+            Some(typ) => Map::Typed(typ, entries),
+            None => Map::Untyped(entries),
+        };
+    }
+
     pub fn r#type(&self) -> Option<&str> {
         match self {
             Map::Typed(ref typ, _) => Some(typ),
@@ -104,6 +216,26 @@ impl Map {
             Map::Untyped(val) => val,
         }
     }
+
+    /// Inserts a key-value pair, returning the old value of the key if present.
+    pub fn insert<K: Into<Value>, V: Into<Value>>(&mut self, key: K, value: V) -> Option<Value> {
+        self.value_mut().insert(key.into(), value.into())
+    }
+
+    /// Removes a key, returning its value if it was present.
+    pub fn remove<K: Into<Value>>(&mut self, key: K) -> Option<Value> {
+        self.value_mut().remove(&key.into())
+    }
+
+    /// Returns a mutable reference to the value of a key.
+    pub fn get_mut<K: Into<Value>>(&mut self, key: K) -> Option<&mut Value> {
+        self.value_mut().get_mut(&key.into())
+    }
+
+    /// Gets the entry of a key for in-place manipulation.
+    pub fn entry<K: Into<Value>>(&mut self, key: K) -> Entry<'_, Value, Value> {
+        self.value_mut().entry(key.into())
+    }
 }
 
 impl From<HashMap<Value, Value>> for Map {
@@ -124,6 +256,17 @@ impl From<(&str, HashMap<Value, Value>)> for Map {
     }
 }
 
+/// An untyped map of the entries, the last value of a key repeated is kept
+impl<K: Into<Value>, V: Into<Value>> FromIterator<(K, V)> for Map {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Map::Untyped(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
 impl Deref for Map {
     type Target = HashMap<Value, Value>;
 
@@ -154,20 +297,30 @@ pub enum Value {
     /// 64-bit millisecond date
     Date(i64),
     /// raw binary data
-    Bytes(Vec<u8>),
+    Bytes(Bytes),
     /// UTF8-encoded string
-    String(String),
+    String(Str),
     /// shared and circular object references
     Ref(u32),
     // list for lists and arrays
     List(List),
     /// map for maps and dictionaries
     Map(Map),
+    /// bytes starting with a tag hessian doesn't define, only read by a
+    /// permissive deserializer, see `Deserializer::permissive`
+    Unknown(Vec<u8>),
+    /// a map or a list held by several parts of a value, e.g. of a linked
+    /// structure. A serializer with shared references writes it once and
+    /// refs to it after, see `Serializer::serialize_shared`. It compares,
+    /// hashes and displays as the value it holds.
+    Shared(Arc<Value>),
 }
 
 impl PartialEq for Value {
     fn eq(&self, other: &Value) -> bool {
         match (self, other) {
+            (Value::Shared(lhs), rhs) => **lhs == *rhs,
+            (lhs, Value::Shared(rhs)) => *lhs == **rhs,
             (Value::Null, Value::Null) => true,
             (Value::Bool(lhs), Value::Bool(rhs)) => lhs == rhs,
             (Value::Int(lhs), Value::Int(rhs)) => lhs == rhs,
@@ -178,12 +331,11 @@ impl PartialEq for Value {
             (Value::String(lhs), Value::String(rhs)) => lhs == rhs,
             (Value::Ref(lhs), Value::Ref(rhs)) => lhs == rhs,
             (Value::List(lhs), Value::List(rhs)) => lhs == rhs,
+            (Value::Unknown(lhs), Value::Unknown(rhs)) => lhs == rhs,
+            // Look the keys up rather than sorting the entries, `Ord` ranks
+            // e.g. `Int(1)` and `Long(1)` equal, they'd sort in hash order
             (Value::Map(lhs), Value::Map(rhs)) => {
-                let mut left_v: Vec<_> = lhs.iter().collect();
-                let mut right_v: Vec<_> = rhs.iter().collect();
-                left_v.sort_by(|l_iter, r_iter| l_iter.0.cmp(r_iter.0));
-                right_v.sort_by(|l_iter, r_iter| l_iter.0.cmp(r_iter.0));
-                left_v == right_v
+                lhs.len() == rhs.len() && lhs.iter().all(|(k, v)| rhs.value().get(k) == Some(v))
             }
             _ => false,
         }
@@ -196,6 +348,15 @@ impl Value {
         std::mem::replace(self, Value::Null)
     }
 
+    /// Returns the value a `Shared` holds, cloned if other parts hold it
+    /// too. Returns other values as they are.
+    pub fn unshared(self) -> Self {
+        match self {
+            Value::Shared(v) => Arc::try_unwrap(v).unwrap_or_else(|v| (*v).clone()),
+            v => v,
+        }
+    }
+
     /// If the `Value` is a `Null`, returns `()`. Returns `None` otherwise.
     pub fn as_null(&self) -> Option<()> {
         match self {
@@ -252,6 +413,39 @@ impl Value {
         self.as_double().is_some()
     }
 
+    /// Returns any `Int`, `Long` or `Double` as an `i64`, doubles are truncated.
+    ///
+    /// These are the coercions `serde_hessian` applies when deserializing an `i64`.
+    pub fn as_i64_lossy(&self) -> Option<i64> {
+        match *self {
+            Value::Int(i) => Some(i as i64),
+            Value::Long(l) => Some(l),
+            Value::Double(d) => Some(d as i64),
+            _ => None,
+        }
+    }
+
+    /// Returns any `Int`, `Long` or `Double` as a `u64` with `as` casting,
+    /// negative numbers wrap around and doubles are truncated.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::Int(i) => Some(i as u64),
+            Value::Long(l) => Some(l as u64),
+            Value::Double(d) => Some(d as u64),
+            _ => None,
+        }
+    }
+
+    /// Returns any `Int`, `Long` or `Double` as an `f64`, large longs lose precision.
+    pub fn as_f64_lossy(&self) -> Option<f64> {
+        match *self {
+            Value::Int(i) => Some(i as f64),
+            Value::Long(l) => Some(l as f64),
+            Value::Double(d) => Some(d),
+            _ => None,
+        }
+    }
+
     pub fn as_date(&self) -> Option<i64> {
         match *self {
             Value::Date(d) => Some(d),
@@ -274,6 +468,18 @@ impl Value {
         self.as_bytes().is_some()
     }
 
+    /// `Bytes` of base64 text, standard or URL safe, padded or not, e.g. to
+    /// write binary in a test. Returns `None` if `s` isn't base64.
+    pub fn from_base64(s: &str) -> Option<Value> {
+        text::decode_base64(s).map(|bytes| Value::Bytes(bytes.into()))
+    }
+
+    /// If the `Value` is `Bytes`, returns them as padded base64, as JSON
+    /// has them. Returns `None` otherwise.
+    pub fn as_base64(&self) -> Option<String> {
+        self.as_bytes().map(text::base64)
+    }
+
     pub fn as_str(&self) -> Option<&str> {
         match self {
             Value::String(s) => Some(s),
@@ -331,6 +537,128 @@ impl Value {
     pub fn is_map(&self) -> bool {
         self.as_map().is_some()
     }
+
+    /// Looks up a map entry by string key, or a list element by index.
+    ///
+    /// Map keys stored as `Int` or `Long` are matched when `key` parses as a number.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(m) => m
+                .get(&Value::String(key.into()))
+                .or_else(|| numeric_key(key).and_then(|k| m.get(&k))),
+            Value::List(l) => key.parse::<usize>().ok().and_then(|i| l.get(i)),
+            _ => None,
+        }
+    }
+
+    /// Mutable version of `get`.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        match self {
+            Value::Map(m) => {
+                let string_key = Value::String(key.into());
+                if m.contains_key(&string_key) {
+                    m.get_mut(string_key)
+                } else {
+                    numeric_key(key).and_then(move |k| m.get_mut(k))
+                }
+            }
+            Value::List(l) => key.parse::<usize>().ok().and_then(move |i| l.get_mut(i)),
+            _ => None,
+        }
+    }
+
+    /// Looks up a value by a JSON Pointer like path, e.g. `/ctx/items/0/id`.
+    ///
+    /// `~1` and `~0` in a path segment are unescaped to `/` and `~`.
+    /// Returns `None` if any segment can't be resolved.
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        if !pointer.starts_with('/') {
+            return None;
+        }
+        pointer
+            .split('/')
+            .skip(1)
+            .map(|x| x.replace("~1", "/").replace("~0", "~"))
+            .try_fold(self, |target, token| target.get(&token))
+    }
+
+    /// Mutable version of `pointer`.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        if !pointer.starts_with('/') {
+            return None;
+        }
+        pointer
+            .split('/')
+            .skip(1)
+            .map(|x| x.replace("~1", "/").replace("~0", "~"))
+            .try_fold(self, |target, token| target.get_mut(&token))
+    }
+}
+
+fn numeric_key(key: &str) -> Option<Value> {
+    let n = key.parse::<i64>().ok()?;
+    Some(match i32::try_from(n) {
+        Ok(i) => Value::Int(i),
+        Err(_) => Value::Long(n),
+    })
+}
+
+static NULL: Value = Value::Null;
+
+/// Index into a map by string key, returns `Null` if the key is missing
+/// or the value isn't a map.
+impl Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        match self {
+            Value::Map(m) => m.get(&Value::String(key.into())).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+/// Index into a list, returns `Null` if out of bounds or the value isn't a list.
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        match self {
+            Value::List(l) => l.get(index).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+/// Panics if the value isn't a map, inserts `Null` when the key is missing.
+impl IndexMut<&str> for Value {
+    fn index_mut(&mut self, key: &str) -> &mut Value {
+        match self {
+            Value::Map(m) => m.entry(key).or_insert(Value::Null),
+            v => panic!("cannot index into {} with a string key", v),
+        }
+    }
+}
+
+/// Panics if the value isn't a list or the index is out of bounds.
+impl IndexMut<usize> for Value {
+    fn index_mut(&mut self, index: usize) -> &mut Value {
+        match self {
+            Value::List(l) => {
+                let len = l.len();
+                l.get_mut(index).unwrap_or_else(|| {
+                    panic!("index {} out of range for list of length {}", index, len)
+                })
+            }
+            v => panic!("cannot index into {} with an integer", v),
+        }
+    }
 }
 
 impl PartialOrd for Value {
@@ -359,6 +687,8 @@ impl Hash for Value {
             List(ref l) => l.hash(state),
             // Hash each key-value is too expensive.
             Map(ref m) => std::ptr::hash(m, state),
+            Unknown(ref bytes) => bytes.hash(state),
+            Shared(ref v) => v.hash(state),
         }
     }
 }
@@ -367,7 +697,11 @@ impl Ord for Value {
     fn cmp(&self, other: &Value) -> Ordering {
         use self::Value::*;
 
+        if let Shared(ref v) = *other {
+            return self.cmp(v);
+        }
         match *self {
+            Shared(ref v) => (**v).cmp(other),
             Null => match *other {
                 Null => Ordering::Equal,
                 _ => Ordering::Less,
@@ -417,22 +751,22 @@ impl Ord for Value {
                 _ => Ordering::Less,
             },
             Bytes(ref bs) => match *other {
-                String(_) | List(_) | Ref(_) | Map(_) => Ordering::Less,
+                String(_) | List(_) | Ref(_) | Map(_) | Unknown(_) => Ordering::Less,
                 Bytes(ref bs2) => bs.cmp(bs2),
                 _ => Ordering::Greater,
             },
             String(ref s) => match *other {
-                Ref(_) | List(_) | Map(_) => Ordering::Less,
+                Ref(_) | List(_) | Map(_) | Unknown(_) => Ordering::Less,
                 String(ref s2) => s.cmp(s2),
                 _ => Ordering::Greater,
             },
             Ref(i) => match *other {
-                List(_) | Map(_) => Ordering::Less,
+                List(_) | Map(_) | Unknown(_) => Ordering::Less,
                 Ref(i2) => i.cmp(&i2),
                 _ => Ordering::Greater,
             },
             List(ref l) => match other {
-                Map(_) => Ordering::Less,
+                Map(_) | Unknown(_) => Ordering::Less,
                 List(l2) => l.cmp(l2),
                 _ => Ordering::Greater,
             },
@@ -444,6 +778,11 @@ impl Ord for Value {
                     v2.sort_by(|l_iter, r_iter| l_iter.0.cmp(r_iter.0));
                     v1.cmp(&v2)
                 }
+                Unknown(_) => Ordering::Less,
+                _ => Ordering::Greater,
+            },
+            Unknown(ref bs) => match other {
+                Unknown(bs2) => bs.cmp(bs2),
                 _ => Ordering::Greater,
             },
         }
@@ -459,6 +798,15 @@ fn float_ord(f: f64, g: f64) -> Ordering {
 
 pub trait ToHessian {
     fn to_hessian(self) -> Value;
+
+    // A `Vec` of the type, a list but for `u8` whose `Vec` is binary
+    #[doc(hidden)]
+    fn vec_to_hessian(v: Vec<Self>) -> Value
+    where
+        Self: Sized,
+    {
+        v.into_iter().map(Self::to_hessian).collect()
+    }
 }
 
 macro_rules! to_hessian (
@@ -475,8 +823,69 @@ to_hessian!(bool, Value::Bool);
 to_hessian!(i32, Value::Int);
 to_hessian!(i64, Value::Long);
 to_hessian!(f64, Value::Double);
-to_hessian!(String, Value::String);
-to_hessian!(Vec<u8>, Value::Bytes);
+// as the serde serializer writes them
+to_hessian!(i8, |v| Value::Int(i32::from(v)));
+to_hessian!(i16, |v| Value::Int(i32::from(v)));
+to_hessian!(u16, |v| Value::Int(i32::from(v)));
+to_hessian!(u32, |v| match i32::try_from(v) {
+    Ok(i) => Value::Int(i),
+    Err(_) => Value::Long(i64::from(v)),
+});
+to_hessian!(f32, |v| Value::Double(f64::from(v)));
+
+impl ToHessian for u8 {
+    fn to_hessian(self) -> Value {
+        Value::Int(i32::from(self))
+    }
+
+    fn vec_to_hessian(v: Vec<Self>) -> Value {
+        Value::Bytes(v.into())
+    }
+}
+
+/// A date, the milliseconds since the epoch, negative before it. Rounds
+/// down as java does, 0.5 ms before the epoch is -1
+impl ToHessian for SystemTime {
+    fn to_hessian(self) -> Value {
+        let millis = match self.duration_since(UNIX_EPOCH) {
+            Ok(after) => after.as_millis() as i64,
+            Err(before) => {
+                let before = before.duration();
+                let partial = before.subsec_nanos() % 1_000_000 != 0;
+                -(before.as_millis() as i64) - i64::from(partial)
+            }
+        };
+        Value::Date(millis)
+    }
+}
+
+impl ToHessian for &[Value] {
+    fn to_hessian(self) -> Value {
+        Value::List(self.to_vec().into())
+    }
+}
+
+// A tuple is an untyped list, as the serde serializer writes it. A pair
+// isn't, `(type, map)` is a typed map
+macro_rules! tuple_to_hessian {
+    ($($name:ident)+) => {
+        impl<$($name: Into<Value>),+> ToHessian for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn to_hessian(self) -> Value {
+                let ($($name,)+) = self;
+                Value::List(vec![$($name.into()),+].into())
+            }
+        }
+    };
+}
+
+tuple_to_hessian!(A);
+tuple_to_hessian!(A B C);
+tuple_to_hessian!(A B C D);
+tuple_to_hessian!(A B C D E);
+tuple_to_hessian!(A B C D E F);
+tuple_to_hessian!(A B C D E F G);
+tuple_to_hessian!(A B C D E F G H);
 
 impl ToHessian for () {
     fn to_hessian(self) -> Value {
@@ -484,81 +893,101 @@ impl ToHessian for () {
     }
 }
 
-impl<'a> ToHessian for &'a str {
+impl ToHessian for String {
     fn to_hessian(self) -> Value {
-        Value::String(self.to_owned())
+        Value::String(self.into())
     }
 }
 
-impl<'a> ToHessian for &'a String {
+impl ToHessian for &str {
     fn to_hessian(self) -> Value {
-        Value::String(self.to_owned())
+        Value::String(self.into())
     }
 }
 
-impl<'a> ToHessian for &'a [u8] {
+impl ToHessian for &String {
     fn to_hessian(self) -> Value {
-        Value::Bytes(self.to_owned())
+        Value::String(self.into())
     }
 }
 
-impl<'a> ToHessian for &'a Vec<u8> {
+impl ToHessian for &[u8] {
     fn to_hessian(self) -> Value {
-        Value::Bytes(self.to_owned())
+        Value::Bytes(self.into())
+    }
+}
+
+impl ToHessian for &Vec<u8> {
+    fn to_hessian(self) -> Value {
+        Value::Bytes(self.as_slice().into())
     }
 }
 
 impl<K, V> ToHessian for HashMap<K, V>
 where
-    K: ToHessian,
-    V: ToHessian,
+    K: Into<Value>,
+    V: Into<Value>,
 {
     fn to_hessian(self) -> Value {
-        let kv: HashMap<Value, Value> = self
-            .into_iter()
-            .map(|(k, v)| (k.to_hessian(), v.to_hessian()))
-            .collect();
-        Value::Map(kv.into())
+        Value::Map(self.into_iter().collect())
     }
 }
 
-impl<T> ToHessian for Vec<T>
-where
-    T: ToHessian,
-{
+/// A list, but a `Vec<u8>` is binary
+impl<T: ToHessian> ToHessian for Vec<T> {
     fn to_hessian(self) -> Value {
-        let v: Vec<Value> = self.into_iter().map(|t| t.to_hessian()).collect();
+        T::vec_to_hessian(self)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(v: Vec<Value>) -> Self {
         Value::List(v.into())
     }
 }
 
 impl<K, V> ToHessian for (String, HashMap<K, V>)
 where
-    K: ToHessian,
-    V: ToHessian,
+    K: Into<Value>,
+    V: Into<Value>,
 {
     fn to_hessian(self) -> Value {
         let (typ, kv) = self;
-        let kv: HashMap<Value, Value> = kv
-            .into_iter()
-            .map(|(k, v)| (k.to_hessian(), v.to_hessian()))
-            .collect();
+        let kv: HashMap<Value, Value> = kv.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
         Value::Map((typ, kv).into())
     }
 }
 
 impl<K, V> ToHessian for (&str, HashMap<K, V>)
 where
-    K: ToHessian,
-    V: ToHessian,
+    K: Into<Value>,
+    V: Into<Value>,
 {
     fn to_hessian(self) -> Value {
         let (typ, kv) = self;
-        let kv: HashMap<Value, Value> = kv
-            .into_iter()
-            .map(|(k, v)| (k.to_hessian(), v.to_hessian()))
-            .collect();
-        Value::Map((typ, kv).into())
+        (typ.to_string(), kv).to_hessian()
+    }
+}
+
+/// `None` is null
+impl<T> ToHessian for Option<T>
+where
+    T: Into<Value>,
+{
+    fn to_hessian(self) -> Value {
+        self.map_or(Value::Null, Into::into)
+    }
+}
+
+impl ToHessian for List {
+    fn to_hessian(self) -> Value {
+        Value::List(self)
+    }
+}
+
+impl ToHessian for Map {
+    fn to_hessian(self) -> Value {
+        Value::Map(self)
     }
 }
 
@@ -568,6 +997,156 @@ impl<T: ToHessian> From<T> for Value {
     }
 }
 
+/// An untyped list of the values
+impl<T: Into<Value>> FromIterator<T> for Value {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Value::List(iter.into_iter().collect())
+    }
+}
+
+fn unexpected(expected: &str, value: &Value) -> Error {
+    Error::SyntaxError(ErrorKind::UnexpectedType(format!(
+        "expect a {} value, but get {}",
+        expected, value
+    )))
+}
+
+macro_rules! try_from_value (
+    ($t:ty, $expected:expr, $($pat:pat => $v:expr),+) => (
+        impl TryFrom<Value> for $t {
+            type Error = Error;
+
+            fn try_from(value: Value) -> Result<Self, Error> {
+                <$t>::try_from(&value)
+            }
+        }
+
+        impl TryFrom<&Value> for $t {
+            type Error = Error;
+
+            fn try_from(value: &Value) -> Result<Self, Error> {
+                match *value {
+                    $($pat => Ok($v),)+
+                    ref v => Err(unexpected($expected, v)),
+                }
+            }
+        }
+    );
+);
+
+try_from_value!(bool, "bool", Value::Bool(b) => b);
+try_from_value!(i32, "int", Value::Int(i) => i);
+try_from_value!(i64, "long", Value::Int(i) => i as i64, Value::Long(l) => l);
+try_from_value!(f64, "double", Value::Double(d) => d, Value::Int(i) => i as f64);
+
+impl TryFrom<Value> for String {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::String(s) => Ok(s.into_string()),
+            v => Err(unexpected("string", &v)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a str {
+    type Error = Error;
+
+    fn try_from(value: &'a Value) -> Result<Self, Error> {
+        value.as_str().ok_or_else(|| unexpected("string", value))
+    }
+}
+
+impl TryFrom<Value> for Vec<u8> {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Bytes(b) => Ok(b.into_vec()),
+            v => Err(unexpected("binary", &v)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a [u8] {
+    type Error = Error;
+
+    fn try_from(value: &'a Value) -> Result<Self, Error> {
+        value.as_bytes().ok_or_else(|| unexpected("binary", value))
+    }
+}
+
+impl TryFrom<Value> for List {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::List(l) => Ok(l),
+            v => Err(unexpected("list", &v)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a List {
+    type Error = Error;
+
+    fn try_from(value: &'a Value) -> Result<Self, Error> {
+        value.as_list().ok_or_else(|| unexpected("list", value))
+    }
+}
+
+impl TryFrom<Value> for Map {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Map(m) => Ok(m),
+            v => Err(unexpected("map", &v)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a Map {
+    type Error = Error;
+
+    fn try_from(value: &'a Value) -> Result<Self, Error> {
+        value.as_map().ok_or_else(|| unexpected("map", value))
+    }
+}
+
+/// Converts every element of a list, the list type is ignored.
+impl<T> TryFrom<Value> for Vec<T>
+where
+    T: TryFrom<Value, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        match List::try_from(value)? {
+            List::Typed(_, v) | List::Untyped(v) => v.into_iter().map(T::try_from).collect(),
+        }
+    }
+}
+
+/// Converts every entry of a map, the map type is ignored.
+impl<K, V> TryFrom<Value> for HashMap<K, V>
+where
+    K: TryFrom<Value, Error = Error> + Eq + Hash,
+    V: TryFrom<Value, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        match Map::try_from(value)? {
+            Map::Typed(_, m) | Map::Untyped(m) => m
+                .into_iter()
+                .map(|(k, v)| Ok((K::try_from(k)?, V::try_from(v)?)))
+                .collect(),
+        }
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -597,6 +1176,7 @@ impl fmt::Display for Value {
                 }
                 write!(f, "}}")
             }
+            Value::Shared(ref v) => v.fmt(f),
             _ => write!(f, "<Unknown Type>"),
         }
     }
@@ -608,7 +1188,7 @@ mod tests {
     fn test_display() {
         use super::*;
         {
-            let v = Value::String("hello".to_owned());
+            let v = Value::String("hello".into());
             assert_eq!(v.to_string(), "\"hello\"");
         }
         {
@@ -639,4 +1219,230 @@ mod tests {
             assert!(v.to_string().contains("\"b\" : 2,"));
         }
     }
+
+    #[test]
+    fn test_map_eq() {
+        // keys `Ord` ranks equal, inserted in either order
+        let a = hessian!({ 1: "int", 1i64: "long" });
+        let b = hessian!({ 1i64: "long", 1: "int" });
+        assert_eq!(a, b);
+        assert_ne!(a, hessian!({ 1: "long", 1i64: "int" }));
+        assert_ne!(a, hessian!({ 1: "int" }));
+    }
+
+    #[test]
+    fn test_index_and_pointer() {
+        use super::*;
+        let mut v = hessian!({
+            "ctx": "example.Ctx" => {
+                "id": 1,
+                "items": [{ "a/b": "x" }, 2],
+            },
+            7: "seven",
+        });
+        assert_eq!(v["ctx"]["id"], Value::Int(1));
+        assert_eq!(v["ctx"]["items"][1], Value::Int(2));
+        assert_eq!(v["missing"][0], Value::Null);
+        assert_eq!(v.pointer("/ctx/items/0/a~1b"), Some(&Value::from("x")));
+        assert_eq!(v.pointer("/7"), Some(&Value::from("seven")));
+        assert_eq!(v.pointer(""), Some(&v));
+        assert_eq!(v.pointer("/ctx/items/5"), None);
+        assert_eq!(v.pointer("ctx"), None);
+
+        *v.pointer_mut("/ctx/id").unwrap() = Value::Int(2);
+        v["ctx"]["items"][1] = Value::Int(3);
+        v["new"] = Value::Bool(true);
+        assert_eq!(v.pointer("/ctx/id"), Some(&Value::Int(2)));
+        assert_eq!(v["ctx"]["items"][1], Value::Int(3));
+        assert_eq!(v.get("new"), Some(&Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_from_containers() {
+        use super::*;
+        assert_eq!(
+            Value::from(vec![Value::Int(1), Value::Null]),
+            Value::List(vec![Value::Int(1), Value::Null].into())
+        );
+        assert_eq!(
+            Value::from(vec![Some(1), None]),
+            Value::List(vec![Value::Int(1), Value::Null].into())
+        );
+        assert_eq!(Value::from(Some("a")), Value::from("a"));
+        assert_eq!(Value::from(None::<i32>), Value::Null);
+
+        let mut fields = HashMap::new();
+        fields.insert("color".to_string(), Value::from("red"));
+        let car = Value::from(("example.Car", fields.clone()));
+        assert_eq!(car, hessian!("example.Car" => { "color": "red" }));
+        assert_eq!(Value::from(fields), hessian!({ "color": "red" }));
+
+        let list: List = vec!["a", "b"].into_iter().collect();
+        assert_eq!(Value::from(list), hessian!(["a", "b"]));
+        let squares: Value = (1..4).map(|i| i * i).collect();
+        assert_eq!(squares, hessian!([1, 4, 9]));
+        let map: Map = vec![("a", 1), ("b", 2)].into_iter().collect();
+        assert_eq!(Value::from(map), hessian!({ "a": 1, "b": 2 }));
+    }
+
+    #[test]
+    fn test_more_to_hessian() {
+        use super::*;
+        use std::time::Duration;
+
+        assert_eq!(Value::from(255_u8), Value::Int(255));
+        assert_eq!(Value::from(vec![1_u8, 2]), Value::Bytes(vec![1, 2].into()));
+        assert_eq!(
+            Value::from(vec![vec![1_u8], vec![]]),
+            Value::List(vec![Value::Bytes(vec![1].into()), Value::Bytes(vec![].into())].into())
+        );
+        assert_eq!(Value::from(vec![Value::Null]), hessian!([null]));
+        assert_eq!(Value::from(-1_i8), Value::Int(-1));
+        assert_eq!(Value::from(-1_i16), Value::Int(-1));
+        assert_eq!(Value::from(u16::MAX), Value::Int(65535));
+        assert_eq!(Value::from(i32::MAX as u32), Value::Int(i32::MAX));
+        assert_eq!(Value::from(u32::MAX), Value::Long(u32::MAX as i64));
+        assert_eq!(Value::from(1.5_f32), Value::Double(1.5));
+        assert_eq!(
+            Value::from(UNIX_EPOCH + Duration::from_millis(894621091000)),
+            Value::Date(894621091000)
+        );
+        assert_eq!(
+            Value::from(UNIX_EPOCH - Duration::from_millis(1)),
+            Value::Date(-1)
+        );
+        // rounds down as java does
+        assert_eq!(
+            Value::from(UNIX_EPOCH - Duration::from_micros(500)),
+            Value::Date(-1)
+        );
+        assert_eq!(
+            Value::from(UNIX_EPOCH - Duration::from_micros(1500)),
+            Value::Date(-2)
+        );
+        assert_eq!(
+            Value::from(UNIX_EPOCH + Duration::from_micros(1500)),
+            Value::Date(1)
+        );
+        let values = [Value::Int(1), Value::from("a")];
+        assert_eq!(Value::from(&values[..]), hessian!([1, "a"]));
+        assert_eq!(Value::from((1, "a", Some(true))), hessian!([1, "a", true]));
+        assert_eq!(Value::from((1.5,)), hessian!([1.5]));
+    }
+
+    #[test]
+    fn test_list_and_map_constructors() {
+        use super::*;
+        let mut list = List::typed("java.util.ArrayList", vec![Value::Int(1)]);
+        list.push("a");
+        let values = vec![Value::Int(1), Value::from("a")];
+        assert_eq!(list, List::Typed("java.util.ArrayList".to_string(), values));
+        list.set_type(None);
+        assert_eq!(list, List::untyped(vec![Value::Int(1), Value::from("a")]));
+        list.set_type(Some("[object".to_string()));
+        assert_eq!(list.r#type(), Some("[object"));
+        assert_eq!(list.len(), 2);
+        let list = List::with_capacity(4);
+        assert!(list.is_empty() && list.r#type().is_none());
+
+        let mut map = Map::with_capacity(1);
+        map.insert("color", "red");
+        map.set_type(Some("example.Car".to_string()));
+        assert_eq!(
+            Value::Map(map.clone()),
+            hessian!("example.Car" => { "color": "red" })
+        );
+        assert_eq!(Map::typed("example.Car", map.value().clone()), map);
+        map.set_type(None);
+        assert_eq!(map, Map::untyped(map.value().clone()));
+        assert_eq!(map.r#type(), None);
+    }
+
+    #[test]
+    fn test_base64() {
+        use super::*;
+        let value = Value::from_base64("aGVzc2lhbg==").unwrap();
+        assert_eq!(value, Value::Bytes(b"hessian".to_vec().into()));
+        assert_eq!(value.as_base64().unwrap(), "aGVzc2lhbg==");
+        assert_eq!(Value::from_base64("aGVzc2lhbg").unwrap(), value);
+        assert_eq!(Value::from_base64("not base64!"), None);
+        assert_eq!(Value::from("aGVzc2lhbg==").as_base64(), None);
+    }
+
+    #[test]
+    fn test_try_from() {
+        use super::*;
+        assert_eq!(i32::try_from(Value::Int(1)).unwrap(), 1);
+        assert_eq!(i64::try_from(&Value::Int(1)).unwrap(), 1);
+        assert_eq!(f64::try_from(Value::Double(1.5)).unwrap(), 1.5);
+        assert!(bool::try_from(Value::Bool(true)).unwrap());
+        assert_eq!(String::try_from(Value::from("a")).unwrap(), "a");
+        assert_eq!(<&str>::try_from(&Value::from("a")).unwrap(), "a");
+        assert_eq!(
+            Vec::<u8>::try_from(Value::Bytes(vec![1].into())).unwrap(),
+            vec![1]
+        );
+
+        let v = hessian!("[int" => [1, 2]);
+        assert_eq!(<&List>::try_from(&v).unwrap().r#type(), Some("[int"));
+        assert_eq!(Vec::<i32>::try_from(v).unwrap(), vec![1, 2]);
+
+        let m: HashMap<String, Vec<i64>> =
+            HashMap::try_from(hessian!({ "a": [1, 2_i64] })).unwrap();
+        assert_eq!(m["a"], vec![1, 2]);
+
+        let err = i32::try_from(Value::Long(1)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: unexpected type expect a int value, but get 1"
+        );
+        assert!(Vec::<i32>::try_from(hessian!([1, "a"])).is_err());
+    }
+
+    #[test]
+    fn test_lossy_numbers() {
+        use super::*;
+        assert_eq!(Value::Int(-1).as_i64_lossy(), Some(-1));
+        assert_eq!(Value::Long(1 << 40).as_i64_lossy(), Some(1 << 40));
+        assert_eq!(Value::Double(1.9).as_i64_lossy(), Some(1));
+        assert_eq!(Value::Int(1).as_u64(), Some(1));
+        assert_eq!(Value::Int(-1).as_u64(), Some(u64::MAX));
+        assert_eq!(Value::Long(2).as_f64_lossy(), Some(2.0));
+        assert_eq!(Value::Double(0.5).as_f64_lossy(), Some(0.5));
+        assert_eq!(Value::from("1").as_i64_lossy(), None);
+        assert_eq!(Value::Date(1).as_f64_lossy(), None);
+    }
+
+    #[test]
+    fn test_mutation() {
+        use super::*;
+        let mut v = hessian!("example.Request" => { "args": [1] });
+        {
+            let map = v.as_map_mut().unwrap();
+            assert_eq!(map.insert("attachments", hessian!({})), None);
+            map.entry("attachments")
+                .or_insert(Value::Null)
+                .as_map_mut()
+                .unwrap()
+                .insert("token", "abc");
+            *map.get_mut("args").unwrap() = hessian!([1, 2]);
+            assert_eq!(map.insert("tmp", 1), None);
+            assert_eq!(map.remove("tmp"), Some(Value::Int(1)));
+            assert_eq!(map.remove("tmp"), None);
+        }
+        {
+            let list = v["args"].as_list_mut().unwrap();
+            list.push(3);
+            list.insert(0, 0);
+            assert_eq!(list.remove(1), Value::Int(1));
+            assert_eq!(list.pop(), Some(Value::Int(3)));
+        }
+        assert_eq!(
+            v,
+            hessian!("example.Request" => {
+                "args": [0, 2],
+                "attachments": { "token": "abc" },
+            })
+        );
+    }
 }
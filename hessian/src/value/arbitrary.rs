@@ -0,0 +1,60 @@
+//! Random values for fuzzing, see the targets of the fuzz crate.
+
+use std::collections::HashMap;
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::{List, Map, Value};
+
+// How deep lists and maps nest, and how many items they have at most
+const MAX_DEPTH: usize = 6;
+const MAX_LEN: usize = 8;
+
+impl<'a> Arbitrary<'a> for Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_value(u, MAX_DEPTH)
+    }
+}
+
+fn arbitrary_value(u: &mut Unstructured<'_>, depth: usize) -> Result<Value> {
+    // lists and maps are the last two kinds
+    let kinds = if depth == 0 { 9 } else { 11 };
+    let value = match u.choose_index(kinds)? {
+        0 => Value::Null,
+        1 => Value::Bool(u.arbitrary()?),
+        2 => Value::Int(u.arbitrary()?),
+        3 => Value::Long(u.arbitrary()?),
+        4 => Value::Double(u.arbitrary()?),
+        5 => Value::Date(u.arbitrary()?),
+        6 => Value::Bytes(u.arbitrary::<Vec<u8>>()?.into()),
+        7 => Value::String(u.arbitrary::<String>()?.into()),
+        8 => Value::Ref(u.arbitrary()?),
+        9 => {
+            let tp: Option<String> = u.arbitrary()?;
+            let len = u.int_in_range(0..=MAX_LEN)?;
+            let values = (0..len)
+                .map(|_| arbitrary_value(u, depth - 1))
+                .collect::<Result<Vec<_>>>()?;
+            Value::List(match tp {
+                Some(tp) => List::Typed(tp, values),
+                None => List::Untyped(values),
+            })
+        }
+        _ => {
+            let tp: Option<String> = u.arbitrary()?;
+            let len = u.int_in_range(0..=MAX_LEN)?;
+            let mut entries = HashMap::new();
+            for _ in 0..len {
+                // `Value::hash` hashes maps by address, so containers which
+                // may hold one aren't keys
+                let key = arbitrary_value(u, 0)?;
+                entries.insert(key, arbitrary_value(u, depth - 1)?);
+            }
+            Value::Map(match tp {
+                Some(tp) => Map::Typed(tp, entries),
+                None => Map::Untyped(entries),
+            })
+        }
+    };
+    Ok(value)
+}
@@ -0,0 +1,88 @@
+//! Values allocated in an arena, see `Deserializer::read_value_in`.
+//!
+//! An `ArenaValue` borrows its strings, bytes and containers from a `Bump`
+//! and owns nothing, dropping a tree doesn't visit its nodes. The memory of
+//! a whole message is freed at once with the arena, or reused after
+//! `Bump::reset`.
+
+use std::collections::HashMap;
+
+pub use bumpalo::Bump;
+
+use super::{List, Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArenaValue<'a> {
+    /// null
+    Null,
+    /// boolean
+    Bool(bool),
+    /// 32-bit int
+    Int(i32),
+    /// 64-bit int
+    Long(i64),
+    /// 64-bit double
+    Double(f64),
+    /// 64-bit millisecond date
+    Date(i64),
+    /// raw binary data
+    Bytes(&'a [u8]),
+    /// UTF8-encoded string
+    String(&'a str),
+    /// reference to a previously read map or list
+    Ref(u32),
+    /// list, with its type when typed
+    List(Option<&'a str>, &'a [ArenaValue<'a>]),
+    /// map entries in the order they were read, with the type of the map
+    /// when typed. Objects are typed maps keyed by their field names.
+    Map(Option<&'a str>, &'a [(ArenaValue<'a>, ArenaValue<'a>)]),
+}
+
+impl<'a> ArenaValue<'a> {
+    /// Returns the value of the string key `key` of a map, the first one
+    /// when the key is repeated, or `None` for other values.
+    pub fn get(&self, key: &str) -> Option<&ArenaValue<'a>> {
+        match self {
+            ArenaValue::Map(_, entries) => entries
+                .iter()
+                .find(|(k, _)| *k == ArenaValue::String(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// Copies the value out of the arena. As in `Value` maps, the last of
+/// repeated keys wins.
+impl From<ArenaValue<'_>> for Value {
+    fn from(value: ArenaValue<'_>) -> Self {
+        match value {
+            ArenaValue::Null => Value::Null,
+            ArenaValue::Bool(b) => Value::Bool(b),
+            ArenaValue::Int(i) => Value::Int(i),
+            ArenaValue::Long(l) => Value::Long(l),
+            ArenaValue::Double(d) => Value::Double(d),
+            ArenaValue::Date(d) => Value::Date(d),
+            ArenaValue::Bytes(b) => Value::Bytes(b.into()),
+            ArenaValue::String(s) => Value::String(s.into()),
+            ArenaValue::Ref(r) => Value::Ref(r),
+            ArenaValue::List(tp, values) => {
+                let values = values.iter().map(|v| Value::from(*v)).collect();
+                Value::List(match tp {
+                    Some(tp) => List::Typed(tp.to_string(), values),
+                    None => List::Untyped(values),
+                })
+            }
+            ArenaValue::Map(tp, entries) => {
+                let entries: HashMap<_, _> = entries
+                    .iter()
+                    .map(|(k, v)| (Value::from(*k), Value::from(*v)))
+                    .collect();
+                Value::Map(match tp {
+                    Some(tp) => Map::Typed(tp.to_string(), entries),
+                    None => Map::Untyped(entries),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Conversions between `Value` and `serde_json::Value`.
+//!
+//! Hessian has types JSON lacks, so converting to JSON is lossy:
+//!
+//! * dates are ISO 8601 strings in UTC, e.g. `"1998-05-08T09:51:31.000Z"`
+//! * binary is a base64 string, with padding
+//! * the bytes of `Value::Unknown` are a base64 string too
+//! * a typed map is an object with the type in an `"@type"` entry
+//! * typed lists lose their type
+//! * map keys which aren't strings are written as their JSON text
+//! * refs are `{"@ref": n}` objects
+//! * NaN and the infinities are `null`
+//!
+//! Converting back, an object with a string `"@type"` entry is a typed map,
+//! numbers are ints when they fit, longs otherwise and doubles when they
+//! aren't integers. Strings stay strings, dates and binary aren't guessed.
+
+use std::collections::HashMap;
+
+use serde_json::{Map as JsonMap, Number, Value as Json};
+
+use super::text::{base64, iso_date, REF_KEY, TYPE_KEY};
+use super::{List, Map, Value};
+
+impl From<Value> for Json {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => Json::Null,
+            Value::Bool(b) => Json::Bool(b),
+            Value::Int(i) => Json::from(i),
+            Value::Long(l) => Json::from(l),
+            Value::Double(d) => Number::from_f64(d).map_or(Json::Null, Json::Number),
+            Value::Date(millis) => Json::String(iso_date(millis)),
+            Value::Bytes(bytes) => Json::String(base64(&bytes)),
+            Value::Unknown(bytes) => Json::String(base64(&bytes)),
+            v @ Value::Shared(_) => Json::from(v.unshared()),
+            Value::String(s) => Json::String(s.into_string()),
+            Value::Ref(n) => {
+                let mut object = JsonMap::new();
+                object.insert(REF_KEY.to_string(), Json::from(n));
+                Json::Object(object)
+            }
+            Value::List(List::Typed(_, list)) | Value::List(List::Untyped(list)) => {
+                Json::Array(list.into_iter().map(Json::from).collect())
+            }
+            Value::Map(map) => {
+                let mut object = JsonMap::new();
+                let (tp, entries) = match map {
+                    Map::Typed(tp, entries) => (Some(tp), entries),
+                    Map::Untyped(entries) => (None, entries),
+                };
+                if let Some(tp) = tp {
+                    object.insert(TYPE_KEY.to_string(), Json::String(tp));
+                }
+                for (k, v) in entries {
+                    let key = match k {
+                        Value::String(s) => s.into_string(),
+                        k => Json::from(k).to_string(),
+                    };
+                    object.insert(key, Json::from(v));
+                }
+                Json::Object(object)
+            }
+        }
+    }
+}
+
+impl From<Json> for Value {
+    fn from(json: Json) -> Self {
+        match json {
+            Json::Null => Value::Null,
+            Json::Bool(b) => Value::Bool(b),
+            Json::Number(n) => match (n.as_i64(), n.as_f64()) {
+                (Some(l), _) if l as i32 as i64 == l => Value::Int(l as i32),
+                (Some(l), _) => Value::Long(l),
+                (None, d) => Value::Double(d.unwrap_or(f64::NAN)),
+            },
+            Json::String(s) => Value::String(s.into()),
+            Json::Array(array) => Value::List(
+                array
+                    .into_iter()
+                    .map(Value::from)
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            Json::Object(mut object) => {
+                let tp = match object.get(TYPE_KEY) {
+                    Some(Json::String(_)) => match object.remove(TYPE_KEY) {
+                        Some(Json::String(tp)) => Some(tp),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let entries: HashMap<Value, Value> = object
+                    .into_iter()
+                    .map(|(k, v)| (Value::String(k.into()), Value::from(v)))
+                    .collect();
+                match tp {
+                    Some(tp) => Value::Map(Map::from((tp, entries))),
+                    None => Value::Map(Map::from(entries)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::value::Value;
+    use serde_json::{json, Value as Json};
+
+    #[test]
+    fn test_to_json() {
+        let value = hessian!("example.Car" => {
+            "color": "red",
+            "mileage": 65536_i64,
+            "price": 1.5,
+            "parts": "[string" => ["wheel"],
+            "prev": null,
+            1: f64::NAN,
+        });
+        assert_eq!(
+            Json::from(value),
+            json!({
+                "@type": "example.Car",
+                "color": "red",
+                "mileage": 65536,
+                "price": 1.5,
+                "parts": ["wheel"],
+                "prev": null,
+                "1": null,
+            })
+        );
+        assert_eq!(
+            Json::from(Value::Date(894621091000)),
+            json!("1998-05-08T09:51:31.000Z")
+        );
+        assert_eq!(
+            Json::from(Value::Bytes(b"hessian".to_vec().into())),
+            json!("aGVzc2lhbg==")
+        );
+        assert_eq!(Json::from(Value::Ref(2)), json!({ "@ref": 2 }));
+    }
+
+    #[test]
+    fn test_from_json() {
+        let json = json!({
+            "@type": "example.Car",
+            "color": "red",
+            "mileage": 4294967296_i64,
+            "price": 1.5,
+            "parts": ["wheel", 1, true],
+            "owner": { "@type": 1 },
+        });
+        assert_eq!(
+            Value::from(json),
+            hessian!("example.Car" => {
+                "color": "red",
+                "mileage": 4294967296_i64,
+                "price": 1.5,
+                "parts": ["wheel", 1, true],
+                "owner": { "@type": 1 },
+            })
+        );
+        assert_eq!(Value::from(json!(u64::MAX)), Value::Double(u64::MAX as f64));
+    }
+}
@@ -0,0 +1,244 @@
+use super::{List, Map, Value};
+
+/// How `Value::merge_with` combines two values.
+///
+/// The default replaces lists and merges maps recursively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MergeStrategy {
+    recursive: bool,
+    append_lists: bool,
+}
+
+impl Default for MergeStrategy {
+    fn default() -> Self {
+        MergeStrategy {
+            recursive: true,
+            append_lists: false,
+        }
+    }
+}
+
+impl MergeStrategy {
+    /// Always replace the old value with the new one.
+    pub fn replace() -> Self {
+        MergeStrategy {
+            recursive: false,
+            append_lists: false,
+        }
+    }
+
+    /// Merge the entries of two maps, merging values of common keys.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Append the elements of the new list to the old list.
+    pub fn append_lists(mut self, append_lists: bool) -> Self {
+        self.append_lists = append_lists;
+        self
+    }
+}
+
+/// A single difference between two values, see `Value::diff`.
+///
+/// `path` is a pointer accepted by `Value::pointer`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delta {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl Value {
+    /// Merge `other` into `self` with the default `MergeStrategy`.
+    pub fn merge(&mut self, other: Value) {
+        self.merge_with(other, MergeStrategy::default())
+    }
+
+    /// Merge `other` into `self`.
+    ///
+    /// Values which can't be merged are replaced by `other`. A typed `other`
+    /// map or list overrides the type of `self`.
+    pub fn merge_with(&mut self, other: Value, strategy: MergeStrategy) {
+        match (self, other) {
+            (Value::Map(lhs), Value::Map(rhs)) if strategy.recursive => {
+                let (typ, entries) = match rhs {
+                    Map::Typed(typ, m) => (Some(typ), m),
+                    Map::Untyped(m) => (None, m),
+                };
+                for (k, v) in entries {
+                    match lhs.get_mut(k.clone()) {
+                        Some(old) => old.merge_with(v, strategy),
+                        None => {
+                            lhs.insert(k, v);
+                        }
+                    }
+                }
+                if let Some(typ) = typ {
+                    *lhs = Map::Typed(typ, std::mem::take(lhs.value_mut()));
+                }
+            }
+            (Value::List(lhs), Value::List(rhs)) if strategy.append_lists => {
+                let (typ, elements) = match rhs {
+                    List::Typed(typ, v) => (Some(typ), v),
+                    List::Untyped(v) => (None, v),
+                };
+                for v in elements {
+                    lhs.push(v);
+                }
+                if let Some(typ) = typ {
+                    let elements = std::mem::take(lhs.vec_mut());
+                    *lhs = List::Typed(typ, elements);
+                }
+            }
+            (lhs, rhs) => *lhs = rhs,
+        }
+    }
+
+    /// Computes the changes which turn `self` into `other`.
+    ///
+    /// Maps and lists of the same type are compared element by element,
+    /// anything else is reported as a whole `Changed` value.
+    pub fn diff(&self, other: &Value) -> Vec<Delta> {
+        let mut deltas = Vec::new();
+        diff_into(self, other, &mut String::new(), &mut deltas);
+        deltas
+    }
+}
+
+pub(super) fn push_segment(path: &mut String, key: &Value) {
+    path.push('/');
+    match key {
+        Value::String(s) => path.push_str(&s.replace('~', "~0").replace('/', "~1")),
+        Value::Int(i) => path.push_str(&i.to_string()),
+        Value::Long(l) => path.push_str(&l.to_string()),
+        v => path.push_str(&v.to_string().replace('~', "~0").replace('/', "~1")),
+    }
+}
+
+fn diff_into(lhs: &Value, rhs: &Value, path: &mut String, deltas: &mut Vec<Delta>) {
+    let len = path.len();
+    match (lhs, rhs) {
+        (Value::Map(l), Value::Map(r)) if l.r#type() == r.r#type() => {
+            let mut keys: Vec<&Value> = l
+                .keys()
+                .chain(r.keys().filter(|k| !l.contains_key(k)))
+                .collect();
+            keys.sort();
+            for key in keys {
+                push_segment(path, key);
+                match (l.value().get(key), r.value().get(key)) {
+                    (Some(lv), Some(rv)) => diff_into(lv, rv, path, deltas),
+                    (Some(lv), None) => deltas.push(Delta::Removed {
+                        path: path.clone(),
+                        value: lv.clone(),
+                    }),
+                    (None, Some(rv)) => deltas.push(Delta::Added {
+                        path: path.clone(),
+                        value: rv.clone(),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+                path.truncate(len);
+            }
+        }
+        (Value::List(l), Value::List(r)) if l.r#type() == r.r#type() => {
+            for i in 0..l.len().max(r.len()) {
+                push_segment(path, &Value::Long(i as i64));
+                match (l.get(i), r.get(i)) {
+                    (Some(lv), Some(rv)) => diff_into(lv, rv, path, deltas),
+                    (Some(lv), None) => deltas.push(Delta::Removed {
+                        path: path.clone(),
+                        value: lv.clone(),
+                    }),
+                    (None, Some(rv)) => deltas.push(Delta::Added {
+                        path: path.clone(),
+                        value: rv.clone(),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+                path.truncate(len);
+            }
+        }
+        (l, r) if l == r => {}
+        (l, r) => deltas.push(Delta::Changed {
+            path: path.clone(),
+            old: l.clone(),
+            new: r.clone(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Delta, MergeStrategy};
+    use crate::value::Value;
+
+    #[test]
+    fn test_merge() {
+        let mut v = hessian!({ "a": 1, "b": { "c": [1], "d": 1 } });
+        v.merge(hessian!({ "b": { "c": [2], "e": 2 }, "f": 3 }));
+        assert_eq!(
+            v,
+            hessian!({ "a": 1, "b": { "c": [2], "d": 1, "e": 2 }, "f": 3 })
+        );
+
+        v.merge_with(
+            hessian!({ "b": "example.B" => { "c": [3] } }),
+            MergeStrategy::default().append_lists(true),
+        );
+        assert_eq!(
+            v["b"],
+            hessian!("example.B" => { "c": [2, 3], "d": 1, "e": 2 })
+        );
+
+        v.merge_with(hessian!({ "a": 2 }), MergeStrategy::replace());
+        assert_eq!(v, hessian!({ "a": 2 }));
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = hessian!({ "a": 1, "b": [1, 2], "c": { "d/e": 1 }, "x": 1 });
+        let new = hessian!({ "a": 1_i64, "b": [1], "c": { "d/e": 2 }, "y": 1 });
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                Delta::Changed {
+                    path: "/a".to_string(),
+                    old: Value::Int(1),
+                    new: Value::Long(1),
+                },
+                Delta::Removed {
+                    path: "/b/1".to_string(),
+                    value: Value::Int(2),
+                },
+                Delta::Changed {
+                    path: "/c/d~1e".to_string(),
+                    old: Value::Int(1),
+                    new: Value::Int(2),
+                },
+                Delta::Removed {
+                    path: "/x".to_string(),
+                    value: Value::Int(1),
+                },
+                Delta::Added {
+                    path: "/y".to_string(),
+                    value: Value::Int(1),
+                },
+            ]
+        );
+        assert!(old.diff(&old).is_empty());
+        assert_eq!(old.pointer("/c/d~1e"), Some(&Value::Int(1)));
+    }
+}
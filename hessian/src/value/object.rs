@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use super::{Definition, Map, Value};
+use crate::error::{Error, ErrorKind};
+
+/// A rust type encoded as an instance of a java class.
+///
+/// Usually implemented with `#[derive(HessianObject)]` of the `hessian-derive`
+/// crate, which names the class and the fields independently of serde:
+///
+/// ```ignore
+/// #[derive(Clone, HessianObject)]
+/// #[hessian(class = "com.example.Car")]
+/// struct Car {
+///     color: String,
+///     #[hessian(field = "modelName")]
+///     model_name: String,
+/// }
+/// ```
+///
+/// Objects decode as typed maps, so `from_value` reads the fields from a map
+/// and ignores its type. `Serializer::serialize_object` writes an object,
+/// while `to_value` returns a typed map, which java reads back into the class
+/// as well.
+pub trait HessianObject: Sized {
+    /// The class name and the field names, in the order they're written.
+    fn definition() -> Definition;
+
+    /// The values of the fields, in the order of the definition.
+    fn to_fields(&self) -> Vec<Value>;
+
+    fn from_value(value: Value) -> Result<Self, Error>;
+
+    fn to_value(&self) -> Value {
+        let Definition { name, fields } = Self::definition();
+        let map: HashMap<Value, Value> = fields
+            .into_iter()
+            .map(|field| Value::String(field.into()))
+            .zip(self.to_fields())
+            .collect();
+        Value::Map((name, map).into())
+    }
+}
+
+/// The fields of an object being converted by `HessianObject::from_value`.
+#[doc(hidden)]
+pub struct ObjectFields {
+    class: String,
+    fields: HashMap<Value, Value>,
+}
+
+impl ObjectFields {
+    pub fn new<T: HessianObject>(value: Value) -> Result<Self, Error> {
+        let fields = match Map::try_from(value)? {
+            Map::Typed(_, m) | Map::Untyped(m) => m,
+        };
+        Ok(ObjectFields {
+            class: T::definition().name,
+            fields,
+        })
+    }
+
+    pub fn take<T: TryFrom<Value, Error = Error>>(&mut self, name: &str) -> Result<T, Error> {
+        match self.fields.remove(&Value::from(name)) {
+            Some(v) => T::try_from(v),
+            None => Err(Error::SyntaxError(ErrorKind::UnexpectedType(format!(
+                "missing field {} of {}",
+                name, self.class
+            )))),
+        }
+    }
+}
@@ -0,0 +1,475 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::merge::push_segment;
+use super::Value;
+
+/// The structure of a value: the types of its values, the class names and
+/// fields of its objects and the types of the elements of its lists, see
+/// `Value::schema`.
+///
+/// Hessian doesn't tell an object from a map whose keys are strings, both
+/// are read as maps, so such maps are `Struct`s. The schemas of several
+/// messages of a service combine with `merge` to cover what each of them
+/// lacks, e.g. a field which is null in one of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schema {
+    /// Nothing seen, the elements of an empty list
+    Empty,
+    Null,
+    Bool,
+    Int,
+    Long,
+    Double,
+    Date,
+    Bytes,
+    String,
+    Ref,
+    List {
+        r#type: Option<String>,
+        element: Box<Schema>,
+    },
+    /// A map whose keys are strings, by field name
+    Struct {
+        r#type: Option<String>,
+        fields: BTreeMap<String, Schema>,
+    },
+    /// A map whose keys aren't all strings
+    Map {
+        r#type: Option<String>,
+        key: Box<Schema>,
+        value: Box<Schema>,
+    },
+    /// A value which is null at times
+    Nullable(Box<Schema>),
+    /// Values of types which don't merge, e.g. objects of two classes
+    Union(Vec<Schema>),
+}
+
+/// A single difference between two schemas, see `Schema::diff`.
+///
+/// `path` is a pointer as for `Delta`, `*` standing for the elements of a
+/// list or the values of a map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaDelta {
+    Added {
+        path: String,
+        schema: Schema,
+    },
+    Removed {
+        path: String,
+        schema: Schema,
+    },
+    Changed {
+        path: String,
+        old: Schema,
+        new: Schema,
+    },
+}
+
+impl Value {
+    /// The structure of the value, see `Schema`.
+    pub fn schema(&self) -> Schema {
+        match self {
+            Value::Null => Schema::Null,
+            Value::Bool(_) => Schema::Bool,
+            Value::Int(_) => Schema::Int,
+            Value::Long(_) => Schema::Long,
+            Value::Double(_) => Schema::Double,
+            Value::Date(_) => Schema::Date,
+            Value::Bytes(_) => Schema::Bytes,
+            Value::String(_) => Schema::String,
+            Value::Ref(_) => Schema::Ref,
+            // corrupt bytes tell nothing of the structure
+            Value::Unknown(_) => Schema::Empty,
+            Value::Shared(v) => v.schema(),
+            Value::List(list) => Schema::List {
+                r#type: list.r#type().map(str::to_string),
+                element: Box::new(merge_all(list.iter().map(Value::schema))),
+            },
+            Value::Map(map) => {
+                let r#type = map.r#type().map(str::to_string);
+                match map.keys().all(|k| matches!(k, Value::String(_))) {
+                    true => Schema::Struct {
+                        r#type,
+                        fields: map
+                            .iter()
+                            .filter_map(|(k, v)| Some((k.as_str()?.to_string(), v.schema())))
+                            .collect(),
+                    },
+                    false => Schema::Map {
+                        r#type,
+                        key: Box::new(merge_all(map.keys().map(Value::schema))),
+                        value: Box::new(merge_all(map.values().map(Value::schema))),
+                    },
+                }
+            }
+        }
+    }
+}
+
+impl Schema {
+    /// The schema of the values of both `self` and `other`.
+    ///
+    /// An int and a long merge to a long, a null makes a schema `Nullable`,
+    /// as does a field only one of two structs has. Lists and maps merge
+    /// their elements, structs merge when they are of the same class.
+    /// Other schemas which differ make a `Union`.
+    pub fn merge(self, other: Schema) -> Schema {
+        match (self, other) {
+            (Schema::Empty, s) | (s, Schema::Empty) => s,
+            (Schema::Null, Schema::Null) => Schema::Null,
+            (Schema::Nullable(s), Schema::Null) | (Schema::Null, Schema::Nullable(s)) => {
+                Schema::Nullable(s)
+            }
+            (Schema::Nullable(a), Schema::Nullable(b)) => Schema::Nullable(Box::new(a.merge(*b))),
+            (Schema::Nullable(a), b) | (b, Schema::Nullable(a)) => {
+                Schema::Nullable(Box::new(a.merge(b)))
+            }
+            (Schema::Null, s) | (s, Schema::Null) => Schema::Nullable(Box::new(s)),
+            (Schema::Int, Schema::Long) | (Schema::Long, Schema::Int) => Schema::Long,
+            (
+                Schema::List {
+                    r#type: a,
+                    element: ea,
+                },
+                Schema::List {
+                    r#type: b,
+                    element: eb,
+                },
+            ) => Schema::List {
+                r#type: if a == b { a } else { None },
+                element: Box::new(ea.merge(*eb)),
+            },
+            (
+                Schema::Map {
+                    r#type: a,
+                    key: ka,
+                    value: va,
+                },
+                Schema::Map {
+                    r#type: b,
+                    key: kb,
+                    value: vb,
+                },
+            ) => Schema::Map {
+                r#type: if a == b { a } else { None },
+                key: Box::new(ka.merge(*kb)),
+                value: Box::new(va.merge(*vb)),
+            },
+            (
+                Schema::Struct {
+                    r#type: a,
+                    fields: mut fa,
+                },
+                Schema::Struct {
+                    r#type: b,
+                    fields: mut fb,
+                },
+            ) if a == b => {
+                let names: Vec<String> = fa.keys().chain(fb.keys()).cloned().collect();
+                let fields = names
+                    .into_iter()
+                    .filter_map(|name| {
+                        let field = match (fa.remove(&name), fb.remove(&name)) {
+                            (Some(x), Some(y)) => x.merge(y),
+                            (Some(x), None) | (None, Some(x)) => x.merge(Schema::Null),
+                            // already merged
+                            (None, None) => return None,
+                        };
+                        Some((name, field))
+                    })
+                    .collect();
+                Schema::Struct { r#type: a, fields }
+            }
+            (Schema::Union(members), s) | (s, Schema::Union(members)) => {
+                let mut union = Schema::Union(Vec::new());
+                for member in members.into_iter().chain(std::iter::once(s)) {
+                    union = union.add(member);
+                }
+                union
+            }
+            (a, b) if a == b => a,
+            (a, b) => Schema::Union(vec![a, b]),
+        }
+    }
+
+    // Add `s` to a union, merging it with the member it merges with
+    fn add(self, s: Schema) -> Schema {
+        let mut members = match self {
+            Schema::Union(members) => members,
+            other => vec![other],
+        };
+        let s = match s {
+            Schema::Union(more) => {
+                let mut union = Schema::Union(members);
+                for member in more {
+                    union = union.add(member);
+                }
+                return union;
+            }
+            s => s,
+        };
+        match members.iter().position(|m| m.merges_with(&s)) {
+            Some(i) => {
+                let member = members.remove(i);
+                members.insert(i, member.merge(s));
+            }
+            None => members.push(s),
+        }
+        Schema::Union(members)
+    }
+
+    // Whether merging with `other` doesn't make a union
+    fn merges_with(&self, other: &Schema) -> bool {
+        match (self, other) {
+            (Schema::Struct { r#type: a, .. }, Schema::Struct { r#type: b, .. }) => a == b,
+            (Schema::List { .. }, Schema::List { .. })
+            | (Schema::Map { .. }, Schema::Map { .. })
+            | (Schema::Int, Schema::Long)
+            | (Schema::Long, Schema::Int) => true,
+            (a, b) => a == b,
+        }
+    }
+
+    /// Computes the changes which turn `self` into `other`, e.g. to compare
+    /// the messages of two versions of a service.
+    ///
+    /// Structs of the same class are compared field by field, lists and
+    /// maps by their elements, anything else is a whole `Changed` schema.
+    pub fn diff(&self, other: &Schema) -> Vec<SchemaDelta> {
+        let mut deltas = Vec::new();
+        diff_into(self, other, &mut String::new(), &mut deltas);
+        deltas
+    }
+}
+
+fn merge_all<I: Iterator<Item = Schema>>(schemas: I) -> Schema {
+    schemas.fold(Schema::Empty, Schema::merge)
+}
+
+fn diff_into(lhs: &Schema, rhs: &Schema, path: &mut String, deltas: &mut Vec<SchemaDelta>) {
+    let len = path.len();
+    match (lhs, rhs) {
+        (
+            Schema::Struct {
+                r#type: a,
+                fields: fa,
+            },
+            Schema::Struct {
+                r#type: b,
+                fields: fb,
+            },
+        ) if a == b => {
+            let mut names: Vec<&String> = fa.keys().chain(fb.keys()).collect();
+            names.sort();
+            names.dedup();
+            for name in names {
+                push_segment(path, &Value::from(name.as_str()));
+                match (fa.get(name), fb.get(name)) {
+                    (Some(l), Some(r)) => diff_into(l, r, path, deltas),
+                    (Some(l), None) => deltas.push(SchemaDelta::Removed {
+                        path: path.clone(),
+                        schema: l.clone(),
+                    }),
+                    (None, Some(r)) => deltas.push(SchemaDelta::Added {
+                        path: path.clone(),
+                        schema: r.clone(),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+                path.truncate(len);
+            }
+        }
+        (
+            Schema::List {
+                r#type: a,
+                element: l,
+            },
+            Schema::List {
+                r#type: b,
+                element: r,
+            },
+        ) if a == b => {
+            path.push_str("/*");
+            diff_into(l, r, path, deltas);
+            path.truncate(len);
+        }
+        (
+            Schema::Map {
+                r#type: a,
+                key: lk,
+                value: l,
+            },
+            Schema::Map {
+                r#type: b,
+                key: rk,
+                value: r,
+            },
+        ) if a == b && lk == rk => {
+            path.push_str("/*");
+            diff_into(l, r, path, deltas);
+            path.truncate(len);
+        }
+        (Schema::Nullable(l), Schema::Nullable(r)) => diff_into(l, r, path, deltas),
+        (l, r) if l == r => {}
+        (l, r) => deltas.push(SchemaDelta::Changed {
+            path: path.clone(),
+            old: l.clone(),
+            new: r.clone(),
+        }),
+    }
+}
+
+/// A schema as a type, e.g. `example.Car { color: string, parts: [string]? }`
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schema::Empty => write!(f, "empty"),
+            Schema::Null => write!(f, "null"),
+            Schema::Bool => write!(f, "bool"),
+            Schema::Int => write!(f, "int"),
+            Schema::Long => write!(f, "long"),
+            Schema::Double => write!(f, "double"),
+            Schema::Date => write!(f, "date"),
+            Schema::Bytes => write!(f, "bytes"),
+            Schema::String => write!(f, "string"),
+            Schema::Ref => write!(f, "ref"),
+            Schema::List { r#type, element } => {
+                if let Some(tp) = r#type {
+                    write!(f, "{} ", tp)?;
+                }
+                write!(f, "[{}]", element)
+            }
+            Schema::Struct { r#type, fields } => {
+                if let Some(tp) = r#type {
+                    write!(f, "{} ", tp)?;
+                }
+                if fields.is_empty() {
+                    return write!(f, "{{}}");
+                }
+                write!(f, "{{ ")?;
+                for (i, (name, schema)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, schema)?;
+                }
+                write!(f, " }}")
+            }
+            Schema::Map { r#type, key, value } => {
+                if let Some(tp) = r#type {
+                    write!(f, "{} ", tp)?;
+                }
+                write!(f, "map<{}, {}>", key, value)
+            }
+            Schema::Nullable(s) => match **s {
+                Schema::Union(_) => write!(f, "({})?", s),
+                _ => write!(f, "{}?", s),
+            },
+            Schema::Union(members) => {
+                for (i, member) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{}", member)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Schema, SchemaDelta};
+    use crate::value::Value;
+
+    #[test]
+    fn test_schema() {
+        let value = hessian!("example.Car" => {
+            "color": "red",
+            "mileage": 65536,
+            "parts": "[string" => ["wheel", "door"],
+            "prev": null,
+            "owners": [
+                (hessian!("example.Person" => { "name": "a", "age": 20 })),
+                (hessian!("example.Person" => { "name": "b", "age": (Value::Long(30)) })),
+                (hessian!("example.Person" => { "name": "c" })),
+                null
+            ],
+            "tags": { 1: "one", 2: "two" },
+            "extra": ["x", 1]
+        });
+        assert_eq!(
+            value.schema().to_string(),
+            "example.Car { color: string, extra: [string | int], mileage: int, \
+             owners: [example.Person { age: long?, name: string }?], \
+             parts: [string [string], prev: null, tags: map<int, string> }"
+        );
+        assert_eq!(
+            Value::List(Vec::new().into()).schema().to_string(),
+            "[empty]"
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let a = hessian!({ "id": 1, "name": "a", "tags": [] }).schema();
+        let b = hessian!({ "id": (Value::Long(2)), "tags": ["x"], "next": null }).schema();
+        assert_eq!(
+            a.merge(b).to_string(),
+            "{ id: long, name: string?, next: null, tags: [string] }"
+        );
+        let union = Schema::Union(vec![Schema::Int, Schema::String]);
+        assert_eq!(
+            union.clone().merge(Schema::Long),
+            Schema::Union(vec![Schema::Long, Schema::String])
+        );
+        assert_eq!(
+            union.clone().merge(Schema::Null).to_string(),
+            "(int | string)?"
+        );
+        assert_eq!(union.clone().merge(union.clone()), union);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = hessian!("example.Car" => {
+            "color": "red",
+            "mileage": 1,
+            "parts": ["wheel"],
+            "owner": "a"
+        });
+        let new = hessian!("example.Car" => {
+            "color": "red",
+            "mileage": (Value::Long(1)),
+            "parts": [1],
+            "made": (Value::Date(0))
+        });
+        assert_eq!(
+            old.schema().diff(&new.schema()),
+            vec![
+                SchemaDelta::Added {
+                    path: "/made".into(),
+                    schema: Schema::Date,
+                },
+                SchemaDelta::Changed {
+                    path: "/mileage".into(),
+                    old: Schema::Int,
+                    new: Schema::Long,
+                },
+                SchemaDelta::Removed {
+                    path: "/owner".into(),
+                    schema: Schema::String,
+                },
+                SchemaDelta::Changed {
+                    path: "/parts/*".into(),
+                    old: Schema::String,
+                    new: Schema::Int,
+                },
+            ]
+        );
+        assert!(old.schema().diff(&old.schema()).is_empty());
+    }
+}
@@ -0,0 +1,335 @@
+//! The string and bytes of a `Value`.
+//!
+//! `Str` and `Bytes` deref to `str` and `[u8]` and convert from and to
+//! `String` and `Vec<u8>`. With the `smol_str` and `smallvec` features they
+//! keep short contents inline rather than on the heap, which saves an
+//! allocation per string or binary of payloads made of short ones. Their
+//! API doesn't change with the features.
+//!
+//! A `Str` can also share its storage with its clones, as the keys
+//! interned by `Deserializer::intern_keys` do.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut};
+#[cfg(not(feature = "smol_str"))]
+use std::sync::Arc;
+
+#[cfg(not(feature = "smol_str"))]
+#[derive(Clone)]
+enum StrRepr {
+    Owned(String),
+    Shared(Arc<str>),
+}
+
+#[cfg(not(feature = "smol_str"))]
+impl Default for StrRepr {
+    fn default() -> Self {
+        StrRepr::Owned(String::new())
+    }
+}
+
+// Up to 23 bytes inline, then shared on the heap
+#[cfg(feature = "smol_str")]
+type StrRepr = smol_str::SmolStr;
+
+#[cfg(not(feature = "smallvec"))]
+type BytesRepr = Vec<u8>;
+// Up to 24 bytes inline, which doesn't make `Value` any larger
+#[cfg(feature = "smallvec")]
+const INLINE_BYTES: usize = 24;
+#[cfg(feature = "smallvec")]
+type BytesRepr = smallvec::SmallVec<[u8; INLINE_BYTES]>;
+
+/// UTF-8 string of `Value::String`
+#[derive(Clone, Default)]
+pub struct Str(StrRepr);
+
+impl Str {
+    /// A string whose clones share its storage rather than copy it
+    pub fn shared(s: &str) -> Self {
+        #[cfg(not(feature = "smol_str"))]
+        return Str(StrRepr::Shared(s.into()));
+        #[cfg(feature = "smol_str")]
+        return Str(s.into());
+    }
+
+    pub fn as_str(&self) -> &str {
+        #[cfg(not(feature = "smol_str"))]
+        return match &self.0 {
+            StrRepr::Owned(s) => s,
+            StrRepr::Shared(s) => s,
+        };
+        #[cfg(feature = "smol_str")]
+        return &self.0;
+    }
+
+    pub fn into_string(self) -> String {
+        #[cfg(not(feature = "smol_str"))]
+        return match self.0 {
+            StrRepr::Owned(s) => s,
+            StrRepr::Shared(s) => s.to_string(),
+        };
+        #[cfg(feature = "smol_str")]
+        return self.0.into();
+    }
+}
+
+impl Deref for Str {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Str {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for Str {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+// Compared and hashed as `str`, whether shared or not
+impl PartialEq for Str {
+    fn eq(&self, other: &Str) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Str {}
+
+impl PartialOrd for Str {
+    fn partial_cmp(&self, other: &Str) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Str {
+    fn cmp(&self, other: &Str) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for Str {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Debug for Str {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Str {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl From<String> for Str {
+    fn from(s: String) -> Self {
+        #[cfg(not(feature = "smol_str"))]
+        return Str(StrRepr::Owned(s));
+        #[cfg(feature = "smol_str")]
+        return Str(s.into());
+    }
+}
+
+impl From<&str> for Str {
+    fn from(s: &str) -> Self {
+        #[cfg(not(feature = "smol_str"))]
+        return Str(StrRepr::Owned(s.to_string()));
+        #[cfg(feature = "smol_str")]
+        return Str(s.into());
+    }
+}
+
+impl From<&String> for Str {
+    fn from(s: &String) -> Self {
+        Str::from(s.as_str())
+    }
+}
+
+impl From<char> for Str {
+    fn from(c: char) -> Self {
+        Str::from(c.encode_utf8(&mut [0; 4]) as &str)
+    }
+}
+
+impl From<Str> for String {
+    fn from(s: Str) -> Self {
+        s.into_string()
+    }
+}
+
+impl FromIterator<char> for Str {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        Str::from(iter.into_iter().collect::<String>())
+    }
+}
+
+impl PartialEq<str> for Str {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Str {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Str {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+/// Binary data of `Value::Bytes`
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bytes(BytesRepr);
+
+impl Bytes {
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        #[cfg(not(feature = "smallvec"))]
+        return self.0;
+        #[cfg(feature = "smallvec")]
+        return self.0.into_vec();
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl DerefMut for Bytes {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Borrow<[u8]> for Bytes {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    // A short vec is copied inline, a long one is kept
+    fn from(v: Vec<u8>) -> Self {
+        #[cfg(not(feature = "smallvec"))]
+        return Bytes(v);
+        #[cfg(feature = "smallvec")]
+        return match v.len() <= INLINE_BYTES {
+            true => Bytes(BytesRepr::from_slice(&v)),
+            false => Bytes(BytesRepr::from_vec(v)),
+        };
+    }
+}
+
+impl From<&[u8]> for Bytes {
+    fn from(v: &[u8]) -> Self {
+        Bytes(v.into())
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for Bytes {
+    fn from(v: [u8; N]) -> Self {
+        Bytes::from(&v[..])
+    }
+}
+
+impl From<Bytes> for Vec<u8> {
+    fn from(b: Bytes) -> Self {
+        b.into_vec()
+    }
+}
+
+impl FromIterator<u8> for Bytes {
+    fn from_iter<I: IntoIterator<Item = u8>>(iter: I) -> Self {
+        Bytes(iter.into_iter().collect())
+    }
+}
+
+impl PartialEq<[u8]> for Bytes {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+impl PartialEq<Vec<u8>> for Bytes {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<const N: usize> PartialEq<[u8; N]> for Bytes {
+    fn eq(&self, other: &[u8; N]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bytes, Str};
+
+    #[test]
+    fn test_str() {
+        let s = Str::from("中文");
+        assert_eq!(s, "中文");
+        assert_eq!(s.len(), 6);
+        assert_eq!(format!("{} {:?}", s, s), "中文 \"中文\"");
+        assert_eq!(String::from(s), "中文");
+        let long: Str = "a".repeat(100).chars().collect();
+        assert_eq!(long.clone().into_string(), "a".repeat(100));
+        let (a, b) = (Str::from("a"), Str::from("b"));
+        assert!(a < b);
+        let shared = Str::shared(&long);
+        assert_eq!(shared, long);
+        assert_eq!(shared.clone().as_ptr(), shared.as_ptr());
+    }
+
+    #[test]
+    fn test_bytes() {
+        let b = Bytes::from(vec![1, 2, 3]);
+        assert_eq!(b, [1, 2, 3]);
+        assert_eq!(format!("{:?}", b), "[1, 2, 3]");
+        assert_eq!(b.into_vec(), vec![1, 2, 3]);
+        let long: Bytes = (0..100).collect();
+        assert_eq!(long.len(), 100);
+        assert_eq!(Vec::from(long.clone()), (0..100).collect::<Vec<u8>>());
+        assert_eq!(Bytes::from(&long[..]), long);
+    }
+}
@@ -0,0 +1,304 @@
+//! JSON text of a `Value`, to log or inspect a message.
+//!
+//! The text is the JSON `serde_json::Value::from` converts a value to, see
+//! the `json` feature, without depending on it: a typed map has its class
+//! name in a `"@type"` entry, a date is an ISO 8601 string in UTC, binary
+//! and unknown bytes are base64 strings and a ref is a `{"@ref": n}`
+//! object. The entries of a map are sorted by key, so the text of a value
+//! doesn't change from a run to the next.
+
+use std::fmt::Write;
+
+use super::{List, Map, Value};
+
+pub(super) const TYPE_KEY: &str = "@type";
+pub(super) const REF_KEY: &str = "@ref";
+
+impl Value {
+    /// The value as compact JSON, e.g.
+    /// `{"@type":"example.Car","color":"red","mileage":65536}`.
+    pub fn to_json_string(&self) -> String {
+        let mut text = Text {
+            out: String::new(),
+            pretty: false,
+            depth: 0,
+        };
+        text.value(self);
+        text.out
+    }
+
+    /// The value as JSON indented by two spaces, one entry or element per
+    /// line.
+    pub fn to_pretty_string(&self) -> String {
+        let mut text = Text {
+            out: String::new(),
+            pretty: true,
+            depth: 0,
+        };
+        text.value(self);
+        text.out
+    }
+}
+
+struct Text {
+    out: String,
+    pretty: bool,
+    // The nesting of the container being written, for the indent
+    depth: usize,
+}
+
+impl Text {
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.out.push_str("null"),
+            Value::Bool(b) => write!(self.out, "{}", b).unwrap(),
+            Value::Int(i) => write!(self.out, "{}", i).unwrap(),
+            Value::Long(l) => write!(self.out, "{}", l).unwrap(),
+            Value::Double(d) if d.is_finite() => write!(self.out, "{:?}", d).unwrap(),
+            Value::Double(_) => self.out.push_str("null"),
+            Value::Date(millis) => self.string(&iso_date(*millis)),
+            Value::Bytes(bytes) => self.string(&base64(bytes)),
+            Value::Unknown(bytes) => self.string(&base64(bytes)),
+            Value::Shared(v) => self.value(v),
+            Value::String(s) => self.string(s),
+            Value::Ref(n) => self.object(vec![(REF_KEY.to_string(), &Value::Int(*n as i32))]),
+            Value::List(List::Typed(_, list)) | Value::List(List::Untyped(list)) => {
+                self.open('[', list.is_empty());
+                for (i, v) in list.iter().enumerate() {
+                    self.separator(i);
+                    self.value(v);
+                }
+                self.close(']', list.is_empty());
+            }
+            Value::Map(map) => {
+                let mut entries: Vec<(String, &Value)> = map
+                    .iter()
+                    .map(|(k, v)| match k {
+                        Value::String(s) => (s.to_string(), v),
+                        k => (k.to_json_string(), v),
+                    })
+                    .collect();
+                let tp;
+                if let Map::Typed(name, _) = map {
+                    tp = Value::from(name.as_str());
+                    entries.push((TYPE_KEY.to_string(), &tp));
+                }
+                self.object(entries);
+            }
+        }
+    }
+
+    fn object(&mut self, mut entries: Vec<(String, &Value)>) {
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        let empty = entries.is_empty();
+        self.open('{', empty);
+        for (i, (k, v)) in entries.into_iter().enumerate() {
+            self.separator(i);
+            self.string(&k);
+            self.out.push_str(if self.pretty { ": " } else { ":" });
+            self.value(v);
+        }
+        self.close('}', empty);
+    }
+
+    fn open(&mut self, bracket: char, empty: bool) {
+        self.out.push(bracket);
+        if !empty {
+            self.depth += 1;
+        }
+    }
+
+    fn close(&mut self, bracket: char, empty: bool) {
+        if !empty {
+            self.depth -= 1;
+            self.newline();
+        }
+        self.out.push(bracket);
+    }
+
+    // Before the `i`th element or entry of a container
+    fn separator(&mut self, i: usize) {
+        if i > 0 {
+            self.out.push(',');
+        }
+        self.newline();
+    }
+
+    fn newline(&mut self) {
+        if self.pretty {
+            self.out.push('\n');
+            for _ in 0..self.depth {
+                self.out.push_str("  ");
+            }
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                '\u{8}' => self.out.push_str("\\b"),
+                '\u{c}' => self.out.push_str("\\f"),
+                c if c < ' ' => write!(self.out, "\\u{:04x}", c as u32).unwrap(),
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+}
+
+// Format milliseconds since the epoch as an ISO 8601 date time in UTC
+pub(super) fn iso_date(millis: i64) -> String {
+    let days = millis.div_euclid(86_400_000);
+    let ms = millis.rem_euclid(86_400_000);
+    // civil from days, http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+pub(super) fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// The bytes of base64 text, with the standard or the URL safe alphabet,
+// padded or not
+pub(super) fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let data = s.trim_end_matches('=');
+    let padding = s.len() - data.len();
+    if data.len() % 4 == 1 || padding > 2 || (padding > 0 && !s.len().is_multiple_of(4)) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in data.bytes() {
+        let sextet = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        n = n << 6 | sextet as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64, decode_base64, iso_date};
+    use crate::value::Value;
+
+    #[test]
+    fn test_to_json_string() {
+        let value = hessian!("example.Car" => {
+            "color": "red\n\"1\"",
+            "mileage": 65536_i64,
+            "price": 1.0,
+            "parts": "[string" => ["wheel", (Value::Bytes(b"hessian".to_vec().into()))],
+            "made": (Value::Date(894621091000)),
+            "prev": (Value::Ref(2)),
+            "empty": [],
+            1: f64::NAN,
+        });
+        assert_eq!(
+            value.to_json_string(),
+            r#"{"1":null,"@type":"example.Car","color":"red\n\"1\"","empty":[],"made":"1998-05-08T09:51:31.000Z","mileage":65536,"parts":["wheel","aGVzc2lhbg=="],"prev":{"@ref":2},"price":1.0}"#
+        );
+        let value = hessian!({ "cars": [{ "color": "red" }], "none": {} });
+        assert_eq!(
+            value.to_pretty_string(),
+            "{\n  \"cars\": [\n    {\n      \"color\": \"red\"\n    }\n  ],\n  \"none\": {}\n}"
+        );
+        assert_eq!(Value::from("\u{1}").to_json_string(), r#""\u0001""#);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_same_as_serde_json() {
+        use serde_json::Value as Json;
+
+        let value = hessian!("example.Car" => {
+            "color": "red",
+            "parts": ["wheel", 1, true, 1.5, null],
+            "owner": { "name": "中文\t" },
+        });
+        let json = Json::from(value.clone());
+        assert_eq!(
+            value.to_json_string(),
+            serde_json::to_string(&json).unwrap()
+        );
+        assert_eq!(
+            value.to_pretty_string(),
+            serde_json::to_string_pretty(&json).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_iso_date() {
+        assert_eq!(iso_date(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(iso_date(-1), "1969-12-31T23:59:59.999Z");
+        assert_eq!(iso_date(951782400000), "2000-02-29T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+
+        for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob", &[0xfb, 0xff]] {
+            assert_eq!(decode_base64(&base64(bytes)).unwrap(), bytes);
+        }
+        assert_eq!(decode_base64("-_8").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode_base64("Zm9v\n"), None);
+        assert_eq!(decode_base64("Z"), None);
+        assert_eq!(decode_base64("Zg="), None);
+        assert_eq!(decode_base64("Zg==="), None);
+    }
+}
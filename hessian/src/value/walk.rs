@@ -0,0 +1,171 @@
+use std::fmt;
+
+use super::merge::push_segment;
+use super::Value;
+
+/// A step from a container to one of its values, see `Path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// The value of this key of a map.
+    Key(Value),
+    /// The element at this index of a list.
+    Index(usize),
+}
+
+/// Where a value is in a tree, the segments from the root to the value.
+///
+/// Displays as a pointer accepted by `Value::pointer`, e.g. `/ctx/items/0`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+impl Path {
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The last segment, `None` for the root.
+    pub fn last(&self) -> Option<&Segment> {
+        self.segments.last()
+    }
+
+    /// The key of the map holding the value, e.g. to redact the values of
+    /// `password` entries.
+    pub fn key(&self) -> Option<&Value> {
+        match self.last() {
+            Some(Segment::Key(key)) => Some(key),
+            _ => None,
+        }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut pointer = String::new();
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Key(key) => push_segment(&mut pointer, key),
+                Segment::Index(i) => push_segment(&mut pointer, &Value::Long(*i as i64)),
+            }
+        }
+        f.write_str(&pointer)
+    }
+}
+
+impl Value {
+    /// Call `f` with every value of the tree and its path, a container
+    /// before its values. Map entries are visited in no particular order.
+    pub fn walk<F: FnMut(&Path, &Value)>(&self, mut f: F) {
+        walk(self, &mut Path::default(), &mut f)
+    }
+
+    /// Replace every value of the tree by what `f` returns for it, e.g. to
+    /// redact secrets or rename the types of maps.
+    ///
+    /// The values of a container are replaced before the container, which
+    /// `f` receives with its new values. Map keys are kept as is.
+    pub fn map_values<F: FnMut(&Path, Value) -> Value>(mut self, mut f: F) -> Value {
+        map_values(&mut self, &mut Path::default(), &mut f);
+        self
+    }
+}
+
+fn walk<F: FnMut(&Path, &Value)>(value: &Value, path: &mut Path, f: &mut F) {
+    f(path, value);
+    match value {
+        Value::Map(m) => {
+            for (k, v) in m.iter() {
+                path.segments.push(Segment::Key(k.clone()));
+                walk(v, path, f);
+                path.segments.pop();
+            }
+        }
+        Value::List(l) => {
+            for (i, v) in l.iter().enumerate() {
+                path.segments.push(Segment::Index(i));
+                walk(v, path, f);
+                path.segments.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn map_values<F: FnMut(&Path, Value) -> Value>(value: &mut Value, path: &mut Path, f: &mut F) {
+    match value {
+        Value::Map(m) => {
+            for (k, v) in m.iter_mut() {
+                path.segments.push(Segment::Key(k.clone()));
+                map_values(v, path, f);
+                path.segments.pop();
+            }
+        }
+        Value::List(l) => {
+            for (i, v) in l.iter_mut().enumerate() {
+                path.segments.push(Segment::Index(i));
+                map_values(v, path, f);
+                path.segments.pop();
+            }
+        }
+        _ => {}
+    }
+    *value = f(path, value.take());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Segment;
+    use crate::value::{Map, Value};
+
+    #[test]
+    fn test_walk() {
+        let v = hessian!({ "user": { "name": "a", "password": "secret" }, "ids": [1, 2] });
+        let mut paths = Vec::new();
+        let mut ints = 0;
+        v.walk(|path, value| {
+            paths.push(path.to_string());
+            if value.is_int() {
+                ints += 1;
+            }
+        });
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                "",
+                "/ids",
+                "/ids/0",
+                "/ids/1",
+                "/user",
+                "/user/name",
+                "/user/password"
+            ]
+        );
+        assert_eq!(ints, 2);
+    }
+
+    #[test]
+    fn test_map_values() {
+        let v = hessian!({ "user": "example.User" => { "name": "a", "password": "secret" }, "ids": [1, 2] });
+        let v = v.map_values(|path, value| match (path.key(), value) {
+            (Some(key), _) if key.as_str() == Some("password") => Value::from("***"),
+            (_, Value::Map(Map::Typed(tp, m))) if tp == "example.User" => {
+                Value::Map(("example.Account", m).into())
+            }
+            (_, Value::Int(i)) => {
+                assert!(matches!(path.last(), Some(Segment::Index(_))));
+                Value::Int(i * 10)
+            }
+            (_, value) => value,
+        });
+        assert_eq!(
+            v,
+            hessian!({ "user": "example.Account" => { "name": "a", "password": "***" }, "ids": [10, 20] })
+        );
+    }
+}
@@ -0,0 +1,230 @@
+import java.io.File;
+import java.io.FileOutputStream;
+import java.io.IOException;
+import java.io.OutputStream;
+import java.util.Arrays;
+
+import com.caucho.hessian.io.Hessian2Output;
+
+/**
+ * Writes the fixtures of the interop tests with com.caucho Hessian. The
+ * checked in fixtures were derived by hand from the same calls, this
+ * checks them against the jar:
+ *
+ * <pre>
+ * javac -cp hessian-4.0.66.jar GenerateFixtures.java
+ * java -cp hessian-4.0.66.jar:. GenerateFixtures fixtures
+ * git diff --stat fixtures
+ * </pre>
+ *
+ * Values are written with the calls serializers make rather than
+ * writeObject, so map entries come in a known order. Keys are sorted like
+ * the canonical output of hessian_rs.
+ */
+public class GenerateFixtures {
+    interface Case {
+        void write(Hessian2Output out) throws IOException;
+    }
+
+    private static File root;
+
+    public static void main(String[] args) throws IOException {
+        root = new File(args.length > 0 ? args[0] : "fixtures");
+
+        fixture("null/null", out -> out.writeNull());
+        fixture("bool/true", out -> out.writeBoolean(true));
+        fixture("bool/false", out -> out.writeBoolean(false));
+
+        int[] ints = {0, -16, 47, 48, -2048, 2047, -262144, 262143, 262144,
+                      Integer.MIN_VALUE, Integer.MAX_VALUE};
+        for (int v : ints) {
+            fixture("int/" + v, out -> out.writeInt(v));
+        }
+
+        long[] longs = {0, -8, 15, 16, -2048, 2047, -262144, 262143, 262144,
+                        Integer.MIN_VALUE, 2147483648L, Long.MIN_VALUE};
+        for (long v : longs) {
+            fixture("long/" + v, out -> out.writeLong(v));
+        }
+
+        fixture("double/0", out -> out.writeDouble(0.0));
+        fixture("double/negative_zero", out -> out.writeDouble(-0.0));
+        fixture("double/1", out -> out.writeDouble(1.0));
+        fixture("double/-128", out -> out.writeDouble(-128.0));
+        fixture("double/127", out -> out.writeDouble(127.0));
+        fixture("double/128", out -> out.writeDouble(128.0));
+        fixture("double/-32768", out -> out.writeDouble(-32768.0));
+        fixture("double/32767", out -> out.writeDouble(32767.0));
+        fixture("double/32768", out -> out.writeDouble(32768.0));
+        fixture("double/12.25", out -> out.writeDouble(12.25));
+        fixture("double/0.001", out -> out.writeDouble(0.001));
+        fixture("double/-1048.571", out -> out.writeDouble(-1048.571));
+        fixture("double/3.14159", out -> out.writeDouble(3.14159));
+        fixture("double/1e300", out -> out.writeDouble(1e300));
+        fixture("double/infinity", out -> out.writeDouble(Double.POSITIVE_INFINITY));
+        fixture("double/nan", out -> out.writeDouble(Double.NaN));
+
+        fixture("date/894621060000", out -> out.writeUTCDate(894621060000L));
+        fixture("date/894621091000", out -> out.writeUTCDate(894621091000L));
+        fixture("date/0", out -> out.writeUTCDate(0));
+        fixture("date/-60000", out -> out.writeUTCDate(-60000));
+
+        fixture("string/empty", out -> out.writeString(""));
+        fixture("string/hello", out -> out.writeString("hello"));
+        fixture("string/u00c3", out -> out.writeString("Ã"));
+        fixture("string/chinese", out -> out.writeString("中文 Chinese"));
+        fixture("string/31", out -> out.writeString(repeat('a', 31)));
+        fixture("string/32", out -> out.writeString(repeat('a', 32)));
+        fixture("string/1023", out -> out.writeString(repeat('a', 1023)));
+        fixture("string/1024", out -> out.writeString(repeat('a', 1024)));
+        fixture("string/emoji", out -> out.writeString("😀"));
+        fixture("string/chunked", out -> out.writeString(repeat('a', 0x8001)));
+        fixture("string/chunked_surrogate",
+                out -> out.writeString(repeat('a', 0x7fff) + "😀b"));
+
+        fixture("binary/empty", out -> out.writeBytes(new byte[0]));
+        fixture("binary/3", out -> out.writeBytes(new byte[] {1, 2, 3}));
+        fixture("binary/15", out -> out.writeBytes(bytes(15)));
+        fixture("binary/16", out -> out.writeBytes(bytes(16)));
+        fixture("binary/2000", out -> out.writeBytes(bytes(2000)));
+
+        fixture("list/untyped_empty", out -> out.writeListBegin(0, null));
+        fixture("list/untyped", out -> {
+            out.writeListBegin(2, null);
+            out.writeInt(1);
+            out.writeInt(2);
+        });
+        fixture("list/untyped_8", out -> {
+            out.writeListBegin(8, null);
+            for (int i = 1; i <= 8; i++) {
+                out.writeInt(i);
+            }
+        });
+        fixture("list/typed", out -> {
+            out.writeListBegin(2, "[int");
+            out.writeInt(0);
+            out.writeInt(1);
+        });
+        fixture("list/typed_8", out -> {
+            out.writeListBegin(8, "[int");
+            for (int i = 1; i <= 8; i++) {
+                out.writeInt(i);
+            }
+        });
+        fixture("list/variable", out -> {
+            out.writeListBegin(-1, null);
+            out.writeInt(1);
+            out.writeListEnd();
+        });
+        fixture("list/typed_variable", out -> {
+            out.writeListBegin(-1, "[int");
+            out.writeInt(1);
+            out.writeListEnd();
+        });
+        fixture("list/nested", out -> {
+            out.writeListBegin(2, null);
+            out.writeListBegin(1, null);
+            out.writeInt(1);
+            out.writeListBegin(0, null);
+        });
+        fixture("list/type_ref", out -> {
+            out.writeListBegin(2, null);
+            out.writeListBegin(1, "[int");
+            out.writeInt(1);
+            out.writeListBegin(1, "[int");
+            out.writeInt(2);
+        });
+
+        fixture("map/empty", out -> {
+            out.writeMapBegin(null);
+            out.writeMapEnd();
+        });
+        fixture("map/untyped", out -> {
+            out.writeMapBegin(null);
+            out.writeInt(1);
+            out.writeString("fee");
+            out.writeInt(16);
+            out.writeString("fie");
+            out.writeInt(256);
+            out.writeString("foe");
+            out.writeMapEnd();
+        });
+        fixture("map/typed", out -> {
+            out.writeMapBegin("com.caucho.test.Car");
+            out.writeString("color");
+            out.writeString("aquamarine");
+            out.writeString("mileage");
+            out.writeInt(65536);
+            out.writeString("model");
+            out.writeString("Beetle");
+            out.writeMapEnd();
+        });
+        fixture("map/nested", out -> {
+            out.writeMapBegin(null);
+            out.writeString("foo");
+            out.writeMapBegin(null);
+            out.writeString("bar");
+            out.writeInt(1);
+            out.writeMapEnd();
+            out.writeMapEnd();
+        });
+        fixture("map/type_ref", out -> {
+            out.writeListBegin(2, null);
+            out.writeMapBegin("example.Car");
+            out.writeMapEnd();
+            out.writeMapBegin("example.Car");
+            out.writeMapEnd();
+        });
+
+        fixture("object/car", out -> car(out, "red", "corvette"));
+        fixture("object/two_cars", out -> {
+            out.writeListBegin(2, null);
+            car(out, "red", "corvette");
+            car(out, "green", "civic");
+        });
+        // the same instance twice, the list takes the ref 0
+        fixture("object/shared", out -> {
+            out.writeListBegin(2, null);
+            car(out, "red", "corvette");
+            out.writeRef(1);
+        });
+    }
+
+    // What JavaSerializer writes for an instance of example.Car
+    private static void car(Hessian2Output out, String color, String model)
+            throws IOException {
+        int ref = out.writeObjectBegin("example.Car");
+        if (ref == -1) {
+            out.writeInt(2);
+            out.writeString("color");
+            out.writeString("model");
+            out.writeObjectBegin("example.Car");
+        }
+        out.writeString(color);
+        out.writeString(model);
+    }
+
+    private static String repeat(char c, int n) {
+        char[] chars = new char[n];
+        Arrays.fill(chars, c);
+        return new String(chars);
+    }
+
+    private static byte[] bytes(int n) {
+        byte[] bytes = new byte[n];
+        for (int i = 0; i < n; i++) {
+            bytes[i] = (byte) i;
+        }
+        return bytes;
+    }
+
+    private static void fixture(String name, Case c) throws IOException {
+        File file = new File(root, name + ".bin");
+        file.getParentFile().mkdirs();
+        try (OutputStream os = new FileOutputStream(file)) {
+            Hessian2Output out = new Hessian2Output(os);
+            c.write(out);
+            out.close();
+        }
+    }
+}
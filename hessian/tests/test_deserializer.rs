@@ -42,15 +42,15 @@ fn test_decode_date() {
 fn test_decode_string() {
     assert_eq!(
         load_value_from_file("tests/fixtures/string/empty.bin").unwrap(),
-        Value::String("".to_string())
+        Value::String("".into())
     );
     assert_eq!(
         load_value_from_file("tests/fixtures/string/foo.bin").unwrap(),
-        Value::String("foo".to_string())
+        Value::String("foo".into())
     );
     assert_eq!(
         load_value_from_file("tests/fixtures/string/chinese.bin").unwrap(),
-        Value::String("中文 Chinese".to_string())
+        Value::String("中文 Chinese".into())
     );
 }
 
@@ -1,111 +1,275 @@
-use std::collections::HashMap;
+//! Roundtrip properties over generated values.
+//!
+//! The strategies are biased towards the boundaries of the compact
+//! encodings. `PROPTEST_CASES` runs more cases than the default, failing
+//! cases are shrunk and saved to be run first the next time.
 
 use hessian_rs::{
     de::Deserializer,
     ser::Serializer,
-    Value::{self, *},
+    value::{List, Map},
+    Value,
 };
+use proptest::collection::{hash_map, vec};
+use proptest::prelude::*;
+use proptest::sample::{select, Index};
 
-fn roundtrip_test(val: Value) {
+fn roundtrip_test(val: &Value) -> Result<(), TestCaseError> {
     let mut encoded = Vec::new();
     let mut ser = Serializer::new(&mut encoded);
-    ser.serialize_value(&val)
-        .unwrap_or_else(|_| panic!("serialization failed for {:?}", val));
+    ser.serialize_value(val)
+        .map_err(|e| TestCaseError::fail(format!("serialization failed: {:?}", e)))?;
     let mut de = Deserializer::new(&encoded);
     let decoded = de
         .read_value()
-        .unwrap_or_else(|_| panic!("deserialization failed for {:?}", val));
-    assert_eq!(decoded, val);
-}
-
-#[test]
-fn test_int_roundtrip() {
-    roundtrip_test(Int(0));
-    roundtrip_test(Int(-16));
-    roundtrip_test(Int(47));
-    roundtrip_test(Int(48));
-    roundtrip_test(Int(-2048));
-    roundtrip_test(Int(-256));
-    roundtrip_test(Int(2047));
-    roundtrip_test(Int(-262144));
-    roundtrip_test(Int(262143));
-    roundtrip_test(Int(262144));
-}
-
-#[test]
-fn test_long_roundtrip() {
-    roundtrip_test(Long(0));
-    roundtrip_test(Long(-16));
-    roundtrip_test(Long(47));
-    roundtrip_test(Long(48));
-    roundtrip_test(Long(-2048));
-    roundtrip_test(Long(-256));
-    roundtrip_test(Long(2047));
-    roundtrip_test(Long(-262144));
-    roundtrip_test(Long(262143));
-    roundtrip_test(Long(262144));
-}
-
-#[test]
-fn test_double_roundtrip() {
-    roundtrip_test(Double(0.0));
-    roundtrip_test(Double(1.0));
-    roundtrip_test(Double(-128.0));
-    roundtrip_test(Double(10.0));
-    roundtrip_test(Double(127.0));
-    roundtrip_test(Double(-32768.0));
-    roundtrip_test(Double(32767.0));
-    roundtrip_test(Double(32766.0));
-    roundtrip_test(Double(12.25));
-    roundtrip_test(Double(32767.99999));
-}
-
-#[test]
-fn test_date_roundtrip() {
-    roundtrip_test(Date(894621091000));
-}
-
-#[test]
-fn test_string_roundtrip() {
-    roundtrip_test(String("".to_string()));
-    roundtrip_test(String("abc".to_string()));
-    roundtrip_test(String("中文 Chinese".to_string()));
-    roundtrip_test(String("abcdefghijklmnopqrstuvwxyz".to_string()));
-    roundtrip_test(String("abcdefghij".repeat(120)));
-    roundtrip_test(String("abcdefghij".repeat(1000)));
-}
-
-#[test]
-fn test_list_roundtrip() {
-    roundtrip_test(List(vec![Int(1), Int(2)].into()));
-    roundtrip_test(List(
-        vec![
-            String("".to_string()),
-            String("abc".to_string()),
-            String("中文".to_string()),
-        ]
-        .into(),
-    ));
-    roundtrip_test(List(vec![Int(1); 13].into()));
-    roundtrip_test(List(vec![String("test".to_string()); 1000].into()));
-}
-
-#[test]
-fn test_map_roundtrip() {
-    let mut map = HashMap::new();
-    map.insert(Value::Int(1), Value::String("fee".to_string()));
-    map.insert(Value::Int(16), Value::String("fie".to_string()));
-    map.insert(Value::Int(256), Value::String("foe".to_string()));
-    roundtrip_test(Map(map.into()));
-    let mut car_map = HashMap::new();
-    car_map.insert(
-        Value::String("color".to_string()),
-        Value::String("aquamarine".to_string()),
-    );
-    car_map.insert(
-        Value::String("model".to_string()),
-        Value::String("Beetle".to_string()),
-    );
-    car_map.insert(Value::String("mileage".to_string()), Value::Int(65536));
-    roundtrip_test(Map(("com.caucho.test.Car", car_map).into()));
+        .map_err(|e| TestCaseError::fail(format!("deserialization failed: {:?}", e)))?;
+    prop_assert_eq!(&decoded, val);
+    prop_assert_eq!(de.position() as usize, encoded.len(), "trailing bytes");
+    #[cfg(feature = "arena")]
+    {
+        let bump = hessian_rs::value::Bump::new();
+        let arena = hessian_rs::from_slice_in(&encoded, &bump).map_err(|e| {
+            TestCaseError::fail(format!("deserialization into an arena failed: {:?}", e))
+        })?;
+        prop_assert_eq!(&Value::from(arena), val);
+    }
+    Ok(())
+}
+
+// A length around the chunk and compact length limits
+fn len() -> impl Strategy<Value = usize> {
+    prop_oneof![
+        8 => select(&[31, 32, 33, 255, 256, 1023, 1024, 4095, 4096][..]),
+        1 => select(&[0x7fff, 0x8000, 0x8001, 70000][..]),
+        55 => 0..40_usize,
+    ]
+}
+
+fn int() -> impl Strategy<Value = i32> {
+    const EDGES: &[i32] = &[
+        0,
+        -16,
+        47,
+        48,
+        -2048,
+        2047,
+        -262_144,
+        262_143,
+        -2049,
+        2048,
+        -262_145,
+        262_144,
+        i32::MIN,
+        i32::MAX,
+    ];
+    prop_oneof![
+        select(EDGES),
+        (select(EDGES), -1..=1).prop_map(|(edge, d)| edge.wrapping_add(d)),
+        (any::<i32>(), 0..32_u32).prop_map(|(i, shift)| i >> shift),
+    ]
+}
+
+fn long() -> impl Strategy<Value = i64> {
+    const EDGES: &[i64] = &[
+        -8,
+        15,
+        -2048,
+        2047,
+        -262_144,
+        262_143,
+        i32::MIN as i64,
+        i32::MAX as i64,
+        i64::MIN,
+        i64::MAX,
+    ];
+    prop_oneof![
+        select(EDGES),
+        (select(EDGES), -1..=1_i64).prop_map(|(edge, d)| edge.wrapping_add(d)),
+        (any::<i64>(), 0..64_u32).prop_map(|(l, shift)| l >> shift),
+    ]
+}
+
+// Any double but NaN, which isn't equal to itself
+fn double() -> impl Strategy<Value = f64> {
+    const EDGES: &[f64] = &[
+        0.0,
+        -0.0,
+        1.0,
+        -128.0,
+        127.0,
+        128.0,
+        -32768.0,
+        32767.0,
+        32768.0,
+        12.25,
+        32767.99999,
+        0.001,
+        f64::MIN,
+        f64::MAX,
+        f64::MIN_POSITIVE,
+        f64::EPSILON,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        5e-324,
+    ];
+    prop_oneof![
+        select(EDGES),
+        int().prop_map(f64::from),
+        int().prop_map(|i| f64::from(i) / 1000.0),
+        any::<f64>().prop_filter("NaN", |d| !d.is_nan()),
+    ]
+}
+
+fn date() -> impl Strategy<Value = i64> {
+    prop_oneof![
+        // whole minutes are written in the compact form
+        any::<i32>().prop_map(|minutes| i64::from(minutes) * 60_000),
+        long(),
+    ]
+}
+
+fn char() -> impl Strategy<Value = char> {
+    prop_oneof![
+        1 => select(&['中', '文', 'é', '\u{7ff}', '\u{800}', '\u{ffff}'][..]),
+        // outside the BMP, java writes these as surrogate pairs
+        1 => select(&['😀', '\u{10000}', '\u{10ffff}'][..]),
+        1 => select(&['\0', '\u{7f}', '\u{80}'][..]),
+        3 => proptest::char::range('a', 'z'),
+    ]
+}
+
+// Long strings repeat a few chars, generating every one of them is slow
+fn string() -> impl Strategy<Value = String> {
+    (len(), vec(char(), 1..40))
+        .prop_map(|(len, chars)| chars.into_iter().cycle().take(len).collect())
+}
+
+fn bytes() -> impl Strategy<Value = Vec<u8>> {
+    len().prop_flat_map(|len| vec(any::<u8>(), len))
+}
+
+fn class() -> impl Strategy<Value = String> {
+    select(&["example.Car", "[int", "java.util.ArrayList", "中文.类"][..]).prop_map(String::from)
+}
+
+fn scalar() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        1 => Just(Value::Null),
+        1 => any::<bool>().prop_map(Value::Bool),
+        1 => int().prop_map(Value::Int),
+        1 => long().prop_map(Value::Long),
+        1 => double().prop_map(Value::Double),
+        1 => date().prop_map(Value::Date),
+        1 => bytes().prop_map(|b| Value::Bytes(b.into())),
+        2 => string().prop_map(|s| Value::String(s.into())),
+    ]
+}
+
+// A map key, doubles are left out as `0.0` and `-0.0` are the same key
+fn key() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        1 => int().prop_map(Value::Int),
+        1 => long().prop_map(Value::Long),
+        2 => string().prop_map(|s| Value::String(s.into())),
+    ]
+}
+
+fn list(values: impl Strategy<Value = Value>) -> impl Strategy<Value = Value> {
+    (vec(values, 0..12), proptest::option::of(class())).prop_map(|(values, tp)| {
+        Value::List(match tp {
+            Some(tp) => List::Typed(tp, values),
+            None => List::Untyped(values),
+        })
+    })
+}
+
+fn map(values: impl Strategy<Value = Value>) -> impl Strategy<Value = Value> {
+    (hash_map(key(), values, 0..8), proptest::option::of(class())).prop_map(|(entries, tp)| {
+        Value::Map(match tp {
+            Some(tp) => Map::Typed(tp, entries),
+            None => Map::Untyped(entries),
+        })
+    })
+}
+
+/// A value nested at most `depth` containers deep.
+fn value(depth: u32) -> impl Strategy<Value = Value> {
+    scalar().prop_recursive(depth, 256, 12, |inner| {
+        prop_oneof![list(inner.clone()), map(inner)]
+    })
+}
+
+// Every strict prefix of the encoding of `val` fails as an eof
+fn truncated_test(val: &Value, cuts: &[Index]) -> Result<(), TestCaseError> {
+    let encoded = hessian_rs::to_vec(val).unwrap();
+    for cut in cuts {
+        let truncated = &encoded[..cut.index(encoded.len())];
+        match Deserializer::new(truncated).read_value() {
+            Err(e) => prop_assert!(e.is_eof(), "{} at {}", e, truncated.len()),
+            Ok(v) => prop_assert!(false, "{:?} read from {} bytes", v, truncated.len()),
+        }
+        if let Err(e) = Deserializer::new(truncated).skip_value() {
+            prop_assert!(e.is_eof(), "skipping: {} at {}", e, truncated.len());
+        }
+        #[cfg(feature = "arena")]
+        {
+            let bump = hessian_rs::value::Bump::new();
+            if let Err(e) = hessian_rs::from_slice_in(truncated, &bump) {
+                prop_assert!(e.is_eof(), "into an arena: {} at {}", e, truncated.len());
+            }
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn test_int_roundtrip(i in int()) {
+        roundtrip_test(&Value::Int(i))?;
+    }
+
+    #[test]
+    fn test_long_roundtrip(l in long()) {
+        roundtrip_test(&Value::Long(l))?;
+    }
+
+    #[test]
+    fn test_double_roundtrip(d in double()) {
+        roundtrip_test(&Value::Double(d))?;
+    }
+
+    #[test]
+    fn test_date_roundtrip(d in date()) {
+        roundtrip_test(&Value::Date(d))?;
+    }
+
+    #[test]
+    fn test_string_roundtrip(s in string()) {
+        roundtrip_test(&Value::String(s.into()))?;
+    }
+
+    #[test]
+    fn test_bytes_roundtrip(b in bytes()) {
+        roundtrip_test(&Value::Bytes(b.into()))?;
+    }
+
+    #[test]
+    fn test_list_roundtrip(v in list(scalar())) {
+        roundtrip_test(&v)?;
+    }
+
+    #[test]
+    fn test_map_roundtrip(v in map(scalar())) {
+        roundtrip_test(&v)?;
+    }
+
+    #[test]
+    fn test_nested_roundtrip(v in value(6)) {
+        roundtrip_test(&v)?;
+    }
+
+    #[test]
+    fn test_truncated_is_eof(v in value(4), cuts in vec(any::<Index>(), 8)) {
+        truncated_test(&v, &cuts)?;
+    }
 }
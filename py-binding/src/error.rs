@@ -0,0 +1,38 @@
+// the exceptions generated by pyo3 0.18 trip this lint on recent compilers
+#![allow(unexpected_cfgs)]
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+create_exception!(
+    hessian_codec,
+    HessianError,
+    PyValueError,
+    "Base class of the errors raised when a value can't be decoded or encoded."
+);
+create_exception!(
+    hessian_codec,
+    HessianDecodeError,
+    HessianError,
+    "Raised when the input isn't valid hessian, `position` is the offset \
+     in the input where decoding stopped."
+);
+create_exception!(
+    hessian_codec,
+    HessianEncodeError,
+    HessianError,
+    "Raised when a value can't be encoded."
+);
+
+pub(crate) fn decode_err(py: Python, e: hessian_rs::Error, position: u64) -> PyErr {
+    let err = HessianDecodeError::new_err(format!("{} at offset {}", e, position));
+    if let Err(e) = err.value(py).setattr("position", position) {
+        return e;
+    }
+    err
+}
+
+pub(crate) fn encode_err(msg: String) -> PyErr {
+    HessianEncodeError::new_err(msg)
+}
@@ -1,37 +1,82 @@
-use hessian_rs::ser::Serializer;
-use hessian_rs::value::Definition;
+use hessian_rs::de::Deserializer;
+use hessian_rs::ser::{MapWriter, Serializer};
+use hessian_rs::value::{Definition, List, Map};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::PyRuntimeError;
 use pyo3::exceptions::PyTypeError;
 use pyo3::exceptions::PyValueError;
+use pyo3::once_cell::GILOnceCell;
 use pyo3::prelude::*;
 use pyo3::PyErr;
 use std::io::Write;
 
+mod error;
+mod object;
+mod stream;
+
+use error::{decode_err, encode_err, HessianDecodeError, HessianEncodeError, HessianError};
+use object::{HessianObject, TypedDict, TypedList};
+use stream::{LoadIter, PyHessianDeserializer, StreamDeserializer};
+
 use pyo3::types::timezone_utc;
+use pyo3::types::IntoPyDict;
 use pyo3::types::PyBool;
+use pyo3::types::PyByteArray;
 use pyo3::types::PyBytes;
+use pyo3::types::PyDate;
 use pyo3::types::PyDateTime;
+use pyo3::types::PyDelta;
 use pyo3::types::PyDict;
 use pyo3::types::PyFloat;
 use pyo3::types::PyInt;
 use pyo3::types::PyList;
 use pyo3::types::PyString;
+use pyo3::types::PyTimeAccess;
 use pyo3::types::PyTuple;
+use pyo3::types::PyType;
+use pyo3::types::PyTzInfoAccess;
 
 #[pymodule]
-fn hessian_codec(_py: Python, m: &PyModule) -> PyResult<()> {
+fn hessian_codec(py: Python, m: &PyModule) -> PyResult<()> {
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
 
+    m.add("HessianError", py.get_type::<HessianError>())?;
+    m.add("HessianDecodeError", py.get_type::<HessianDecodeError>())?;
+    m.add("HessianEncodeError", py.get_type::<HessianEncodeError>())?;
+
     m.add_wrapped(wrap_pyfunction!(load))?;
+    m.add_wrapped(wrap_pyfunction!(load_iter))?;
     m.add_wrapped(wrap_pyfunction!(loads))?;
 
     m.add_wrapped(wrap_pyfunction!(dump))?;
     m.add_wrapped(wrap_pyfunction!(dumps))?;
+    m.add_wrapped(wrap_pyfunction!(dump_into))?;
+
+    m.add_wrapped(wrap_pyfunction!(register_class))?;
+    m.add_class::<HessianObject>()?;
+    m.add_class::<TypedList>()?;
+    m.add_class::<TypedDict>()?;
+    m.add_class::<LoadIter>()?;
+    m.add_class::<PyHessianSerializer>()?;
+    m.add_class::<PyHessianDeserializer>()?;
 
     Ok(())
 }
 
+/// Read the first value of a binary file, the file is read in chunks until
+/// the value is complete.
 #[pyfunction]
-pub fn load(py: Python, fp: PyObject, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+pub fn load(
+    py: Python,
+    fp: PyObject,
+    object_hook: Option<PyObject>,
+    preserve_class: Option<bool>,
+    dates: Option<&str>,
+    tzinfo: Option<PyObject>,
+    _kwargs: Option<&PyDict>,
+) -> PyResult<PyObject> {
+    let options = DecodeOptions::new(object_hook, preserve_class, dates, tzinfo)?;
+
     // Temporary workaround for
     // https://github.com/PyO3/pyo3/issues/145
     let io: &PyAny = fp.extract(py)?;
@@ -41,83 +86,289 @@ pub fn load(py: Python, fp: PyObject, kwargs: Option<&PyDict>) -> PyResult<PyObj
     // value, because `seek` does not strictly need to exist on the object
     let _success = io.call_method("seek", (0,), None);
 
-    let s_obj = io.call_method0("read")?;
-    loads(py, s_obj.to_object(py), None, None, None, kwargs)
+    let mut de = StreamDeserializer::new();
+    match de.read_from(io)? {
+        Some(value) => Decoder::new(py, &options).decode(value),
+        None => Err(decode_err(
+            py,
+            hessian_rs::Error::IoError(std::io::ErrorKind::UnexpectedEof.into()),
+            0,
+        )),
+    }
 }
 
+/// Iterate over the successive values of a binary stream, which is read in
+/// chunks as the values are consumed.
 #[pyfunction]
+pub fn load_iter(
+    fp: PyObject,
+    object_hook: Option<PyObject>,
+    preserve_class: Option<bool>,
+    dates: Option<&str>,
+    tzinfo: Option<PyObject>,
+) -> PyResult<LoadIter> {
+    let options = DecodeOptions::new(object_hook, preserve_class, dates, tzinfo)?;
+    Ok(LoadIter::new(fp, options))
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
 pub fn loads(
     py: Python,
     s: PyObject,
     encoding: Option<PyObject>,
     cls: Option<PyObject>,
     object_hook: Option<PyObject>,
+    preserve_class: Option<bool>,
+    dates: Option<&str>,
+    tzinfo: Option<PyObject>,
     kwargs: Option<&PyDict>,
 ) -> PyResult<PyObject> {
-    loads_impl(py, s, encoding, cls, object_hook, kwargs)
+    let options = DecodeOptions::new(object_hook, preserve_class, dates, tzinfo)?;
+    loads_impl(py, s, encoding, cls, &options, kwargs)
 }
 
-pub fn loads_impl(
+fn loads_impl(
     py: Python,
     s: PyObject,
     _encoding: Option<PyObject>,
     _cls: Option<PyObject>,
-    _object_hook: Option<PyObject>,
+    options: &DecodeOptions,
     _kwargs: Option<&PyDict>,
 ) -> PyResult<PyObject> {
-    let bytes: Vec<u8> = s.extract(py).map_err(|e| {
+    // the bytes are parsed to a `Value` without the GIL when they're large,
+    // python objects are only built afterwards
+    let value = with_buffer(py, s.as_ref(py), |bytes| {
+        let mut de = Deserializer::new(bytes);
+        de.read_value().map_err(|e| (e, de.position()))
+    })?
+    .map_err(|(e, position)| decode_err(py, e, position))?;
+
+    Decoder::new(py, options).decode(value)
+}
+
+// Inputs from which the GIL is released while they're read
+const RELEASE_GIL_LEN: usize = 16 * 1024;
+
+// Call `f` with the bytes of an object implementing the buffer protocol,
+// they are only copied when the buffer isn't contiguous. The GIL is
+// released during `f` when the bytes are large and can't be written by
+// other threads meanwhile, i.e. they are `bytes` or a copy.
+fn with_buffer<T, F>(py: Python, obj: &PyAny, f: F) -> PyResult<T>
+where
+    T: Send,
+    F: FnOnce(&[u8]) -> T + Send,
+{
+    let release_gil = |bytes: &[u8], f: F| {
+        if bytes.len() >= RELEASE_GIL_LEN {
+            py.allow_threads(|| f(bytes))
+        } else {
+            f(bytes)
+        }
+    };
+    if let Ok(bytes) = obj.downcast::<PyBytes>() {
+        return Ok(release_gil(bytes.as_bytes(), f));
+    }
+    let buf = PyBuffer::<u8>::get(obj).map_err(|e| {
         PyTypeError::new_err(format!(
-            "the hessian object must be bytes or bytearray, got: {:?}",
+            "the hessian object must be a bytes-like object, got: {:?}",
             e
         ))
     })?;
+    if !buf.is_c_contiguous() {
+        return Ok(release_gil(&buf.to_vec(py)?, f));
+    }
+    if buf.len_bytes() == 0 {
+        return Ok(f(&[]));
+    }
+    // SAFETY: the buffer is contiguous and stays exported while `f` runs,
+    // the GIL is held and `f` doesn't run python code so it isn't written
+    // meanwhile
+    let bytes = unsafe { std::slice::from_raw_parts(buf.buf_ptr() as *const u8, buf.len_bytes()) };
+    Ok(f(bytes))
+}
+
+static CLASSES: GILOnceCell<Py<PyDict>> = GILOnceCell::new();
+static CLASS_NAMES: GILOnceCell<Py<PyDict>> = GILOnceCell::new();
+
+// The classes registered with `register_class`, by class name
+fn classes(py: Python<'_>) -> &PyDict {
+    CLASSES
+        .get_or_init(py, || PyDict::new(py).into())
+        .as_ref(py)
+}
+
+// The class names registered with `register_class`, by class
+fn class_names(py: Python<'_>) -> &PyDict {
+    CLASS_NAMES
+        .get_or_init(py, || PyDict::new(py).into())
+        .as_ref(py)
+}
+
+/// Decode the typed maps and objects named `class_name` by calling `cls`
+/// with their fields as keyword arguments. It takes precedence over the
+/// `object_hook` of `loads`. Dataclass instances of `cls` are dumped as
+/// objects named `class_name`.
+#[pyfunction]
+pub fn register_class(py: Python, class_name: &str, cls: PyObject) -> PyResult<()> {
+    if !cls.as_ref(py).is_callable() {
+        return Err(PyTypeError::new_err(format!(
+            "the class of {} must be callable",
+            class_name
+        )));
+    }
+    if cls.as_ref(py).is_instance_of::<PyType>()? {
+        class_names(py).set_item(&cls, class_name)?;
+    }
+    classes(py).set_item(class_name, cls)
+}
+
+// How dates are decoded
+enum Dates {
+    // datetimes in `tzinfo`, UTC by default
+    Aware,
+    // datetimes in UTC without tzinfo
+    Naive,
+    // milliseconds since the epoch
+    Millis,
+}
 
-    let value = hessian_rs::from_slice(&bytes)
-        .map_err(|e| PyTypeError::new_err(format!("Parse hessian error: {:?}", e)))?;
+// The options shared by `loads`, `load`, `load_iter` and `PyHessianDeserializer`
+struct DecodeOptions {
+    object_hook: Option<PyObject>,
+    preserve_class: bool,
+    dates: Dates,
+    tzinfo: Option<PyObject>,
+}
+
+impl DecodeOptions {
+    fn new(
+        object_hook: Option<PyObject>,
+        preserve_class: Option<bool>,
+        dates: Option<&str>,
+        tzinfo: Option<PyObject>,
+    ) -> PyResult<Self> {
+        let dates = match dates {
+            None | Some("aware") => Dates::Aware,
+            Some("naive") => Dates::Naive,
+            Some("millis") => Dates::Millis,
+            Some(dates) => {
+                return Err(PyValueError::new_err(format!(
+                    "dates must be \"aware\", \"naive\" or \"millis\", got {:?}",
+                    dates
+                )))
+            }
+        };
+        Ok(DecodeOptions {
+            object_hook,
+            preserve_class: preserve_class.unwrap_or(false),
+            dates,
+            tzinfo,
+        })
+    }
+}
 
-    Ok(HessianValueWrapper(value).to_object(py))
+// Converts decoded values to python objects. A typed map or object is
+// passed to its registered class, or to `object_hook(class_name, dict)`,
+// otherwise it's a `HessianObject` with `preserve_class` or a plain dict.
+struct Decoder<'py> {
+    py: Python<'py>,
+    classes: &'py PyDict,
+    options: &'py DecodeOptions,
 }
 
-struct HessianValueWrapper(hessian_rs::Value);
+impl<'py> Decoder<'py> {
+    fn new(py: Python<'py>, options: &'py DecodeOptions) -> Self {
+        Decoder {
+            py,
+            classes: classes(py),
+            options,
+        }
+    }
 
-impl ToPyObject for HessianValueWrapper {
-    fn to_object(&self, py: Python<'_>) -> PyObject {
-        match &self.0 {
+    fn decode(&self, value: hessian_rs::Value) -> PyResult<PyObject> {
+        let py = self.py;
+        Ok(match value {
             hessian_rs::Value::Null => py.None(),
             hessian_rs::Value::Bool(b) => b.to_object(py),
             hessian_rs::Value::Int(i) => i.to_object(py),
             hessian_rs::Value::Long(l) => l.to_object(py),
             hessian_rs::Value::Double(d) => d.to_object(py),
-            hessian_rs::Value::Date(d) => {
-                PyDateTime::from_timestamp(py, (*d as f64) / 1000.0, Some(timezone_utc(py)))
-                    .unwrap()
-                    .to_object(py)
-            }
+            hessian_rs::Value::Date(d) => self.decode_date(d)?,
             hessian_rs::Value::String(s) => s.to_object(py),
-            hessian_rs::Value::Bytes(b) => PyBytes::new(py, b).to_object(py),
-            hessian_rs::Value::List(l) => l
-                .value()
-                .iter()
-                .map(|v| HessianValueWrapper(v.clone()).to_object(py))
-                .collect::<Vec<_>>()
-                .to_object(py),
+            hessian_rs::Value::Bytes(b) => PyBytes::new(py, &b).to_object(py),
+            hessian_rs::Value::List(List::Typed(_, l))
+            | hessian_rs::Value::List(List::Untyped(l)) => {
+                let items = l
+                    .into_iter()
+                    .map(|v| self.decode(v))
+                    .collect::<PyResult<Vec<_>>>()?;
+                PyList::new(py, items).to_object(py)
+            }
             hessian_rs::Value::Map(m) => {
+                let (class_name, entries) = match m {
+                    Map::Typed(name, entries) => (Some(name), entries),
+                    Map::Untyped(entries) => (None, entries),
+                };
                 let dict = PyDict::new(py);
-                for (k, v) in m.value().iter() {
-                    dict.set_item(
-                        HessianValueWrapper(k.clone()).to_object(py),
-                        HessianValueWrapper(v.clone()).to_object(py),
-                    )
-                    .unwrap();
+                for (k, v) in entries {
+                    dict.set_item(self.decode(k)?, self.decode(v)?)?;
+                }
+                match class_name {
+                    Some(name) => self.decode_object(&name, dict)?,
+                    None => dict.to_object(py),
                 }
-                dict.to_object(py)
             }
             _ => py.None(),
+        })
+    }
+
+    fn decode_date(&self, millis: i64) -> PyResult<PyObject> {
+        let py = self.py;
+        if let Dates::Millis = self.options.dates {
+            return Ok(millis.to_object(py));
+        }
+        // added to the epoch rather than going through a float timestamp
+        let delta = PyDelta::new(
+            py,
+            millis.div_euclid(86_400_000) as i32,
+            (millis.rem_euclid(86_400_000) / 1000) as i32,
+            (millis.rem_euclid(1000) * 1000) as i32,
+            false,
+        )?;
+        let epoch = PyDateTime::new(py, 1970, 1, 1, 0, 0, 0, 0, Some(timezone_utc(py)))?;
+        let utc = epoch.call_method1("__add__", (delta,))?;
+        let date = match (&self.options.dates, &self.options.tzinfo) {
+            (Dates::Naive, _) => {
+                let kwargs = [("tzinfo", py.None())].into_py_dict(py);
+                utc.call_method("replace", (), Some(kwargs))?
+            }
+            (_, Some(tzinfo)) => utc.call_method1("astimezone", (tzinfo,))?,
+            (_, None) => utc,
+        };
+        Ok(date.to_object(py))
+    }
+
+    fn decode_object(&self, class_name: &str, dict: &PyDict) -> PyResult<PyObject> {
+        if let Some(cls) = self.classes.get_item(class_name) {
+            return Ok(cls.call((), Some(dict))?.to_object(self.py));
+        }
+        match &self.options.object_hook {
+            Some(hook) => Ok(hook.call1(self.py, (class_name, dict))?),
+            None if self.options.preserve_class => {
+                let object = HessianObject {
+                    class_name: class_name.to_string(),
+                    fields: dict.into(),
+                };
+                Ok(Py::new(self.py, object)?.to_object(self.py))
+            }
+            None => Ok(dict.to_object(self.py)),
         }
     }
 }
 
 #[pyfunction]
+#[allow(clippy::too_many_arguments)]
 pub fn dump(
     py: Python,
     obj: PyObject,
@@ -125,9 +376,10 @@ pub fn dump(
     allow_nan: Option<PyObject>,
     cls: Option<PyObject>,
     default: Option<PyObject>,
+    sort_keys: Option<PyObject>,
     kwargs: Option<&PyDict>,
 ) -> PyResult<PyObject> {
-    let s = dumps(py, obj, allow_nan, cls, default, kwargs)?;
+    let s = dumps(py, obj, allow_nan, cls, default, sort_keys, kwargs)?;
     let fp_ref: &PyAny = fp.extract(py)?;
     fp_ref.call_method1("write", (s,))?;
     Ok(pyo3::Python::None(py))
@@ -137,104 +389,342 @@ pub fn dump(
 pub fn dumps(
     py: Python,
     obj: PyObject,
-    _allow_nan: Option<PyObject>,
-    _cls: Option<PyObject>,
-    _default: Option<PyObject>,
+    allow_nan: Option<PyObject>,
+    cls: Option<PyObject>,
+    default: Option<PyObject>,
+    sort_keys: Option<PyObject>,
     _kwargs: Option<&PyDict>,
 ) -> PyResult<PyObject> {
-    let mut buf = Vec::new();
-    let mut ser = hessian_rs::ser::Serializer::new(&mut buf);
-    dump_value(obj.extract(py)?, &mut ser)?;
+    let buf = dumps_impl(py, obj, allow_nan, cls, default, sort_keys)?;
     Ok(PyBytes::new(py, &buf).into())
 }
 
+/// Append the encoding of `obj` to the bytearray `buf`, returns the number of
+/// bytes appended.
+#[pyfunction]
+pub fn dump_into(
+    py: Python,
+    obj: PyObject,
+    buf: &PyByteArray,
+    allow_nan: Option<PyObject>,
+    cls: Option<PyObject>,
+    default: Option<PyObject>,
+    sort_keys: Option<PyObject>,
+) -> PyResult<usize> {
+    let bytes = dumps_impl(py, obj, allow_nan, cls, default, sort_keys)?;
+    let start = buf.len();
+    buf.resize(start + bytes.len())?;
+    // SAFETY: no python code runs while the bytearray is borrowed
+    unsafe { buf.as_bytes_mut()[start..].copy_from_slice(&bytes) };
+    Ok(bytes.len())
+}
+
+fn dumps_impl(
+    py: Python,
+    obj: PyObject,
+    allow_nan: Option<PyObject>,
+    cls: Option<PyObject>,
+    default: Option<PyObject>,
+    sort_keys: Option<PyObject>,
+) -> PyResult<Vec<u8>> {
+    let allow_nan = match allow_nan {
+        Some(v) => v.extract::<bool>(py)?,
+        None => true,
+    };
+    let sort_keys = match sort_keys {
+        Some(v) => v.extract::<bool>(py)?,
+        None => false,
+    };
+    // unlike `loads`, the GIL is held throughout: objects are read while
+    // they're written, and going through a `Value` would lose the
+    // difference between objects and typed maps
+    let mut buf = Vec::new();
+    let mut ser = hessian_rs::ser::Serializer::new(&mut buf)
+        .allow_nan(allow_nan)
+        .canonical(sort_keys);
+    // like `json.JSONEncoder`, an instance of `cls` provides `default`
+    let default = match (default, cls) {
+        (Some(default), _) => Some(default.into_ref(py)),
+        (None, Some(cls)) => Some(cls.call0(py)?.into_ref(py).getattr("default")?),
+        (None, None) => None,
+    };
+    dump_value(obj.extract(py)?, &mut ser, default)?;
+    Ok(buf)
+}
+
 fn convert_err(e: hessian_rs::Error) -> PyErr {
-    PyErr::new::<PyValueError, _>(format!("Cannot serialize value: {:?}", e))
+    encode_err(format!("Cannot serialize value: {}", e))
+}
+
+fn dump_value<W: Write>(
+    obj: &PyAny,
+    ser: &mut Serializer<W>,
+    default: Option<&PyAny>,
+) -> PyResult<()> {
+    if dump_builtin(obj, ser, default)? {
+        return Ok(());
+    }
+    if let Some(default) = default {
+        // the value returned by `default` is dumped without calling it again,
+        // so `default` can't recurse forever
+        if dump_builtin(default.call1((obj,))?, ser, Some(default))? {
+            return Ok(());
+        }
+    }
+    match obj.repr() {
+        Ok(repr) => Err(encode_err(format!(
+            "Value is not hessian serializable: {}",
+            repr
+        ))),
+        Err(_) => Err(encode_err(format!(
+            "Type is not hessian serializable: {}",
+            obj.get_type().name()?
+        ))),
+    }
 }
 
-fn dump_value<'a, W>(obj: &'a PyAny, ser: &'a mut Serializer<W>) -> PyResult<()>
+// Dump `obj` if it has a builtin conversion, returns whether it had one
+fn dump_builtin<'a, W>(
+    obj: &'a PyAny,
+    ser: &mut Serializer<W>,
+    default: Option<&PyAny>,
+) -> PyResult<bool>
 where
     W: Write,
 {
+    if obj.hasattr("__hessian__")? {
+        let (class_name, fields, values) = obj.call_method0("__hessian__")?.extract()?;
+        dump_object(class_name, fields, values, ser, default)?;
+        return Ok(true);
+    }
+
     if let Ok(val) = obj.extract::<PySerializeObject>() {
-        let def = Definition {
-            name: val.class_name,
-            fields: val.fields,
-        };
-        ser.write_definition(&def).map_err(convert_err)?;
-        ser.write_object_start(&def).map_err(convert_err)?;
-        for v in val.values {
-            dump_value(v, ser)?;
+        dump_object(val.class_name, val.fields, val.values, ser, default)?;
+        return Ok(true);
+    }
+
+    if let Ok(val) = obj.extract::<PyRef<HessianObject>>() {
+        dump_hessian_object(obj.py(), &val, ser, default)?;
+        return Ok(true);
+    }
+
+    if let Ok(val) = obj.extract::<PyRef<TypedList>>() {
+        let items = val.items.as_ref(obj.py());
+        let mut list = ser
+            .begin_list(Some(items.len()), Some(&val.type_name))
+            .map_err(convert_err)?;
+        for v in items.iter() {
+            dump_value(v, list.next_element().map_err(convert_err)?, default)?;
         }
-        return Ok(());
+        return written(list.end());
+    }
+
+    if let Ok(val) = obj.extract::<PyRef<TypedDict>>() {
+        let entries = dict_entries(val.items.as_ref(obj.py()), ser)?;
+        let map = ser.begin_map(Some(&val.type_name)).map_err(convert_err)?;
+        dump_entries(entries, map, default)?;
+        return Ok(true);
     }
 
     if let Ok(val) = obj.extract::<&'a PyDict>() {
-        ser.write_map_start(None).map_err(convert_err)?;
-        for (k, v) in val.iter() {
-            dump_value(k, ser)?;
-            dump_value(v, ser)?;
-        }
-        ser.write_object_end().map_err(convert_err)?;
-        return Ok(());
+        let entries = dict_entries(val, ser)?;
+        let map = ser.begin_map(None).map_err(convert_err)?;
+        dump_entries(entries, map, default)?;
+        return Ok(true);
     }
 
     if let Ok(val) = obj.extract::<&'a PyList>() {
-        ser.write_list_begin(val.len(), None).map_err(convert_err)?;
+        let mut list = ser.begin_list(Some(val.len()), None).map_err(convert_err)?;
         for v in val.iter() {
-            dump_value(v, ser)?;
+            dump_value(v, list.next_element().map_err(convert_err)?, default)?;
         }
-        ser.write_object_end().map_err(convert_err)?;
-        return Ok(());
+        return written(list.end());
     }
 
     if let Ok(val) = obj.extract::<&'a PyTuple>() {
-        ser.write_list_begin(val.len(), None).map_err(convert_err)?;
+        let mut list = ser.begin_list(Some(val.len()), None).map_err(convert_err)?;
         for v in val.iter() {
-            dump_value(v, ser)?;
+            dump_value(v, list.next_element().map_err(convert_err)?, default)?;
         }
-        ser.write_object_end().map_err(convert_err)?;
-        return Ok(());
+        return written(list.end());
     }
 
     if let Ok(val) = obj.extract::<&'a PyDateTime>() {
-        let timestamp = val.call_method0("timestamp")?.extract::<f64>()?;
-        ser.serialize_date((timestamp * 1000.0) as i64)
-            .map_err(convert_err)?;
-        return Ok(());
+        return written(ser.serialize_date(datetime_millis(val)?));
+    }
+    if let Ok(val) = obj.extract::<&'a PyDate>() {
+        return written(ser.serialize_date(date_millis(val)?));
     }
 
     if let Ok(val) = FromPyObject::extract(obj) {
-        return ser.serialize_string(val).map_err(convert_err);
+        return written(ser.serialize_string(val));
     }
     if let Ok(val) = FromPyObject::extract(obj) {
-        return ser.serialize_binary(val).map_err(convert_err);
+        return written(ser.serialize_binary(val));
     }
     if let Ok(val) = FromPyObject::extract(obj) {
-        return ser.serialize_bool(val).map_err(convert_err);
+        return written(ser.serialize_bool(val));
     }
     if let Ok(val) = FromPyObject::extract(obj) {
-        return ser.serialize_int(val).map_err(convert_err);
+        return written(ser.serialize_int(val));
     }
     if let Ok(val) = FromPyObject::extract(obj) {
-        return ser.serialize_long(val).map_err(convert_err);
+        return written(ser.serialize_long(val));
     }
     if let Ok(val) = FromPyObject::extract(obj) {
-        return ser.serialize_double(val).map_err(convert_err);
+        return written(ser.serialize_double(val));
     }
     if obj.is_none() {
-        return ser.serialize_null().map_err(convert_err);
+        return written(ser.serialize_null());
     }
-    match obj.repr() {
-        Ok(repr) => Err(PyErr::new::<PyValueError, _>(format!(
-            "Value is not hessian serializable: {}",
-            repr
-        ))),
-        Err(_) => Err(PyErr::new::<PyValueError, _>(format!(
-            "Type is not JSON serializable: {}",
-            obj.get_type().name()?
-        ))),
+    if let Some((class_name, fields, values)) = dataclass_fields(obj)? {
+        dump_object(class_name, fields, values, ser, default)?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+// The ordinal of 1970-01-01 in the proleptic gregorian calendar
+const EPOCH_ORDINAL: i64 = 719_163;
+
+// Milliseconds since the epoch of a datetime, a naive one is taken as UTC
+// like `loads(b, dates="naive")` returns it
+fn datetime_millis(dt: &PyDateTime) -> PyResult<i64> {
+    let utc = match dt.get_tzinfo() {
+        Some(_) => dt
+            .call_method1("astimezone", (timezone_utc(dt.py()),))?
+            .downcast::<PyDateTime>()?,
+        None => dt,
+    };
+    let seconds =
+        utc.get_hour() as i64 * 3600 + utc.get_minute() as i64 * 60 + utc.get_second() as i64;
+    Ok(date_millis(utc)? + seconds * 1000 + utc.get_microsecond() as i64 / 1000)
+}
+
+// Milliseconds since the epoch of midnight UTC of a date
+fn date_millis(date: &PyAny) -> PyResult<i64> {
+    let days = date.call_method0("toordinal")?.extract::<i64>()? - EPOCH_ORDINAL;
+    Ok(days * 86_400_000)
+}
+
+fn written(res: Result<(), hessian_rs::Error>) -> PyResult<bool> {
+    res.map(|()| true).map_err(convert_err)
+}
+
+// Write an object of `class_name`, preceded by its definition the first time
+fn dump_object<W: Write>(
+    class_name: String,
+    fields: Vec<String>,
+    values: Vec<&PyAny>,
+    ser: &mut Serializer<W>,
+    default: Option<&PyAny>,
+) -> PyResult<()> {
+    let def = Definition {
+        name: class_name,
+        fields,
+    };
+    let mut object = ser.begin_object(&def).map_err(convert_err)?;
+    for v in values {
+        dump_value(v, object.next_field().map_err(convert_err)?, default)?;
+    }
+    object.end().map_err(convert_err)
+}
+
+// The class name, field names and values of a dataclass instance. The class
+// name is the one registered with `register_class`, `module.qualname` when
+// there is none.
+#[allow(clippy::type_complexity)]
+fn dataclass_fields(obj: &PyAny) -> PyResult<Option<(String, Vec<String>, Vec<&PyAny>)>> {
+    let cls = obj.get_type();
+    if !cls.hasattr("__dataclass_fields__")? {
+        return Ok(None);
+    }
+    let py = obj.py();
+    let fields = py
+        .import("dataclasses")?
+        .call_method1("fields", (obj,))?
+        .iter()?
+        .map(|field| field?.getattr("name")?.extract())
+        .collect::<PyResult<Vec<String>>>()?;
+    let values = fields
+        .iter()
+        .map(|field| obj.getattr(field.as_str()))
+        .collect::<PyResult<Vec<_>>>()?;
+    let class_name = match class_names(py).get_item(cls) {
+        Some(name) => name.extract()?,
+        None => format!(
+            "{}.{}",
+            cls.getattr("__module__")?,
+            cls.getattr("__qualname__")?
+        ),
+    };
+    Ok(Some((class_name, fields, values)))
+}
+
+// The entries of a dict, in key order for a canonical serializer
+fn dict_entries<'a, W: Write>(
+    dict: &'a PyDict,
+    ser: &Serializer<W>,
+) -> PyResult<Vec<(&'a PyAny, &'a PyAny)>> {
+    if !ser.is_canonical() {
+        return Ok(dict.iter().collect());
+    }
+    let keys = dict.keys();
+    keys.sort()?;
+    keys.iter()
+        .map(|k| match dict.get_item(k) {
+            Some(v) => Ok((k, v)),
+            // comparing the keys ran python code which removed one
+            None => Err(PyRuntimeError::new_err("dictionary changed during sort")),
+        })
+        .collect()
+}
+
+// Write the entries of a dict and end the map
+fn dump_entries<W: Write>(
+    entries: Vec<(&PyAny, &PyAny)>,
+    mut map: MapWriter<W>,
+    default: Option<&PyAny>,
+) -> PyResult<()> {
+    for (k, v) in entries {
+        dump_value(k, map.next_key().map_err(convert_err)?, default)?;
+        dump_value(v, map.next_value().map_err(convert_err)?, default)?;
+    }
+    map.end().map_err(convert_err)
+}
+
+// Write an object when every field name is a string, a typed map otherwise
+fn dump_hessian_object<W: Write>(
+    py: Python,
+    val: &HessianObject,
+    ser: &mut Serializer<W>,
+    default: Option<&PyAny>,
+) -> PyResult<()> {
+    let fields = val.fields.as_ref(py);
+    let names = fields
+        .keys()
+        .iter()
+        .map(|k| k.extract::<String>())
+        .collect::<PyResult<Vec<_>>>();
+    match names {
+        Ok(names) => {
+            let def = Definition {
+                name: val.class_name.clone(),
+                fields: names,
+            };
+            let mut object = ser.begin_object(&def).map_err(convert_err)?;
+            for v in fields.values() {
+                dump_value(v, object.next_field().map_err(convert_err)?, default)?;
+            }
+            object.end().map_err(convert_err)?;
+        }
+        Err(_) => {
+            let entries = dict_entries(fields, ser)?;
+            let map = ser.begin_map(Some(&val.class_name)).map_err(convert_err)?;
+            dump_entries(entries, map, default)?;
+        }
     }
+    Ok(())
 }
 
 #[derive(FromPyObject)]
@@ -261,6 +751,11 @@ impl PyHessianSerializer {
         }
     }
 
+    /// The bytes written so far.
+    fn getvalue(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new(py, self.ser.get_ref()).into()
+    }
+
     fn serialize_bool(&mut self, b: &PyBool) -> PyResult<()> {
         self.ser.serialize_bool(b.is_true()).map_err(convert_err)?;
         Ok(())
@@ -293,15 +788,14 @@ impl PyHessianSerializer {
     }
 
     fn serialize_date(&mut self, d: &PyDateTime) -> PyResult<()> {
-        let timestamp = d.call_method0("timestamp")?.extract::<f64>()?;
         self.ser
-            .serialize_date((timestamp * 1000.0) as i64)
+            .serialize_date(datetime_millis(d)?)
             .map_err(convert_err)?;
         Ok(())
     }
 
     fn serialize_value(&mut self, d: &PyAny) -> PyResult<()> {
-        dump_value(d, &mut self.ser)?;
+        dump_value(d, &mut self.ser, None)?;
         Ok(())
     }
 
@@ -0,0 +1,211 @@
+// the slots generated by pyo3 0.18 trip this lint on recent compilers
+#![allow(non_local_definitions)]
+
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::PyAttributeError;
+use pyo3::exceptions::PyKeyError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyIterator, PyList};
+
+/// A typed map or object decoded with `loads(b, preserve_class=True)`.
+///
+/// Fields are read as attributes or items, and `dumps` writes it back as an
+/// object of `class_name`.
+#[pyclass(module = "hessian_codec")]
+pub struct HessianObject {
+    #[pyo3(get, set)]
+    pub class_name: String,
+    pub fields: Py<PyDict>,
+}
+
+#[pymethods]
+impl HessianObject {
+    #[new]
+    fn new(py: Python, class_name: String, fields: Option<&PyDict>) -> PyResult<Self> {
+        let fields = match fields {
+            Some(fields) => fields.copy()?,
+            None => PyDict::new(py),
+        };
+        Ok(HessianObject {
+            class_name,
+            fields: fields.into(),
+        })
+    }
+
+    #[getter]
+    fn fields(&self, py: Python) -> Py<PyDict> {
+        self.fields.clone_ref(py)
+    }
+
+    fn __getattr__(&self, py: Python, name: &str) -> PyResult<PyObject> {
+        match self.fields.as_ref(py).get_item(name) {
+            Some(v) => Ok(v.into()),
+            None => Err(PyAttributeError::new_err(format!(
+                "{} has no field {}",
+                self.class_name, name
+            ))),
+        }
+    }
+
+    fn __getitem__(&self, py: Python, key: &PyAny) -> PyResult<PyObject> {
+        match self.fields.as_ref(py).get_item(key) {
+            Some(v) => Ok(v.into()),
+            None => Err(PyKeyError::new_err(key.to_object(py))),
+        }
+    }
+
+    fn __setitem__(&self, py: Python, key: &PyAny, value: &PyAny) -> PyResult<()> {
+        self.fields.as_ref(py).set_item(key, value)
+    }
+
+    fn __len__(&self, py: Python) -> usize {
+        self.fields.as_ref(py).len()
+    }
+
+    fn __richcmp__(&self, py: Python, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let other = match other.extract::<PyRef<HessianObject>>() {
+            Ok(other) => other,
+            Err(_) => return Ok(py.NotImplemented()),
+        };
+        let eq = self.class_name == other.class_name
+            && self.fields.as_ref(py).eq(other.fields.as_ref(py))?;
+        compare(py, eq, op)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "HessianObject({:?}, {})",
+            self.class_name,
+            self.fields.as_ref(py).repr()?
+        ))
+    }
+}
+
+/// A list `dumps` writes with the type `type_name`, e.g. `TypedList("[int", [1, 2])`.
+#[pyclass(module = "hessian_codec")]
+pub struct TypedList {
+    #[pyo3(get, set)]
+    pub type_name: String,
+    pub items: Py<PyList>,
+}
+
+#[pymethods]
+impl TypedList {
+    #[new]
+    fn new(py: Python, type_name: String, items: Option<&PyAny>) -> PyResult<Self> {
+        let items = match items {
+            Some(items) => PyList::new(py, items.iter()?.collect::<PyResult<Vec<_>>>()?),
+            None => PyList::empty(py),
+        };
+        Ok(TypedList {
+            type_name,
+            items: items.into(),
+        })
+    }
+
+    #[getter]
+    fn items(&self, py: Python) -> Py<PyList> {
+        self.items.clone_ref(py)
+    }
+
+    fn __getitem__(&self, py: Python, index: &PyAny) -> PyResult<PyObject> {
+        Ok(PyAny::get_item(self.items.as_ref(py), index)?.into())
+    }
+
+    fn __iter__(&self, py: Python) -> PyResult<Py<PyIterator>> {
+        Ok(PyAny::iter(self.items.as_ref(py))?.into())
+    }
+
+    fn __len__(&self, py: Python) -> usize {
+        self.items.as_ref(py).len()
+    }
+
+    fn __richcmp__(&self, py: Python, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let other = match other.extract::<PyRef<TypedList>>() {
+            Ok(other) => other,
+            Err(_) => return Ok(py.NotImplemented()),
+        };
+        let eq = self.type_name == other.type_name
+            && self.items.as_ref(py).eq(other.items.as_ref(py))?;
+        compare(py, eq, op)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "TypedList({:?}, {})",
+            self.type_name,
+            self.items.as_ref(py).repr()?
+        ))
+    }
+}
+
+/// A map `dumps` writes with the type `type_name`, e.g.
+/// `TypedDict("java.util.Hashtable", {"a": 1})`.
+#[pyclass(module = "hessian_codec")]
+pub struct TypedDict {
+    #[pyo3(get, set)]
+    pub type_name: String,
+    pub items: Py<PyDict>,
+}
+
+#[pymethods]
+impl TypedDict {
+    #[new]
+    fn new(py: Python, type_name: String, items: Option<&PyDict>) -> PyResult<Self> {
+        let items = match items {
+            Some(items) => items.copy()?,
+            None => PyDict::new(py),
+        };
+        Ok(TypedDict {
+            type_name,
+            items: items.into(),
+        })
+    }
+
+    #[getter]
+    fn items(&self, py: Python) -> Py<PyDict> {
+        self.items.clone_ref(py)
+    }
+
+    fn __getitem__(&self, py: Python, key: &PyAny) -> PyResult<PyObject> {
+        match self.items.as_ref(py).get_item(key) {
+            Some(v) => Ok(v.into()),
+            None => Err(PyKeyError::new_err(key.to_object(py))),
+        }
+    }
+
+    fn __setitem__(&self, py: Python, key: &PyAny, value: &PyAny) -> PyResult<()> {
+        self.items.as_ref(py).set_item(key, value)
+    }
+
+    fn __len__(&self, py: Python) -> usize {
+        self.items.as_ref(py).len()
+    }
+
+    fn __richcmp__(&self, py: Python, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let other = match other.extract::<PyRef<TypedDict>>() {
+            Ok(other) => other,
+            Err(_) => return Ok(py.NotImplemented()),
+        };
+        let eq = self.type_name == other.type_name
+            && self.items.as_ref(py).eq(other.items.as_ref(py))?;
+        compare(py, eq, op)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "TypedDict({:?}, {})",
+            self.type_name,
+            self.items.as_ref(py).repr()?
+        ))
+    }
+}
+
+// The result of `==` or `!=` for two values which are `eq`
+fn compare(py: Python, eq: bool, op: CompareOp) -> PyResult<PyObject> {
+    match op {
+        CompareOp::Eq => Ok(eq.into_py(py)),
+        CompareOp::Ne => Ok((!eq).into_py(py)),
+        _ => Ok(py.NotImplemented()),
+    }
+}
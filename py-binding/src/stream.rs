@@ -0,0 +1,157 @@
+use hessian_rs::parser::ValueParser;
+use hessian_rs::Value;
+use pyo3::exceptions::PyEOFError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::error::decode_err;
+use crate::{with_buffer, DecodeOptions, Decoder};
+
+// Bytes read from a file at once
+const READ_CHUNK: usize = 64 * 1024;
+
+// A parser fed piece by piece, whose values are read one by one
+pub(crate) struct StreamDeserializer {
+    parser: ValueParser,
+    // the error the parser met, raised once the values before it are read
+    error: Option<PyErr>,
+}
+
+impl StreamDeserializer {
+    pub(crate) fn new() -> Self {
+        StreamDeserializer {
+            parser: ValueParser::new(),
+            error: None,
+        }
+    }
+
+    // Parse `bytes`, which doesn't need the GIL. The stream is lost after
+    // an error, the bytes fed next are ignored.
+    fn parse(&mut self, bytes: &[u8]) -> Result<(), hessian_rs::Error> {
+        match self.error {
+            Some(_) => Ok(()),
+            None => self.parser.feed(bytes),
+        }
+    }
+
+    // Keep the error of `parse` until the values before it are read
+    fn keep_error(&mut self, py: Python, result: Result<(), hessian_rs::Error>) {
+        if let Err(e) = result {
+            self.error = Some(decode_err(py, e, self.parser.position()));
+        }
+    }
+
+    // The next value fed, or `None` while it's incomplete
+    fn try_read_value(&mut self, py: Python) -> PyResult<Option<Value>> {
+        match (self.parser.next_value(), &self.error) {
+            (Some(value), _) => Ok(Some(value)),
+            (None, Some(e)) => Err(e.clone_ref(py)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    // Read the next value of `fp`, or `None` when the file ends between values
+    pub(crate) fn read_from(&mut self, fp: &PyAny) -> PyResult<Option<Value>> {
+        let py = fp.py();
+        loop {
+            if let Some(value) = self.try_read_value(py)? {
+                return Ok(Some(value));
+            }
+            let chunk: &PyBytes = fp.call_method1("read", (READ_CHUNK,))?.downcast()?;
+            if chunk.as_bytes().is_empty() {
+                // the bytes left are the start of a truncated value
+                return match self.parser.finish() {
+                    Ok(()) => Ok(None),
+                    Err(e) => Err(decode_err(py, e, self.parser.position())),
+                };
+            }
+            let bytes = chunk.as_bytes();
+            let de = &mut *self;
+            let result = py.allow_threads(|| de.parse(bytes));
+            self.keep_error(py, result);
+        }
+    }
+}
+
+/// The values of a binary stream, see `load_iter`.
+#[pyclass(module = "hessian_codec")]
+pub struct LoadIter {
+    fp: PyObject,
+    de: StreamDeserializer,
+    options: DecodeOptions,
+}
+
+impl LoadIter {
+    pub(crate) fn new(fp: PyObject, options: DecodeOptions) -> Self {
+        LoadIter {
+            fp,
+            de: StreamDeserializer::new(),
+            options,
+        }
+    }
+}
+
+#[pymethods]
+impl LoadIter {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        match self.de.read_from(self.fp.as_ref(py))? {
+            Some(value) => Decoder::new(py, &self.options).decode(value).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Decodes values as their bytes arrive, e.g. from a socket.
+///
+/// `read_value` raises `EOFError` while the next value is incomplete, its
+/// bytes are kept until more are fed.
+#[pyclass(module = "hessian_codec")]
+pub struct PyHessianDeserializer {
+    de: StreamDeserializer,
+    // a value decoded ahead by `has_more`
+    pending: Option<Value>,
+    options: DecodeOptions,
+}
+
+#[pymethods]
+impl PyHessianDeserializer {
+    #[new]
+    fn new(
+        object_hook: Option<PyObject>,
+        preserve_class: Option<bool>,
+        dates: Option<&str>,
+        tzinfo: Option<PyObject>,
+    ) -> PyResult<Self> {
+        Ok(PyHessianDeserializer {
+            de: StreamDeserializer::new(),
+            pending: None,
+            options: DecodeOptions::new(object_hook, preserve_class, dates, tzinfo)?,
+        })
+    }
+
+    fn feed(&mut self, py: Python, data: &PyAny) -> PyResult<()> {
+        let result = with_buffer(py, data, |bytes| self.de.parse(bytes))?;
+        self.de.keep_error(py, result);
+        Ok(())
+    }
+
+    /// Whether a complete value has been fed and not read yet.
+    fn has_more(&mut self, py: Python) -> PyResult<bool> {
+        if self.pending.is_none() {
+            self.pending = self.de.try_read_value(py)?;
+        }
+        Ok(self.pending.is_some())
+    }
+
+    fn read_value(&mut self, py: Python) -> PyResult<PyObject> {
+        if !self.has_more(py)? {
+            return Err(PyEOFError::new_err("the next value is incomplete"));
+        }
+        let value = self.pending.take().unwrap();
+        Decoder::new(py, &self.options).decode(value)
+    }
+}
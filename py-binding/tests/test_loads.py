@@ -2,6 +2,7 @@ import math
 
 import hessian_codec
 import io
+import threading
 
 import unittest
 
@@ -43,6 +44,146 @@ class HessianLoadsTest(unittest.TestCase):
     def test_struct(self):
         self.assertEqual(hessian_codec.loads(b"C\x0bexample.Car\x92\x05Color\x05ModelO\x90\x03red\x08corvette"), {"Color": "red", "Model": "corvette"})
 
+    def test_object_hook(self):
+        car = b"C\x0bexample.Car\x92\x05Color\x05ModelO\x90\x03red\x08corvette"
+        self.assertEqual(
+            hessian_codec.loads(car, object_hook=lambda name, d: (name, d)),
+            ("example.Car", {"Color": "red", "Model": "corvette"}),
+        )
+        # untyped maps aren't passed to the hook
+        self.assertEqual(hessian_codec.loads(b"H\x91\x03feeZ", object_hook=lambda name, d: None), {1: "fee"})
+
+    def test_register_class(self):
+        class Car:
+            def __init__(self, Color, Model):
+                self.color = Color
+                self.model = Model
+
+        hessian_codec.register_class("example.RegisteredCar", Car)
+        car = hessian_codec.loads(b"C\x15example.RegisteredCar\x92\x05Color\x05ModelO\x90\x03red\x08corvette")
+        self.assertIsInstance(car, Car)
+        self.assertEqual((car.color, car.model), ("red", "corvette"))
+        with self.assertRaises(TypeError):
+            hessian_codec.register_class("example.Car", 1)
+
+    def test_buffers(self):
+        data = b"V\x04[int\x92\x90\x91"
+        self.assertEqual(hessian_codec.loads(bytearray(data)), [0, 1])
+        self.assertEqual(hessian_codec.loads(memoryview(b"--" + data)[2:]), [0, 1])
+        # not contiguous
+        self.assertEqual(hessian_codec.loads(memoryview(b"\x91\x00")[::2]), 1)
+        with self.assertRaises(TypeError):
+            hessian_codec.loads("\x91")
+        de = hessian_codec.PyHessianDeserializer()
+        de.feed(memoryview(data))
+        self.assertEqual(de.read_value(), [0, 1])
+
+    def test_large_payload(self):
+        # parsed without the GIL, while another thread runs
+        value = [{"id": i, "name": "x" * 100} for i in range(1000)]
+        data = hessian_codec.dumps(value)
+        counter = []
+        stop = threading.Event()
+
+        def count():
+            while not stop.is_set():
+                counter.append(1)
+
+        thread = threading.Thread(target=count)
+        thread.start()
+        try:
+            for _ in range(5):
+                self.assertEqual(hessian_codec.loads(data), value)
+            self.assertEqual(hessian_codec.loads(memoryview(data)[::1]), value)
+        finally:
+            stop.set()
+            thread.join()
+        self.assertTrue(counter)
+
+    def test_load(self):
+        self.assertEqual(hessian_codec.load(io.BytesIO(b"V\x04[int\x92\x90\x91")), [0, 1])
+        with self.assertRaises(hessian_codec.HessianDecodeError):
+            hessian_codec.load(io.BytesIO(b""))
+        with self.assertRaises(hessian_codec.HessianDecodeError):
+            hessian_codec.load(io.BytesIO(b"V\x04[int\x92\x90"))
+
+    def test_load_iter(self):
+        class Trickle(io.RawIOBase):
+            # returns at most 3 bytes per read
+            def __init__(self, data):
+                self.data = data
+
+            def read(self, n=-1):
+                chunk, self.data = self.data[:3], self.data[3:]
+                return chunk
+
+        car = b"C\x0bexample.Car\x91\x05colorO\x90\x03red"
+        stream = car + b"\x60\x04blue" + b"\x91"
+        self.assertEqual(
+            list(hessian_codec.load_iter(Trickle(stream))),
+            [{"color": "red"}, {"color": "blue"}, 1],
+        )
+        # a large list isn't parsed again for every read
+        value = [{"id": i} for i in range(20000)]
+        self.assertEqual(list(hessian_codec.load_iter(Trickle(hessian_codec.dumps(value)))), [value])
+        self.assertEqual(list(hessian_codec.load_iter(io.BytesIO(b""))), [])
+        values = hessian_codec.load_iter(io.BytesIO(b"\x91\x03re"))
+        self.assertEqual(next(values), 1)
+        with self.assertRaises(hessian_codec.HessianDecodeError):
+            next(values)
+
+    def test_deserializer(self):
+        ser = hessian_codec.PyHessianSerializer()
+        ser.serialize_value(hessian_codec.HessianObject("example.Car", {"color": "red"}))
+        ser.serialize_value(None)
+        ser.serialize_value([1, "two"])
+        data = ser.getvalue()
+
+        de = hessian_codec.PyHessianDeserializer()
+        self.assertFalse(de.has_more())
+        values = []
+        for i in range(0, len(data), 4):
+            de.feed(data[i:i + 4])
+            while de.has_more():
+                values.append(de.read_value())
+        self.assertEqual(values, [{"color": "red"}, None, [1, "two"]])
+        with self.assertRaises(EOFError):
+            de.read_value()
+
+        de = hessian_codec.PyHessianDeserializer(preserve_class=True)
+        de.feed(data)
+        self.assertEqual(de.read_value().class_name, "example.Car")
+        self.assertEqual([de.read_value(), de.read_value()], [None, [1, "two"]])
+        de.feed(b"\x45")
+        with self.assertRaises(hessian_codec.HessianDecodeError):
+            de.read_value()
+
+        # a large list fed in small pieces isn't parsed again for every piece
+        value = [{"id": i} for i in range(20000)]
+        data = hessian_codec.dumps(value) + b"\x91\x45"
+        de = hessian_codec.PyHessianDeserializer()
+        for i in range(0, len(data), 3):
+            de.feed(data[i:i + 3])
+        # the values before a bad one are read first
+        self.assertEqual([de.read_value(), de.read_value()], [value, 1])
+        with self.assertRaises(hessian_codec.HessianDecodeError):
+            de.read_value()
+
+    def test_errors(self):
+        self.assertTrue(issubclass(hessian_codec.HessianDecodeError, hessian_codec.HessianError))
+        self.assertTrue(issubclass(hessian_codec.HessianEncodeError, hessian_codec.HessianError))
+        self.assertTrue(issubclass(hessian_codec.HessianError, ValueError))
+        with self.assertRaises(hessian_codec.HessianDecodeError) as cm:
+            hessian_codec.loads(b"\x7a\x91\x45")
+        self.assertEqual(cm.exception.position, 3)
+        values = hessian_codec.load_iter(io.BytesIO(b"\x91\x92\x45"))
+        self.assertEqual([next(values), next(values)], [1, 2])
+        with self.assertRaises(hessian_codec.HessianDecodeError) as cm:
+            next(values)
+        self.assertEqual(cm.exception.position, 3)
+        with self.assertRaises(hessian_codec.HessianEncodeError):
+            hessian_codec.dumps(object())
+
 
 if __name__ == '__main__':
     unittest.main()
\ No newline at end of file
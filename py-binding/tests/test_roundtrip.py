@@ -19,12 +19,109 @@ class HessianRoundTripTest(unittest.TestCase):
             hessian_class_name = "test.TestStruct"
 
         st = hessian_codec.dumps(TestStruct())
-        self.assertEqual(st, b"C\x0ftest.TestStruct\x92\x04test\x05test2O\x90\x91\x92")
-        # todo: add reflection to loads
+        self.assertEqual(st, b"C\x0ftest.TestStruct\x92\x04test\x05test2`\x91\x92")
         t = hessian_codec.loads(st)
         self.assertEqual(t["test"], 1)
         self.assertEqual(t["test2"], 2)
 
+    def test_hessian_protocol(self):
+        class Car:
+            def __init__(self, color):
+                self.color = color
+
+            def __hessian__(self):
+                return "example.Car", ["color"], [self.color]
+
+        st = hessian_codec.dumps([Car("red"), Car("blue")])
+        self.assertEqual(st, b"\x7aC\x0bexample.Car\x91\x05color`\x03red`\x04blue")
+
+        class Broken:
+            def __hessian__(self):
+                return "example.Broken", ["a", "b"], [1]
+
+        with self.assertRaises(ValueError):
+            hessian_codec.dumps(Broken())
+
+    def test_dataclass(self):
+        import dataclasses
+
+        @dataclasses.dataclass
+        class Part:
+            id: int
+            name: str
+
+        @dataclasses.dataclass
+        class Point:
+            x: int
+            y: int
+
+        hessian_codec.register_class("example.Part", Part)
+        self.assertEqual(hessian_codec.dumps(Part(1, "wheel")), b"C\x0cexample.Part\x92\x02id\x04name`\x91\x05wheel")
+        self.roundtrip(Part(1, "wheel"))
+        point = hessian_codec.loads(hessian_codec.dumps(Point(1, 2)), preserve_class=True)
+        self.assertEqual(point.class_name, Point.__module__ + "." + Point.__qualname__)
+        self.assertEqual((point.x, point.y), (1, 2))
+
+    def test_preserve_class(self):
+        car = hessian_codec.HessianObject("example.Car", {"color": "red", "parts": ["wheel"]})
+        t = hessian_codec.loads(hessian_codec.dumps(car), preserve_class=True)
+        self.assertIsInstance(t, hessian_codec.HessianObject)
+        self.assertEqual(t, car)
+        self.assertEqual((t.class_name, t.color, t["parts"]), ("example.Car", "red", ["wheel"]))
+        with self.assertRaises(AttributeError):
+            t.model
+        # a typed map whose keys aren't strings
+        m = hessian_codec.HessianObject("java.util.TreeMap", {1: "a"})
+        self.assertEqual(hessian_codec.loads(hessian_codec.dumps(m), preserve_class=True), m)
+        self.assertEqual(hessian_codec.loads(hessian_codec.dumps(m)), {1: "a"})
+
+    def test_default(self):
+        class Point:
+            def __init__(self, x, y):
+                self.x = x
+                self.y = y
+
+        def default(o):
+            if isinstance(o, Point):
+                return {"x": o.x, "y": o.y}
+            if isinstance(o, set):
+                return sorted(o)
+            raise TypeError(repr(o))
+
+        val = {"origin": Point(0, 0), "tags": {2, 1}}
+        self.assertEqual(hessian_codec.loads(hessian_codec.dumps(val, default=default)), {"origin": {"x": 0, "y": 0}, "tags": [1, 2]})
+        with self.assertRaises(TypeError):
+            hessian_codec.dumps(object(), default=default)
+        # the value returned by default must be serializable
+        with self.assertRaises(ValueError):
+            hessian_codec.dumps(object(), default=lambda o: object())
+
+        class Encoder:
+            def default(self, o):
+                return list(o)
+
+        self.assertEqual(hessian_codec.loads(hessian_codec.dumps({1}, cls=Encoder)), [1])
+
+    def test_dump_into(self):
+        buf = bytearray(b"head")
+        self.assertEqual(hessian_codec.dump_into([1, "two"], buf), 6)
+        self.assertEqual(hessian_codec.dump_into(None, buf), 1)
+        self.assertEqual(buf, b"head" + hessian_codec.dumps([1, "two"]) + b"N")
+        with self.assertRaises(TypeError):
+            hessian_codec.dump_into(1, b"")
+
+    def test_typed(self):
+        ints = hessian_codec.TypedList("[int", (0, 1))
+        self.assertEqual(hessian_codec.dumps(ints), b"\x72\x04[int\x90\x91")
+        self.assertEqual((len(ints), ints[1], list(ints)), (2, 1, [0, 1]))
+        table = hessian_codec.TypedDict("java.util.Hashtable", {"a": ints})
+        st = hessian_codec.dumps(table)
+        self.assertEqual(st, b"M\x13java.util.Hashtable\x01a\x72\x04[int\x90\x91Z")
+        self.assertEqual(hessian_codec.loads(st), {"a": [0, 1]})
+        self.assertEqual(table, hessian_codec.TypedDict("java.util.Hashtable", {"a": ints}))
+        self.assertNotEqual(table, hessian_codec.TypedDict("java.util.HashMap", {"a": ints}))
+        self.assertEqual(table["a"].type_name, "[int")
+
     def test_load_bytes(self):
         self.roundtrip(b"")
         self.roundtrip(b"\x01\x02\x03")
@@ -51,6 +148,42 @@ class HessianRoundTripTest(unittest.TestCase):
         self.roundtrip(datetime.datetime(1998, 5, 8, 9, 51, 31, tzinfo=datetime.timezone.utc))
 
 
+    def test_dates(self):
+        import datetime
+        utc = datetime.timezone.utc
+        cst = datetime.timezone(datetime.timedelta(hours=8))
+        dt = datetime.datetime(1998, 5, 8, 9, 51, 31, 1000, tzinfo=utc)
+        st = hessian_codec.dumps(dt)
+        self.assertEqual(hessian_codec.loads(st), dt)
+        self.assertEqual(hessian_codec.loads(st, dates="millis"), 894621091001)
+        self.assertEqual(hessian_codec.loads(st, dates="naive"), dt.replace(tzinfo=None))
+        self.assertEqual(hessian_codec.loads(st, tzinfo=cst).utcoffset(), datetime.timedelta(hours=8))
+        self.assertEqual(hessian_codec.loads(st, tzinfo=cst), dt)
+        self.assertEqual(hessian_codec.dumps(dt.astimezone(cst)), st)
+        self.assertEqual(hessian_codec.dumps(dt.replace(tzinfo=None)), st)
+        self.assertEqual(hessian_codec.loads(hessian_codec.dumps(datetime.datetime(1969, 12, 31, 23, 59, 59, tzinfo=utc)), dates="millis"), -1000)
+        self.assertEqual(hessian_codec.loads(hessian_codec.dumps(datetime.date(1998, 5, 8))), datetime.datetime(1998, 5, 8, tzinfo=utc))
+        with self.assertRaises(ValueError):
+            hessian_codec.loads(st, dates="local")
+
+    def test_allow_nan(self):
+        self.roundtrip(float("inf"))
+        with self.assertRaises(ValueError):
+            hessian_codec.dumps(float("nan"), allow_nan=False)
+        with self.assertRaises(ValueError):
+            hessian_codec.dumps([float("-inf")], allow_nan=False)
+
+    def test_sort_keys(self):
+        a = {"b": 1, "a": {"y": 2, "x": 3}}
+        b = {"a": {"x": 3, "y": 2}, "b": 1}
+        self.assertNotEqual(hessian_codec.dumps(a), hessian_codec.dumps(b))
+        self.assertEqual(hessian_codec.dumps(a, sort_keys=True), hessian_codec.dumps(b, sort_keys=True))
+        self.assertEqual(hessian_codec.dumps({2: "x", 1: "y"}, sort_keys=True), b"H\x91\x01y\x92\x01xZ")
+        self.assertEqual(hessian_codec.loads(hessian_codec.dumps(a, sort_keys=True)), a)
+        with self.assertRaises(TypeError):
+            hessian_codec.dumps({1: 1, "a": 2}, sort_keys=True)
+
+
     def test_list(self):
         self.roundtrip([0, 1])
 
@@ -0,0 +1,54 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "example.Car")]
+struct Car {
+    color: String,
+    model: String,
+    mileage: i32,
+    options: Vec<String>,
+}
+
+fn cars() -> Vec<Car> {
+    (0..1000)
+        .map(|i| Car {
+            color: "red".to_string(),
+            model: "corvette".to_string(),
+            mileage: i,
+            options: vec!["sunroof".to_string(), "中文".to_string()],
+        })
+        .collect()
+}
+
+// The same payload through serde and through `hessian_rs::Value`
+fn serde_benchmark(c: &mut Criterion) {
+    let cars = cars();
+    let value = serde_hessian::to_value(&cars).unwrap();
+    let buf = serde_hessian::ser::to_vec(&cars).unwrap();
+
+    let mut group = c.benchmark_group("serde");
+    group.throughput(Throughput::Bytes(buf.len() as u64));
+    group.bench_function("encode_serde", |b| {
+        b.iter(|| serde_hessian::ser::to_vec(black_box(&cars)).unwrap())
+    });
+    group.bench_function("encode_value", |b| {
+        b.iter(|| hessian_rs::to_vec(black_box(&value)).unwrap())
+    });
+    group.bench_function("decode_serde", |b| {
+        b.iter(|| serde_hessian::de::from_slice::<_, Vec<Car>>(black_box(&buf)).unwrap())
+    });
+    group.bench_function("decode_value", |b| {
+        b.iter(|| hessian_rs::from_slice(black_box(&buf)).unwrap())
+    });
+    group.bench_function("decode_value_from_value", |b| {
+        b.iter(|| {
+            let value = hessian_rs::from_slice(black_box(&buf)).unwrap();
+            serde_hessian::from_value::<Vec<Car>>(value).unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, serde_benchmark);
+criterion_main!(benches);
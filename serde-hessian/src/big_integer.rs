@@ -0,0 +1,153 @@
+//! 128-bit integers as `java.math.BigInteger` objects.
+//!
+//! A `BigInteger` is written like java's `JavaSerializer` writes it, an
+//! object with the `signum` int field and the `mag` field, the magnitude
+//! as big endian 32-bit words without leading zeros in an `[int` list.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use hessian_rs::value::{Definition, List, Map};
+use hessian_rs::Value;
+
+use crate::error::Error;
+
+pub(crate) const BIG_INTEGER: &str = "java.math.BigInteger";
+
+pub(crate) fn definition() -> Definition {
+    Definition {
+        name: BIG_INTEGER.to_string(),
+        fields: vec!["signum".to_string(), "mag".to_string()],
+    }
+}
+
+/// Returns the values of the fields of the `BigInteger` of the given sign
+/// and magnitude, in the order of `definition()`.
+pub(crate) fn fields(negative: bool, magnitude: u128) -> [Value; 2] {
+    let signum = match (negative, magnitude) {
+        (_, 0) => 0,
+        (true, _) => -1,
+        (false, _) => 1,
+    };
+    let mag = magnitude
+        .to_be_bytes()
+        .chunks(4)
+        .map(|w| i32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+        .skip_while(|w| *w == 0)
+        .map(Value::Int)
+        .collect::<Vec<_>>();
+    [Value::Int(signum), Value::List(List::from(("[int", mag)))]
+}
+
+/// A `BigInteger` as it's decoded by `hessian_rs`, a typed map.
+pub(crate) fn to_value(negative: bool, magnitude: u128) -> Value {
+    let def = definition();
+    let map: HashMap<Value, Value> = def
+        .fields
+        .into_iter()
+        .map(|field| Value::String(field.into()))
+        .zip(fields(negative, magnitude))
+        .collect();
+    Value::Map(Map::from((BIG_INTEGER, map)))
+}
+
+fn out_of_range(value: &Value, typ: &str) -> Error {
+    Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(format!(
+        "{} is out of the range of {}",
+        value, typ
+    )))
+}
+
+// Returns the sign and the magnitude of an integer, a decimal string or
+// a `BigInteger`.
+fn sign_magnitude(value: &Value, typ: &str) -> Result<(bool, u128), Error> {
+    let invalid = || {
+        Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(format!(
+            "deserialize {} expect an integer or a {}, but get {}",
+            typ, BIG_INTEGER, value
+        )))
+    };
+    match value {
+        Value::Int(v) => Ok((*v < 0, (*v as i64).unsigned_abs() as u128)),
+        Value::Long(v) => Ok((*v < 0, v.unsigned_abs() as u128)),
+        Value::String(s) => {
+            let (negative, digits) = match s.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, s.as_str()),
+            };
+            let magnitude = digits.parse().map_err(|_| invalid())?;
+            Ok((negative, magnitude))
+        }
+        Value::Map(map) if map.r#type() == Some(BIG_INTEGER) => {
+            let signum = map
+                .get(&Value::from("signum"))
+                .and_then(Value::as_int)
+                .ok_or_else(invalid)?;
+            let mag = match map.get(&Value::from("mag")) {
+                Some(Value::List(mag)) => mag,
+                Some(Value::Null) | None if signum == 0 => return Ok((false, 0)),
+                _ => return Err(invalid()),
+            };
+            let mut magnitude: u128 = 0;
+            for word in mag.iter() {
+                let word = word.as_int().ok_or_else(invalid)? as u32;
+                if magnitude >> 96 != 0 {
+                    return Err(out_of_range(value, typ));
+                }
+                magnitude = magnitude << 32 | word as u128;
+            }
+            Ok((signum < 0, magnitude))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+pub(crate) fn to_i128(value: &Value) -> Result<i128, Error> {
+    let (negative, magnitude) = sign_magnitude(value, "i128")?;
+    if negative {
+        0_i128
+            .checked_sub_unsigned(magnitude)
+            .ok_or_else(|| out_of_range(value, "i128"))
+    } else {
+        i128::try_from(magnitude).map_err(|_| out_of_range(value, "i128"))
+    }
+}
+
+pub(crate) fn to_u128(value: &Value) -> Result<u128, Error> {
+    match sign_magnitude(value, "u128")? {
+        (true, magnitude) if magnitude != 0 => Err(out_of_range(value, "u128")),
+        (_, magnitude) => Ok(magnitude),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fields, to_i128, to_u128, to_value};
+    use hessian_rs::value::List;
+    use hessian_rs::Value;
+
+    #[test]
+    fn test_big_integer() {
+        assert_eq!(
+            fields(true, 1 << 32 | 2),
+            [
+                Value::Int(-1),
+                Value::List(List::from(("[int", vec![Value::Int(1), Value::Int(2)])))
+            ]
+        );
+        for v in [0, 1, -1, i64::MIN as i128, i128::MAX, i128::MIN] {
+            let value = to_value(v < 0, v.unsigned_abs());
+            assert_eq!(to_i128(&value).unwrap(), v);
+        }
+        assert_eq!(to_u128(&to_value(false, u128::MAX)).unwrap(), u128::MAX);
+        assert!(to_i128(&to_value(false, u128::MAX)).is_err());
+        assert!(to_u128(&to_value(true, 1)).is_err());
+        assert_eq!(to_i128(&Value::Long(-5)).unwrap(), -5);
+        assert_eq!(
+            to_u128(&Value::from(u128::MAX.to_string())).unwrap(),
+            u128::MAX
+        );
+        assert_eq!(to_i128(&Value::from("-12")).unwrap(), -12);
+        assert!(to_u128(&Value::from("x")).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+//! Binary fields, as `serde_bytes` has them, which are base64 text in
+//! JSON. `#[serde(with = "serde_hessian::bytes")]` takes the place of
+//! `#[serde(with = "serde_bytes")]`.
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! # use serde_hessian::{de::from_slice, from_value, ser::to_vec};
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! struct Attachment {
+//!     #[serde(with = "serde_hessian::bytes")]
+//!     data: Vec<u8>,
+//! }
+//!
+//! let attachment = Attachment { data: vec![1, 2, 3] };
+//! let buf = to_vec(&attachment).unwrap();
+//! assert_eq!(from_slice::<_, Attachment>(&buf[..]).unwrap(), attachment);
+//!
+//! let base64 = hessian_rs::hessian!({ "data": "AQID" });
+//! assert_eq!(from_value::<Attachment>(base64).unwrap(), attachment);
+//! ```
+//!
+//! The field is written as hessian binary rather than a list of ints, like
+//! java writes a `byte[]`, and as a padded base64 string by human readable
+//! formats, e.g. `serde_json`. It's read from binary, a list of ints or
+//! base64 text, standard or URL safe, by the hessian deserializers of the
+//! input and of a `Value` alike. Any type which is `AsRef<[u8]>` and
+//! `From<Vec<u8>>` can be the field, e.g. a `Vec<u8>` or a `Box<[u8]>`.
+
+use std::fmt;
+
+use hessian_rs::Value;
+use serde::de::{Deserializer, Error, SeqAccess, Visitor};
+use serde::ser::Serializer;
+
+pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]> + ?Sized,
+    S: Serializer,
+{
+    if serializer.is_human_readable() {
+        let text = Value::Bytes(bytes.as_ref().to_vec().into()).as_base64();
+        return serializer.serialize_str(&text.unwrap_or_default());
+    }
+    serializer.serialize_bytes(bytes.as_ref())
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: From<Vec<u8>>,
+    D: Deserializer<'de>,
+{
+    // a human readable format may read a string asked for bytes as its
+    // UTF-8, as `serde_json` does
+    if deserializer.is_human_readable() {
+        return deserializer.deserialize_any(BytesVisitor).map(T::from);
+    }
+    deserializer.deserialize_byte_buf(BytesVisitor).map(T::from)
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "binary or base64 text")
+    }
+
+    fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        match Value::from_base64(v) {
+            Some(Value::Bytes(bytes)) => Ok(bytes.into_vec()),
+            _ => Err(E::custom(format!("invalid base64 {:?}", v))),
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(b) = seq.next_element()? {
+            bytes.push(b);
+        }
+        Ok(bytes)
+    }
+}
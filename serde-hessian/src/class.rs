@@ -0,0 +1,230 @@
+//! Java class names of serde types.
+//!
+//! Structs and enums are written with the name serde gives them, the rust
+//! type name unless it's renamed. The class names registered here replace
+//! it, in every serializer and when a deserializer validates class names.
+//! `SerializerConfig::class_name` and `Deserializer::class_name` override
+//! them for a single serializer or deserializer.
+//!
+//! Serde only gives the name of a type to serializers, so two rust types of
+//! the same serde name, e.g. `a::Car` and `b::Car`, can't be written as
+//! different classes. `register_java_type` fails for the second one rather
+//! than writing the first one as the class of the second.
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! # use serde_hessian::{class, ser::to_vec, JavaType};
+//! # use hessian_rs::hessian;
+//! # #[derive(Serialize, Deserialize)]
+//! # struct Car {
+//! #     color: String,
+//! # }
+//! impl JavaType for Car {
+//!     fn java_class() -> &'static str {
+//!         "com.example.Car"
+//!     }
+//! }
+//!
+//! class::register_java_type::<Car>()?;
+//! let buf = to_vec(&Car { color: "red".to_string() })?;
+//! let car = hessian!("com.example.Car" => { "color": "red" });
+//! assert_eq!(hessian_rs::from_slice(&buf)?, car);
+//! # Ok::<(), serde_hessian::error::Error>(())
+//! ```
+
+use std::any::{type_name, TypeId};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::error::Error;
+
+/// A type written as an instance of a java class.
+pub trait JavaType {
+    fn java_class() -> &'static str;
+}
+
+// The class of a serde name, with the rust type which registered it
+struct Registration {
+    class: &'static str,
+    rust_type: Option<(TypeId, &'static str)>,
+}
+
+// Class names by serde name
+static CLASS_NAMES: RwLock<Option<HashMap<&'static str, Registration>>> = RwLock::new(None);
+
+/// Write the type named `name` for serde as the class `class`, whichever
+/// rust type it is.
+pub fn register_class_name(name: &'static str, class: &'static str) {
+    let mut names = CLASS_NAMES.write().unwrap_or_else(|e| e.into_inner());
+    let registration = Registration {
+        class,
+        rust_type: None,
+    };
+    names
+        .get_or_insert_with(HashMap::new)
+        .insert(name, registration);
+}
+
+/// Write `T` as its `JavaType::java_class`.
+///
+/// The serde name of `T` is found by deserializing it, which fails for types
+/// other than structs, enums and tuple structs. Fails as well if another
+/// type of the same serde name is registered.
+pub fn register_java_type<T: JavaType + DeserializeOwned + 'static>() -> Result<(), Error> {
+    let name = serde_name::<T>()?;
+    let mut names = CLASS_NAMES.write().unwrap_or_else(|e| e.into_inner());
+    let names = names.get_or_insert_with(HashMap::new);
+    if let Some(Registration {
+        rust_type: Some((id, other)),
+        ..
+    }) = names.get(name)
+    {
+        if *id != TypeId::of::<T>() {
+            return Err(de::Error::custom(format!(
+                "{} and {} are both named {} for serde",
+                other,
+                type_name::<T>(),
+                name
+            )));
+        }
+    }
+    let registration = Registration {
+        class: T::java_class(),
+        rust_type: Some((TypeId::of::<T>(), type_name::<T>())),
+    };
+    names.insert(name, registration);
+    Ok(())
+}
+
+/// The class of the type named `name` for serde, `name` unless one was
+/// registered.
+pub fn class_name(name: &'static str) -> &'static str {
+    let names = CLASS_NAMES.read().unwrap_or_else(|e| e.into_inner());
+    names
+        .as_ref()
+        .and_then(|names| names.get(name))
+        .map_or(name, |registration| registration.class)
+}
+
+// The name `T` gives to `deserialize_struct` or `deserialize_enum`
+fn serde_name<T: DeserializeOwned>() -> Result<&'static str, Error> {
+    let mut name = None;
+    // the probe always fails, after recording the name
+    let _ = T::deserialize(NameProbe(&mut name));
+    name.ok_or_else(|| de::Error::custom("not a struct or an enum"))
+}
+
+struct NameProbe<'a>(&'a mut Option<&'static str>);
+
+impl<'de, 'a> de::Deserializer<'de> for NameProbe<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(de::Error::custom("not a struct or an enum"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Error> {
+        *self.0 = Some(name);
+        Err(de::Error::custom("name probed"))
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, Error> {
+        *self.0 = Some(name);
+        Err(de::Error::custom("name probed"))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Error> {
+        *self.0 = Some(name);
+        Err(de::Error::custom("name probed"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        map identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{class_name, register_class_name, register_java_type, JavaType};
+    use serde::Deserialize;
+
+    // The registrations are global, the serde names of the tests are unique
+    #[derive(Deserialize)]
+    #[serde(rename = "class.tests.Renamed")]
+    struct Probed {
+        _a: i32,
+    }
+
+    mod other {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "class.tests.Renamed")]
+        pub struct Probed {
+            _a: i32,
+        }
+
+        impl super::JavaType for Probed {
+            fn java_class() -> &'static str {
+                "example.OtherProbed"
+            }
+        }
+    }
+
+    impl JavaType for Probed {
+        fn java_class() -> &'static str {
+            "example.Probed"
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        assert_eq!(
+            class_name("class.tests.Unregistered"),
+            "class.tests.Unregistered"
+        );
+        register_class_name("class.tests.Registered", "example.Registered");
+        assert_eq!(class_name("class.tests.Registered"), "example.Registered");
+
+        register_java_type::<Probed>().unwrap();
+        assert_eq!(class_name("class.tests.Renamed"), "example.Probed");
+        assert!(register_java_type::<JavaString>().is_err());
+
+        // another type of the same serde name
+        register_java_type::<Probed>().unwrap();
+        assert!(register_java_type::<other::Probed>().is_err());
+        assert_eq!(class_name("class.tests.Renamed"), "example.Probed");
+    }
+
+    struct JavaString;
+
+    impl<'de> Deserialize<'de> for JavaString {
+        fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            String::deserialize(d).map(|_| JavaString)
+        }
+    }
+
+    impl JavaType for JavaString {
+        fn java_class() -> &'static str {
+            "java.lang.String"
+        }
+    }
+}
@@ -1,19 +1,161 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use hessian_rs::{de::Deserializer as HessianDecoder, ByteCodecType};
 
+use crate::big_integer;
+use crate::class;
 use crate::error::Error;
-use hessian_rs::constant::List as ListType;
+use crate::keys::STRINGIFY_KEYS;
+use crate::typed::TYPED;
+use hessian_rs::constant::{List as ListType, Object as ObjectType};
+use hessian_rs::value::Definition;
 use hessian_rs::Value;
 use serde::de::{self, IntoDeserializer, Visitor};
 
+mod key;
+
+pub use hessian_rs::de::DuplicateKeys;
+
+/// Configuration of a `Deserializer`, see `Deserializer::from_bytes_with`.
+#[derive(Debug, Clone, Default)]
+pub struct DeserializerConfig {
+    deny_unknown_fields: bool,
+    validate_class_name: bool,
+    class_names: HashMap<&'static str, &'static str>,
+    lenient: bool,
+    strict_numbers: bool,
+    duplicate_keys: Option<DuplicateKeys>,
+}
+
+impl DeserializerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// See `Deserializer::deny_unknown_fields`.
+    pub fn deny_unknown_fields(mut self, deny: bool) -> Self {
+        self.deny_unknown_fields = deny;
+        self
+    }
+
+    /// See `Deserializer::validate_class_name`.
+    pub fn validate_class_name(mut self, validate: bool) -> Self {
+        self.validate_class_name = validate;
+        self
+    }
+
+    /// See `Deserializer::class_name`.
+    pub fn class_name(mut self, name: &'static str, class: &'static str) -> Self {
+        self.class_names.insert(name, class);
+        self
+    }
+
+    /// Coerce the values java services send for another type: a string to a
+    /// number, a number to a bool, non-zero being `true`, and a list of a
+    /// single element to the element where a number, a bool or a string is
+    /// expected. Disabled by default.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Return an error when a number doesn't fit the integer it's
+    /// deserialized to: a long out of the range of an `i32`, a negative
+    /// number for an unsigned integer or a double with a fraction. By default
+    /// it's truncated as by `as`.
+    pub fn strict_numbers(mut self, strict: bool) -> Self {
+        self.strict_numbers = strict;
+        self
+    }
+
+    /// What to do with a key repeated in a map, keys are compared as they
+    /// are written. By default every entry is passed to the target, so most
+    /// maps keep the last value and a struct fails on a duplicate field.
+    /// `DuplicateKeys::Last` reads each map twice, once to find the last
+    /// entries.
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = Some(policy);
+        self
+    }
+}
+
+/// The input of a `Deserializer`, strings and bytes are borrowed from it
+/// for `&'de str` and `&'de [u8]` when it's a reference which lives for
+/// `'de`, and copied otherwise.
+pub trait Input<'de>: AsRef<[u8]> {
+    /// The input, if it lives for `'de`
+    fn borrow_input(&self) -> Option<&'de [u8]>;
+}
+
+impl<'de, T: AsRef<[u8]> + ?Sized> Input<'de> for &'de T {
+    fn borrow_input(&self) -> Option<&'de [u8]> {
+        Some((*self).as_ref())
+    }
+}
+
+impl<'de> Input<'de> for Vec<u8> {
+    fn borrow_input(&self) -> Option<&'de [u8]> {
+        None
+    }
+}
+
+impl<'de> Input<'de> for Box<[u8]> {
+    fn borrow_input(&self) -> Option<&'de [u8]> {
+        None
+    }
+}
+
+impl<'de, const N: usize> Input<'de> for [u8; N] {
+    fn borrow_input(&self) -> Option<&'de [u8]> {
+        None
+    }
+}
+
+/// Deserialize hessian 2.0 values with serde.
+///
+/// The entries of a map and the fields of an object are visited in the
+/// order they are in the input, so an `IndexMap` keeps the order they were
+/// written in, a `#[serde(flatten)]` field gets them in that order and when
+/// a key is repeated the last value is the one kept by most maps, see
+/// `DeserializerConfig::duplicate_keys`.
 pub struct Deserializer<R: AsRef<[u8]>> {
     de: HessianDecoder<R>,
+    config: DeserializerConfig,
+    // The containers the value being deserialized is in, outermost first
+    path: Vec<Segment>,
+    // Whether the path has been added to the error being returned
+    path_reported: bool,
+    // Whether non-string map keys are read as strings, see `StringifyKeys`
+    stringify_keys: bool,
+}
+
+// A step of the path to the value being deserialized
+enum Segment {
+    // the element at an index of a list
+    Index(usize),
+    // the value of the map entry whose key starts at an offset of the input,
+    // the key is only read again for an error
+    Key(u64),
+    // the value of a field of an object
+    Field(Arc<Definition>, usize),
 }
 
 struct MapAccess<'a, R: AsRef<[u8]>> {
     de: &'a mut Deserializer<R>,
     name: Option<String>,
+    // Known fields of the target struct, only set when unknown fields are denied
+    fields: Option<&'static [&'static str]>,
+    // The keys read so far as written, when the first of duplicate keys
+    // wins or they are denied
+    seen: HashSet<Vec<u8>>,
+    // Whether each entry is followed by one with the same key, when the
+    // last of duplicate keys wins
+    superseded: Option<Vec<bool>>,
+    entry: usize,
 }
 
 struct SeqAccess<'a, R: AsRef<[u8]>> {
@@ -23,6 +165,15 @@ struct SeqAccess<'a, R: AsRef<[u8]>> {
     inx: usize,
 }
 
+/// Visit a hessian object as a map, the keys are the field names
+/// from its class definition.
+struct ObjectAccess<'a, R: AsRef<[u8]>> {
+    de: &'a mut Deserializer<R>,
+    // Shared with the deserializer, the names aren't copied per object
+    definition: Arc<Definition>,
+    inx: usize,
+}
+
 struct EnumAccess<'a, R: AsRef<[u8]>> {
     de: &'a mut Deserializer<R>,
 }
@@ -33,7 +184,7 @@ impl<'a, R: AsRef<[u8]>> EnumAccess<'a, R> {
     }
 }
 
-impl<'de, 'a, R: AsRef<[u8]>> de::EnumAccess<'de> for EnumAccess<'a, R> {
+impl<'de, 'a, R: Input<'de>> de::EnumAccess<'de> for EnumAccess<'a, R> {
     type Error = Error;
 
     type Variant = Self;
@@ -47,65 +198,249 @@ impl<'de, 'a, R: AsRef<[u8]>> de::EnumAccess<'de> for EnumAccess<'a, R> {
     }
 }
 
-impl<'de, 'a, R: AsRef<[u8]>> de::VariantAccess<'de> for EnumAccess<'a, R> {
+impl<'a, R: AsRef<[u8]>> EnumAccess<'a, R> {
+    // Read the end of the single entry map holding the variant
+    fn end(&mut self) -> Result<(), Error> {
+        match self.de.de.read_byte()? {
+            b'Z' => Ok(()),
+            tag => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
+                format!(
+                    "deserialize enum expect the end of the variant map, but get tag {}",
+                    ByteCodecType::from(tag)
+                ),
+            ))),
+        }
+    }
+}
+
+// Variants other than unit variants are written as a single entry map
+// typed by the enum name, `{ VARIANT: CONTENT }`, the variant name has
+// been read by `variant_seed`.
+impl<'de, 'a, R: Input<'de>> de::VariantAccess<'de> for EnumAccess<'a, R> {
     type Error = Error;
 
-    // If the `Visitor` expected this variant to be a unit variant, the input
-    // should have been the plain string case handled in `deserialize_enum`.
-    fn unit_variant(self) -> Result<(), Self::Error> {
-        unreachable!("unit_variant")
+    // Unit variants are usually plain strings handled in `deserialize_enum`,
+    // accept `{ VARIANT: null }` as well.
+    fn unit_variant(mut self) -> Result<(), Self::Error> {
+        match self.de.de.read_value()? {
+            Value::Null => self.end(),
+            v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
+                format!(
+                    "deserialize unit variant expect a null value, but get {}",
+                    v
+                ),
+            ))),
+        }
     }
 
-    // Newtype variants are represented in JSON as `{ NAME: VALUE }` so
-    // deserialize the value here.
-    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    // Newtype variants are `{ VARIANT: VALUE }`
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value, Self::Error>
     where
         T: de::DeserializeSeed<'de>,
     {
-        seed.deserialize(self.de)
+        let value = seed.deserialize(&mut *self.de)?;
+        self.end()?;
+        Ok(value)
     }
 
-    // Tuple variants are represented in JSON as `{ NAME: [DATA...] }` so
-    // deserialize the sequence of data here.
-    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    // Tuple variants are `{ VARIANT: [DATA...] }`, the list is typed
+    // `NAME.VARIANT`
+    fn tuple_variant<V>(mut self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        de::Deserializer::deserialize_seq(self.de, visitor)
+        let value = de::Deserializer::deserialize_seq(&mut *self.de, visitor)?;
+        self.end()?;
+        Ok(value)
     }
 
-    // Struct variants are represented in JSON as `{ NAME: { K: V, ... } }` so
-    // deserialize the inner map here.
+    // Struct variants are `{ VARIANT: { K: V, ... } }`, the inner map is
+    // typed `VARIANT`
     fn struct_variant<V>(
-        self,
+        mut self,
         _fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        de::Deserializer::deserialize_map(self.de, visitor)
+        let value = de::Deserializer::deserialize_map(&mut *self.de, visitor)?;
+        self.end()?;
+        Ok(value)
     }
 }
 
 impl<'a, R: AsRef<[u8]>> MapAccess<'a, R> {
-    fn new(de: &'a mut Deserializer<R>, name: Option<String>) -> Self {
-        MapAccess { de, name }
+    fn new(
+        de: &'a mut Deserializer<R>,
+        name: Option<String>,
+        fields: Option<&'static [&'static str]>,
+    ) -> Self {
+        let position = de.de.position();
+        de.push(Segment::Key(position));
+        MapAccess {
+            de,
+            name,
+            fields,
+            seen: HashSet::new(),
+            superseded: None,
+            entry: 0,
+        }
+    }
+
+    // Skip the next entry if its key is a duplicate the policy drops, or
+    // fail if it denies duplicates
+    fn skip_duplicate(&mut self, policy: DuplicateKeys) -> Result<bool, Error> {
+        let entry = self.entry;
+        self.entry += 1;
+        let skip = match policy {
+            DuplicateKeys::Last => {
+                if self.superseded.is_none() {
+                    self.superseded = Some(superseded(&mut self.de.de)?);
+                }
+                self.superseded.as_ref().and_then(|s| s.get(entry)) == Some(&true)
+            }
+            DuplicateKeys::First | DuplicateKeys::Error => {
+                let key = next_key(&mut self.de.de)?;
+                match self.seen.contains(&key) {
+                    false => {
+                        self.seen.insert(key);
+                        false
+                    }
+                    true if policy == DuplicateKeys::First => true,
+                    true => {
+                        let key = match HessianDecoder::new(&key[..]).read_value() {
+                            Ok(key) => key.to_string(),
+                            Err(_) => "?".to_string(),
+                        };
+                        return Err(Error::SyntaxError(hessian_rs::ErrorKind::DuplicateKey(key)));
+                    }
+                }
+            }
+        };
+        if skip {
+            self.de.de.skip_value()?;
+            self.de.de.skip_value()?;
+        }
+        Ok(skip)
+    }
+}
+
+// The key of the next entry of a map as written
+fn next_key<R: AsRef<[u8]>>(de: &mut HessianDecoder<R>) -> Result<Vec<u8>, Error> {
+    let start = de.position() as usize;
+    let end = de.look_ahead(|de| {
+        de.skip_value()?;
+        Ok(de.position() as usize)
+    })?;
+    Ok(de.get_ref().as_ref()[start..end].to_vec())
+}
+
+// Whether each of the next entries of a map is followed by one with the
+// same key
+fn superseded<R: AsRef<[u8]>>(de: &mut HessianDecoder<R>) -> Result<Vec<bool>, Error> {
+    let superseded = de.look_ahead(|de| {
+        let mut last = HashMap::new();
+        let mut superseded = Vec::new();
+        while de.peek_byte()? != b'Z' {
+            let start = de.position() as usize;
+            de.skip_value()?;
+            let key = de.get_ref().as_ref()[start..de.position() as usize].to_vec();
+            if let Some(entry) = last.insert(key, superseded.len()) {
+                superseded[entry] = true;
+            }
+            superseded.push(false);
+            de.skip_value()?;
+        }
+        Ok(superseded)
+    })?;
+    Ok(superseded)
+}
+
+impl<'a, R: AsRef<[u8]>> Drop for MapAccess<'a, R> {
+    fn drop(&mut self) {
+        self.de.path.pop();
     }
 }
 
-impl<'de, 'a, R: AsRef<[u8]>> de::MapAccess<'de> for MapAccess<'a, R> {
+impl<'de, 'a, R: Input<'de>> de::MapAccess<'de> for MapAccess<'a, R> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
     where
         K: de::DeserializeSeed<'de>,
     {
-        if self.de.de.peek_byte()? == b'Z' {
-            self.de.de.read_byte()?;
-            Ok(None)
-        } else {
-            Ok(Some(seed.deserialize(&mut *self.de)?))
+        loop {
+            if self.de.de.peek_byte()? == b'Z' {
+                self.de.de.read_byte()?;
+                return Ok(None);
+            }
+            if let Some(Segment::Key(key)) = self.de.path.last_mut() {
+                *key = self.de.de.position();
+            }
+            let policy = self.de.config.duplicate_keys;
+            match policy {
+                Some(policy) if self.skip_duplicate(policy)? => continue,
+                _ => break,
+            }
+        }
+        let key = match self.fields {
+            Some(fields) => match self.de.de.read_value()? {
+                Value::String(key) if fields.contains(&key.as_str()) => {
+                    let key: de::value::StringDeserializer<Error> =
+                        key.into_string().into_deserializer();
+                    Ok(Some(seed.deserialize(key)?))
+                }
+                Value::String(key) => Err(de::Error::unknown_field(&key, fields)),
+                v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
+                    format!(
+                        "deserialize struct field expect a string key, but get {}",
+                        v
+                    ),
+                ))),
+            },
+            None => Ok(Some(seed.deserialize(key::MapKey { de: &mut *self.de })?)),
+        };
+        key.map_err(|e| self.de.at_path(e))
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.de)
+            .map_err(|e| self.de.at_path(e))
+    }
+}
+
+impl<'a, R: AsRef<[u8]>> ObjectAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>, definition: Arc<Definition>) -> Self {
+        de.push(Segment::Field(definition.clone(), 0));
+        ObjectAccess {
+            de,
+            definition,
+            inx: 0,
+        }
+    }
+}
+
+impl<'de, 'a, R: Input<'de>> de::MapAccess<'de> for ObjectAccess<'a, R> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.definition.fields.get(self.inx) {
+            Some(field) => {
+                if let Some(Segment::Field(_, inx)) = self.de.path.last_mut() {
+                    *inx = self.inx;
+                }
+                self.inx += 1;
+                let key: de::value::StrDeserializer<Error> = field.as_str().into_deserializer();
+                Ok(Some(seed.deserialize(key)?))
+            }
+            None => Ok(None),
         }
     }
 
@@ -113,8 +448,25 @@ impl<'de, 'a, R: AsRef<[u8]>> de::MapAccess<'de> for MapAccess<'a, R> {
     where
         V: de::DeserializeSeed<'de>,
     {
-        let v = seed.deserialize(&mut *self.de)?;
-        Ok(v)
+        seed.deserialize(&mut *self.de)
+            .map_err(|e| self.de.at_path(e))
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.definition.fields.len() - self.inx)
+    }
+}
+
+impl<'a, R: AsRef<[u8]>> Drop for ObjectAccess<'a, R> {
+    fn drop(&mut self) {
+        self.de.path.pop();
+    }
+}
+
+impl<'a, R: AsRef<[u8]>> fmt::Display for ObjectAccess<'a, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ObjectAccess(class: {})", self.definition.name)
     }
 }
 
@@ -140,6 +492,7 @@ impl<'a, R: AsRef<[u8]>> fmt::Display for SeqAccess<'a, R> {
 
 impl<'a, R: AsRef<[u8]>> SeqAccess<'a, R> {
     fn new(de: &'a mut Deserializer<R>, name: Option<String>, len: Option<usize>) -> Self {
+        de.push(Segment::Index(0));
         SeqAccess {
             de,
             name,
@@ -149,7 +502,7 @@ impl<'a, R: AsRef<[u8]>> SeqAccess<'a, R> {
     }
 }
 
-impl<'de, 'a, R: AsRef<[u8]>> de::SeqAccess<'de> for SeqAccess<'a, R> {
+impl<'de, 'a, R: Input<'de>> de::SeqAccess<'de> for SeqAccess<'a, R> {
     type Error = Error;
 
     #[inline]
@@ -157,8 +510,8 @@ impl<'de, 'a, R: AsRef<[u8]>> de::SeqAccess<'de> for SeqAccess<'a, R> {
     where
         T: de::DeserializeSeed<'de>,
     {
-        let end = if self.len.is_some() {
-            self.len.unwrap() == self.inx
+        let end = if let Some(len) = self.len {
+            len == self.inx
         } else {
             self.de.de.peek_byte()? == b'Z'
         };
@@ -171,34 +524,386 @@ impl<'de, 'a, R: AsRef<[u8]>> de::SeqAccess<'de> for SeqAccess<'a, R> {
 
             return Ok(None);
         }
-        let value = seed.deserialize(&mut *self.de)?;
+        if let Some(Segment::Index(inx)) = self.de.path.last_mut() {
+            *inx = self.inx;
+        }
+        let value = seed
+            .deserialize(&mut *self.de)
+            .map_err(|e| self.de.at_path(e))?;
         self.inx += 1;
         Ok(Some(value))
     }
 
     #[inline(always)]
     fn size_hint(&self) -> Option<usize> {
-        if self.len.is_some() {
-            Some(self.len.unwrap() - self.inx)
-        } else {
-            None
-        }
+        self.len.map(|len| len - self.inx)
+    }
+}
+
+impl<'a, R: AsRef<[u8]>> Drop for SeqAccess<'a, R> {
+    fn drop(&mut self) {
+        self.de.path.pop();
     }
 }
 
 impl<R: AsRef<[u8]>> Deserializer<R> {
     pub fn new(de: HessianDecoder<R>) -> Self {
-        Deserializer { de }
+        Deserializer {
+            de,
+            config: DeserializerConfig::default(),
+            path: Vec::new(),
+            path_reported: false,
+            stringify_keys: false,
+        }
     }
 
     pub fn from_bytes(s: R) -> Result<Self, Error> {
         Ok(Deserializer::new(HessianDecoder::new(s)))
     }
+
+    pub fn from_bytes_with(s: R, config: DeserializerConfig) -> Result<Self, Error> {
+        Ok(Deserializer::from_bytes(s)?.config(config))
+    }
+
+    pub fn config(mut self, config: DeserializerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Return an error when a struct is decoded from a map or object
+    /// which has fields the struct doesn't declare, by default they are skipped.
+    pub fn deny_unknown_fields(mut self, deny: bool) -> Self {
+        self.config = self.config.deny_unknown_fields(deny);
+        self
+    }
+
+    /// Check the type of an incoming object or typed map against the struct name,
+    /// e.g. `#[serde(rename = "example.Car")]`, and return an error if they differ.
+    /// A class name registered in `crate::class` replaces the struct name.
+    /// Untyped maps carry no type and are always accepted.
+    pub fn validate_class_name(mut self, validate: bool) -> Self {
+        self.config = self.config.validate_class_name(validate);
+        self
+    }
+
+    /// Expect the class `class` for the struct or enum named `name` for
+    /// serde, overrides the class names registered in `crate::class`.
+    pub fn class_name(mut self, name: &'static str, class: &'static str) -> Self {
+        self.config = self.config.class_name(name, class);
+        self
+    }
+
+    /// Decode strings written by java with surrogate pairs (CESU-8), see
+    /// `hessian_rs::de::Deserializer::cesu8`.
+    pub fn cesu8(mut self, cesu8: bool) -> Self {
+        self.de = self.de.cesu8(cesu8);
+        self
+    }
+
+    /// Accept NaN and the infinities, see
+    /// `hessian_rs::de::Deserializer::allow_nan`. Enabled by default.
+    pub fn allow_nan(mut self, allow_nan: bool) -> Self {
+        self.de = self.de.allow_nan(allow_nan);
+        self
+    }
+
+    /// The class definitions read so far, see
+    /// `hessian_rs::de::Deserializer::definitions`.
+    pub fn definitions(&self) -> impl ExactSizeIterator<Item = &Definition> + '_ {
+        self.de.definitions()
+    }
+
+    // Enter a container, an error raised in it is a new one
+    fn push(&mut self, segment: Segment) {
+        self.path.push(segment);
+        self.path_reported = false;
+    }
+
+    // The path to the value being deserialized, e.g. `cars[1].color`
+    fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in &self.path {
+            let field = match segment {
+                Segment::Index(inx) => {
+                    path += &format!("[{}]", inx);
+                    continue;
+                }
+                Segment::Key(position) => {
+                    let input = &self.de.get_ref().as_ref()[*position as usize..];
+                    match HessianDecoder::new(input).read_value() {
+                        Ok(Value::String(key)) => key.into_string(),
+                        Ok(key) => {
+                            path += &format!("[{}]", key);
+                            continue;
+                        }
+                        Err(_) => "?".to_string(),
+                    }
+                }
+                Segment::Field(definition, inx) => definition.fields[*inx].clone(),
+            };
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path += &field;
+        }
+        path
+    }
+
+    // Add the path to the value being deserialized to the message of `err`,
+    // once, in the innermost container the error is raised in
+    fn at_path(&mut self, err: Error) -> Error {
+        if self.path_reported || self.path.is_empty() {
+            return err;
+        }
+        self.path_reported = true;
+        match err {
+            Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(msg)) => Error::SyntaxError(
+                hessian_rs::ErrorKind::UnexpectedType(format!("{} at {}", msg, self.path())),
+            ),
+            Error::SerdeDesrializeError(msg) => {
+                Error::SerdeDesrializeError(format!("{} at {}", msg, self.path()))
+            }
+            err => err,
+        }
+    }
+
+    // Read the value of a number, a bool or a string, a list of a single
+    // element is unwrapped in lenient mode
+    fn read_scalar(&mut self) -> Result<Value, Error> {
+        match self.de.read_value()? {
+            Value::List(mut list) if self.config.lenient && list.value().len() == 1 => {
+                Ok(list.pop().unwrap_or(Value::Null))
+            }
+            v => Ok(v),
+        }
+    }
+
+    // Parse the string read for a number in lenient mode
+    fn parse<T: FromStr>(&self, s: &str, typ: &str) -> Result<T, Error> {
+        s.trim().parse().map_err(|_| {
+            Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(format!(
+                "deserialize {} expect a number, but get string {:?}",
+                typ, s
+            )))
+        })
+    }
+
+    // Check in strict mode that the number read for an integer of type `typ`
+    // converts to it exactly, other values are left to the caller
+    fn check_integer<T: TryFrom<i128>>(&self, value: &Value, typ: &str) -> Result<(), Error> {
+        let problem = match *value {
+            Value::Int(v) if T::try_from(v as i128).is_err() => "is out of range",
+            Value::Long(v) if T::try_from(v as i128).is_err() => "is out of range",
+            // NaN and the infinities have no fraction either
+            Value::Double(v) if v.fract() != 0.0 => "isn't an integer",
+            // saturated when out of the range of `i128`
+            Value::Double(v) if T::try_from(v as i128).is_err() => "is out of range",
+            _ => return Ok(()),
+        };
+        Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
+            format!(
+                "deserialize {} expect a {} value, but get {} which {}",
+                typ, typ, value, problem
+            ),
+        )))
+    }
+
+    fn check_class_name(&self, expected: &'static str, actual: &str) -> Result<(), Error> {
+        let expected = match self.config.class_names.get(expected) {
+            Some(class) => class,
+            None => class::class_name(expected),
+        };
+        if self.config.validate_class_name && expected != actual {
+            return Err(Error::ClassNameMismatch(expected.into(), actual.into()));
+        }
+        Ok(())
+    }
+
+    /// Visit an object instance whose tag has already been consumed,
+    /// field values are keyed by the names in its class definition.
+    fn deserialize_object<'de, V>(
+        &mut self,
+        tag: ObjectType,
+        class_name: Option<&'static str>,
+        known_fields: Option<&'static [&'static str]>,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        R: Input<'de>,
+        V: de::Visitor<'de>,
+    {
+        let definition = self.de.read_shared_definition(tag)?;
+        self.visit_object(definition, class_name, known_fields, visitor)
+    }
+
+    fn visit_object<'de, V>(
+        &mut self,
+        definition: Arc<Definition>,
+        class_name: Option<&'static str>,
+        known_fields: Option<&'static [&'static str]>,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        R: Input<'de>,
+        V: de::Visitor<'de>,
+    {
+        if let Some(class_name) = class_name {
+            self.check_class_name(class_name, &definition.name)?;
+        }
+        if let Some(known_fields) = known_fields.filter(|_| self.config.deny_unknown_fields) {
+            let fields = &definition.fields;
+            if let Some(field) = fields.iter().find(|f| !known_fields.contains(&f.as_str())) {
+                return Err(de::Error::unknown_field(field, known_fields));
+            }
+        }
+        visitor.visit_map(ObjectAccess::new(self, definition))
+    }
+
+    /// Visit an object or a typed map as `{ "class": CLASS, "value": VALUE }`,
+    /// see `Typed`.
+    fn deserialize_typed<'de, V>(&mut self, visitor: V) -> Result<V::Value, Error>
+    where
+        R: Input<'de>,
+        V: de::Visitor<'de>,
+    {
+        let header = loop {
+            match ByteCodecType::from(self.de.read_byte()?) {
+                ByteCodecType::Definition => self.de.read_definition()?,
+                ByteCodecType::Map(true) => break Header::Map(self.de.read_type()?),
+                ByteCodecType::Object(o) => {
+                    break Header::Object(self.de.read_shared_definition(o)?)
+                }
+                v => {
+                    return Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
+                        format!(
+                        "deserialize typed value expect an object or a typed map, but get tag {}",
+                        v
+                    ),
+                    )))
+                }
+            }
+        };
+        visitor.visit_map(TypedAccess {
+            de: self,
+            header: Some(header),
+            inx: 0,
+        })
+    }
+}
+
+// The header of an object or a typed map which has been read
+enum Header {
+    Map(String),
+    Object(Arc<Definition>),
+}
+
+impl Header {
+    fn class(&self) -> &str {
+        match self {
+            Header::Map(name) => name,
+            Header::Object(def) => &def.name,
+        }
+    }
+}
+
+/// Visit the class name and the value of an object or a typed map whose
+/// header has been read.
+struct TypedAccess<'a, R: AsRef<[u8]>> {
+    de: &'a mut Deserializer<R>,
+    header: Option<Header>,
+    inx: usize,
+}
+
+impl<'de, 'a, R: Input<'de>> de::MapAccess<'de> for TypedAccess<'a, R> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let key = match self.inx {
+            0 => "class",
+            1 => "value",
+            _ => return Ok(None),
+        };
+        seed.deserialize(key.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        self.inx += 1;
+        let header = match self.header.take() {
+            Some(header) if self.inx == 1 => {
+                let class = header.class().to_string();
+                self.header = Some(header);
+                return seed.deserialize(class.into_deserializer());
+            }
+            Some(header) => header,
+            None => return Err(de::Error::custom("typed value visited twice")),
+        };
+        seed.deserialize(OpenedDeserializer {
+            de: &mut *self.de,
+            header,
+        })
+    }
+}
+
+/// Deserialize the fields of an object or a typed map whose header has
+/// been read, as a struct or a map.
+struct OpenedDeserializer<'a, R: AsRef<[u8]>> {
+    de: &'a mut Deserializer<R>,
+    header: Header,
+}
+
+impl<'de, 'a, R: Input<'de>> serde::Deserializer<'de> for OpenedDeserializer<'a, R> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.header {
+            Header::Map(name) => visitor.visit_map(MapAccess::new(self.de, Some(name), None)),
+            Header::Object(def) => self.de.visit_object(def, None, None, visitor),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let OpenedDeserializer { de, header } = self;
+        match header {
+            Header::Map(type_name) => {
+                de.check_class_name(name, &type_name)?;
+                let known_fields = Some(fields).filter(|_| de.config.deny_unknown_fields);
+                visitor.visit_map(MapAccess::new(de, Some(type_name), known_fields))
+            }
+            Header::Object(def) => de.visit_object(def, Some(name), Some(fields), visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
 }
 
-impl<'de, 'a, R> serde::Deserializer<'de> for &'a mut Deserializer<R>
+impl<'de, R> serde::Deserializer<'de> for &mut Deserializer<R>
 where
-    R: AsRef<[u8]>,
+    R: Input<'de>,
 {
     type Error = Error;
 
@@ -214,6 +919,11 @@ where
             hessian_rs::ByteCodecType::Long(_) => self.deserialize_i64(visitor),
             hessian_rs::ByteCodecType::Double(_) => self.deserialize_f64(visitor),
             hessian_rs::ByteCodecType::Binary(_) => self.deserialize_bytes(visitor),
+            // borrowed from the input when it can be, e.g. for a `Cow<str>` in
+            // an untagged enum
+            hessian_rs::ByteCodecType::String(_) if self.de.get_ref().borrow_input().is_some() => {
+                self.deserialize_str(visitor)
+            }
             hessian_rs::ByteCodecType::String(_) => self.deserialize_string(visitor),
             hessian_rs::ByteCodecType::List(_) => self.deserialize_seq(visitor),
             hessian_rs::ByteCodecType::Map(_) => self.deserialize_map(visitor),
@@ -222,10 +932,20 @@ where
                 self.de.read_definition()?;
                 self.deserialize_any(visitor)
             }
-            hessian_rs::ByteCodecType::Date(_) => todo!(),
-            hessian_rs::ByteCodecType::Object(_) => todo!(),
+            hessian_rs::ByteCodecType::Date(_) => match self.de.read_value()? {
+                Value::Date(v) => visitor.visit_i64(v),
+                v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
+                    format!("deserialize date expect a date value, but get {}", v),
+                ))),
+            },
+            hessian_rs::ByteCodecType::Object(o) => {
+                self.de.read_byte()?;
+                self.deserialize_object(o, None, None, visitor)
+            }
             hessian_rs::ByteCodecType::Ref => Err(Error::UnSupportedRefType),
-            hessian_rs::ByteCodecType::Unknown => todo!(),
+            hessian_rs::ByteCodecType::Unknown => Err(Error::SyntaxError(
+                hessian_rs::ErrorKind::InvalidTag(self.de.peek_byte()?),
+            )),
         }
     }
 
@@ -233,8 +953,10 @@ where
     where
         V: de::Visitor<'de>,
     {
-        match self.de.read_value()? {
+        match self.read_scalar()? {
             hessian_rs::Value::Bool(v) => visitor.visit_bool(v),
+            hessian_rs::Value::Int(v) if self.config.lenient => visitor.visit_bool(v != 0),
+            hessian_rs::Value::Long(v) if self.config.lenient => visitor.visit_bool(v != 0),
             _ => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 "deserialize bool expect a bool value".into(),
             ))),
@@ -259,10 +981,21 @@ where
     where
         V: de::Visitor<'de>,
     {
-        match self.de.read_value()? {
+        // Fast path for ints, the elements of `[int` lists are read without a `Value`
+        if let ByteCodecType::Int(_) = self.de.peek_byte_code_type()? {
+            return visitor.visit_i32(self.de.read_i32()?);
+        }
+        let value = self.read_scalar()?;
+        if self.config.strict_numbers {
+            self.check_integer::<i32>(&value, "i32")?;
+        }
+        match value {
             hessian_rs::Value::Int(v) => visitor.visit_i32(v),
             hessian_rs::Value::Long(v) => visitor.visit_i32(v as i32),
             hessian_rs::Value::Double(v) => visitor.visit_i32(v as i32),
+            hessian_rs::Value::String(s) if self.config.lenient => {
+                visitor.visit_i32(self.parse(&s, "i32")?)
+            }
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize i32 expect a i32 value, but get {}", v),
             ))),
@@ -273,10 +1006,20 @@ where
     where
         V: de::Visitor<'de>,
     {
-        match self.de.read_value()? {
+        if let ByteCodecType::Int(_) | ByteCodecType::Long(_) = self.de.peek_byte_code_type()? {
+            return visitor.visit_i64(self.de.read_i64()?);
+        }
+        let value = self.read_scalar()?;
+        if self.config.strict_numbers {
+            self.check_integer::<i64>(&value, "i64")?;
+        }
+        match value {
             hessian_rs::Value::Int(v) => visitor.visit_i64(v as i64),
             hessian_rs::Value::Long(v) => visitor.visit_i64(v),
             hessian_rs::Value::Double(v) => visitor.visit_i64(v as i64),
+            hessian_rs::Value::String(s) if self.config.lenient => {
+                visitor.visit_i64(self.parse(&s, "i64")?)
+            }
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize i64 expect a i64 value, but get {}", v),
             ))),
@@ -287,7 +1030,11 @@ where
     where
         V: de::Visitor<'de>,
     {
-        match self.de.read_value()? {
+        let value = self.read_scalar()?;
+        if self.config.strict_numbers {
+            self.check_integer::<u8>(&value, "u8")?;
+        }
+        match value {
             hessian_rs::Value::Int(v) => visitor.visit_u8(v as u8),
             hessian_rs::Value::Long(v) => visitor.visit_u8(v as u8),
             // Allow deserializing a double/bytes(length is 1) as a u8
@@ -304,6 +1051,9 @@ where
                     visitor.visit_char(b[0] as char)
                 }
             }
+            hessian_rs::Value::String(s) if self.config.lenient => {
+                visitor.visit_u8(self.parse(&s, "u8")?)
+            }
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize u8 expect a int/long value, but get {}", v),
             ))),
@@ -314,10 +1064,17 @@ where
     where
         V: de::Visitor<'de>,
     {
-        match self.de.read_value()? {
+        let value = self.read_scalar()?;
+        if self.config.strict_numbers {
+            self.check_integer::<u16>(&value, "u16")?;
+        }
+        match value {
             hessian_rs::Value::Int(v) => visitor.visit_u16(v as u16),
             hessian_rs::Value::Long(v) => visitor.visit_u16(v as u16),
             hessian_rs::Value::Double(v) => visitor.visit_u16(v as u16),
+            hessian_rs::Value::String(s) if self.config.lenient => {
+                visitor.visit_u16(self.parse(&s, "u16")?)
+            }
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize u16 expect a int/long value, but get {}", v),
             ))),
@@ -328,24 +1085,52 @@ where
     where
         V: de::Visitor<'de>,
     {
-        match self.de.read_value()? {
+        let value = self.read_scalar()?;
+        if self.config.strict_numbers {
+            self.check_integer::<u32>(&value, "u32")?;
+        }
+        match value {
             hessian_rs::Value::Int(v) => visitor.visit_u32(v as u32),
             hessian_rs::Value::Long(v) => visitor.visit_u32(v as u32),
             hessian_rs::Value::Double(v) => visitor.visit_u32(v as u32),
+            hessian_rs::Value::String(s) if self.config.lenient => {
+                visitor.visit_u32(self.parse(&s, "u32")?)
+            }
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize u32 expect a int/long value, but get {}", v),
             ))),
         }
     }
 
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_i128(big_integer::to_i128(&self.de.read_value()?)?)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_u128(big_integer::to_u128(&self.de.read_value()?)?)
+    }
+
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        match self.de.read_value()? {
+        let value = self.read_scalar()?;
+        if self.config.strict_numbers {
+            self.check_integer::<u64>(&value, "u64")?;
+        }
+        match value {
             hessian_rs::Value::Int(v) => visitor.visit_u64(v as u64),
             hessian_rs::Value::Long(v) => visitor.visit_u64(v as u64),
             hessian_rs::Value::Double(v) => visitor.visit_u64(v as u64),
+            hessian_rs::Value::String(s) if self.config.lenient => {
+                visitor.visit_u64(self.parse(&s, "u64")?)
+            }
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize u64 expect a int/long value, but get {}", v),
             ))),
@@ -356,10 +1141,13 @@ where
     where
         V: de::Visitor<'de>,
     {
-        match self.de.read_value()? {
+        match self.read_scalar()? {
             hessian_rs::Value::Int(v) => visitor.visit_f32(v as f32),
             hessian_rs::Value::Long(v) => visitor.visit_f32(v as f32),
             hessian_rs::Value::Double(v) => visitor.visit_f32(v as f32),
+            hessian_rs::Value::String(s) if self.config.lenient => {
+                visitor.visit_f32(self.parse(&s, "f32")?)
+            }
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize f32 expect a int/long value, but get {}", v),
             ))),
@@ -370,10 +1158,13 @@ where
     where
         V: de::Visitor<'de>,
     {
-        match self.de.read_value()? {
+        match self.read_scalar()? {
             hessian_rs::Value::Int(v) => visitor.visit_f64(v as f64),
             hessian_rs::Value::Long(v) => visitor.visit_f64(v as f64),
             hessian_rs::Value::Double(v) => visitor.visit_f64(v),
+            hessian_rs::Value::String(s) if self.config.lenient => {
+                visitor.visit_f64(self.parse(&s, "f64")?)
+            }
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize f64 expect a int/long value, but get {}", v),
             ))),
@@ -415,6 +1206,19 @@ where
                     )))
                 }
             }
+            // a java char is written as a string of one char
+            hessian_rs::Value::String(s) => {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => visitor.visit_char(c),
+                    _ => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
+                        format!(
+                            "deserialize char expect a string of one char, but get {:?}",
+                            s
+                        ),
+                    ))),
+                }
+            }
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize char expect a char value, but get {}", v),
             ))),
@@ -425,9 +1229,16 @@ where
     where
         V: de::Visitor<'de>,
     {
-        match self.de.read_value()? {
+        if let Some(input) = self.de.get_ref().borrow_input() {
+            if let Some(span) = self.de.read_str_span()? {
+                let s = std::str::from_utf8(&input[span])
+                    .map_err(|e| Error::SerdeDesrializeError(e.to_string()))?;
+                return visitor.visit_borrowed_str(s);
+            }
+        }
+        match self.read_scalar()? {
             hessian_rs::Value::Bytes(b) => {
-                let s = String::from_utf8(b)?;
+                let s = String::from_utf8(b.into_vec())?;
                 visitor.visit_str(&s)
             }
             hessian_rs::Value::String(s) => visitor.visit_str(&s),
@@ -441,12 +1252,12 @@ where
     where
         V: de::Visitor<'de>,
     {
-        match self.de.read_value()? {
+        match self.read_scalar()? {
             hessian_rs::Value::Bytes(b) => {
-                let s = String::from_utf8(b)?;
+                let s = String::from_utf8(b.into_vec())?;
                 visitor.visit_string(s)
             }
-            hessian_rs::Value::String(s) => visitor.visit_string(s),
+            hessian_rs::Value::String(s) => visitor.visit_string(s.into_string()),
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize string expect a string value, but get {}", v),
             ))),
@@ -457,20 +1268,37 @@ where
     where
         V: de::Visitor<'de>,
     {
+        // `[byte` lists are accepted as bytes
+        if let ByteCodecType::List(_) = self.de.peek_byte_code_type()? {
+            return visitor.visit_byte_buf(self.de.read_byte_list()?);
+        }
+        if let Some(input) = self.de.get_ref().borrow_input() {
+            if let Some(span) = self.de.read_bytes_span()? {
+                return visitor.visit_borrowed_bytes(&input[span]);
+            }
+        }
         match self.de.read_value()? {
             hessian_rs::Value::Bytes(b) => visitor.visit_bytes(&b),
+            hessian_rs::Value::String(s) => visitor.visit_string(s.into_string()),
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize bytes expect a bytes value, but get {}", v),
             ))),
         }
     }
 
+    // The bytes read are given to the visitor, which keeps them without a
+    // copy if it takes a `Vec<u8>`, e.g. `bytes::Bytes`. A string is given
+    // as a string, the visitor decides what bytes it is
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
+        if let ByteCodecType::List(_) = self.de.peek_byte_code_type()? {
+            return visitor.visit_byte_buf(self.de.read_byte_list()?);
+        }
         match self.de.read_value()? {
-            hessian_rs::Value::Bytes(b) => visitor.visit_byte_buf(b),
+            hessian_rs::Value::Bytes(b) => visitor.visit_byte_buf(b.into_vec()),
+            hessian_rs::Value::String(s) => visitor.visit_string(s.into_string()),
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize byte_buf expect a bytes value, but get {}", v),
             ))),
@@ -518,12 +1346,18 @@ where
 
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
+        if name == STRINGIFY_KEYS {
+            let stringify_keys = std::mem::replace(&mut self.stringify_keys, true);
+            let value = visitor.visit_newtype_struct(&mut *self);
+            self.stringify_keys = stringify_keys;
+            return value;
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -531,8 +1365,14 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let tag = self.de.read_byte()?;
-        match ByteCodecType::from(tag) {
+        // binary is a seq of bytes, e.g. a `Vec<u8>`
+        if let ByteCodecType::Binary(_) = self.de.peek_byte_code_type()? {
+            if let Value::Bytes(b) = self.de.read_value()? {
+                return visitor
+                    .visit_seq(de::value::SeqDeserializer::new(b.into_vec().into_iter()));
+            }
+        }
+        let (type_name, length) = match ByteCodecType::from(self.de.read_byte()?) {
             ByteCodecType::List(ListType::FixedLength(typed)) => {
                 let type_name = if typed {
                     Some(self.de.read_type()?)
@@ -547,7 +1387,7 @@ where
                         )))
                     }
                 };
-                visitor.visit_seq(SeqAccess::new(self, type_name, Some(length)))
+                (type_name, Some(length))
             }
             ByteCodecType::List(ListType::ShortFixedLength(typed, length)) => {
                 let type_name = if typed {
@@ -555,7 +1395,7 @@ where
                 } else {
                     None
                 };
-                visitor.visit_seq(SeqAccess::new(self, type_name, Some(length)))
+                (type_name, Some(length))
             }
             ByteCodecType::List(ListType::VarLength(typed)) => {
                 let type_name = if typed {
@@ -563,15 +1403,23 @@ where
                 } else {
                     None
                 };
-                visitor.visit_seq(SeqAccess::new(self, type_name, None))
+                (type_name, None)
             }
-            v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
-                format!(
-                    "deserialize seq expect a list or map tag, but get tag {}",
-                    v
-                ),
-            ))),
+            v => {
+                return Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
+                    format!(
+                        "deserialize seq expect a list or map tag, but get tag {}",
+                        v
+                    ),
+                )))
+            }
+        };
+        // a `[byte` list is read at once, e.g. into a `Vec<u8>`
+        if type_name.as_deref() == Some("[byte") {
+            let bytes = self.de.read_byte_items(length)?;
+            return visitor.visit_seq(de::value::SeqDeserializer::new(bytes.into_iter()));
         }
+        visitor.visit_seq(SeqAccess::new(self, type_name, length))
     }
 
     fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
@@ -605,8 +1453,14 @@ where
                 } else {
                     None
                 };
-                visitor.visit_map(MapAccess::new(self, type_name))
+                visitor.visit_map(MapAccess::new(self, type_name, None))
             }
+            // an object as the map of its fields
+            ByteCodecType::Definition => {
+                self.de.read_definition()?;
+                self.deserialize_map(visitor)
+            }
+            ByteCodecType::Object(o) => self.deserialize_object(o, None, None, visitor),
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize map expect a map tag, but get tag {}", v),
             ))),
@@ -622,6 +1476,9 @@ where
     where
         V: de::Visitor<'de>,
     {
+        if name == TYPED {
+            return self.deserialize_typed(visitor);
+        }
         let tag = self.de.read_byte()?;
         match ByteCodecType::from(tag) {
             ByteCodecType::Map(typed) => {
@@ -630,16 +1487,18 @@ where
                 } else {
                     None
                 };
-                visitor.visit_map(MapAccess::new(self, type_name))
+                if let Some(type_name) = &type_name {
+                    self.check_class_name(name, type_name)?;
+                }
+                let known_fields = Some(fields).filter(|_| self.config.deny_unknown_fields);
+                visitor.visit_map(MapAccess::new(self, type_name, known_fields))
             }
             ByteCodecType::Definition => {
                 self.de.read_definition()?;
                 self.deserialize_struct(name, fields, visitor)
             }
             ByteCodecType::Object(o) => {
-                // todo: check object type and fields
-                let def_len = self.de.read_definition_id(o)?.fields.len();
-                visitor.visit_seq(SeqAccess::new(self, None, Some(def_len)))
+                self.deserialize_object(o, Some(name), Some(fields), visitor)
             }
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize map expect a map tag, but get tag {}", v),
@@ -649,8 +1508,8 @@ where
 
     fn deserialize_enum<V>(
         self,
-        _name: &'static str,
-        _variants: &'static [&'static str],
+        name: &'static str,
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
@@ -669,6 +1528,30 @@ where
                 }
                 visitor.visit_enum(EnumAccess::new(self))
             }
+            ByteCodecType::Definition => {
+                self.de.read_byte()?;
+                self.de.read_definition()?;
+                self.deserialize_enum(name, variants, visitor)
+            }
+            // A java enum, an object with a `name` field
+            ByteCodecType::Object(o) => {
+                self.de.read_byte()?;
+                let def = self.de.read_shared_definition(o)?;
+                self.check_class_name(name, &def.name)?;
+                let mut variant = None;
+                for field in def.fields.iter() {
+                    let value = self.de.read_value()?;
+                    if field == "name" {
+                        variant = Some(value);
+                    }
+                }
+                match variant {
+                    Some(Value::String(variant)) => visitor.visit_enum(variant.into_deserializer()),
+                    _ => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
+                        format!("deserialize enum expect a name field in {}", def.name),
+                    ))),
+                }
+            }
             v => Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                 format!("deserialize enum can't support tag {}", v),
             ))),
@@ -686,13 +1569,18 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        self.de.skip_value()?;
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
     }
 }
 
 pub fn from_slice<'de, R, T>(read: R) -> Result<T, Error>
 where
-    R: AsRef<[u8]>,
+    R: Input<'de>,
     T: de::Deserialize<'de>,
 {
     let mut de = Deserializer::from_bytes(read)?;
@@ -705,10 +1593,11 @@ where
 mod tests {
     use crate::de::from_slice;
     use crate::de::Deserializer;
+    use crate::error::Error;
     use serde::Deserialize;
     use std::collections::HashMap;
 
-    fn test_decode_ok<'a, T>(rdr: &[u8], target: T)
+    fn test_decode_ok<'a, T>(rdr: &'a [u8], target: T)
     where
         T: Deserialize<'a> + std::cmp::PartialEq + std::fmt::Debug,
     {
@@ -736,11 +1625,11 @@ mod tests {
 
         // null
         {
-            test_decode_ok(&[b'N'], ());
+            test_decode_ok(b"N", ());
         }
 
         {
-            test_decode_ok(&[b'N'], None::<()>);
+            test_decode_ok(b"N", None::<()>);
         }
 
         // BasicType f32/f64
@@ -767,6 +1656,50 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_typed_list() {
+        test_decode_ok(
+            &[0x72, 0x05, b'[', b'l', b'o', b'n', b'g', 0xe1, 0x91],
+            vec![1_i64, 1_i64],
+        );
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Bytes(#[serde(deserialize_with = "deserialize_byte_buf")] Vec<u8>);
+
+        fn deserialize_byte_buf<'de, D: serde::Deserializer<'de>>(
+            de: D,
+        ) -> Result<Vec<u8>, D::Error> {
+            struct ByteBufVisitor;
+            impl<'de> serde::de::Visitor<'de> for ByteBufVisitor {
+                type Value = Vec<u8>;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    write!(f, "bytes")
+                }
+
+                fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+                    Ok(v)
+                }
+            }
+            de.deserialize_byte_buf(ByteBufVisitor)
+        }
+
+        test_decode_ok(
+            &[0x72, 0x05, b'[', b'b', b'y', b't', b'e', 0x91, 0x92],
+            Bytes(vec![1, 2]),
+        );
+        test_decode_ok(&[0x22, 0x01, 0x02], Bytes(vec![1, 2]));
+        // a plain `Vec<u8>` reads a `[byte` list at once, java bytes are signed
+        test_decode_ok(
+            &[0x72, 0x05, b'[', b'b', b'y', b't', b'e', 0x91, 0x8f],
+            vec![1_u8, 0xff],
+        );
+        assert!(from_slice::<_, Vec<u8>>(&[
+            0x72, 0x05, b'[', b'b', b'y', b't', b'e', 0x91, 0xc9, 0x00
+        ])
+        .is_err());
+    }
+
     #[test]
     fn test_basic_object_type() {
         {
@@ -779,8 +1712,8 @@ mod tests {
         }
 
         {
-            test_decode_ok(&[b'T'], true);
-            test_decode_ok(&[b'F'], false);
+            test_decode_ok(b"T", true);
+            test_decode_ok(b"F", false);
         }
 
         {
@@ -841,6 +1774,610 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_struct_field_names() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(rename = "example.Car")]
+        struct Car {
+            #[serde(rename = "Color")]
+            color: String,
+            #[serde(rename = "Model")]
+            model: String,
+            #[serde(default)]
+            mileage: i32,
+        }
+
+        // fields of the definition are declared in a different order from the struct
+        test_decode_ok(
+            &[
+                b'C', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r', 0x92,
+                0x05, b'M', b'o', b'd', b'e', b'l', 0x05, b'C', b'o', b'l', b'o', b'r', b'O', 0x90,
+                0x08, b'c', b'o', b'r', b'v', b'e', b't', b't', b'e', 0x03, b'r', b'e', b'd',
+            ],
+            Car {
+                color: "red".to_string(),
+                model: "corvette".to_string(),
+                mileage: 0,
+            },
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(rename = "example.Car")]
+        struct Car {
+            #[serde(rename = "Color")]
+            color: String,
+        }
+
+        // object with an extra `Made` date field and a `Parts` list field
+        let object = &[
+            b'C', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r', 0x93,
+            0x04, b'M', b'a', b'd', b'e', 0x05, b'C', b'o', b'l', b'o', b'r', 0x05, b'P', b'a',
+            b'r', b't', b's', b'O', 0x90, 0x4a, 0x00, 0x00, 0x00, 0xd0, 0x4b, 0x92, 0x84, 0xb8,
+            0x03, b'r', b'e', b'd', 0x79, 0x04, b'w', b'h', b'e', b'e', b'l',
+        ];
+        let car = Car {
+            color: "red".to_string(),
+        };
+        test_decode_ok(object, car);
+
+        let mut de = Deserializer::from_bytes(object)
+            .unwrap()
+            .deny_unknown_fields(true);
+        assert!(Car::deserialize(&mut de).is_err());
+
+        let map = &[
+            b'H', 0x05, b'C', b'o', b'l', b'o', b'r', 0x03, b'r', b'e', b'd', 0x04, b'M', b'a',
+            b'd', b'e', 0x4a, 0x00, 0x00, 0x00, 0xd0, 0x4b, 0x92, 0x84, 0xb8, b'Z',
+        ];
+        let mut de = Deserializer::from_bytes(map)
+            .unwrap()
+            .deny_unknown_fields(true);
+        assert!(Car::deserialize(&mut de).is_err());
+    }
+
+    #[test]
+    fn test_error_path() {
+        use hessian_rs::{hessian, to_vec};
+
+        #[derive(Debug, Deserialize)]
+        struct Car {
+            #[allow(dead_code)]
+            color: String,
+            #[allow(dead_code)]
+            mileage: i32,
+        }
+        #[derive(Debug, Deserialize)]
+        struct Garage {
+            #[allow(dead_code)]
+            cars: Vec<Car>,
+            #[allow(dead_code)]
+            owners: HashMap<String, Vec<i32>>,
+        }
+        let error = |value| {
+            let buf = to_vec(&value).unwrap();
+            from_slice::<_, Garage>(buf).unwrap_err().to_string()
+        };
+
+        // a map
+        let car = |mileage| hessian!({ "color": "red", "mileage": mileage });
+        let garage = hessian!({
+            "cars": [(car(hessian!(1))), (car(hessian!("far")))],
+            "owners": {}
+        });
+        assert!(error(garage).ends_with(" at cars[1].mileage"));
+        let garage = hessian!({ "cars": [], "owners": { "bob": [1, "two"] } });
+        assert!(error(garage).ends_with(" at owners.bob[1]"));
+        // a missing field is reported at the struct
+        let garage = hessian!({ "cars": [(car(hessian!(1))), { "color": "red" }], "owners": {} });
+        assert_eq!(
+            error(garage),
+            "serde deserialize error: missing field `mileage` at cars[1]"
+        );
+
+        // an object
+        #[derive(serde::Serialize)]
+        #[serde(rename = "example.Car")]
+        struct BadCar {
+            color: i32,
+            mileage: i32,
+        }
+        #[derive(serde::Serialize)]
+        struct BadGarage {
+            cars: Vec<BadCar>,
+            owners: HashMap<String, Vec<i32>>,
+        }
+        let buf = crate::ser::to_vec(&BadGarage {
+            cars: vec![BadCar {
+                color: 1,
+                mileage: 1,
+            }],
+            owners: HashMap::new(),
+        })
+        .unwrap();
+        let err = from_slice::<_, Garage>(buf).unwrap_err();
+        assert!(err.to_string().ends_with(" at cars[0].color"), "{}", err);
+
+        // the path isn't kept for the next value
+        let buf = to_vec(&hessian!("far")).unwrap();
+        let err = from_slice::<_, i32>(buf).unwrap_err();
+        assert!(!err.to_string().contains(" at "), "{}", err);
+    }
+
+    #[test]
+    fn test_lenient() {
+        use super::DeserializerConfig;
+        use hessian_rs::{hessian, to_vec};
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Car {
+            mileage: i32,
+            price: f64,
+            used: bool,
+            color: String,
+        }
+        let buf = to_vec(&hessian!({
+            "mileage": " 65536",
+            "price": "1.5",
+            "used": 1,
+            "color": ["red"]
+        }))
+        .unwrap();
+        assert!(from_slice::<_, Car>(&buf).is_err());
+        let config = DeserializerConfig::new().lenient(true);
+        let mut de = Deserializer::from_bytes_with(&buf, config.clone()).unwrap();
+        assert_eq!(
+            Car::deserialize(&mut de).unwrap(),
+            Car {
+                mileage: 65536,
+                price: 1.5,
+                used: true,
+                color: "red".to_string(),
+            }
+        );
+
+        let buf = to_vec(&hessian!(["1", "2"])).unwrap();
+        let mut de = Deserializer::from_bytes_with(&buf, config.clone()).unwrap();
+        assert_eq!(Vec::<u64>::deserialize(&mut de).unwrap(), vec![1, 2]);
+        let buf = to_vec(&hessian!("far")).unwrap();
+        let mut de = Deserializer::from_bytes_with(&buf, config).unwrap();
+        assert!(i32::deserialize(&mut de).is_err());
+    }
+
+    #[test]
+    fn test_strict_numbers() {
+        use super::DeserializerConfig;
+        use hessian_rs::{hessian, to_vec, Value};
+
+        let strict = |value: Value| {
+            let buf = to_vec(&value).unwrap();
+            let config = DeserializerConfig::new().strict_numbers(true);
+            Deserializer::from_bytes_with(buf, config).unwrap()
+        };
+        let err = i32::deserialize(&mut strict(Value::Long(1 << 40))).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: unexpected type deserialize i32 expect a i32 value, but get 1099511627776 which is out of range"
+        );
+        assert!(u32::deserialize(&mut strict(hessian!(-1))).is_err());
+        assert!(u8::deserialize(&mut strict(hessian!(256))).is_err());
+        let err = i64::deserialize(&mut strict(hessian!(1.5))).unwrap_err();
+        assert!(
+            err.to_string().ends_with("which isn't an integer"),
+            "{}",
+            err
+        );
+        assert!(u64::deserialize(&mut strict(hessian!(f64::INFINITY))).is_err());
+        assert!(i64::deserialize(&mut strict(hessian!(1e20))).is_err());
+
+        assert_eq!(i32::deserialize(&mut strict(Value::Long(-5))).unwrap(), -5);
+        assert_eq!(u16::deserialize(&mut strict(hessian!(2.0))).unwrap(), 2);
+        assert_eq!(
+            u64::deserialize(&mut strict(Value::Long(i64::MAX))).unwrap(),
+            i64::MAX as u64
+        );
+        // truncated by default
+        let buf = to_vec(&Value::Long(1 << 40 | 7)).unwrap();
+        assert_eq!(from_slice::<_, i32>(buf).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_char() {
+        use hessian_rs::{hessian, to_vec};
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Grade {
+            letter: char,
+        }
+        let buf = to_vec(&hessian!({ "letter": "A" })).unwrap();
+        assert_eq!(from_slice::<_, Grade>(buf).unwrap(), Grade { letter: 'A' });
+        test_decode_ok(&[0x01, 0xe4, 0xb8, 0xad], '中');
+        assert!(from_slice::<_, char>(&[0x02, b'a', b'b']).is_err());
+        assert!(from_slice::<_, char>(&[0x00]).is_err());
+    }
+
+    #[test]
+    fn test_borrowed() {
+        use hessian_rs::{hessian, to_vec, Value};
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Log<'a> {
+            msg: &'a str,
+            data: &'a [u8],
+            #[serde(borrow)]
+            tag: std::borrow::Cow<'a, str>,
+        }
+        let buf = to_vec(&hessian!({
+            "msg": "中文",
+            "data": (Value::Bytes(vec![1, 2, 3].into())),
+            "tag": "a"
+        }))
+        .unwrap();
+        let log: Log = from_slice(&buf).unwrap();
+        assert_eq!(log.msg, "中文");
+        assert_eq!(log.data, [1, 2, 3]);
+        assert!(matches!(log.tag, std::borrow::Cow::Borrowed("a")));
+        assert!(buf.as_ptr_range().contains(&log.msg.as_ptr()));
+
+        // a string of chunks can't be borrowed
+        let long = "a".repeat(70000);
+        let buf = to_vec(&Value::from(long.as_str())).unwrap();
+        assert!(from_slice::<_, &str>(&buf).is_err());
+        assert_eq!(from_slice::<_, String>(&buf).unwrap(), long);
+    }
+
+    #[test]
+    fn test_cow_and_bytes() {
+        use hessian_rs::{hessian, to_vec, Value};
+        use std::borrow::Cow;
+
+        #[derive(Debug, Deserialize)]
+        struct Log<'a> {
+            #[serde(borrow)]
+            msg: Cow<'a, str>,
+            #[serde(borrow)]
+            data: Cow<'a, [u8]>,
+            payload: bytes::Bytes,
+        }
+        #[derive(Debug, Deserialize)]
+        #[serde(untagged)]
+        enum Message<'a> {
+            Int(i32),
+            Text(#[serde(borrow)] Cow<'a, str>),
+        }
+
+        let long = "a".repeat(70000);
+        let buf = to_vec(&hessian!({
+            "msg": (Value::from(long.as_str())),
+            "data": (Value::Bytes(vec![1, 2].into())),
+            "payload": (Value::Bytes(vec![3, 4].into()))
+        }))
+        .unwrap();
+        let log: Log = from_slice(&buf).unwrap();
+        // a string of chunks is copied
+        assert!(matches!(&log.msg, Cow::Owned(msg) if *msg == long));
+        assert!(matches!(log.data, Cow::Borrowed([1, 2])));
+        assert_eq!(log.payload, bytes::Bytes::from_static(&[3, 4]));
+
+        let buf = to_vec(&hessian!("short")).unwrap();
+        match from_slice(&buf).unwrap() {
+            Message::Text(Cow::Borrowed("short")) => {}
+            v => panic!("{:?}", v),
+        }
+        // an owned input can't be borrowed from
+        match from_slice(buf).unwrap() {
+            Message::Text(Cow::Owned(s)) => assert_eq!(s, "short"),
+            v => panic!("{:?}", v),
+        }
+        let buf = to_vec(&hessian!(1)).unwrap();
+        assert!(matches!(from_slice(&buf).unwrap(), Message::Int(1)));
+    }
+
+    #[test]
+    fn test_map_order() {
+        use hessian_rs::Value;
+        use indexmap::IndexMap;
+        use serde::Serialize;
+
+        let keys = ["z", "a", "m", "b", "y", "c", "x", "d"];
+        let mut map = IndexMap::new();
+        for (i, key) in keys.iter().enumerate() {
+            map.insert(key.to_string(), i as i32);
+        }
+        // untyped and typed maps, then an object
+        let mut buf = Vec::new();
+        let mut ser = hessian_rs::ser::Serializer::new(&mut buf);
+        ser.write_map_start(None).unwrap();
+        for (key, value) in &map {
+            ser.serialize_value(&Value::from(key.as_str())).unwrap();
+            ser.serialize_value(&Value::Int(*value)).unwrap();
+        }
+        ser.write_object_end().unwrap();
+        let decoded: IndexMap<String, i32> = from_slice(&buf).unwrap();
+        assert!(decoded.keys().eq(keys.iter()));
+
+        #[derive(Serialize)]
+        #[serde(rename = "example.Letters")]
+        struct Letters {
+            z: i32,
+            a: i32,
+            m: i32,
+        }
+        let buf = crate::ser::to_vec(&Letters { z: 0, a: 1, m: 2 }).unwrap();
+        let decoded: IndexMap<String, i32> = from_slice(&buf).unwrap();
+        assert!(decoded.keys().eq(["z", "a", "m"].iter()));
+
+        #[derive(Deserialize)]
+        struct Flattened {
+            z: i32,
+            #[serde(flatten)]
+            rest: IndexMap<String, i32>,
+        }
+        let decoded: Flattened = from_slice(&buf).unwrap();
+        assert_eq!(decoded.z, 0);
+        assert!(decoded.rest.keys().eq(["a", "m"].iter()));
+
+        // a typed map with a repeated key, the last value is kept
+        let mut buf = Vec::new();
+        let mut ser = hessian_rs::ser::Serializer::new(&mut buf);
+        ser.write_map_start(Some("java.util.LinkedHashMap"))
+            .unwrap();
+        for (key, value) in [("b", 1), ("a", 2), ("b", 3)] {
+            ser.serialize_value(&Value::from(key)).unwrap();
+            ser.serialize_value(&Value::Int(value)).unwrap();
+        }
+        ser.write_object_end().unwrap();
+        let decoded: IndexMap<String, i32> = from_slice(&buf).unwrap();
+        assert_eq!(
+            decoded.into_iter().collect::<Vec<_>>(),
+            [("b".into(), 3), ("a".into(), 2)]
+        );
+        let decoded: std::collections::BTreeMap<String, i32> = from_slice(&buf).unwrap();
+        assert_eq!(decoded["b"], 3);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        use crate::de::{DeserializerConfig, DuplicateKeys};
+        use indexmap::IndexMap;
+        use serde::ser::{Serialize, SerializeMap, Serializer};
+
+        #[derive(Debug, PartialEq, Deserialize, serde::Serialize)]
+        #[serde(rename = "example.Car")]
+        struct Car {
+            color: String,
+        }
+
+        // a map with a repeated key, its values are objects of one definition
+        struct Entries(Vec<(&'static str, Car)>);
+
+        impl Serialize for Entries {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut map = serializer.serialize_map(Some(self.0.len()))?;
+                for (k, v) in &self.0 {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Garage {
+            a: Car,
+            b: Car,
+        }
+
+        let car = |color: &str| Car {
+            color: color.to_string(),
+        };
+        let buf = crate::ser::to_vec(&Entries(vec![
+            ("b", car("red")),
+            ("a", car("green")),
+            ("b", car("blue")),
+        ]))
+        .unwrap();
+        let decode = |policy| {
+            let config = DeserializerConfig::new().duplicate_keys(policy);
+            Deserializer::from_bytes_with(&buf[..], config).unwrap()
+        };
+
+        // by default a map keeps the last value and a struct fails
+        let garage: IndexMap<String, Car> = from_slice(&buf).unwrap();
+        assert_eq!(garage["b"], car("blue"));
+        assert!(from_slice::<_, Garage>(&buf).is_err());
+
+        let mut de = decode(DuplicateKeys::First);
+        let garage = Garage::deserialize(&mut de).unwrap();
+        assert_eq!(garage.b, car("red"));
+        let garage =
+            IndexMap::<String, Car>::deserialize(&mut decode(DuplicateKeys::First)).unwrap();
+        assert!(garage.keys().eq(["b", "a"].iter()));
+        assert_eq!(garage["b"], car("red"));
+
+        let garage = Garage::deserialize(&mut decode(DuplicateKeys::Last)).unwrap();
+        assert_eq!(garage.a, car("green"));
+        assert_eq!(garage.b, car("blue"));
+        let garage =
+            IndexMap::<String, Car>::deserialize(&mut decode(DuplicateKeys::Last)).unwrap();
+        assert!(garage.keys().eq(["a", "b"].iter()));
+
+        match Garage::deserialize(&mut decode(DuplicateKeys::Error)) {
+            Err(Error::SyntaxError(hessian_rs::ErrorKind::DuplicateKey(key))) => {
+                assert_eq!(key, "\"b\"")
+            }
+            v => panic!("{:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_map_keys() {
+        use crate::StringifyKeys;
+        use hessian_rs::{hessian, Value};
+
+        // string keys for integer keys, and the other way round
+        let buf = hessian_rs::to_vec(&hessian!({ "1": "one", "-2": "minus two" })).unwrap();
+        let decoded: HashMap<i32, String> = from_slice(&buf).unwrap();
+        assert_eq!(decoded[&1], "one");
+        assert_eq!(decoded[&-2], "minus two");
+        let buf = hessian_rs::to_vec(&hessian!({ "x": "one" })).unwrap();
+        assert!(from_slice::<_, HashMap<i64, String>>(&buf).is_err());
+
+        let map = Value::Map(
+            vec![
+                (Value::Int(1), Value::Int(10)),
+                (Value::Long(1 << 40), Value::Int(20)),
+            ]
+            .into_iter()
+            .collect::<HashMap<_, _>>()
+            .into(),
+        );
+        let buf = hessian_rs::to_vec(&map).unwrap();
+        let decoded: HashMap<i64, i32> = from_slice(&buf).unwrap();
+        assert_eq!(decoded[&(1 << 40)], 20);
+        assert!(from_slice::<_, HashMap<String, i32>>(&buf).is_err());
+        let decoded: StringifyKeys<HashMap<String, i32>> = from_slice(&buf).unwrap();
+        assert_eq!(decoded.0["1"], 10);
+        assert_eq!(decoded.0["1099511627776"], 20);
+
+        // the maps nested in the value too
+        let nested = Value::Map(
+            vec![(Value::Bool(true), map.clone()), (Value::Double(1.5), map)]
+                .into_iter()
+                .collect::<HashMap<_, _>>()
+                .into(),
+        );
+        let buf = hessian_rs::to_vec(&nested).unwrap();
+        let decoded: StringifyKeys<HashMap<String, HashMap<String, i32>>> =
+            from_slice(&buf).unwrap();
+        assert_eq!(decoded.0["true"]["1"], 10);
+        assert_eq!(decoded.0["1.5"]["1"], 10);
+    }
+
+    #[test]
+    fn test_validate_class_name() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(rename = "example.Bike")]
+        struct Bike {
+            #[serde(rename = "Color")]
+            color: String,
+        }
+
+        let object = &[
+            b'C', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r', 0x91,
+            0x05, b'C', b'o', b'l', b'o', b'r', b'O', 0x90, 0x03, b'r', b'e', b'd',
+        ];
+        test_decode_ok(
+            object,
+            Bike {
+                color: "red".to_string(),
+            },
+        );
+        let mut de = Deserializer::from_bytes(object)
+            .unwrap()
+            .validate_class_name(true);
+        match Bike::deserialize(&mut de) {
+            Err(Error::ClassNameMismatch(expected, actual)) => {
+                assert_eq!(expected, "example.Bike");
+                assert_eq!(actual, "example.Car");
+            }
+            v => panic!("expect class name mismatch, but get {:?}", v),
+        }
+        // the class the payload has
+        let classes: Vec<&str> = de.definitions().map(|def| def.name.as_str()).collect();
+        assert_eq!(classes, ["example.Car"]);
+
+        let typed_map = &[
+            b'M', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r', 0x05,
+            b'C', b'o', b'l', b'o', b'r', 0x03, b'r', b'e', b'd', b'Z',
+        ];
+        let mut de = Deserializer::from_bytes(typed_map)
+            .unwrap()
+            .validate_class_name(true);
+        assert!(Bike::deserialize(&mut de).is_err());
+
+        // untyped map carries no class name
+        let untyped_map = &[
+            b'H', 0x05, b'C', b'o', b'l', b'o', b'r', 0x03, b'r', b'e', b'd', b'Z',
+        ];
+        let mut de = Deserializer::from_bytes(untyped_map)
+            .unwrap()
+            .validate_class_name(true);
+        assert!(Bike::deserialize(&mut de).is_ok());
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Coupe {
+            #[serde(rename = "Color")]
+            color: String,
+        }
+        let mut de = Deserializer::from_bytes(object)
+            .unwrap()
+            .validate_class_name(true)
+            .class_name("Coupe", "example.Car");
+        assert!(Coupe::deserialize(&mut de).is_ok());
+    }
+
+    #[test]
+    fn test_typed() {
+        use crate::Typed;
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Car {
+            #[serde(rename = "Color")]
+            color: String,
+        }
+        let red = || Car {
+            color: "red".to_string(),
+        };
+
+        let objects = &[
+            0x7a, b'C', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r',
+            0x91, 0x05, b'C', b'o', b'l', b'o', b'r', 0x60, 0x03, b'r', b'e', b'd', 0x60, 0x03,
+            b'r', b'e', b'd',
+        ];
+        test_decode_ok(
+            objects,
+            vec![
+                Typed {
+                    class: "example.Car".to_string(),
+                    value: red(),
+                },
+                Typed {
+                    class: "example.Car".to_string(),
+                    value: red(),
+                },
+            ],
+        );
+
+        let typed_map = &[
+            b'M', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r', 0x05,
+            b'C', b'o', b'l', b'o', b'r', 0x03, b'r', b'e', b'd', b'Z',
+        ];
+        test_decode_ok(
+            typed_map,
+            Typed {
+                class: "example.Car".to_string(),
+                value: red(),
+            },
+        );
+        let mut map = HashMap::new();
+        map.insert("Color".to_string(), "red".to_string());
+        test_decode_ok(
+            typed_map,
+            Typed {
+                class: "example.Car".to_string(),
+                value: map,
+            },
+        );
+
+        let untyped_map = &[
+            b'H', 0x05, b'C', b'o', b'l', b'o', b'r', 0x03, b'r', b'e', b'd', b'Z',
+        ];
+        assert!(from_slice::<_, Typed<Car>>(untyped_map).is_err());
+    }
+
     #[test]
     fn test_enum() {
         #[derive(Deserialize, PartialEq, Debug)]
@@ -852,6 +2389,7 @@ mod tests {
         }
 
         test_decode_ok(&[0x04, b'U', b'n', b'i', b't'], E::Unit);
+        test_decode_ok(&[b'H', 0x04, b'U', b'n', b'i', b't', b'N', b'Z'], E::Unit);
         test_decode_ok(
             &[
                 b'H', 0x07, b'N', b'e', b'w', b't', b'y', b'p', b'e', 0x91, b'Z',
@@ -872,6 +2410,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bytes_field() {
+        use crate::ser::to_vec;
+        use crate::value::{from_value, to_value};
+        use hessian_rs::{hessian, Value};
+        use serde::Serialize;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Attachment {
+            #[serde(with = "crate::bytes")]
+            data: Vec<u8>,
+        }
+
+        let attachment = Attachment {
+            data: vec![1, 2, 3],
+        };
+        let value = to_value(&attachment).unwrap();
+        assert_eq!(
+            value,
+            hessian!({ "data": (Value::from_base64("AQID").unwrap()) })
+        );
+        assert_eq!(value["data"].as_base64().unwrap(), "AQID");
+        assert_eq!(from_value::<Attachment>(value).unwrap(), attachment);
+        let buf = to_vec(&attachment).unwrap();
+        assert_eq!(from_slice::<_, Attachment>(&buf[..]).unwrap(), attachment);
+
+        // read the same from the input and from a value
+        for value in [
+            hessian!({ "data": [1, 2, 3] }),
+            hessian!({ "data": "AQID" }),
+        ] {
+            let buf = hessian_rs::to_vec(&value).unwrap();
+            assert_eq!(from_slice::<_, Attachment>(&buf[..]).unwrap(), attachment);
+            assert_eq!(from_value::<Attachment>(value).unwrap(), attachment);
+        }
+        let value = hessian!({ "data": "AQ?D" });
+        let buf = hessian_rs::to_vec(&value).unwrap();
+        assert!(from_slice::<_, Attachment>(&buf[..]).is_err());
+        assert!(from_value::<Attachment>(value).is_err());
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Shared {
+            #[serde(with = "crate::bytes")]
+            data: bytes::Bytes,
+        }
+
+        let shared = Shared {
+            data: bytes::Bytes::from_static(&[1, 2, 3]),
+        };
+        let buf = to_vec(&shared).unwrap();
+        assert_eq!(
+            hessian_rs::from_slice(&buf).unwrap(),
+            hessian!({ "data": (Value::from_base64("AQID").unwrap()) })
+        );
+        assert_eq!(from_slice::<_, Shared>(&buf[..]).unwrap(), shared);
+        let value = hessian!({ "data": "AQID" });
+        assert_eq!(from_value::<Shared>(value).unwrap(), shared);
+
+        // without the module, a string is its UTF-8 on both paths
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Text {
+            data: bytes::Bytes,
+        }
+
+        let value = hessian!({ "data": "AQID" });
+        let buf = hessian_rs::to_vec(&value).unwrap();
+        let text = Text {
+            data: bytes::Bytes::from_static(b"AQID"),
+        };
+        assert_eq!(from_slice::<_, Text>(&buf[..]).unwrap(), text);
+        assert_eq!(from_value::<Text>(value).unwrap(), text);
+
+        #[cfg(feature = "json")]
+        {
+            let json = serde_json::to_string(&attachment).unwrap();
+            assert_eq!(json, r#"{"data":"AQID"}"#);
+            assert_eq!(
+                serde_json::from_str::<Attachment>(&json).unwrap(),
+                attachment
+            );
+        }
+    }
+
     #[test]
     fn test_newtype_struct() {
         #[derive(Deserialize, Debug)]
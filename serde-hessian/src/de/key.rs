@@ -0,0 +1,160 @@
+use serde::de::{self, Visitor};
+
+use super::{Deserializer, Input};
+use crate::error::Error;
+use hessian_rs::{ByteCodecType, Value};
+
+/// Deserialize the key of a map entry.
+///
+/// A string key is parsed for an integer, `HashMap<i32, V>` decodes from
+/// a map written with string keys as well. A number or a bool is written
+/// as a string for a string when keys are stringified, see
+/// `StringifyKeys`. Other keys are deserialized as any value.
+pub(super) struct MapKey<'a, R: AsRef<[u8]>> {
+    pub(super) de: &'a mut Deserializer<R>,
+}
+
+macro_rules! deserialize_integer {
+    ($($method:ident => $visit:ident $ty:ident,)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self.de.de.peek_byte_code_type()? {
+                ByteCodecType::String(_) => {
+                    let key = self.de.de.read_string_value()?;
+                    visitor.$visit(self.de.parse::<$ty>(&key, stringify!($ty))?)
+                }
+                _ => self.de.$method(visitor),
+            }
+        }
+    )*};
+}
+
+macro_rules! forward {
+    ($($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.de.$method(visitor)
+        }
+    )*};
+}
+
+impl<'a, R: AsRef<[u8]>> MapKey<'a, R> {
+    // The key as a string, if it's a number or a bool to stringify
+    fn stringified(&mut self) -> Result<Option<String>, Error> {
+        if !self.de.stringify_keys {
+            return Ok(None);
+        }
+        match self.de.de.peek_byte_code_type()? {
+            ByteCodecType::Int(_)
+            | ByteCodecType::Long(_)
+            | ByteCodecType::Double(_)
+            | ByteCodecType::True
+            | ByteCodecType::False => {}
+            _ => return Ok(None),
+        }
+        let key = match self.de.de.read_value()? {
+            Value::Int(v) => v.to_string(),
+            Value::Long(v) => v.to_string(),
+            Value::Double(v) => v.to_string(),
+            Value::Bool(v) => v.to_string(),
+            _ => return Ok(None),
+        };
+        Ok(Some(key))
+    }
+}
+
+impl<'de, 'a, R: Input<'de>> de::Deserializer<'de> for MapKey<'a, R> {
+    type Error = Error;
+
+    deserialize_integer! {
+        deserialize_i8 => visit_i8 i8,
+        deserialize_i16 => visit_i16 i16,
+        deserialize_i32 => visit_i32 i32,
+        deserialize_i64 => visit_i64 i64,
+        deserialize_u8 => visit_u8 u8,
+        deserialize_u16 => visit_u16 u16,
+        deserialize_u32 => visit_u32 u32,
+        deserialize_u64 => visit_u64 u64,
+    }
+
+    forward! {
+        deserialize_bool deserialize_i128 deserialize_u128 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_bytes deserialize_byte_buf
+        deserialize_option deserialize_unit deserialize_seq deserialize_map
+        deserialize_ignored_any
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        match self.stringified()? {
+            Some(key) => visitor.visit_string(key),
+            None => self.de.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        match self.stringified()? {
+            Some(key) => visitor.visit_string(key),
+            None => self.de.deserialize_str(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        match self.stringified()? {
+            Some(key) => visitor.visit_string(key),
+            None => self.de.deserialize_string(visitor),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.de.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.de.deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.de.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.de.deserialize_tuple_struct(name, len, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.de.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.de.deserialize_enum(name, variants, visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
@@ -16,6 +16,50 @@ pub enum Error {
     SerdeDesrializeError(String),
     SerdeSerializeError(String),
     UnSupportedRefType,
+    /// The class name of an incoming object, expected and actual
+    ClassNameMismatch(String, String),
+}
+
+impl Error {
+    /// Whether the input ends before the value does, see
+    /// `hessian_rs::Error::is_eof`
+    pub fn is_eof(&self) -> bool {
+        match self {
+            Error::SyntaxError(kind) => *kind == ErrorKind::UnexpectedEofInChunk,
+            Error::IoError(err) => err.kind() == io::ErrorKind::UnexpectedEof,
+            _ => false,
+        }
+    }
+
+    /// Whether the input isn't valid hessian
+    pub fn is_syntax(&self) -> bool {
+        match self {
+            Error::SyntaxError(_) => !self.is_eof(),
+            Error::FromUtf8Error(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether reading or writing the underlying stream failed, other than
+    /// by ending early
+    pub fn is_io(&self) -> bool {
+        match self {
+            Error::IoError(_) => !self.is_eof(),
+            _ => false,
+        }
+    }
+
+    /// Whether the input is valid hessian which doesn't fit the type it's
+    /// deserialized to, or a value can't be serialized
+    pub fn is_data(&self) -> bool {
+        matches!(
+            self,
+            Error::SerdeDesrializeError(_)
+                | Error::SerdeSerializeError(_)
+                | Error::UnSupportedRefType
+                | Error::ClassNameMismatch(..)
+        )
+    }
 }
 
 impl fmt::Display for Error {
@@ -27,6 +71,11 @@ impl fmt::Display for Error {
             Error::SerdeSerializeError(err) => write!(f, "serde serialize error: {}", err),
             Error::FromUtf8Error(err) => err.fmt(f),
             Error::UnSupportedRefType => write!(f, "unsupported ref type"),
+            Error::ClassNameMismatch(expected, actual) => write!(
+                f,
+                "class name mismatch, expect {} but get {}",
+                expected, actual
+            ),
         }
     }
 }
@@ -74,6 +123,7 @@ impl StdError for Error {
             Error::IoError(err) => Some(err),
             Error::FromUtf8Error(err) => Some(err),
             Error::UnSupportedRefType => Some(self),
+            Error::ClassNameMismatch(..) => None,
         }
     }
 }
@@ -0,0 +1,60 @@
+//! Decode the numbers and bools used as map keys as strings.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+// The newtype name `StringifyKeys` passes to `deserialize_newtype_struct`,
+// the deserializer of this crate then reads non-string keys as strings
+pub(crate) const STRINGIFY_KEYS: &str = "$serde_hessian::StringifyKeys";
+
+/// A value whose maps have string keys, e.g. a `HashMap<String, V>` or a
+/// `serde_json::Value`, decoded from maps whose keys are ints, longs,
+/// doubles or bools, as java maps often are. The keys are written as by
+/// `Display`, `1` or `true`, the maps nested in the value included.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use serde_hessian::{de::from_slice, StringifyKeys};
+/// # use hessian_rs::hessian;
+/// # let buf = hessian_rs::to_vec(&hessian!({ 200: 3, 404: 1 }))?;
+/// let counts: StringifyKeys<HashMap<String, i32>> = from_slice(&buf[..])?;
+/// assert_eq!(counts.0["404"], 1);
+/// # Ok::<(), serde_hessian::error::Error>(())
+/// ```
+///
+/// Without it a non-string key fails to decode into a string. It's
+/// serialized as the value it wraps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StringifyKeys<T>(pub T);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for StringifyKeys<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(STRINGIFY_KEYS, StringifyKeysVisitor(PhantomData))
+    }
+}
+
+impl<T: Serialize> Serialize for StringifyKeys<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+struct StringifyKeysVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for StringifyKeysVisitor<T> {
+    type Value = StringifyKeys<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a value with maps")
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        T::deserialize(deserializer).map(StringifyKeys)
+    }
+}
@@ -1,3 +1,20 @@
+mod big_integer;
+pub mod bytes;
+pub mod class;
 pub mod de;
 pub mod error;
+mod keys;
+pub mod polymorphic;
 pub mod ser;
+pub mod transcode;
+mod typed;
+pub mod value;
+
+pub use class::JavaType;
+pub use keys::StringifyKeys;
+pub use polymorphic::Dispatcher;
+pub use transcode::transcode;
+#[cfg(feature = "json")]
+pub use transcode::{transcode_from_json, transcode_to_json};
+pub use typed::Typed;
+pub use value::{from_value, to_value};
@@ -0,0 +1,162 @@
+//! Decode objects into the rust type registered for their class.
+//!
+//! ```
+//! # use std::any::Any;
+//! # use serde::Deserialize;
+//! # use serde_hessian::Dispatcher;
+//! # use hessian_rs::hessian;
+//! # #[derive(Deserialize)]
+//! # struct Circle {
+//! #     radius: f64,
+//! # }
+//! # #[derive(Deserialize)]
+//! # struct Square {
+//! #     side: f64,
+//! # }
+//! # let buf = hessian_rs::to_vec(&hessian!([
+//! #     (hessian!("example.Circle" => { "radius": 1.0 })),
+//! #     (hessian!("example.Square" => { "side": 2.0 }))
+//! # ]))?;
+//! let mut dispatcher = Dispatcher::new();
+//! dispatcher
+//!     .register::<Circle>("example.Circle")
+//!     .register::<Square>("example.Square");
+//! let shapes = dispatcher.from_slice(&buf)?;
+//! for shape in shapes.downcast::<Vec<Box<dyn Any>>>().unwrap().iter() {
+//!     if let Some(circle) = shape.downcast_ref::<Circle>() {
+//!         assert_eq!(circle.radius, 1.0);
+//!     } else if let Some(square) = shape.downcast_ref::<Square>() {
+//!         assert_eq!(square.side, 2.0);
+//!     }
+//! }
+//! # Ok::<(), serde_hessian::error::Error>(())
+//! ```
+
+use std::any::Any;
+use std::collections::HashMap;
+
+use hessian_rs::value::{List, Map};
+use hessian_rs::Value;
+use serde::de::DeserializeOwned;
+
+use crate::error::Error;
+use crate::value::from_value;
+
+type Decode = fn(Value) -> Result<Box<dyn Any>, Error>;
+
+fn decode<T: DeserializeOwned + 'static>(value: Value) -> Result<Box<dyn Any>, Error> {
+    Ok(Box::new(from_value::<T>(value)?))
+}
+
+/// Decoders of objects and typed maps by class name.
+///
+/// An object or typed map of a registered class decodes into its type, a
+/// list into a `Vec<Box<dyn Any>>` of its decoded elements, and any other
+/// value, including objects of unregistered classes, into a `Value`.
+#[derive(Default, Clone)]
+pub struct Dispatcher {
+    decoders: HashMap<String, Decode>,
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode objects of the class `class` into a `T`.
+    pub fn register<T: DeserializeOwned + 'static>(&mut self, class: &str) -> &mut Self {
+        self.decoders.insert(class.to_string(), decode::<T>);
+        self
+    }
+
+    pub fn is_registered(&self, class: &str) -> bool {
+        self.decoders.contains_key(class)
+    }
+
+    /// Decode the first value of `buf`.
+    pub fn from_slice(&self, buf: &[u8]) -> Result<Box<dyn Any>, Error> {
+        self.from_value(hessian_rs::from_slice(buf)?)
+    }
+
+    pub fn from_value(&self, value: Value) -> Result<Box<dyn Any>, Error> {
+        match value {
+            Value::Map(Map::Typed(class, m)) => match self.decoders.get(&class) {
+                Some(decode) => decode(Value::Map(Map::Typed(class, m))),
+                None => Ok(Box::new(Value::Map(Map::Typed(class, m)))),
+            },
+            Value::List(List::Typed(_, l)) | Value::List(List::Untyped(l)) => {
+                let elems = l
+                    .into_iter()
+                    .map(|v| self.from_value(v))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Box::new(elems))
+            }
+            v => Ok(Box::new(v)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dispatcher;
+    use crate::ser::to_vec;
+    use hessian_rs::Value;
+    use serde::{Deserialize, Serialize};
+    use std::any::Any;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename = "example.Circle")]
+    struct Circle {
+        radius: i32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename = "example.Square")]
+    struct Square {
+        side: i32,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename = "example.Triangle")]
+    struct Triangle {
+        side: i32,
+    }
+
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum Shape {
+        Circle(Circle),
+        Square(Square),
+        Triangle(Triangle),
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let shapes = vec![
+            Shape::Circle(Circle { radius: 1 }),
+            Shape::Square(Square { side: 2 }),
+            Shape::Triangle(Triangle { side: 3 }),
+        ];
+        let buf = to_vec(&shapes).unwrap();
+
+        let mut dispatcher = Dispatcher::new();
+        dispatcher
+            .register::<Circle>("example.Circle")
+            .register::<Square>("example.Square");
+        assert!(dispatcher.is_registered("example.Circle"));
+
+        let decoded = dispatcher.from_slice(&buf).unwrap();
+        let decoded = decoded.downcast::<Vec<Box<dyn Any>>>().unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[0].downcast_ref(), Some(&Circle { radius: 1 }));
+        assert_eq!(decoded[1].downcast_ref(), Some(&Square { side: 2 }));
+        let triangle = decoded[2].downcast_ref::<Value>().unwrap();
+        assert_eq!(
+            triangle.as_map().unwrap().r#type(),
+            Some("example.Triangle")
+        );
+
+        let one = dispatcher.from_slice(&[0x91]).unwrap();
+        assert_eq!(one.downcast_ref(), Some(&Value::Int(1)));
+    }
+}
@@ -1,52 +1,444 @@
+use crate::big_integer;
+use crate::class;
 use crate::error::Error;
-use hessian_rs::{ser::Serializer as ValueSerializer, value::Definition};
+use crate::value::to_value;
+use hessian_rs::{
+    ser::{DefinitionCache, Serializer as ValueSerializer},
+    value::Definition,
+    ErrorKind, Value,
+};
 
 use serde::{
     ser::{self},
     Serialize,
 };
+use std::collections::{HashMap, HashSet};
 use std::io;
+use std::sync::Arc;
 
 type Result<T> = std::result::Result<T, Error>;
 
-pub struct Serializer<W: io::Write>(ValueSerializer<W>);
+// Type of a sequence of `u8` in a `ListSerializer`, written as binary
+const BINARY: &str = "[byte";
+
+/// How a struct is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StructEncoding {
+    /// A class definition and an object instance, like a Java bean.
+    #[default]
+    Object,
+    /// A map typed by the struct name.
+    TypedMap,
+    /// An untyped map.
+    Map,
+}
+
+/// How 128-bit integers are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Int128Encoding {
+    /// A `java.math.BigInteger` object.
+    #[default]
+    BigInteger,
+    /// A decimal string.
+    String,
+}
+
+/// Configuration of a `Serializer`.
+#[derive(Debug, Clone, Default)]
+pub struct SerializerConfig {
+    struct_encoding: StructEncoding,
+    struct_encodings: HashMap<&'static str, StructEncoding>,
+    class_names: HashMap<&'static str, &'static str>,
+    typed_lists: bool,
+    bytes_as_binary: bool,
+    skip_none_fields: bool,
+    java_enums: bool,
+    int128_encoding: Int128Encoding,
+}
+
+impl SerializerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How structs are written, `StructEncoding::Object` by default.
+    pub fn struct_encoding(mut self, encoding: StructEncoding) -> Self {
+        self.struct_encoding = encoding;
+        self
+    }
+
+    /// How the struct named `name` is written, overrides `struct_encoding`.
+    ///
+    /// The name is the one given to serde, e.g. by `#[serde(rename = "...")]`.
+    pub fn struct_encoding_for(mut self, name: &'static str, encoding: StructEncoding) -> Self {
+        self.struct_encodings.insert(name, encoding);
+        self
+    }
+
+    /// Write the struct or enum named `name` for serde as the class `class`,
+    /// overrides the class names registered in `crate::class`.
+    pub fn class_name(mut self, name: &'static str, class: &'static str) -> Self {
+        self.class_names.insert(name, class);
+        self
+    }
+
+    /// Write sequences whose elements are all `i32`, `i64`, `f64`, `bool` or
+    /// strings as lists typed `[int`, `[long`, `[double`, `[boolean` or
+    /// `[string`, the java arrays of these types. Disabled by default.
+    ///
+    /// Elements are buffered until the type of the list is known.
+    pub fn typed_lists(mut self, typed_lists: bool) -> Self {
+        self.typed_lists = typed_lists;
+        self
+    }
+
+    /// Write sequences of `u8`, e.g. a `Vec<u8>`, as binary instead of a
+    /// list of ints, like java writes a `byte[]`. Disabled by default.
+    ///
+    /// Elements are buffered until the sequence is known to be bytes, an
+    /// empty sequence is written as a list. Types calling `serialize_bytes`,
+    /// e.g. with `serde_bytes` or `crate::bytes`, are always written as
+    /// binary. Both forms deserialize into a `Vec<u8>`.
+    pub fn bytes_as_binary(mut self, bytes_as_binary: bool) -> Self {
+        self.bytes_as_binary = bytes_as_binary;
+        self
+    }
+
+    /// Omit the fields of structs written as maps whose value is `None`,
+    /// instead of writing the key and a null. Disabled by default.
+    ///
+    /// Objects always have every field of their definition. Missing fields
+    /// deserialize as `None`, or as the default of a `#[serde(default)]` field.
+    pub fn skip_none_fields(mut self, skip_none_fields: bool) -> Self {
+        self.skip_none_fields = skip_none_fields;
+        self
+    }
+
+    /// Write unit variants like java writes enums, as an object of the
+    /// enum's class with a single `name` field, instead of a bare string.
+    /// Disabled by default.
+    ///
+    /// The class name is the name of the enum given to serde, e.g. by
+    /// `#[serde(rename = "example.Color")]`. Both forms deserialize into
+    /// a unit variant.
+    pub fn java_enums(mut self, java_enums: bool) -> Self {
+        self.java_enums = java_enums;
+        self
+    }
+
+    /// How `i128` and `u128` are written, `Int128Encoding::BigInteger` by
+    /// default. Both forms, ints and longs deserialize into them.
+    pub fn int128_encoding(mut self, encoding: Int128Encoding) -> Self {
+        self.int128_encoding = encoding;
+        self
+    }
+
+    // Whether the elements of a list of type `tp` are buffered
+    fn buffers(&self, tp: &str) -> bool {
+        if tp == BINARY {
+            self.bytes_as_binary
+        } else {
+            self.typed_lists
+        }
+    }
+
+    fn class_of(&self, name: &'static str) -> &'static str {
+        match self.class_names.get(name) {
+            Some(class) => class,
+            None => class::class_name(name),
+        }
+    }
+
+    fn encoding_of(&self, name: &str) -> StructEncoding {
+        self.struct_encodings
+            .get(name)
+            .copied()
+            .unwrap_or(self.struct_encoding)
+    }
+}
+
+pub struct Serializer<W: io::Write> {
+    ser: ValueSerializer<W>,
+    config: Arc<SerializerConfig>,
+    // Address and type of the values being serialized, from outermost to innermost
+    path: Vec<(usize, &'static str)>,
+    // Structs whose definition was written by an instance with skipped
+    // fields, which lacks their names
+    partial: HashSet<&'static str>,
+}
 
 impl<W: io::Write> Serializer<W> {
     pub fn new(writer: W) -> Self {
-        Serializer(ValueSerializer::new(writer))
+        Self::with_config(writer, SerializerConfig::default())
+    }
+
+    pub fn with_config(writer: W, config: SerializerConfig) -> Self {
+        Serializer::from(ValueSerializer::new(writer)).config(config)
+    }
+
+    pub fn config(mut self, config: SerializerConfig) -> Self {
+        self.config = Arc::new(config);
+        self
+    }
+
+    /// Write the same bytes for the same logical value.
+    ///
+    /// Map entries are collected into `Value`s and written in key order, so
+    /// a struct nested in a map is written as a typed map in this mode.
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.ser = self.ser.canonical(canonical);
+        self
+    }
+
+    /// Write struct instances with the compact one byte object tags, see
+    /// `hessian_rs::ser::Serializer::compact_objects`. Enabled by default.
+    pub fn compact_objects(mut self, compact_objects: bool) -> Self {
+        self.ser = self.ser.compact_objects(compact_objects);
+        self
+    }
+
+    /// Write NaN and the infinities, see
+    /// `hessian_rs::ser::Serializer::allow_nan`. Enabled by default.
+    pub fn allow_nan(mut self, allow_nan: bool) -> Self {
+        self.ser = self.ser.allow_nan(allow_nan);
+        self
+    }
+
+    /// Use class definitions the reader already knows, see
+    /// `hessian_rs::ser::Serializer::preload_definitions`.
+    pub fn preload_definitions(mut self, defs: DefinitionCache) -> Self {
+        self.ser = self.ser.preload_definitions(defs);
+        self
+    }
+
+    /// Returns the preloaded class definitions and the ones written so far.
+    pub fn definition_cache(&self) -> DefinitionCache {
+        self.ser.definition_cache()
+    }
+
+    /// Reset the serializer to write a new, unrelated message, see
+    /// `hessian_rs::ser::Serializer::reset`.
+    pub fn reset(&mut self) {
+        self.ser.reset();
+        self.path.clear();
+        self.partial.clear();
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.ser.flush()?)
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.ser.get_ref()
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.ser.get_mut()
+    }
+
+    /// Unwrap the underlying writer, without flushing it.
+    pub fn into_inner(self) -> W {
+        self.ser.into_inner()
+    }
+
+    fn serialize_int128(&mut self, negative: bool, magnitude: u128) -> Result<()> {
+        match self.config.int128_encoding {
+            Int128Encoding::BigInteger => self.ser.serialize_fields_with_definition(
+                &big_integer::definition(),
+                &big_integer::fields(negative, magnitude),
+            )?,
+            Int128Encoding::String => {
+                let sign = if negative && magnitude != 0 { "-" } else { "" };
+                self.ser
+                    .serialize_string(&format!("{}{}", sign, magnitude))?
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize a value nested in a container.
+    ///
+    /// A value met again while it is still being serialized, e.g. through a
+    /// `Rc<RefCell<T>>` cycle, fails with `ErrorKind::CircularReference`
+    /// instead of recursing forever.
+    fn serialize_nested<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = (
+            value as *const T as *const u8 as usize,
+            std::any::type_name::<T>(),
+        );
+        if self.path.contains(&key) {
+            return Err(Error::SyntaxError(ErrorKind::CircularReference(
+                key.1.to_string(),
+            )));
+        }
+        self.path.push(key);
+        let res = value.serialize(&mut *self);
+        self.path.pop();
+        res
+    }
+}
+
+/// Serializer of a struct for any `StructEncoding`
+pub enum SerializeStruct<'a, W: io::Write> {
+    Object(StructSerializer<'a, W>),
+    Map(MapSerializer<'a, W>),
+}
+
+impl<'a, W: io::Write> ser::SerializeStruct for SerializeStruct<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        match self {
+            SerializeStruct::Object(s) => s.serialize_field(key, value),
+            SerializeStruct::Map(s) => s.serialize_field(key, value),
+        }
+    }
+
+    #[inline]
+    fn skip_field(&mut self, key: &'static str) -> Result<()> {
+        match self {
+            SerializeStruct::Object(s) => s.skip_field(key),
+            SerializeStruct::Map(_) => Ok(()),
+        }
+    }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        match self {
+            SerializeStruct::Object(s) => s.end(),
+            SerializeStruct::Map(s) => ser::SerializeStruct::end(s),
+        }
+    }
+}
+
+/// Use a `hessian_rs` serializer, e.g. one made by `hessian_rs::ser::Serializer::builder()`
+impl<W: io::Write> From<ValueSerializer<W>> for Serializer<W> {
+    fn from(ser: ValueSerializer<W>) -> Self {
+        Serializer {
+            ser,
+            config: Arc::new(SerializerConfig::default()),
+            path: Vec::new(),
+            partial: HashSet::new(),
+        }
     }
 }
 
 pub struct StructSerializer<'a, W: io::Write> {
     name: &'static str,
     ser: &'a mut Serializer<W>,
-    fields: Vec<&'a str>,
+    buffered: Option<Box<BufferedFields>>,
     inx: usize,
-    buf: Vec<u8>,
+}
+
+// Field names and values of an object whose definition is not written
+// yet, buffered until the field names are known
+struct BufferedFields {
+    names: Vec<&'static str>,
+    ser: Serializer<Vec<u8>>,
+    // Whether a field was skipped, e.g. by `skip_serializing_if`
+    skipped: bool,
 }
 
 pub struct MapSerializer<'a, W: io::Write> {
     _name: Option<&'static str>,
     encoder: &'a mut Serializer<W>,
+    // Buffered entries in canonical mode
+    entries: Option<Vec<(Value, Value)>>,
+}
+
+impl<'a, W: io::Write> MapSerializer<'a, W> {
+    fn new(name: Option<&'static str>, encoder: &'a mut Serializer<W>) -> Self {
+        let entries = if encoder.ser.is_canonical() {
+            Some(Vec::new())
+        } else {
+            None
+        };
+        MapSerializer {
+            _name: name,
+            encoder,
+            entries,
+        }
+    }
+
+    fn write_entries(&mut self) -> Result<()> {
+        if let Some(mut entries) = self.entries.take() {
+            entries.sort_by(|l, r| l.0.cmp(&r.0));
+            for (k, v) in entries.iter() {
+                self.encoder.ser.serialize_value(k)?;
+                self.encoder.ser.serialize_value(v)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 pub struct ListSerializer<'a, W: io::Write> {
     ser: &'a mut Serializer<W>,
     sized: bool,
+    // Length, type and elements of a list whose header is not written yet
+    pending: Option<(usize, Option<&'static str>, Vec<Value>)>,
+}
+
+impl<'a, W: io::Write> ListSerializer<'a, W> {
+    // Write the header and the elements buffered so far
+    fn write_pending(&mut self) -> Result<()> {
+        if let Some((len, tp, elems)) = self.pending.take() {
+            if tp == Some(BINARY) {
+                let bytes: Vec<u8> = elems
+                    .iter()
+                    .map(|e| e.as_int().unwrap_or(0) as u8)
+                    .collect();
+                self.ser.ser.serialize_binary(&bytes)?;
+                return Ok(());
+            }
+            self.ser.ser.write_list_begin(len, tp)?;
+            for elem in elems.iter() {
+                self.ser.ser.serialize_value(elem)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<'a, W> StructSerializer<'a, W>
 where
     W: io::Write,
 {
-    pub fn new(name: &'static str, ser: &'a mut Serializer<W>) -> Self {
-        StructSerializer {
+    pub fn new(name: &'static str, ser: &'a mut Serializer<W>) -> Result<Self> {
+        let buffered = match ser.ser.get_definition_index(name) {
+            Some(ref_num) => {
+                ser.ser.write_object_ref(ref_num)?;
+                None
+            }
+            None => {
+                let fields = Serializer {
+                    ser: ser.ser.begin_buffered_object(name),
+                    config: ser.config.clone(),
+                    path: std::mem::take(&mut ser.path),
+                    partial: std::mem::take(&mut ser.partial),
+                };
+                Some(Box::new(BufferedFields {
+                    names: Vec::new(),
+                    ser: fields,
+                    skipped: false,
+                }))
+            }
+        };
+        Ok(StructSerializer {
             name,
             ser,
-            fields: Vec::new(),
+            buffered,
             inx: 0,
-            buf: Vec::new(),
-        }
+        })
     }
 }
 
@@ -59,35 +451,59 @@ impl<'a, W: io::Write> ser::SerializeStruct for StructSerializer<'a, W> {
         key: &'static str,
         value: &U,
     ) -> Result<()> {
-        if let Some(definition) = self.ser.0.get_definition(self.name) {
-            if key != definition.fields[self.inx] {
+        if let Some(buffered) = self.buffered.as_mut() {
+            buffered.names.push(key);
+            return buffered.ser.serialize_nested(value);
+        }
+        // the definition is unknown while it's reserved by an enclosing
+        // instance of the same struct
+        if let Some(definition) = self.ser.ser.get_definition(self.name) {
+            if definition.fields.get(self.inx).map(String::as_str) != Some(key) {
                 return Err(Error::SyntaxError(hessian_rs::ErrorKind::UnexpectedType(
                     "field name mismatch".to_string(),
                 )));
             }
+        }
+        self.inx += 1;
+        self.ser.serialize_nested(value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<()> {
+        if let Some(buffered) = self.buffered.as_mut() {
+            buffered.skipped = true;
+            return Ok(());
+        }
+        // a field of the definition still takes its place in the object
+        let skipped = match self.ser.ser.get_definition(self.name) {
+            Some(definition) => definition.fields.get(self.inx).map(String::as_str) == Some(key),
+            None => false,
+        };
+        if skipped {
             self.inx += 1;
-        } else {
-            self.fields.push(key);
+            self.ser.ser.serialize_null()?;
         }
-        value.serialize(&mut Serializer::new(&mut self.buf))?;
         Ok(())
     }
 
     #[inline]
     fn end(self) -> Result<()> {
-        let def = match self.ser.0.get_definition(self.name) {
-            Some(def) => def.clone(),
-            None => {
-                let def = Definition {
-                    name: self.name.into(),
-                    fields: self.fields.iter().map(|v| v.to_string()).collect(),
-                };
-                self.ser.0.write_definition(&def)?;
-                def
+        if let Some(buffered) = self.buffered {
+            let BufferedFields {
+                names,
+                ser,
+                skipped,
+            } = *buffered;
+            let def = Definition {
+                name: self.name.into(),
+                fields: names.iter().map(|v| v.to_string()).collect(),
+            };
+            self.ser.path = ser.path;
+            self.ser.partial = ser.partial;
+            if skipped {
+                self.ser.partial.insert(self.name);
             }
-        };
-        self.ser.0.write_object_start(&def)?;
-        self.ser.0.extend_from_slice(&self.buf)?;
+            self.ser.ser.end_buffered_object(&def, ser.ser)?;
+        }
         Ok(())
     }
 }
@@ -98,12 +514,31 @@ impl<'a, W: io::Write> ser::SerializeSeq for ListSerializer<'a, W> {
 
     #[inline]
     fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
-        value.serialize(&mut *self.ser)?;
+        if let Some((_, tp, elems)) = self.pending.as_mut() {
+            match value.serialize(ElementSerializer) {
+                Ok((Some(elem_tp), elem))
+                    if self.ser.config.buffers(elem_tp)
+                        && (tp.is_none() || *tp == Some(elem_tp)) =>
+                {
+                    *tp = Some(elem_tp);
+                    elems.push(elem);
+                    return Ok(());
+                }
+                // not an array of a primitive type, fallback to an untyped list
+                _ => *tp = None,
+            }
+            self.write_pending()?;
+        }
+        self.ser.serialize_nested(value)?;
         Ok(())
     }
 
     #[inline]
-    fn end(self) -> Result<()> {
+    fn end(mut self) -> Result<()> {
+        self.write_pending()?;
+        if !self.sized {
+            self.ser.ser.write_object_end()?;
+        }
         Ok(())
     }
 }
@@ -114,14 +549,14 @@ impl<'a, W: io::Write> ser::SerializeTuple for ListSerializer<'a, W> {
 
     #[inline]
     fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
-        value.serialize(&mut *self.ser)?;
+        self.ser.serialize_nested(value)?;
         Ok(())
     }
 
     #[inline]
     fn end(self) -> Result<()> {
         if !self.sized {
-            self.ser.0.write_object_end()?;
+            self.ser.ser.write_object_end()?;
         }
         Ok(())
     }
@@ -153,7 +588,7 @@ impl<'a, W: io::Write> ser::SerializeTupleVariant for ListSerializer<'a, W> {
 
     #[inline]
     fn end(self) -> Result<()> {
-        self.ser.0.write_object_end()?;
+        self.ser.ser.write_object_end()?;
         ser::SerializeTuple::end(self)?;
         Ok(())
     }
@@ -165,31 +600,33 @@ impl<'a, W: io::Write> ser::SerializeMap for MapSerializer<'a, W> {
 
     #[inline]
     fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
-        key.serialize(&mut *self.encoder)
+        match self.entries {
+            Some(ref mut entries) => {
+                entries.push((to_value(key)?, Value::Null));
+                Ok(())
+            }
+            None => self.encoder.serialize_nested(key),
+        }
     }
 
     #[inline]
     fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
-        value.serialize(&mut *self.encoder)
-    }
-
-    #[inline]
-    fn serialize_entry<K: ?Sized, V: ?Sized>(
-        &mut self,
-        key: &K,
-        value: &V,
-    ) -> std::result::Result<(), Self::Error>
-    where
-        K: Serialize,
-        V: Serialize,
-    {
-        key.serialize(&mut *self.encoder)?;
-        value.serialize(&mut *self.encoder)
+        match self.entries {
+            Some(ref mut entries) => {
+                let entry = entries.last_mut().ok_or_else(|| {
+                    <Error as ser::Error>::custom("serialize_value called before serialize_key")
+                })?;
+                entry.1 = to_value(value)?;
+                Ok(())
+            }
+            None => self.encoder.serialize_nested(value),
+        }
     }
 
     #[inline]
-    fn end(self) -> Result<()> {
-        self.encoder.0.write_object_end()?;
+    fn end(mut self) -> Result<()> {
+        self.write_entries()?;
+        self.encoder.ser.write_object_end()?;
         Ok(())
     }
 }
@@ -203,13 +640,19 @@ impl<'a, W: io::Write> ser::SerializeStruct for MapSerializer<'a, W> {
         key: &'static str,
         value: &T,
     ) -> Result<()> {
+        if self.encoder.config.skip_none_fields {
+            if let Ok((None, Value::Null)) = value.serialize(ElementSerializer) {
+                return Ok(());
+            }
+        }
         ser::SerializeMap::serialize_key(self, key)?;
         ser::SerializeMap::serialize_value(self, value)
     }
 
     #[inline]
-    fn end(self) -> Result<()> {
-        self.encoder.0.write_object_end()?;
+    fn end(mut self) -> Result<()> {
+        self.write_entries()?;
+        self.encoder.ser.write_object_end()?;
         Ok(())
     }
 }
@@ -229,10 +672,11 @@ impl<'a, W: io::Write> ser::SerializeStructVariant for MapSerializer<'a, W> {
     }
 
     #[inline]
-    fn end(self) -> Result<()> {
-        self.encoder.0.write_object_end()?;
+    fn end(mut self) -> Result<()> {
+        self.write_entries()?;
+        self.encoder.ser.write_object_end()?;
         // end of variant
-        self.encoder.0.write_object_end()?;
+        self.encoder.ser.write_object_end()?;
         Ok(())
     }
 }
@@ -246,117 +690,124 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
     type SerializeTupleStruct = Self::SerializeTuple;
     type SerializeTupleVariant = Self::SerializeTuple;
     type SerializeMap = MapSerializer<'a, W>;
-    type SerializeStruct = StructSerializer<'a, W>;
+    type SerializeStruct = SerializeStruct<'a, W>;
     type SerializeStructVariant = MapSerializer<'a, W>;
 
     #[inline]
     fn serialize_bool(self, value: bool) -> Result<()> {
-        self.0.serialize_bool(value)?;
+        self.ser.serialize_bool(value)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_i8(self, value: i8) -> Result<()> {
-        self.0.serialize_int(value as i32)?;
+        self.ser.serialize_int(value as i32)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_i16(self, value: i16) -> Result<()> {
-        self.0.serialize_int(value as i32)?;
+        self.ser.serialize_int(value as i32)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_i32(self, value: i32) -> Result<()> {
-        self.0.serialize_int(value)?;
+        self.ser.serialize_int(value)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_i64(self, value: i64) -> Result<()> {
-        self.0.serialize_long(value)?;
+        self.ser.serialize_long(value)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_u8(self, value: u8) -> Result<()> {
-        self.0.serialize_int(value as i32)?;
+        self.ser.serialize_int(value as i32)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_u16(self, value: u16) -> Result<()> {
-        self.0.serialize_int(value as i32)?;
+        self.ser.serialize_int(value as i32)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_u32(self, value: u32) -> Result<()> {
-        if value < i32::max_value() as u32 {
-            self.0.serialize_int(value as i32)?;
+        if value < i32::MAX as u32 {
+            self.ser.serialize_int(value as i32)?;
         } else {
-            self.0.serialize_long(value as i64)?;
+            self.ser.serialize_long(value as i64)?;
         }
         Ok(())
     }
 
     #[inline]
     fn serialize_u64(self, value: u64) -> Result<()> {
-        self.0.serialize_long(value as i64)?;
+        self.ser.serialize_long(value as i64)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_f32(self, value: f32) -> Result<()> {
-        self.0.serialize_double(value as f64)?;
+        self.ser.serialize_double(value as f64)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_f64(self, value: f64) -> Result<()> {
-        self.0.serialize_double(value)?;
+        self.ser.serialize_double(value)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_char(self, value: char) -> Result<()> {
         let mut buf = [0; 4];
-        self.0.serialize_string(value.encode_utf8(&mut buf))?;
+        self.ser.serialize_string(value.encode_utf8(&mut buf))?;
         Ok(())
     }
 
     #[inline]
     fn serialize_str(self, value: &str) -> Result<()> {
-        self.0.serialize_string(value)?;
+        self.ser.serialize_string(value)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_bytes(self, value: &[u8]) -> Result<()> {
-        self.0.serialize_binary(value)?;
+        self.ser.serialize_binary(value)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_unit(self) -> Result<()> {
-        self.0.serialize_null()?;
+        self.ser.serialize_null()?;
         Ok(())
     }
 
     #[inline]
     fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
-        self.0.serialize_null()?;
+        self.ser.serialize_null()?;
         Ok(())
     }
 
     #[inline]
     fn serialize_unit_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<()> {
+        if self.config.java_enums {
+            let def = Definition {
+                name: self.config.class_of(name).into(),
+                fields: vec!["name".into()],
+            };
+            self.ser.write_object_start(&def)?;
+        }
         self.serialize_str(variant)
     }
 
@@ -366,7 +817,7 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
         _name: &'static str,
         value: &T,
     ) -> Result<()> {
-        value.serialize(self)
+        self.serialize_nested(value)
     }
 
     #[inline]
@@ -377,10 +828,10 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
         variant: &'static str,
         value: &T,
     ) -> Result<()> {
-        self.0.write_map_start(Some(name))?;
+        self.ser.write_map_start(Some(self.config.class_of(name)))?;
         variant.serialize(&mut *self)?;
-        value.serialize(&mut *self)?;
-        self.0.write_object_end()?;
+        self.serialize_nested(value)?;
+        self.ser.write_object_end()?;
         Ok(())
     }
 
@@ -391,32 +842,45 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
 
     #[inline]
     fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
-        value.serialize(self)
+        self.serialize_nested(value)
     }
 
     #[inline]
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
         match len {
+            Some(len) if self.config.typed_lists || self.config.bytes_as_binary => {
+                Ok(ListSerializer {
+                    ser: self,
+                    sized: true,
+                    pending: Some((len, None, Vec::with_capacity(len))),
+                })
+            }
             Some(len) => {
-                self.0.write_list_begin(len, None)?;
+                self.ser.write_list_begin(len, None)?;
                 Ok(ListSerializer {
                     ser: self,
                     sized: true,
+                    pending: None,
+                })
+            }
+            None => {
+                self.ser.write_var_list_begin(None)?;
+                Ok(ListSerializer {
+                    ser: self,
+                    sized: false,
+                    pending: None,
                 })
             }
-            None => Ok(ListSerializer {
-                ser: self,
-                sized: false,
-            }),
         }
     }
 
     #[inline]
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
-        self.0.write_list_begin(len, None)?;
+        self.ser.write_list_begin(len, None)?;
         Ok(ListSerializer {
             ser: self,
             sized: true,
+            pending: None,
         })
     }
 
@@ -426,10 +890,12 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
         name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        self.0.write_list_begin(len, Some(name))?;
+        self.ser
+            .write_list_begin(len, Some(self.config.class_of(name)))?;
         Ok(ListSerializer {
             ser: self,
             sized: true,
+            pending: None,
         })
     }
 
@@ -441,28 +907,52 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        self.0.write_map_start(Some(name))?;
-        self.0.serialize_string(variant)?;
-        self.0
+        let name = self.config.class_of(name);
+        self.ser.write_map_start(Some(name))?;
+        self.ser.serialize_string(variant)?;
+        self.ser
             .write_list_begin(len, Some(&format!("{}.{}", name, variant)))?;
         Ok(ListSerializer {
             ser: self,
             sized: true,
+            pending: None,
         })
     }
 
     #[inline]
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.0.write_map_start(None)?;
-        Ok(MapSerializer {
-            _name: None,
-            encoder: self,
-        })
+        self.ser.write_map_start(None)?;
+        Ok(MapSerializer::new(None, self))
     }
 
     #[inline]
-    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        Ok(StructSerializer::new(name, self))
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        let mut encoding = self.config.encoding_of(name);
+        let name = self.config.class_of(name);
+        // The fields of a struct may vary between instances, e.g. with
+        // `#[serde(skip_serializing_if = "...")]` or adjacently tagged enums,
+        // an instance not matching the definition is written as a typed map.
+        // A definition written by an instance with skipped fields lacks them,
+        // the fields of the next instances can't be told from their count.
+        if encoding == StructEncoding::Object
+            && matches!(self.ser.get_definition(name),
+                Some(def) if def.fields.len() != len || self.partial.contains(name))
+        {
+            encoding = StructEncoding::TypedMap;
+        }
+        match encoding {
+            StructEncoding::Object => {
+                Ok(SerializeStruct::Object(StructSerializer::new(name, self)?))
+            }
+            StructEncoding::TypedMap => {
+                self.ser.write_map_start(Some(name))?;
+                Ok(SerializeStruct::Map(MapSerializer::new(Some(name), self)))
+            }
+            StructEncoding::Map => {
+                self.ser.write_map_start(None)?;
+                Ok(SerializeStruct::Map(MapSerializer::new(None, self)))
+            }
+        }
     }
 
     #[inline]
@@ -473,28 +963,23 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        self.0.write_map_start(Some(name))?;
+        self.ser.write_map_start(Some(self.config.class_of(name)))?;
         self.serialize_str(variant)?;
-        self.0.write_map_start(Some(variant))?;
-        Ok(MapSerializer {
-            _name: Some(variant),
-            encoder: self,
-        })
+        self.ser.write_map_start(Some(variant))?;
+        Ok(MapSerializer::new(Some(variant), self))
     }
 
     fn serialize_i128(self, v: i128) -> std::result::Result<Self::Ok, Self::Error> {
-        let _ = v;
-        Err(ser::Error::custom("i128 is not supported"))
+        self.serialize_int128(v < 0, v.unsigned_abs())
     }
 
     fn serialize_u128(self, v: u128) -> std::result::Result<Self::Ok, Self::Error> {
-        let _ = v;
-        Err(ser::Error::custom("u128 is not supported"))
+        self.serialize_int128(false, v)
     }
 
-    fn collect_str<T: ?Sized>(self, value: &T) -> std::result::Result<Self::Ok, Self::Error>
+    fn collect_str<T>(self, value: &T) -> std::result::Result<Self::Ok, Self::Error>
     where
-        T: std::fmt::Display,
+        T: std::fmt::Display + ?Sized,
     {
         self.serialize_str(&value.to_string())
     }
@@ -504,21 +989,493 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
     }
 }
 
+/// Serializer of the elements of a typed list or binary, which fails for
+/// any value but the primitives with a java array type and `None`.
+struct ElementSerializer;
+
+fn not_primitive() -> Error {
+    ser::Error::custom("not a primitive value")
+}
+
+impl ser::Serializer for ElementSerializer {
+    type Ok = (Option<&'static str>, Value);
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<Self::Ok, Error>;
+    type SerializeTuple = ser::Impossible<Self::Ok, Error>;
+    type SerializeTupleStruct = ser::Impossible<Self::Ok, Error>;
+    type SerializeTupleVariant = ser::Impossible<Self::Ok, Error>;
+    type SerializeMap = ser::Impossible<Self::Ok, Error>;
+    type SerializeStruct = ser::Impossible<Self::Ok, Error>;
+    type SerializeStructVariant = ser::Impossible<Self::Ok, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        Ok((Some("[boolean"), Value::Bool(v)))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        Ok((Some("[int"), Value::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        Ok((Some("[long"), Value::Long(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        Ok((Some(BINARY), Value::Int(v as i32)))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        Ok((Some("[double"), Value::Double(v)))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        Ok((Some("[string"), Value::String(v.into())))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok((None, Value::Null))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok> {
+        Err(not_primitive())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(not_primitive())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(not_primitive())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(not_primitive())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(not_primitive())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(not_primitive())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(not_primitive())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(not_primitive())
+    }
+}
+
 pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
 where
     T: Serialize,
 {
     let mut buf = Vec::new();
-    let mut ser = Serializer::new(&mut buf);
-    value.serialize(&mut ser)?;
+    to_vec_in(&mut buf, value)?;
     Ok(buf)
 }
 
+/// Serialize a `T` to the end of `buf`, reusing its allocation
+pub fn to_vec_in<T>(buf: &mut Vec<u8>, value: &T) -> Result<()>
+where
+    T: Serialize + ?Sized,
+{
+    to_writer(buf, value)
+}
+
+/// Serialize a `T` to a writer
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: Serialize + ?Sized,
+{
+    let mut ser = Serializer::new(writer);
+    value.serialize(&mut ser)
+}
+
 #[cfg(test)]
 mod test {
-    use crate::ser::to_vec;
+    use crate::ser::{to_vec, to_vec_in, to_writer, Serializer};
     use serde::Serialize;
 
+    #[test]
+    fn test_writer_accessors() {
+        let mut ser = Serializer::new(std::io::BufWriter::new(Vec::new()));
+        "a".serialize(&mut ser).unwrap();
+        ser.flush().unwrap();
+        assert_eq!(ser.get_ref().get_ref(), &[0x01, b'a']);
+        assert_eq!(ser.into_inner().into_inner().unwrap(), &[0x01, b'a']);
+    }
+
+    #[test]
+    fn test_preload_definitions() {
+        #[derive(Serialize)]
+        #[serde(rename = "example.Car")]
+        struct Car {
+            color: &'static str,
+        }
+        let car = Car { color: "red" };
+
+        let mut ser = Serializer::new(Vec::new());
+        car.serialize(&mut ser).unwrap();
+        let cache = ser.definition_cache();
+
+        let mut ser = Serializer::new(Vec::new()).preload_definitions(cache);
+        car.serialize(&mut ser).unwrap();
+        ser.reset();
+        Some(&car).serialize(&mut ser).unwrap();
+        assert_eq!(
+            ser.into_inner(),
+            &[0x60, 0x03, b'r', b'e', b'd', 0x60, 0x03, b'r', b'e', b'd']
+        );
+    }
+
+    #[test]
+    fn test_to_vec_in() {
+        let mut buf = Vec::new();
+        to_vec_in(&mut buf, &1).unwrap();
+        to_vec_in(&mut buf, "a").unwrap();
+        assert_eq!(buf, &[0x91, 0x01, b'a']);
+        buf.clear();
+        to_writer(&mut buf, &()).unwrap();
+        assert_eq!(buf, b"N");
+    }
+
+    #[test]
+    fn test_unsized_seq() {
+        let mut output = Vec::new();
+        let mut ser = Serializer::new(&mut output);
+        serde::Serializer::collect_seq(&mut ser, (0..4).filter(|i| i % 2 == 1)).unwrap();
+        assert_eq!(output, [0x57, 0x91, 0x93, b'Z']);
+        assert_eq!(
+            crate::de::from_slice::<_, Vec<i32>>(&output).unwrap(),
+            vec![1, 3]
+        );
+    }
+
+    #[test]
+    fn test_typed_lists() {
+        use super::SerializerConfig;
+
+        fn to_vec_typed<T: Serialize>(value: &T) -> Vec<u8> {
+            let mut output = Vec::new();
+            let config = SerializerConfig::new().typed_lists(true);
+            value
+                .serialize(&mut Serializer::with_config(&mut output, config))
+                .unwrap();
+            output
+        }
+
+        assert_eq!(to_vec_typed(&vec![1, 2]), b"\x72\x04[int\x91\x92");
+        assert_eq!(to_vec_typed(&vec![1_i64]), b"\x71\x05[long\xe1");
+        assert_eq!(
+            to_vec_typed(&vec![vec!["a"], vec![]]),
+            b"\x7a\x71\x07[string\x01a\x78"
+        );
+        // the type of a list is written once
+        assert_eq!(
+            to_vec_typed(&(vec![true], vec![false])),
+            b"\x7a\x71\x08[boolean\x54\x71\x90\x46"
+        );
+        // other lists are untyped
+        assert_eq!(
+            to_vec_typed(&vec![Some(1), None]),
+            to_vec(&vec![Some(1), None]).unwrap()
+        );
+        assert_eq!(to_vec_typed(&vec![1_u8]), to_vec(&vec![1_u8]).unwrap());
+        assert_eq!(to_vec_typed(&[1.5, 2.0]), to_vec(&[1.5, 2.0]).unwrap());
+        assert_eq!(to_vec(&vec![1, 2]).unwrap(), b"\x7a\x91\x92");
+
+        let decoded: Vec<String> = crate::de::from_slice(to_vec_typed(&vec!["a", "b"])).unwrap();
+        assert_eq!(decoded, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_bytes_as_binary() {
+        use super::SerializerConfig;
+
+        let to_vec_with = |value: &dyn Fn(&mut Serializer<&mut Vec<u8>>), config| {
+            let mut output = Vec::new();
+            value(&mut Serializer::with_config(&mut output, config));
+            output
+        };
+        let bytes = vec![1_u8, 2];
+        let config = SerializerConfig::new().bytes_as_binary(true);
+        let output = to_vec_with(&|ser| bytes.serialize(ser).unwrap(), config.clone());
+        assert_eq!(output, &[0x22, 0x01, 0x02]);
+        assert_eq!(crate::de::from_slice::<_, Vec<u8>>(&output).unwrap(), bytes);
+        // lists of other ints are not affected
+        let output = to_vec_with(&|ser| vec![1_i32].serialize(ser).unwrap(), config.clone());
+        assert_eq!(output, &[0x79, 0x91]);
+        let output = to_vec_with(
+            &|ser| vec![Vec::<u8>::new()].serialize(ser).unwrap(),
+            config.typed_lists(true),
+        );
+        assert_eq!(output, &[0x79, 0x78]);
+
+        // without the option a `Vec<u8>` is a list of ints
+        let output = to_vec(&bytes).unwrap();
+        assert_eq!(output, &[0x7a, 0x91, 0x92]);
+        assert_eq!(crate::de::from_slice::<_, Vec<u8>>(&output).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_skip_none_fields() {
+        use super::{SerializerConfig, StructEncoding};
+        use serde::Deserialize;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Car {
+            color: Option<String>,
+            #[serde(default)]
+            mileage: Option<i32>,
+            #[serde(default)]
+            parts: Vec<String>,
+        }
+        let car = Car {
+            color: None,
+            mileage: Some(1),
+            parts: vec![],
+        };
+        let config = SerializerConfig::new()
+            .struct_encoding(StructEncoding::Map)
+            .skip_none_fields(true);
+        let mut output = Vec::new();
+        car.serialize(&mut Serializer::with_config(&mut output, config))
+            .unwrap();
+        assert_eq!(
+            output,
+            [&b"H\x07mileage\x91\x05parts\x78"[..], b"Z"].concat()
+        );
+        assert_eq!(crate::de::from_slice::<_, Car>(&output).unwrap(), car);
+        assert_eq!(
+            crate::de::from_slice::<_, Car>(b"H\x05color\x03redZ").unwrap(),
+            Car {
+                color: Some("red".to_string()),
+                mileage: None,
+                parts: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_skip_serializing_if() {
+        use serde::Deserialize;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(rename = "example.S")]
+        struct S {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            a: Option<i32>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            b: Option<i32>,
+        }
+        let values = vec![
+            S {
+                a: Some(1),
+                b: None,
+            },
+            S {
+                a: None,
+                b: Some(2),
+            },
+            S {
+                a: Some(3),
+                b: None,
+            },
+        ];
+        let output = to_vec(&values).unwrap();
+        assert_eq!(crate::de::from_slice::<_, Vec<S>>(&output).unwrap(), values);
+        // the definition has the fields of the first instance only
+        let value = hessian_rs::de::Deserializer::new(&output)
+            .read_value()
+            .unwrap();
+        assert_eq!(value[1], hessian_rs::hessian!("example.S" => { "b": 2 }));
+
+        // instances with all the fields written first share their definition
+        let values = vec![
+            S {
+                a: Some(1),
+                b: Some(2),
+            },
+            S {
+                a: None,
+                b: Some(2),
+            },
+            S {
+                a: Some(1),
+                b: Some(2),
+            },
+        ];
+        let output = to_vec(&values).unwrap();
+        assert_eq!(crate::de::from_slice::<_, Vec<S>>(&output).unwrap(), values);
+        assert_eq!(output.iter().filter(|b| **b == 0x60).count(), 2);
+    }
+
+    #[test]
+    fn test_java_enums() {
+        use super::SerializerConfig;
+        use serde::Deserialize;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(rename = "example.Color")]
+        enum Color {
+            Red,
+            Blue,
+        }
+        let config = SerializerConfig::new().java_enums(true);
+        let mut output = Vec::new();
+        let colors = (Color::Red, Color::Blue);
+        colors
+            .serialize(&mut Serializer::with_config(&mut output, config))
+            .unwrap();
+        assert_eq!(
+            output,
+            [
+                &[0x7a, b'C', 0x0d][..],
+                b"example.Color",
+                &[0x91, 0x04],
+                b"name",
+                &[0x60, 0x03],
+                b"Red",
+                &[0x60, 0x04],
+                b"Blue",
+            ]
+            .concat()
+        );
+        assert_eq!(
+            crate::de::from_slice::<_, (Color, Color)>(&output).unwrap(),
+            colors
+        );
+        assert_eq!(to_vec(&Color::Red).unwrap(), b"\x03Red");
+    }
+
+    #[test]
+    fn test_int128() {
+        use super::{Int128Encoding, SerializerConfig};
+
+        let output = to_vec(&(-(1_i128 << 32))).unwrap();
+        assert_eq!(
+            output,
+            [
+                &[b'C', 0x14][..],
+                b"java.math.BigInteger",
+                &[0x92, 0x06],
+                b"signum",
+                &[0x03],
+                b"mag",
+                &[0x60, 0x8f, 0x72, 0x04],
+                b"[int",
+                &[0x91, 0x90],
+            ]
+            .concat()
+        );
+        assert_eq!(
+            crate::de::from_slice::<_, i128>(&output).unwrap(),
+            -(1 << 32)
+        );
+
+        let mut output = Vec::new();
+        let config = SerializerConfig::new().int128_encoding(Int128Encoding::String);
+        u128::MAX
+            .serialize(&mut Serializer::with_config(&mut output, config))
+            .unwrap();
+        assert_eq!(output[..2], [0x30, 39]);
+        assert_eq!(output[2..], *u128::MAX.to_string().as_bytes());
+        assert_eq!(
+            crate::de::from_slice::<_, u128>(&output).unwrap(),
+            u128::MAX
+        );
+        assert_eq!(crate::de::from_slice::<_, i128>(&[0x91]).unwrap(), 1);
+    }
+
     #[test]
     fn test_struct() {
         {
@@ -538,8 +1495,7 @@ mod test {
                 &[
                     b'C', 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'C', b'a', b'r',
                     0x92, 0x05, b'c', b'o', b'l', b'o', b'r', 0x05, b'm', b'o', b'd', b'e', b'l',
-                    b'O', 0x90, 0x03, b'r', b'e', b'd', 0x07, b'F', b'e', b'r', b'r', b'a', b'r',
-                    b'i',
+                    0x60, 0x03, b'r', b'e', b'd', 0x07, b'F', b'e', b'r', b'r', b'a', b'r', b'i',
                 ]
             );
         }
@@ -558,11 +1514,195 @@ mod test {
             output,
             &[
                 b'C', 0x04, b'T', b'e', b's', b't', 0x92, 0x03, b'i', b'n', b't', 0x03, b's', b'e',
-                b'q', b'O', 0x90, 0x91, 0x7a, 0x01, b'a', 0x01, b'b'
+                b'q', 0x60, 0x91, 0x7a, 0x01, b'a', 0x01, b'b'
             ]
         )
     }
 
+    #[test]
+    fn test_class_name() {
+        use super::{Serializer, SerializerConfig};
+        use crate::class::register_class_name;
+
+        #[derive(Serialize)]
+        struct Point {
+            x: i32,
+        }
+        // the registrations are global, the serde name is unique to the test
+        #[derive(Serialize)]
+        #[serde(rename = "ser.tests.Shape")]
+        enum Shape {
+            Dot(Point),
+        }
+
+        let to_vec_with = |config: SerializerConfig| {
+            let mut output = Vec::new();
+            Shape::Dot(Point { x: 1 })
+                .serialize(&mut Serializer::with_config(&mut output, config))
+                .unwrap();
+            output
+        };
+
+        register_class_name("ser.tests.Shape", "example.Shape");
+        let output = to_vec_with(SerializerConfig::new().class_name("Point", "example.Point"));
+        assert_eq!(
+            output,
+            [
+                &[b'M', 0x0d][..],
+                b"example.Shape",
+                &[0x03],
+                b"Dot",
+                &[b'C', 0x0d],
+                b"example.Point",
+                &[0x91, 0x01, b'x', 0x60, 0x91, b'Z'],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_struct_encoding() {
+        use super::{Serializer, SerializerConfig, StructEncoding};
+
+        #[derive(Serialize)]
+        #[serde(rename = "example.Car")]
+        struct Car {
+            color: &'static str,
+        }
+        #[derive(Serialize)]
+        struct Garage {
+            car: Car,
+        }
+
+        let to_vec_with = |config: SerializerConfig| {
+            let mut output = Vec::new();
+            let garage = Garage {
+                car: Car { color: "red" },
+            };
+            garage
+                .serialize(&mut Serializer::with_config(&mut output, config))
+                .unwrap();
+            output
+        };
+
+        let output = to_vec_with(SerializerConfig::new().struct_encoding(StructEncoding::Map));
+        assert_eq!(
+            output,
+            [
+                &[b'H', 0x03][..],
+                b"car",
+                &[b'H', 0x05],
+                b"color",
+                &[0x03],
+                b"red",
+                b"ZZ"
+            ]
+            .concat()
+        );
+
+        let output = to_vec_with(
+            SerializerConfig::new()
+                .struct_encoding(StructEncoding::Map)
+                .struct_encoding_for("example.Car", StructEncoding::TypedMap),
+        );
+        assert_eq!(
+            output,
+            [
+                &[b'H', 0x03][..],
+                b"car",
+                &[b'M', 0x0b],
+                b"example.Car",
+                &[0x05],
+                b"color",
+                &[0x03],
+                b"red",
+                b"ZZ"
+            ]
+            .concat()
+        );
+
+        let output = to_vec_with(
+            SerializerConfig::new().struct_encoding_for("Garage", StructEncoding::TypedMap),
+        );
+        assert_eq!(
+            &output[..8],
+            &[b'M', 0x06, b'G', b'a', b'r', b'a', b'g', b'e']
+        );
+        assert_eq!(output[12], b'C');
+    }
+
+    #[test]
+    fn test_value_serializer_options() {
+        use super::Serializer;
+
+        #[derive(Serialize)]
+        struct Test {
+            int: i32,
+        }
+        let mut output = Vec::new();
+        let builder = hessian_rs::ser::Serializer::builder()
+            .compact_int(false)
+            .compact_objects(false);
+        Test { int: 1 }
+            .serialize(&mut Serializer::from(builder.build(&mut output)))
+            .unwrap();
+        // the definition ref and the field are both written in the long form
+        assert_eq!(
+            &output[output.len() - 11..],
+            &[b'O', b'I', 0, 0, 0, 0, b'I', 0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn test_canonical() {
+        use super::Serializer;
+        use std::collections::HashMap;
+
+        let map: HashMap<&str, i32> = (0..16).map(|i| (["a", "b", "c", "d"][i % 4], 1)).collect();
+        let mut output = Vec::new();
+        map.serialize(&mut Serializer::new(&mut output).canonical(true))
+            .unwrap();
+        assert_eq!(
+            output,
+            &[b'H', 0x01, b'a', 0x91, 0x01, b'b', 0x91, 0x01, b'c', 0x91, 0x01, b'd', 0x91, b'Z']
+        );
+    }
+
+    #[test]
+    fn test_circular_reference() {
+        use hessian_rs::ErrorKind;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        #[derive(Serialize)]
+        struct Node {
+            value: i32,
+            next: Option<Rc<RefCell<Node>>>,
+        }
+
+        let tail = Rc::new(RefCell::new(Node {
+            value: 2,
+            next: None,
+        }));
+        let head = Node {
+            value: 1,
+            next: Some(tail.clone()),
+        };
+        // the same value may appear more than once
+        assert!(to_vec(&vec![tail.clone(), tail.clone()]).is_ok());
+        assert!(to_vec(&head).is_ok());
+
+        tail.borrow_mut().next = Some(tail.clone());
+        match to_vec(&head) {
+            Err(crate::error::Error::SyntaxError(ErrorKind::CircularReference(_))) => {}
+            v => panic!(
+                "expect circular reference error, but get {:?}",
+                v.map(|_| ())
+            ),
+        }
+        tail.borrow_mut().next = None;
+    }
+
     // todo: how keep consistence with java class?
     #[test]
     fn test_enum() {
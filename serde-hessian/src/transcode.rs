@@ -0,0 +1,274 @@
+//! Conversion between serde formats without building a `Value` tree.
+//!
+//! `transcode` drives a serializer with the values of a self-describing
+//! deserializer as they are read, e.g. to convert hessian streams to JSON
+//! and back with `transcode_to_json` and `transcode_from_json`.
+
+use std::cell::RefCell;
+use std::fmt;
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
+
+#[cfg(feature = "json")]
+use crate::error::Error;
+
+/// Serialize the value read by `deserializer` with `serializer`.
+///
+/// The value is read with `deserialize_any`, so a struct is transcoded as a
+/// map of its fields and a date as its milliseconds. Each element is
+/// serialized once, serializer options which serialize an element twice to
+/// inspect it first, like `SerializerConfig::typed_lists`, aren't supported.
+pub fn transcode<'de, D, S>(deserializer: D, serializer: S) -> Result<S::Ok, S::Error>
+where
+    D: Deserializer<'de>,
+    S: Serializer,
+{
+    Transcoder::new(deserializer).serialize(serializer)
+}
+
+/// Convert the hessian value in `input` to JSON, written to `writer`.
+#[cfg(feature = "json")]
+pub fn transcode_to_json<R, W>(input: R, writer: W) -> Result<(), Error>
+where
+    R: AsRef<[u8]>,
+    W: std::io::Write,
+{
+    let input = input.as_ref();
+    let mut de = crate::de::Deserializer::from_bytes(input)?;
+    let mut ser = serde_json::Serializer::new(writer);
+    transcode(&mut de, &mut ser).map_err(|e| {
+        // the json error only keeps the message of an error of the input,
+        // which is read again for it, e.g. so that a truncated input is an eof
+        match hessian_rs::de::Deserializer::new(input).skip_value() {
+            Err(e) => e.into(),
+            Ok(()) => ser::Error::custom(e),
+        }
+    })
+}
+
+/// Convert the JSON value read from `reader` to hessian, written to `writer`.
+#[cfg(feature = "json")]
+pub fn transcode_from_json<R, W>(reader: R, writer: W) -> Result<(), Error>
+where
+    R: std::io::Read,
+    W: std::io::Write,
+{
+    let mut de = serde_json::Deserializer::from_reader(reader);
+    let mut ser = crate::ser::Serializer::new(writer);
+    transcode(&mut de, &mut ser)?;
+    de.end().map_err(<Error as de::Error>::custom)?;
+    ser.flush()
+}
+
+// A deserializer as a value to serialize, it can be serialized once
+struct Transcoder<D>(RefCell<Option<D>>);
+
+impl<D> Transcoder<D> {
+    fn new(deserializer: D) -> Self {
+        Transcoder(RefCell::new(Some(deserializer)))
+    }
+}
+
+impl<'de, D: Deserializer<'de>> Serialize for Transcoder<D> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0.borrow_mut().take() {
+            Some(d) => d
+                .deserialize_any(Visitor(serializer))
+                .map_err(ser::Error::custom),
+            None => Err(ser::Error::custom("value already transcoded")),
+        }
+    }
+}
+
+struct Visitor<S>(S);
+
+macro_rules! forward_visit {
+    ($($visit:ident($ty:ty) => $serialize:ident,)*) => {
+        $(
+            fn $visit<E: de::Error>(self, v: $ty) -> Result<S::Ok, E> {
+                self.0.$serialize(v).map_err(de::Error::custom)
+            }
+        )*
+    };
+}
+
+impl<'de, S: Serializer> de::Visitor<'de> for Visitor<S> {
+    type Value = S::Ok;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    forward_visit! {
+        visit_bool(bool) => serialize_bool,
+        visit_i8(i8) => serialize_i8,
+        visit_i16(i16) => serialize_i16,
+        visit_i32(i32) => serialize_i32,
+        visit_i64(i64) => serialize_i64,
+        visit_i128(i128) => serialize_i128,
+        visit_u8(u8) => serialize_u8,
+        visit_u16(u16) => serialize_u16,
+        visit_u32(u32) => serialize_u32,
+        visit_u64(u64) => serialize_u64,
+        visit_u128(u128) => serialize_u128,
+        visit_f32(f32) => serialize_f32,
+        visit_f64(f64) => serialize_f64,
+        visit_char(char) => serialize_char,
+        visit_str(&str) => serialize_str,
+        visit_bytes(&[u8]) => serialize_bytes,
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<S::Ok, E> {
+        self.0.serialize_unit().map_err(de::Error::custom)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<S::Ok, E> {
+        self.0.serialize_none().map_err(de::Error::custom)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<S::Ok, D::Error> {
+        self.0
+            .serialize_some(&Transcoder::new(d))
+            .map_err(de::Error::custom)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<S::Ok, D::Error> {
+        Transcoder::new(d)
+            .serialize(self.0)
+            .map_err(de::Error::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<S::Ok, A::Error> {
+        let mut s = self
+            .0
+            .serialize_seq(seq.size_hint())
+            .map_err(de::Error::custom)?;
+        while let Some(()) = seq.next_element_seed(SeqSeed(&mut s))? {}
+        s.end().map_err(de::Error::custom)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<S::Ok, A::Error> {
+        let mut s = self
+            .0
+            .serialize_map(map.size_hint())
+            .map_err(de::Error::custom)?;
+        while let Some(()) = map.next_key_seed(KeySeed(&mut s))? {
+            map.next_value_seed(ValueSeed(&mut s))?;
+        }
+        s.end().map_err(de::Error::custom)
+    }
+}
+
+struct SeqSeed<'a, S>(&'a mut S);
+
+impl<'de, 'a, S: SerializeSeq> DeserializeSeed<'de> for SeqSeed<'a, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+        self.0
+            .serialize_element(&Transcoder::new(d))
+            .map_err(de::Error::custom)
+    }
+}
+
+struct KeySeed<'a, S>(&'a mut S);
+
+impl<'de, 'a, S: SerializeMap> DeserializeSeed<'de> for KeySeed<'a, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+        self.0
+            .serialize_key(&Transcoder::new(d))
+            .map_err(de::Error::custom)
+    }
+}
+
+struct ValueSeed<'a, S>(&'a mut S);
+
+impl<'de, 'a, S: SerializeMap> DeserializeSeed<'de> for ValueSeed<'a, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+        self.0
+            .serialize_value(&Transcoder::new(d))
+            .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::transcode;
+    use crate::de::Deserializer;
+    use crate::ser::{to_vec, Serializer};
+    use hessian_rs::hessian;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    #[serde(rename = "example.Car")]
+    struct Car {
+        color: String,
+        model: Option<String>,
+        parts: Vec<Part>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename = "example.Part")]
+    struct Part {
+        id: i64,
+        weight: f64,
+    }
+
+    #[test]
+    fn test_transcode() {
+        let car = Car {
+            color: "red".into(),
+            model: None,
+            parts: vec![Part { id: 1, weight: 1.5 }, Part { id: 2, weight: 0.1 }],
+        };
+        let input = to_vec(&car).unwrap();
+        let mut output = Vec::new();
+        let mut de = Deserializer::from_bytes(&input).unwrap();
+        transcode(&mut de, &mut Serializer::new(&mut output)).unwrap();
+
+        // objects are transcoded as maps
+        let value = hessian_rs::de::Deserializer::new(&output).read_value();
+        assert_eq!(
+            value.unwrap(),
+            hessian!({
+                "color": "red",
+                "model": null,
+                "parts": [{ "id": 1_i64, "weight": 1.5 }, { "id": 2_i64, "weight": 0.1 }],
+            })
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_transcode_json() {
+        use super::{transcode_from_json, transcode_to_json};
+
+        let car = Car {
+            color: "red".into(),
+            model: None,
+            parts: vec![Part { id: 1, weight: 1.5 }],
+        };
+        let mut json = Vec::new();
+        transcode_to_json(to_vec(&car).unwrap(), &mut json).unwrap();
+        assert_eq!(
+            String::from_utf8(json.clone()).unwrap(),
+            r#"{"color":"red","model":null,"parts":[{"id":1,"weight":1.5}]}"#
+        );
+
+        let mut hessian = Vec::new();
+        transcode_from_json(&json[..], &mut hessian).unwrap();
+        let mut back = Vec::new();
+        transcode_to_json(&hessian, &mut back).unwrap();
+        assert_eq!(back, json);
+        assert!(transcode_from_json(&b"[1, 2"[..], &mut Vec::new()).is_err());
+        let truncated = &hessian[..hessian.len() - 1];
+        assert!(transcode_to_json(truncated, &mut Vec::new())
+            .unwrap_err()
+            .is_eof());
+    }
+}
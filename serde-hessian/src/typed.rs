@@ -0,0 +1,70 @@
+//! Capture the class name of an object or a typed map with its value.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+
+// The struct name `Typed` passes to `deserialize_struct`, which the
+// deserializers of this crate visit as `{ "class": CLASS, "value": VALUE }`
+pub(crate) const TYPED: &str = "$serde_hessian::Typed";
+const FIELDS: &[&str] = &["class", "value"];
+
+/// A value decoded from an object or a typed map, with its class name.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_hessian::{de::from_slice, Typed};
+/// # use hessian_rs::hessian;
+/// # #[derive(Deserialize)]
+/// # struct Shape {
+/// #     size: f64,
+/// # }
+/// # let buf = hessian_rs::to_vec(&hessian!("example.Circle" => { "size": 2.0 }))?;
+/// let shape: Typed<Shape> = from_slice(&buf[..])?;
+/// let area = match shape.class.as_str() {
+///     "example.Circle" => std::f64::consts::PI * shape.value.size.powi(2) / 4.0,
+///     _ => shape.value.size.powi(2),
+/// };
+/// assert_eq!(area, std::f64::consts::PI);
+/// # Ok::<(), serde_hessian::error::Error>(())
+/// ```
+///
+/// Untyped maps and other values fail to decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Typed<T> {
+    pub class: String,
+    pub value: T,
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Typed<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct(TYPED, FIELDS, TypedVisitor(PhantomData))
+    }
+}
+
+struct TypedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for TypedVisitor<T> {
+    type Value = Typed<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an object or a typed map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut class = None;
+        let mut value = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "class" => class = Some(map.next_value()?),
+                "value" => value = Some(map.next_value()?),
+                _ => return Err(de::Error::unknown_field(&key, FIELDS)),
+            }
+        }
+        Ok(Typed {
+            class: class.ok_or_else(|| de::Error::missing_field("class"))?,
+            value: value.ok_or_else(|| de::Error::missing_field("value"))?,
+        })
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::{hash_map, HashMap};
+use std::vec;
+
+use hessian_rs::value::{List, Map};
+use hessian_rs::{ErrorKind, Value};
+use serde::de::{self, IntoDeserializer, Visitor};
+
+use crate::big_integer;
+use crate::error::Error;
+use crate::typed::TYPED;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Deserializer which reads from an owned `Value` tree.
+pub struct Deserializer(Value);
+
+impl Deserializer {
+    pub fn new(value: Value) -> Self {
+        // a shared value is read as the value it holds
+        Deserializer(value.unshared())
+    }
+}
+
+fn unexpected(expected: &str, value: &Value) -> Error {
+    Error::SyntaxError(ErrorKind::UnexpectedType(format!(
+        "expect a {} value, but get {}",
+        expected, value
+    )))
+}
+
+fn list_into_vec(list: List) -> Vec<Value> {
+    match list {
+        List::Typed(_, v) | List::Untyped(v) => v,
+    }
+}
+
+fn map_into_iter(map: Map) -> hash_map::IntoIter<Value, Value> {
+    match map {
+        Map::Typed(_, m) | Map::Untyped(m) => m.into_iter(),
+    }
+}
+
+struct SeqAccess {
+    iter: vec::IntoIter<Value>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(value) => seed.deserialize(Deserializer::new(value)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapAccess {
+    iter: hash_map::IntoIter<Value, Value>,
+    value: Option<Value>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(Deserializer::new(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(Deserializer::new(value)),
+            None => Err(de::Error::custom(
+                "next_value_seed called before next_key_seed",
+            )),
+        }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EnumAccess {
+    variant: Value,
+    value: Value,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = Error;
+    type Variant = Deserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(Deserializer::new(self.variant))?;
+        Ok((variant, Deserializer::new(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.0 {
+            Value::Null => Ok(()),
+            v => Err(unexpected("unit variant", &v)),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+macro_rules! deserialize_number {
+    ($method:ident, $visit:ident, $ty:ty) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: Visitor<'de>,
+        {
+            match self.0 {
+                Value::Int(v) => visitor.$visit(v as $ty),
+                Value::Long(v) => visitor.$visit(v as $ty),
+                Value::Double(v) => visitor.$visit(v as $ty),
+                v => Err(unexpected(stringify!($ty), &v)),
+            }
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(v) => visitor.visit_bool(v),
+            Value::Int(v) => visitor.visit_i32(v),
+            Value::Long(v) => visitor.visit_i64(v),
+            Value::Double(v) => visitor.visit_f64(v),
+            Value::Date(v) => visitor.visit_i64(v),
+            Value::Bytes(v) => visitor.visit_byte_buf(v.into_vec()),
+            Value::String(v) => visitor.visit_string(v.into_string()),
+            Value::Ref(_) => Err(Error::UnSupportedRefType),
+            Value::List(l) => visitor.visit_seq(SeqAccess {
+                iter: list_into_vec(l).into_iter(),
+            }),
+            Value::Map(m) => visitor.visit_map(MapAccess {
+                iter: map_into_iter(m),
+                value: None,
+            }),
+            Value::Unknown(bytes) => Err(Error::SyntaxError(ErrorKind::InvalidTag(
+                bytes.first().copied().unwrap_or_default(),
+            ))),
+            v @ Value::Shared(_) => Deserializer::new(v).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Bool(v) => visitor.visit_bool(v),
+            v => Err(unexpected("bool", &v)),
+        }
+    }
+
+    deserialize_number!(deserialize_i8, visit_i8, i8);
+    deserialize_number!(deserialize_i16, visit_i16, i16);
+    deserialize_number!(deserialize_i32, visit_i32, i32);
+    deserialize_number!(deserialize_u8, visit_u8, u8);
+    deserialize_number!(deserialize_u16, visit_u16, u16);
+    deserialize_number!(deserialize_u32, visit_u32, u32);
+    deserialize_number!(deserialize_u64, visit_u64, u64);
+    deserialize_number!(deserialize_f32, visit_f32, f32);
+    deserialize_number!(deserialize_f64, visit_f64, f64);
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_i128(big_integer::to_i128(&self.0)?)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_u128(big_integer::to_u128(&self.0)?)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Int(v) => visitor.visit_i64(v as i64),
+            Value::Long(v) | Value::Date(v) => visitor.visit_i64(v),
+            Value::Double(v) => visitor.visit_i64(v as i64),
+            v => Err(unexpected("i64", &v)),
+        }
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::String(s) => {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => visitor.visit_char(c),
+                    _ => Err(unexpected("char", &Value::String(s))),
+                }
+            }
+            Value::Int(v) if (0..256).contains(&v) => visitor.visit_char(v as u8 as char),
+            v => Err(unexpected("char", &v)),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::String(s) => visitor.visit_string(s.into_string()),
+            Value::Bytes(b) => visitor.visit_string(String::from_utf8(b.into_vec())?),
+            v => Err(unexpected("string", &v)),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Bytes(b) => visitor.visit_byte_buf(b.into_vec()),
+            Value::String(s) => visitor.visit_string(s.into_string()),
+            Value::List(l) => {
+                let bytes = list_into_vec(l)
+                    .into_iter()
+                    .map(|v| match v {
+                        Value::Int(i) => Ok(i as u8),
+                        v => Err(unexpected("byte", &v)),
+                    })
+                    .collect::<Result<Vec<u8>>>()?;
+                visitor.visit_byte_buf(bytes)
+            }
+            v => Err(unexpected("bytes", &v)),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Null => visitor.visit_unit(),
+            v => Err(unexpected("unit", &v)),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::List(l) => visitor.visit_seq(SeqAccess {
+                iter: list_into_vec(l).into_iter(),
+            }),
+            v => Err(unexpected("list", &v)),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Map(m) => visitor.visit_map(MapAccess {
+                iter: map_into_iter(m),
+                value: None,
+            }),
+            v => Err(unexpected("map", &v)),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            // `Typed` visits `{ "class": CLASS, "value": VALUE }`
+            Value::Map(Map::Typed(class, m)) if name == TYPED => {
+                let mut typed = HashMap::new();
+                typed.insert(Value::from("class"), Value::from(class.as_str()));
+                typed.insert(Value::from("value"), Value::Map(Map::Typed(class, m)));
+                visitor.visit_map(MapAccess {
+                    iter: typed.into_iter(),
+                    value: None,
+                })
+            }
+            v if name == TYPED => Err(unexpected("typed map", &v)),
+            v => Deserializer::new(v).deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::String(s) => visitor.visit_enum(s.into_deserializer()),
+            Value::Map(m) => {
+                let mut iter = map_into_iter(m);
+                match (iter.next(), iter.next()) {
+                    (Some((variant, value)), None) => {
+                        visitor.visit_enum(EnumAccess { variant, value })
+                    }
+                    _ => Err(de::Error::invalid_length(
+                        iter.len(),
+                        &"map with a single key",
+                    )),
+                }
+            }
+            v => Err(unexpected("enum", &v)),
+        }
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,98 @@
+pub mod de;
+pub mod ser;
+
+use hessian_rs::Value;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::Error;
+
+/// Convert a `T` into a `Value` tree without encoding it to bytes
+pub fn to_value<T>(value: &T) -> Result<Value, Error>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(ser::Serializer)
+}
+
+/// Interpret a `Value` tree as an instance of type `T`
+pub fn from_value<T>(value: Value) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(de::Deserializer::new(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_value, to_value};
+    use crate::{de::from_slice, ser::to_vec};
+    use hessian_rs::Value;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename = "example.Car")]
+    struct Car {
+        color: String,
+        mileage: i64,
+        parts: Vec<String>,
+        owner: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum E {
+        Unit,
+        Newtype(u32),
+        Tuple(u32, u32),
+        Struct { a: u32 },
+    }
+
+    #[test]
+    fn test_struct_value() {
+        let car = Car {
+            color: "red".to_string(),
+            mileage: 65536,
+            parts: vec!["wheel".to_string()],
+            owner: None,
+        };
+        let value = to_value(&car).unwrap();
+        let map = value.as_map().unwrap();
+        assert_eq!(map.r#type(), Some("example.Car"));
+        assert_eq!(map[&"mileage".into()], Value::Long(65536));
+        assert_eq!(map[&"owner".into()], Value::Null);
+
+        // the value tree matches the one decoded from the encoded bytes
+        let decoded = hessian_rs::from_slice(&to_vec(&car).unwrap()).unwrap();
+        assert_eq!(value, decoded);
+
+        let car2: Car = from_value(value).unwrap();
+        assert_eq!(car, car2);
+        let car3: Car = from_value(decoded).unwrap();
+        assert_eq!(car, car3);
+
+        let typed: crate::Typed<Car> = from_value(to_value(&car).unwrap()).unwrap();
+        assert_eq!(typed.class, "example.Car");
+        assert_eq!(typed.value, car);
+        assert!(from_value::<crate::Typed<Car>>(Value::Null).is_err());
+    }
+
+    #[test]
+    fn test_enum_value() {
+        for e in [E::Unit, E::Newtype(1), E::Tuple(1, 2), E::Struct { a: 1 }] {
+            let value = to_value(&e).unwrap();
+            assert_eq!(from_value::<E>(value).unwrap(), e);
+        }
+    }
+
+    #[test]
+    fn test_collection_value() {
+        let mut map = HashMap::new();
+        map.insert("a".to_string(), vec![1, 2]);
+        let value = to_value(&map).unwrap();
+        assert_eq!(from_value::<HashMap<String, Vec<i32>>>(value).unwrap(), map);
+
+        let decoded: Vec<i32> = from_slice([0x57, 0x90, 0x91, b'Z']).unwrap();
+        assert_eq!(to_value(&decoded).unwrap(), vec![0, 1].into());
+        assert!(from_value::<String>(Value::Int(1)).is_err());
+    }
+}
@@ -72,6 +72,13 @@ fn test_string_roundtrip() {
     roundtrip_test("abcdefghij".repeat(1000));
 }
 
+#[test]
+fn test_char_roundtrip() {
+    roundtrip_test('a');
+    roundtrip_test('中');
+    roundtrip_test('😀');
+}
+
 #[test]
 fn test_list_roundtrip() {
     roundtrip_test(vec![1, 2]);
@@ -111,7 +118,68 @@ fn test_basic_struct() {
     roundtrip_test(None::<BasicStruct>);
 }
 
-// todo: fix enum round trip
+#[test]
+fn test_nested_struct() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Car {
+        color: String,
+    }
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Garage {
+        car: Car,
+        cars: Vec<Car>,
+        size: i32,
+    }
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Node {
+        value: i32,
+        next: Option<Box<Node>>,
+    }
+    let car = |color: &str| Car {
+        color: color.to_string(),
+    };
+    roundtrip_test(Garage {
+        car: car("red"),
+        cars: vec![car("blue"), car("green")],
+        size: 2,
+    });
+    roundtrip_test(vec![
+        Node {
+            value: 1,
+            next: Some(Box::new(Node {
+                value: 2,
+                next: None,
+            })),
+        },
+        Node {
+            value: 3,
+            next: None,
+        },
+    ]);
+}
+
+#[test]
+fn test_truncated_is_eof() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Car {
+        color: String,
+        model: Option<String>,
+        parts: Vec<(i64, f64)>,
+        tags: HashMap<String, Vec<u8>>,
+    }
+    let car = Car {
+        color: "中文".to_string(),
+        model: None,
+        parts: vec![(1, 1.5), (2, 0.1)],
+        tags: HashMap::from([("a".to_string(), vec![1, 2, 3])]),
+    };
+    let buf = to_vec(&car).unwrap();
+    for len in 0..buf.len() {
+        let err = from_slice::<_, Car>(&buf[..len]).unwrap_err();
+        assert!(err.is_eof(), "{} at {}", err, len);
+    }
+}
+
 #[test]
 fn test_enum() {
     #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -121,8 +189,70 @@ fn test_enum() {
         Tuple(u32, u32),
         Struct { a: u32 },
     }
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    struct Wrapper {
+        e: E,
+        next: Option<Box<E>>,
+    }
     roundtrip_test(E::Unit);
     roundtrip_test(E::Newtype(1));
     roundtrip_test(E::Tuple(1, 2));
     roundtrip_test(E::Struct { a: 1 });
+    // variants nested in containers are followed by other values
+    roundtrip_test(vec![
+        E::Newtype(1),
+        E::Unit,
+        E::Tuple(1, 2),
+        E::Struct { a: 1 },
+        E::Unit,
+    ]);
+    roundtrip_test(Wrapper {
+        e: E::Struct { a: 2 },
+        next: Some(Box::new(E::Tuple(3, 4))),
+    });
+    let mut map = HashMap::new();
+    map.insert("a".to_string(), E::Newtype(5));
+    map.insert("b".to_string(), E::Unit);
+    roundtrip_test(map);
+}
+
+#[test]
+fn test_untagged_enum() {
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum U {
+        Struct { a: u32 },
+        Str(String),
+        List(Vec<i32>),
+        Int(i64),
+    }
+    roundtrip_test(U::Struct { a: 1 });
+    roundtrip_test(U::Str("x".to_string()));
+    roundtrip_test(U::List(vec![1, 2]));
+    roundtrip_test(U::Int(i64::MAX));
+    roundtrip_test(vec![U::Int(1), U::Struct { a: 2 }, U::Str("y".to_string())]);
+}
+
+#[test]
+fn test_tagged_enum() {
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(tag = "t", content = "c")]
+    enum Adjacent {
+        Unit,
+        Newtype(String),
+        Struct { a: u32 },
+    }
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(tag = "t")]
+    enum Internal {
+        Unit,
+        Struct { a: u32 },
+    }
+    roundtrip_test(Adjacent::Unit);
+    roundtrip_test(Adjacent::Newtype("x".to_string()));
+    roundtrip_test(Adjacent::Struct { a: 1 });
+    roundtrip_test(vec![Adjacent::Struct { a: 1 }, Adjacent::Unit]);
+    roundtrip_test(Internal::Unit);
+    roundtrip_test(Internal::Struct { a: 1 });
+    roundtrip_test(vec![Internal::Struct { a: 1 }, Internal::Unit]);
 }